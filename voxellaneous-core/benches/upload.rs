@@ -0,0 +1,72 @@
+//! Times the upload of a 64 MiB scene with its voxels as a JS number array, as
+//! `upload_scene` reads them, against one `Uint8Array` for
+//! `upload_scene_with_voxels`. The number array is the only path there was before
+//! `upload_scene_with_voxels`, so one run gives the before and after times. Run in a
+//! browser with WebGPU, with `wasm-bindgen-test-runner` as the wasm32 target runner:
+//! `cargo bench --target wasm32-unknown-unknown`.
+
+use voxellaneous_core::Renderer;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_test::{wasm_bindgen_bench, wasm_bindgen_test_configure, Criterion};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+/// One byte per voxel, so 64 MiB of voxels.
+const DIMS: [u32; 3] = [512, 512, 256];
+
+async fn create_renderer() -> Renderer {
+    let canvas: web_sys::HtmlCanvasElement = web_sys::window()
+        .and_then(|window| window.document())
+        .expect("document")
+        .create_element("canvas")
+        .expect("canvas element")
+        .dyn_into()
+        .expect("HtmlCanvasElement");
+    Renderer::new(canvas).await.expect("renderer")
+}
+
+/// A scene of one `DIMS` object, with `voxels` if given.
+fn scene(voxels: Option<&JsValue>) -> JsValue {
+    let json = format!(
+        r#"{{
+            "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+            "objects": [{{ "id": "volume", "dims": {:?} }}]
+        }}"#,
+        DIMS
+    );
+    let scene = js_sys::JSON::parse(&json).expect("scene JSON");
+    if let Some(voxels) = voxels {
+        let objects = js_sys::Reflect::get(&scene, &"objects".into()).expect("objects");
+        let object = js_sys::Reflect::get_u32(&objects, 0).expect("object");
+        js_sys::Reflect::set(&object, &"voxels".into(), voxels).expect("voxels");
+    }
+    scene
+}
+
+#[wasm_bindgen_bench]
+async fn upload_64_mib_scene(c: &mut Criterion) {
+    let mut renderer = create_renderer().await;
+    let voxels: Vec<u8> = (0..DIMS.iter().product::<u32>())
+        .map(|i| (i % 3 == 0) as u8)
+        .collect();
+    let array = js_sys::Uint8Array::from(voxels.as_slice());
+    let numbers = JsValue::from(js_sys::Array::from(&array));
+    let number_scene = scene(Some(&numbers));
+    let array_scene = scene(None);
+
+    // Forced, as an unchanged scene would be skipped after the first upload
+    c.bench_function("upload_scene (number array)", |b| {
+        b.iter(|| {
+            renderer
+                .force_upload_scene(number_scene.clone())
+                .expect("upload")
+        })
+    });
+    c.bench_function("upload_scene_with_voxels (Uint8Array)", |b| {
+        b.iter(|| {
+            renderer
+                .upload_scene_with_voxels(array_scene.clone(), array.clone(), &[0])
+                .expect("upload")
+        })
+    });
+}
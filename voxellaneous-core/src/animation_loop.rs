@@ -0,0 +1,165 @@
+//! `requestAnimationFrame` loop driving `Renderer::render_frame`.
+
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use wasm_bindgen::{prelude::*, JsCast};
+
+use crate::Renderer;
+
+type TickClosure = Closure<dyn FnMut(f64)>;
+
+struct LoopState {
+    window: web_sys::Window,
+    frame_id: Cell<Option<i32>>,
+    /// Timestamp of the last rendered frame, for palette animation.
+    last_timestamp: Cell<Option<f64>>,
+    /// Set once the loop must never render again (stopped, dropped or failed).
+    stopped: Cell<bool>,
+    tick: RefCell<Option<TickClosure>>,
+}
+
+impl LoopState {
+    fn schedule(&self) {
+        if self.stopped.get() || self.frame_id.get().is_some() {
+            return;
+        }
+        if let Some(tick) = self.tick.borrow().as_ref() {
+            let id = self
+                .window
+                .request_animation_frame(tick.as_ref().unchecked_ref());
+            self.frame_id.set(id.ok());
+        }
+    }
+
+    fn cancel(&self) {
+        if let Some(id) = self.frame_id.take() {
+            let _ = self.window.cancel_animation_frame(id);
+        }
+    }
+}
+
+/// Calls the host callback with the frame timestamp, then renders with the
+/// renderer's stored frame settings. Pauses while the document is hidden and
+/// skips frames held back by the renderer's FPS cap.
+pub struct AnimationLoop {
+    state: Rc<LoopState>,
+    document: web_sys::Document,
+    visibility_listener: Closure<dyn FnMut()>,
+}
+
+impl AnimationLoop {
+    /// # Safety
+    ///
+    /// `renderer` must stay valid until the loop is dropped. The renderer owns
+    /// its loop, so this holds as long as the loop is stored in it.
+    pub unsafe fn start(
+        renderer: *mut Renderer,
+        callback: js_sys::Function,
+        on_error: Option<js_sys::Function>,
+    ) -> Result<AnimationLoop, JsValue> {
+        let window = web_sys::window().ok_or_else(|| JsValue::from_str("no global window"))?;
+        let document = window
+            .document()
+            .ok_or_else(|| JsValue::from_str("no document"))?;
+
+        let state = Rc::new(LoopState {
+            window,
+            frame_id: Cell::new(None),
+            last_timestamp: Cell::new(None),
+            stopped: Cell::new(false),
+            tick: RefCell::new(None),
+        });
+
+        let weak = Rc::downgrade(&state);
+        let tick_document = document.clone();
+        let tick = TickClosure::new(move |timestamp: f64| {
+            let Some(state) = weak.upgrade() else {
+                return;
+            };
+            state.frame_id.set(None);
+
+            // SAFETY: as below; checked before handing control to the callback.
+            if !unsafe { (*renderer).frame_due(timestamp) } {
+                state.schedule();
+                return;
+            }
+
+            let previous = state.last_timestamp.replace(Some(timestamp));
+            let dt_ms = previous.map_or(0.0, |previous| timestamp - previous);
+
+            let result = callback
+                .call1(&JsValue::UNDEFINED, &JsValue::from_f64(timestamp))
+                .and_then(|_| {
+                    // The callback may have stopped the loop or freed the renderer
+                    if state.stopped.get() {
+                        return Ok(());
+                    }
+                    // SAFETY: not stopped, so the owning renderer is still alive, and
+                    // no other borrow of it is active while a RAF callback runs.
+                    let renderer = unsafe { &mut *renderer };
+                    renderer.advance_palette_animation(dt_ms / 1000.0);
+                    renderer.render_frame().map_err(JsValue::from)
+                });
+
+            match result {
+                Ok(()) if !tick_document.hidden() => state.schedule(),
+                Ok(()) => {}
+                Err(error) => {
+                    state.stopped.set(true);
+                    report_error(on_error.as_ref(), &error);
+                }
+            }
+        });
+        state.tick.replace(Some(tick));
+
+        let weak = Rc::downgrade(&state);
+        let listener_document = document.clone();
+        let visibility_listener = Closure::<dyn FnMut()>::new(move || {
+            let Some(state) = weak.upgrade() else {
+                return;
+            };
+            if listener_document.hidden() {
+                state.cancel();
+            } else {
+                state.schedule();
+            }
+        });
+        document.add_event_listener_with_callback(
+            "visibilitychange",
+            visibility_listener.as_ref().unchecked_ref(),
+        )?;
+
+        if !document.hidden() {
+            state.schedule();
+        }
+        Ok(AnimationLoop {
+            state,
+            document,
+            visibility_listener,
+        })
+    }
+}
+
+impl Drop for AnimationLoop {
+    fn drop(&mut self) {
+        self.state.stopped.set(true);
+        self.state.cancel();
+        let _ = self.document.remove_event_listener_with_callback(
+            "visibilitychange",
+            self.visibility_listener.as_ref().unchecked_ref(),
+        );
+        self.state.tick.take();
+    }
+}
+
+fn report_error(on_error: Option<&js_sys::Function>, error: &JsValue) {
+    match on_error {
+        Some(on_error) => {
+            let _ = on_error.call1(&JsValue::UNDEFINED, error);
+        }
+        None => web_sys::console::error_1(error),
+    }
+}
@@ -0,0 +1,477 @@
+//! Bakes of per-voxel ambient occlusion into R8Unorm 3D textures: hemisphere ray
+//! tracing (`AoBakePipelines`) and neighbour counting on the GPU
+//! (`NeighbourAoPipeline`) or CPU (`neighbour_ao_cpu`).
+
+use wgpu::util::DeviceExt;
+
+/// Hemisphere samples per face traced in one dispatch; larger counts are split over
+/// several dispatches so no single one runs long enough to trip GPU watchdogs.
+const SAMPLES_PER_DISPATCH: u32 = 32;
+/// Rays travelling further than this many voxels count as unoccluded.
+const AO_MAX_DISTANCE: f32 = 8.0;
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct AoParams {
+    dims: [u32; 3],
+    row_words: u32,
+    sample_offset: u32,
+    sample_count: u32,
+    total_samples: u32,
+    max_distance: f32,
+    empty_index: u32,
+    _padding: [u32; 3],
+}
+
+pub struct AoBakePipelines {
+    layout: wgpu::BindGroupLayout,
+    accumulate: wgpu::ComputePipeline,
+    resolve: wgpu::ComputePipeline,
+}
+
+impl AoBakePipelines {
+    pub fn new(device: &wgpu::Device, cache: Option<&wgpu::PipelineCache>) -> Self {
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("AO Bake Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                storage_entry(1),
+                storage_entry(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("AO Bake Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("AO Bake Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/ao_bake.wgsl").into()),
+        });
+        let create_pipeline = |entry_point: &str, label: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache,
+            })
+        };
+
+        AoBakePipelines {
+            accumulate: create_pipeline("cs_accumulate", "AO Accumulate Pipeline"),
+            resolve: create_pipeline("cs_resolve", "AO Resolve Pipeline"),
+            layout,
+        }
+    }
+
+    /// Records and submits the bake of `voxels` (an R8Uint 3D texture, `empty_index`
+    /// marking empty voxels) with `samples` rays per exposed face, returning the
+    /// R8Unorm AO texture (1 = unoccluded) and a buffer that becomes mappable once the
+    /// bake has finished on the GPU.
+    pub fn bake(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        voxels: &wgpu::Texture,
+        samples: u32,
+        empty_index: u8,
+    ) -> (wgpu::Texture, wgpu::Buffer) {
+        let size = voxels.size();
+        let dims = [size.width, size.height, size.depth_or_array_layers];
+        let voxel_count = dims.iter().map(|&d| d as u64).product::<u64>();
+        let row_bytes = size.width.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let row_words = row_bytes / 4;
+
+        let hits = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("AO Hits"),
+            size: voxel_count * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            // Zero-initialized by wgpu
+            mapped_at_creation: false,
+        });
+        let output = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("AO Output"),
+            size: row_bytes as u64 * size.height as u64 * size.depth_or_array_layers as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let done = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("AO Bake Done"),
+            size: 4,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let ao_texture = create_ao_texture(device, size);
+
+        let voxel_view = voxels.create_view(&wgpu::TextureViewDescriptor::default());
+        let create_bind_group = |sample_offset: u32, sample_count: u32| {
+            let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("AO Bake Params"),
+                contents: bytemuck::cast_slice(&[AoParams {
+                    dims,
+                    row_words,
+                    sample_offset,
+                    sample_count,
+                    total_samples: samples,
+                    max_distance: AO_MAX_DISTANCE,
+                    empty_index: empty_index as u32,
+                    _padding: [0; 3],
+                }]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("AO Bake BG"),
+                layout: &self.layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&voxel_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: hits.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: output.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: params.as_entire_binding(),
+                    },
+                ],
+            })
+        };
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("AO Bake Encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("AO Bake Pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.accumulate);
+            let mut sample_offset = 0;
+            while sample_offset < samples {
+                let sample_count = SAMPLES_PER_DISPATCH.min(samples - sample_offset);
+                pass.set_bind_group(0, &create_bind_group(sample_offset, sample_count), &[]);
+                pass.dispatch_workgroups(
+                    size.width.div_ceil(4),
+                    size.height.div_ceil(4),
+                    size.depth_or_array_layers.div_ceil(4),
+                );
+                sample_offset += sample_count;
+            }
+
+            pass.set_pipeline(&self.resolve);
+            pass.set_bind_group(0, &create_bind_group(0, 0), &[]);
+            pass.dispatch_workgroups(
+                row_words.div_ceil(4),
+                size.height.div_ceil(4),
+                size.depth_or_array_layers.div_ceil(4),
+            );
+        }
+        copy_ao_output(&mut encoder, &output, row_bytes, &ao_texture);
+        encoder.copy_buffer_to_buffer(&output, 0, &done, 0, 4);
+        queue.submit(Some(encoder.finish()));
+
+        (ao_texture, done)
+    }
+}
+
+/// Largest neighbour-counting radius; the shader's shared-memory tile is sized for it.
+pub const MAX_NEIGHBOUR_AO_RADIUS: u32 = 6;
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct NeighbourAoParams {
+    dims: [u32; 3],
+    row_words: u32,
+    radius: u32,
+    empty_index: u32,
+    _padding: [u32; 2],
+}
+
+pub struct NeighbourAoPipeline {
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl NeighbourAoPipeline {
+    pub fn new(device: &wgpu::Device, cache: Option<&wgpu::PipelineCache>) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Neighbour AO Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Neighbour AO Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Neighbour AO Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/ao_neighbour.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Neighbour AO Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache,
+        });
+        NeighbourAoPipeline { layout, pipeline }
+    }
+
+    /// Records and submits the bake of `voxels` (an R8Uint 3D texture, `empty_index`
+    /// marking empty voxels), returning the AO texture and a buffer that becomes
+    /// mappable once the GPU has finished. `radius` must not exceed
+    /// `MAX_NEIGHBOUR_AO_RADIUS`.
+    pub fn bake(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        voxels: &wgpu::Texture,
+        radius: u32,
+        empty_index: u8,
+    ) -> (wgpu::Texture, wgpu::Buffer) {
+        let size = voxels.size();
+        let row_bytes = size.width.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Neighbour AO Params"),
+            contents: bytemuck::cast_slice(&[NeighbourAoParams {
+                dims: [size.width, size.height, size.depth_or_array_layers],
+                row_words: row_bytes / 4,
+                radius,
+                empty_index: empty_index as u32,
+                _padding: [0; 2],
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let output = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Neighbour AO Output"),
+            size: row_bytes as u64 * size.height as u64 * size.depth_or_array_layers as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let done = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Neighbour AO Done"),
+            size: 4,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let ao_texture = create_ao_texture(device, size);
+
+        let voxel_view = voxels.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Neighbour AO BG"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&voxel_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: output.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Neighbour AO Encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Neighbour AO Pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(
+                size.width.div_ceil(4),
+                size.height.div_ceil(4),
+                size.depth_or_array_layers.div_ceil(4),
+            );
+        }
+        copy_ao_output(&mut encoder, &output, row_bytes, &ao_texture);
+        encoder.copy_buffer_to_buffer(&output, 0, &done, 0, 4);
+        queue.submit(Some(encoder.finish()));
+
+        (ao_texture, done)
+    }
+}
+
+/// CPU reference of `ao_neighbour.wgsl`: AO bytes for x-fastest `voxels`, 255 for
+/// empty voxels (`empty_index`).
+pub fn neighbour_ao_cpu(dims: [u32; 3], voxels: &[u8], radius: u32, empty_index: u8) -> Vec<u8> {
+    let [nx, ny, nz] = dims.map(|d| d as i64);
+    let r = radius as i64;
+    let filled = |x: i64, y: i64, z: i64| {
+        x >= 0
+            && y >= 0
+            && z >= 0
+            && x < nx
+            && y < ny
+            && z < nz
+            && voxels
+                .get(((z * ny + y) * nx + x) as usize)
+                .is_some_and(|&v| v != empty_index)
+    };
+    let width = 2 * radius + 1;
+    let total = width * width * width - 1;
+
+    let mut ao = Vec::with_capacity((nx * ny * nz) as usize);
+    for z in 0..nz {
+        for y in 0..ny {
+            for x in 0..nx {
+                if !filled(x, y, z) || total == 0 {
+                    ao.push(255);
+                    continue;
+                }
+                let mut occupied = 0u32;
+                for dz in -r..=r {
+                    for dy in -r..=r {
+                        for dx in -r..=r {
+                            occupied += filled(x + dx, y + dy, z + dz) as u32;
+                        }
+                    }
+                }
+                // Excludes the voxel itself
+                occupied -= 1;
+                ao.push(((255 * (total - occupied) + total / 2) / total) as u8);
+            }
+        }
+    }
+    ao
+}
+
+pub fn create_ao_texture(device: &wgpu::Device, size: wgpu::Extent3d) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("object_ao"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D3,
+        format: wgpu::TextureFormat::R8Unorm,
+        // COPY_SRC for `Renderer::read_ao`
+        usage: wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
+
+/// A single unoccluded AO texel, bound for objects with no baked AO.
+pub fn create_unoccluded_view(device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::TextureView {
+    let size = wgpu::Extent3d {
+        width: 1,
+        height: 1,
+        depth_or_array_layers: 1,
+    };
+    let texture = create_ao_texture(device, size);
+    queue.write_texture(
+        texture.as_image_copy(),
+        &[255],
+        wgpu::TexelCopyBufferLayout::default(),
+        size,
+    );
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Copies row-padded AO bytes from a compute output buffer into `ao_texture`.
+fn copy_ao_output(
+    encoder: &mut wgpu::CommandEncoder,
+    output: &wgpu::Buffer,
+    row_bytes: u32,
+    ao_texture: &wgpu::Texture,
+) {
+    let size = ao_texture.size();
+    encoder.copy_buffer_to_texture(
+        wgpu::TexelCopyBufferInfo {
+            buffer: output,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(row_bytes),
+                rows_per_image: Some(size.height),
+            },
+        },
+        wgpu::TexelCopyTextureInfo {
+            texture: ao_texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        size,
+    );
+}
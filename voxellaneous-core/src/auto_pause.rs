@@ -0,0 +1,51 @@
+//! Pausing while the document is hidden, for `RendererBuilder::with_auto_pause`.
+
+use std::{cell::Cell, rc::Rc};
+
+use wasm_bindgen::{prelude::*, JsCast};
+
+use crate::RendererError;
+
+/// Tracks the document's visibility through its `visibilitychange` event.
+pub struct AutoPause {
+    hidden: Rc<Cell<bool>>,
+    document: web_sys::Document,
+    visibility_listener: Closure<dyn FnMut()>,
+}
+
+impl AutoPause {
+    pub fn start() -> Result<AutoPause, RendererError> {
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or_else(|| RendererError::from("auto-pause requires a document"))?;
+        let hidden = Rc::new(Cell::new(document.hidden()));
+
+        let listener_hidden = hidden.clone();
+        let listener_document = document.clone();
+        let visibility_listener = Closure::<dyn FnMut()>::new(move || {
+            listener_hidden.set(listener_document.hidden());
+        });
+        document.add_event_listener_with_callback(
+            "visibilitychange",
+            visibility_listener.as_ref().unchecked_ref(),
+        )?;
+        Ok(AutoPause {
+            hidden,
+            document,
+            visibility_listener,
+        })
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.hidden.get()
+    }
+}
+
+impl Drop for AutoPause {
+    fn drop(&mut self) {
+        let _ = self.document.remove_event_listener_with_callback(
+            "visibilitychange",
+            self.visibility_listener.as_ref().unchecked_ref(),
+        );
+    }
+}
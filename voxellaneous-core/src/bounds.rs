@@ -0,0 +1,209 @@
+use serde::Serialize;
+
+use crate::math::{self, Mat4};
+
+/// World-space axis-aligned bounding box.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    /// Bounds of an object's unit proxy cube ([-0.5, 0.5]^3) after `model_matrix`.
+    pub fn from_model_matrix(model_matrix: &Mat4) -> Aabb {
+        let unit = Aabb {
+            min: [-0.5; 3],
+            max: [0.5; 3],
+        };
+        unit.transformed(model_matrix)
+    }
+
+    /// Bounds of the box after the affine transform `m`.
+    pub fn transformed(&self, m: &Mat4) -> Aabb {
+        let mut min = [f32::INFINITY; 3];
+        let mut max = [f32::NEG_INFINITY; 3];
+        for corner in 0..8 {
+            let p = std::array::from_fn(|axis| {
+                if corner & (1 << axis) == 0 {
+                    self.min[axis]
+                } else {
+                    self.max[axis]
+                }
+            });
+            let ws = math::transform_point(m, p);
+            for axis in 0..3 {
+                min[axis] = min[axis].min(ws[axis]);
+                max[axis] = max[axis].max(ws[axis]);
+            }
+        }
+        Aabb { min, max }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: std::array::from_fn(|i| self.min[i].min(other.min[i])),
+            max: std::array::from_fn(|i| self.max[i].max(other.max[i])),
+        }
+    }
+
+    /// Overlap of the two boxes, `None` unless it has a volume.
+    pub fn intersection(&self, other: &Aabb) -> Option<Aabb> {
+        let overlap = Aabb {
+            min: std::array::from_fn(|i| self.min[i].max(other.min[i])),
+            max: std::array::from_fn(|i| self.max[i].min(other.max[i])),
+        };
+        (0..3)
+            .all(|i| overlap.min[i] < overlap.max[i])
+            .then_some(overlap)
+    }
+
+    pub fn center(&self) -> [f32; 3] {
+        std::array::from_fn(|i| (self.min[i] + self.max[i]) * 0.5)
+    }
+
+    /// Radius of the sphere through the box corners.
+    pub fn half_diagonal(&self) -> f32 {
+        (0..3)
+            .map(|i| (self.max[i] - self.min[i]) * 0.5)
+            .map(|h| h * h)
+            .sum::<f32>()
+            .sqrt()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sphere {
+    pub center: [f32; 3],
+    pub radius: f32,
+}
+
+impl Aabb {
+    /// Circumscribed sphere of the box.
+    pub fn bounding_sphere(&self) -> Sphere {
+        Sphere {
+            center: self.center(),
+            radius: self.half_diagonal(),
+        }
+    }
+}
+
+impl Sphere {
+    /// Circumscribed sphere of an object's unit proxy cube after `model_matrix`, taken
+    /// straight from the transformed axes. Tighter than the sphere of the world AABB
+    /// for rotated objects.
+    pub fn from_model_matrix(model_matrix: &Mat4) -> Sphere {
+        let axis = |i: usize| [0, 1, 2].map(|j| model_matrix[i * 4 + j] * 0.5);
+        let [a, b, c] = [axis(0), axis(1), axis(2)];
+        // Opposite corners are equidistant, so 4 of the 8 corners suffice
+        let radius = [(1.0, 1.0), (1.0, -1.0), (-1.0, 1.0), (-1.0, -1.0)]
+            .iter()
+            .map(|&(sb, sc)| {
+                (0..3)
+                    .map(|k| a[k] + sb * b[k] + sc * c[k])
+                    .map(|d| d * d)
+                    .sum::<f32>()
+            })
+            .fold(0.0f32, f32::max)
+            .sqrt();
+        Sphere {
+            center: math::transform_point(model_matrix, [0.0; 3]),
+            radius,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrustumResult {
+    Outside,
+    Intersects,
+    Inside,
+}
+
+/// View frustum as 6 inward-facing planes `[a, b, c, d]` with unit normals.
+pub struct Frustum {
+    planes: [[f32; 4]; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes of a column-major view-projection with WebGPU's [0, 1]
+    /// clip-space depth.
+    pub fn from_vp_matrix(vp: &Mat4) -> Frustum {
+        let row = |i: usize| [vp[i], vp[4 + i], vp[8 + i], vp[12 + i]];
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+        let add = |a: [f32; 4], b: [f32; 4]| std::array::from_fn(|i| a[i] + b[i]);
+        let sub = |a: [f32; 4], b: [f32; 4]| std::array::from_fn(|i| a[i] - b[i]);
+        let planes = [
+            add(r3, r0),
+            sub(r3, r0),
+            add(r3, r1),
+            sub(r3, r1),
+            r2,
+            sub(r3, r2),
+        ]
+        .map(|p: [f32; 4]| {
+            let len = (p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt();
+            if len > 0.0 {
+                p.map(|v| v / len)
+            } else {
+                p
+            }
+        });
+        Frustum { planes }
+    }
+
+    fn distance(plane: &[f32; 4], p: [f32; 3]) -> f32 {
+        plane[0] * p[0] + plane[1] * p[1] + plane[2] * p[2] + plane[3]
+    }
+
+    pub fn contains_sphere(&self, sphere: Sphere) -> FrustumResult {
+        let mut result = FrustumResult::Inside;
+        for plane in &self.planes {
+            let d = Frustum::distance(plane, sphere.center);
+            if d < -sphere.radius {
+                return FrustumResult::Outside;
+            }
+            if d < sphere.radius {
+                result = FrustumResult::Intersects;
+            }
+        }
+        result
+    }
+
+    pub fn contains_aabb(&self, aabb: &Aabb) -> FrustumResult {
+        let mut result = FrustumResult::Inside;
+        for plane in &self.planes {
+            // Corners furthest along and against the plane normal
+            let positive = std::array::from_fn(|i| {
+                if plane[i] >= 0.0 {
+                    aabb.max[i]
+                } else {
+                    aabb.min[i]
+                }
+            });
+            let negative = std::array::from_fn(|i| {
+                if plane[i] >= 0.0 {
+                    aabb.min[i]
+                } else {
+                    aabb.max[i]
+                }
+            });
+            if Frustum::distance(plane, positive) < 0.0 {
+                return FrustumResult::Outside;
+            }
+            if Frustum::distance(plane, negative) < 0.0 {
+                result = FrustumResult::Intersects;
+            }
+        }
+        result
+    }
+
+    /// Sphere test first; the AABB is only tested when the sphere straddles a plane.
+    pub fn is_visible(&self, sphere: Sphere, aabb: &Aabb) -> bool {
+        match self.contains_sphere(sphere) {
+            FrustumResult::Outside => false,
+            FrustumResult::Inside => true,
+            FrustumResult::Intersects => self.contains_aabb(aabb) != FrustumResult::Outside,
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! Checkerboard rendering: the G-buffer pass raymarches half the pixels each frame
+//! and a resolve pass reconstructs the other half from the previous frame.
+
+use crate::constants::GBUFFER_NORMAL_FORMAT;
+use crate::create_render_texture;
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ResolveUniforms {
+    inv_vp_matrix: [f32; 16],
+    prev_vp_matrix: [f32; 16],
+    camera_position: [f32; 3],
+    parity: u32,
+    prev_camera_position: [f32; 3],
+    has_history: u32,
+}
+
+/// Resolved G-buffer of one frame, kept as the next frame's history.
+struct ResolvedTargets {
+    albedo: wgpu::Texture,
+    normal: wgpu::Texture,
+    linear_z: wgpu::Texture,
+    material: wgpu::Texture,
+    views: [wgpu::TextureView; 5],
+}
+
+impl ResolvedTargets {
+    fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        linear_z_format: wgpu::TextureFormat,
+    ) -> Self {
+        let rgba = wgpu::TextureFormat::Rgba8Unorm;
+        let albedo = create_render_texture(device, width, height, rgba, "Checkerboard Albedo");
+        let normal = create_render_texture(
+            device,
+            width,
+            height,
+            GBUFFER_NORMAL_FORMAT,
+            "Checkerboard Normal",
+        );
+        let linear_z = create_render_texture(
+            device,
+            width,
+            height,
+            linear_z_format,
+            "Checkerboard LinearZ",
+        );
+        let material = create_render_texture(
+            device,
+            width,
+            height,
+            linear_z_format,
+            "Checkerboard Material",
+        );
+        let status = create_render_texture(device, width, height, rgba, "Checkerboard Status");
+        let views = [&albedo, &normal, &linear_z, &material, &status]
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        ResolvedTargets {
+            albedo,
+            normal,
+            linear_z,
+            material,
+            views,
+        }
+    }
+}
+
+pub struct CheckerboardPass {
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    linear_z_format: wgpu::TextureFormat,
+    /// Ping-pong pair; `current` is written this frame, the other is its history.
+    targets: Option<[ResolvedTargets; 2]>,
+    current: usize,
+    frame_index: u32,
+    /// Camera of the previous resolved frame, `None` when there is no usable history.
+    previous: Option<([f32; 16], [f32; 3])>,
+    /// Set when the last frame only completed the one before it.
+    follow_up: bool,
+}
+
+impl CheckerboardPass {
+    pub fn new(
+        device: &wgpu::Device,
+        linear_z_format: wgpu::TextureFormat,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let texture_entry = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let float = wgpu::TextureSampleType::Float { filterable: false };
+        let uint = wgpu::TextureSampleType::Uint;
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Checkerboard Resolve Bind Group Layout"),
+            entries: &[
+                texture_entry(0, float),
+                texture_entry(1, float),
+                texture_entry(2, uint),
+                texture_entry(3, uint),
+                texture_entry(4, float),
+                texture_entry(5, float),
+                texture_entry(6, uint),
+                texture_entry(7, uint),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Checkerboard Resolve Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                include_str!("shaders/checkerboard_resolve.wgsl").into(),
+            ),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Checkerboard Resolve Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let target = |format| {
+            Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+        };
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Checkerboard Resolve Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[
+                    target(wgpu::TextureFormat::Rgba8Unorm),
+                    target(GBUFFER_NORMAL_FORMAT),
+                    target(linear_z_format),
+                    target(linear_z_format),
+                    target(wgpu::TextureFormat::Rgba8Unorm),
+                ],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+            cache,
+        });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Checkerboard Resolve Uniform Buffer"),
+            size: std::mem::size_of::<ResolveUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        CheckerboardPass {
+            layout,
+            pipeline,
+            uniform_buffer,
+            linear_z_format,
+            targets: None,
+            current: 0,
+            frame_index: 0,
+            previous: None,
+            follow_up: false,
+        }
+    }
+
+    /// Drops the history, e.g. when checkerboarding is switched back on.
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    /// Advances to the next frame and returns the parity of the pixels the G-buffer
+    /// pass must render: those with `(x + y) % 2 == parity`.
+    pub fn begin_frame(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        (width, height): (u32, u32),
+        vp_matrix: &[f32; 16],
+        camera_position: [f32; 3],
+    ) -> u32 {
+        let size = self
+            .targets
+            .as_ref()
+            .map(|targets| targets[0].albedo.size());
+        if size.is_none_or(|size| size.width != width || size.height != height) {
+            self.targets = Some(std::array::from_fn(|_| {
+                ResolvedTargets::new(device, width, height, self.linear_z_format)
+            }));
+            self.previous = None;
+        }
+        self.current ^= 1;
+        self.frame_index = self.frame_index.wrapping_add(1);
+        let parity = self.frame_index % 2;
+
+        let (prev_vp_matrix, prev_camera_position) = self.previous.unwrap_or_default();
+        let uniforms = ResolveUniforms {
+            inv_vp_matrix: crate::math::invert(vp_matrix).unwrap_or_default(),
+            prev_vp_matrix,
+            camera_position,
+            parity,
+            prev_camera_position,
+            has_history: self.previous.is_some() as u32,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+        self.previous = Some((*vp_matrix, camera_position));
+        parity
+    }
+
+    /// Returns whether another frame is needed to render the half of the pixels
+    /// skipped this frame: true after a frame caused by a change, false after the
+    /// frame that follows it.
+    pub fn finish_frame(&mut self) -> bool {
+        self.follow_up = !self.follow_up;
+        self.follow_up
+    }
+
+    /// Status of the last resolved frame for the debug view: black where rendered,
+    /// green where reprojected and red where interpolated from neighbours.
+    pub fn status_view(&self) -> Option<&wgpu::TextureView> {
+        let targets = self.targets.as_ref()?;
+        Some(&targets[self.current].views[4])
+    }
+
+    /// Resolves the half-rendered G-buffer `gbuffer` (albedo, normal, linear-Z and
+    /// material textures with their views) and copies the result back into it.
+    pub fn encode(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        gbuffer: [(&wgpu::Texture, &wgpu::TextureView); 4],
+    ) {
+        let Some(targets) = &self.targets else {
+            return;
+        };
+        let (current, history) = (&targets[self.current], &targets[self.current ^ 1]);
+
+        let views = [
+            gbuffer[0].1,
+            gbuffer[1].1,
+            gbuffer[2].1,
+            gbuffer[3].1,
+            &history.views[0],
+            &history.views[1],
+            &history.views[2],
+            &history.views[3],
+        ];
+        let mut entries: Vec<_> = views
+            .iter()
+            .enumerate()
+            .map(|(binding, view)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: wgpu::BindingResource::TextureView(view),
+            })
+            .collect();
+        entries.push(wgpu::BindGroupEntry {
+            binding: 8,
+            resource: self.uniform_buffer.as_entire_binding(),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Checkerboard Resolve BG"),
+            layout: &self.layout,
+            entries: &entries,
+        });
+
+        {
+            let attachments = current.views.each_ref().map(|view| {
+                Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })
+            });
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Checkerboard Resolve Pass"),
+                color_attachments: &attachments,
+                depth_stencil_attachment: None,
+                ..Default::default()
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        let resolved = [
+            &current.albedo,
+            &current.normal,
+            &current.linear_z,
+            &current.material,
+        ];
+        for (source, (destination, _)) in resolved.into_iter().zip(gbuffer) {
+            encoder.copy_texture_to_texture(
+                source.as_image_copy(),
+                destination.as_image_copy(),
+                source.size(),
+            );
+        }
+    }
+}
@@ -1,9 +1,58 @@
+/// Distance mapped to the top of the linear-Z range; must match `shader.wgsl`.
+pub const LINEAR_Z_MAX_DISTANCE: f32 = 100.0;
+/// Largest value stored in the linear-Z target (R16Uint, or R32Uint as a fallback).
+pub const LINEAR_Z_MAX_VALUE: f32 = 65535.0;
+/// G-buffer normal target, octahedral-encoded by the geometry shaders.
+pub const GBUFFER_NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg8Unorm;
+/// Estimated driver-side cost of one bind group, used for memory reporting.
+pub const BIND_GROUP_OVERHEAD_BYTES: u64 = 512;
+
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
     position: [f32; 3],
 }
 
+impl Vertex {
+    pub fn scaled(self, factor: f32) -> Vertex {
+        Vertex {
+            position: self.position.map(|c| c * factor),
+        }
+    }
+}
+
+/// Vertex of user-supplied mesh geometry (`Renderer::upload_mesh`), in world space.
+///
+/// The layout is part of the public API; meshes are uploaded as raw bytes:
+///
+/// | location | field           | format      | byte offset |
+/// |----------|-----------------|-------------|-------------|
+/// | 0        | `position`      | `Float32x3` | 0           |
+/// | 1        | `normal`        | `Float32x3` | 12          |
+/// | 2        | `palette_index` | `Uint32`    | 24          |
+///
+/// Stride is 28 bytes.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub palette_index: u32,
+}
+
+impl MeshVertex {
+    pub const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Uint32];
+
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<MeshVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
 pub const CUBE_VERTICES: &[Vertex] = &[
     Vertex {
         position: [-0.5, -0.5, 0.5],
@@ -99,3 +148,707 @@ pub const CUBE_EDGE_INDICES: &[u16] = &[
     // Connecting edges (front to back)
     0, 4, 1, 5, 2, 6, 3, 7,
 ];
+
+// Icosphere of radius 1, an icosahedron subdivided twice (162 vertices, 320 triangles),
+// as the proxy of `BoundingShape::Icosphere`. Faces wind counter-clockwise from outside.
+pub const ICOSPHERE_VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [-0.5257311, 0.8506508, 0.0],
+    },
+    Vertex {
+        position: [0.5257311, 0.8506508, 0.0],
+    },
+    Vertex {
+        position: [-0.5257311, -0.8506508, 0.0],
+    },
+    Vertex {
+        position: [0.5257311, -0.8506508, 0.0],
+    },
+    Vertex {
+        position: [0.0, -0.5257311, 0.8506508],
+    },
+    Vertex {
+        position: [0.0, 0.5257311, 0.8506508],
+    },
+    Vertex {
+        position: [0.0, -0.5257311, -0.8506508],
+    },
+    Vertex {
+        position: [0.0, 0.5257311, -0.8506508],
+    },
+    Vertex {
+        position: [0.8506508, 0.0, -0.5257311],
+    },
+    Vertex {
+        position: [0.8506508, 0.0, 0.5257311],
+    },
+    Vertex {
+        position: [-0.8506508, 0.0, -0.5257311],
+    },
+    Vertex {
+        position: [-0.8506508, 0.0, 0.5257311],
+    },
+    Vertex {
+        position: [-0.809017, 0.5, 0.309017],
+    },
+    Vertex {
+        position: [-0.5, 0.309017, 0.809017],
+    },
+    Vertex {
+        position: [-0.309017, 0.809017, 0.5],
+    },
+    Vertex {
+        position: [0.309017, 0.809017, 0.5],
+    },
+    Vertex {
+        position: [0.0, 1.0, 0.0],
+    },
+    Vertex {
+        position: [0.309017, 0.809017, -0.5],
+    },
+    Vertex {
+        position: [-0.309017, 0.809017, -0.5],
+    },
+    Vertex {
+        position: [-0.5, 0.309017, -0.809017],
+    },
+    Vertex {
+        position: [-0.809017, 0.5, -0.309017],
+    },
+    Vertex {
+        position: [-1.0, 0.0, 0.0],
+    },
+    Vertex {
+        position: [0.5, 0.309017, 0.809017],
+    },
+    Vertex {
+        position: [0.809017, 0.5, 0.309017],
+    },
+    Vertex {
+        position: [-0.5, -0.309017, 0.809017],
+    },
+    Vertex {
+        position: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [-0.809017, -0.5, -0.309017],
+    },
+    Vertex {
+        position: [-0.809017, -0.5, 0.309017],
+    },
+    Vertex {
+        position: [0.0, 0.0, -1.0],
+    },
+    Vertex {
+        position: [-0.5, -0.309017, -0.809017],
+    },
+    Vertex {
+        position: [0.809017, 0.5, -0.309017],
+    },
+    Vertex {
+        position: [0.5, 0.309017, -0.809017],
+    },
+    Vertex {
+        position: [0.809017, -0.5, 0.309017],
+    },
+    Vertex {
+        position: [0.5, -0.309017, 0.809017],
+    },
+    Vertex {
+        position: [0.309017, -0.809017, 0.5],
+    },
+    Vertex {
+        position: [-0.309017, -0.809017, 0.5],
+    },
+    Vertex {
+        position: [0.0, -1.0, 0.0],
+    },
+    Vertex {
+        position: [-0.309017, -0.809017, -0.5],
+    },
+    Vertex {
+        position: [0.309017, -0.809017, -0.5],
+    },
+    Vertex {
+        position: [0.5, -0.309017, -0.809017],
+    },
+    Vertex {
+        position: [0.809017, -0.5, -0.309017],
+    },
+    Vertex {
+        position: [1.0, 0.0, 0.0],
+    },
+    Vertex {
+        position: [-0.6937805, 0.7020464, 0.160622],
+    },
+    Vertex {
+        position: [-0.5877853, 0.688191, 0.4253254],
+    },
+    Vertex {
+        position: [-0.4338886, 0.8626685, 0.2598919],
+    },
+    Vertex {
+        position: [-0.7020464, 0.160622, 0.6937805],
+    },
+    Vertex {
+        position: [-0.688191, 0.4253254, 0.5877853],
+    },
+    Vertex {
+        position: [-0.8626685, 0.2598919, 0.4338886],
+    },
+    Vertex {
+        position: [-0.160622, 0.6937805, 0.7020464],
+    },
+    Vertex {
+        position: [-0.4253254, 0.5877853, 0.688191],
+    },
+    Vertex {
+        position: [-0.2598919, 0.4338886, 0.8626685],
+    },
+    Vertex {
+        position: [-0.1624598, 0.9510565, 0.2628656],
+    },
+    Vertex {
+        position: [-0.2732665, 0.9619384, 0.0],
+    },
+    Vertex {
+        position: [0.160622, 0.6937805, 0.7020464],
+    },
+    Vertex {
+        position: [0.0, 0.8506508, 0.5257311],
+    },
+    Vertex {
+        position: [0.2732665, 0.9619384, 0.0],
+    },
+    Vertex {
+        position: [0.1624598, 0.9510565, 0.2628656],
+    },
+    Vertex {
+        position: [0.4338886, 0.8626685, 0.2598919],
+    },
+    Vertex {
+        position: [-0.1624598, 0.9510565, -0.2628656],
+    },
+    Vertex {
+        position: [-0.4338886, 0.8626685, -0.2598919],
+    },
+    Vertex {
+        position: [0.4338886, 0.8626685, -0.2598919],
+    },
+    Vertex {
+        position: [0.1624598, 0.9510565, -0.2628656],
+    },
+    Vertex {
+        position: [-0.160622, 0.6937805, -0.7020464],
+    },
+    Vertex {
+        position: [0.0, 0.8506508, -0.5257311],
+    },
+    Vertex {
+        position: [0.160622, 0.6937805, -0.7020464],
+    },
+    Vertex {
+        position: [-0.5877853, 0.688191, -0.4253254],
+    },
+    Vertex {
+        position: [-0.6937805, 0.7020464, -0.160622],
+    },
+    Vertex {
+        position: [-0.2598919, 0.4338886, -0.8626685],
+    },
+    Vertex {
+        position: [-0.4253254, 0.5877853, -0.688191],
+    },
+    Vertex {
+        position: [-0.8626685, 0.2598919, -0.4338886],
+    },
+    Vertex {
+        position: [-0.688191, 0.4253254, -0.5877853],
+    },
+    Vertex {
+        position: [-0.7020464, 0.160622, -0.6937805],
+    },
+    Vertex {
+        position: [-0.8506508, 0.5257311, 0.0],
+    },
+    Vertex {
+        position: [-0.9619384, 0.0, -0.2732665],
+    },
+    Vertex {
+        position: [-0.9510565, 0.2628656, -0.1624598],
+    },
+    Vertex {
+        position: [-0.9510565, 0.2628656, 0.1624598],
+    },
+    Vertex {
+        position: [-0.9619384, 0.0, 0.2732665],
+    },
+    Vertex {
+        position: [0.5877853, 0.688191, 0.4253254],
+    },
+    Vertex {
+        position: [0.6937805, 0.7020464, 0.160622],
+    },
+    Vertex {
+        position: [0.2598919, 0.4338886, 0.8626685],
+    },
+    Vertex {
+        position: [0.4253254, 0.5877853, 0.688191],
+    },
+    Vertex {
+        position: [0.8626685, 0.2598919, 0.4338886],
+    },
+    Vertex {
+        position: [0.688191, 0.4253254, 0.5877853],
+    },
+    Vertex {
+        position: [0.7020464, 0.160622, 0.6937805],
+    },
+    Vertex {
+        position: [-0.2628656, 0.1624598, 0.9510565],
+    },
+    Vertex {
+        position: [0.0, 0.2732665, 0.9619384],
+    },
+    Vertex {
+        position: [-0.7020464, -0.160622, 0.6937805],
+    },
+    Vertex {
+        position: [-0.5257311, 0.0, 0.8506508],
+    },
+    Vertex {
+        position: [0.0, -0.2732665, 0.9619384],
+    },
+    Vertex {
+        position: [-0.2628656, -0.1624598, 0.9510565],
+    },
+    Vertex {
+        position: [-0.2598919, -0.4338886, 0.8626685],
+    },
+    Vertex {
+        position: [-0.9510565, -0.2628656, 0.1624598],
+    },
+    Vertex {
+        position: [-0.8626685, -0.2598919, 0.4338886],
+    },
+    Vertex {
+        position: [-0.8626685, -0.2598919, -0.4338886],
+    },
+    Vertex {
+        position: [-0.9510565, -0.2628656, -0.1624598],
+    },
+    Vertex {
+        position: [-0.6937805, -0.7020464, 0.160622],
+    },
+    Vertex {
+        position: [-0.8506508, -0.5257311, 0.0],
+    },
+    Vertex {
+        position: [-0.6937805, -0.7020464, -0.160622],
+    },
+    Vertex {
+        position: [-0.5257311, 0.0, -0.8506508],
+    },
+    Vertex {
+        position: [-0.7020464, -0.160622, -0.6937805],
+    },
+    Vertex {
+        position: [0.0, 0.2732665, -0.9619384],
+    },
+    Vertex {
+        position: [-0.2628656, 0.1624598, -0.9510565],
+    },
+    Vertex {
+        position: [-0.2598919, -0.4338886, -0.8626685],
+    },
+    Vertex {
+        position: [-0.2628656, -0.1624598, -0.9510565],
+    },
+    Vertex {
+        position: [0.0, -0.2732665, -0.9619384],
+    },
+    Vertex {
+        position: [0.4253254, 0.5877853, -0.688191],
+    },
+    Vertex {
+        position: [0.2598919, 0.4338886, -0.8626685],
+    },
+    Vertex {
+        position: [0.6937805, 0.7020464, -0.160622],
+    },
+    Vertex {
+        position: [0.5877853, 0.688191, -0.4253254],
+    },
+    Vertex {
+        position: [0.7020464, 0.160622, -0.6937805],
+    },
+    Vertex {
+        position: [0.688191, 0.4253254, -0.5877853],
+    },
+    Vertex {
+        position: [0.8626685, 0.2598919, -0.4338886],
+    },
+    Vertex {
+        position: [0.6937805, -0.7020464, 0.160622],
+    },
+    Vertex {
+        position: [0.5877853, -0.688191, 0.4253254],
+    },
+    Vertex {
+        position: [0.4338886, -0.8626685, 0.2598919],
+    },
+    Vertex {
+        position: [0.7020464, -0.160622, 0.6937805],
+    },
+    Vertex {
+        position: [0.688191, -0.4253254, 0.5877853],
+    },
+    Vertex {
+        position: [0.8626685, -0.2598919, 0.4338886],
+    },
+    Vertex {
+        position: [0.160622, -0.6937805, 0.7020464],
+    },
+    Vertex {
+        position: [0.4253254, -0.5877853, 0.688191],
+    },
+    Vertex {
+        position: [0.2598919, -0.4338886, 0.8626685],
+    },
+    Vertex {
+        position: [0.1624598, -0.9510565, 0.2628656],
+    },
+    Vertex {
+        position: [0.2732665, -0.9619384, 0.0],
+    },
+    Vertex {
+        position: [-0.160622, -0.6937805, 0.7020464],
+    },
+    Vertex {
+        position: [0.0, -0.8506508, 0.5257311],
+    },
+    Vertex {
+        position: [-0.2732665, -0.9619384, 0.0],
+    },
+    Vertex {
+        position: [-0.1624598, -0.9510565, 0.2628656],
+    },
+    Vertex {
+        position: [-0.4338886, -0.8626685, 0.2598919],
+    },
+    Vertex {
+        position: [0.1624598, -0.9510565, -0.2628656],
+    },
+    Vertex {
+        position: [0.4338886, -0.8626685, -0.2598919],
+    },
+    Vertex {
+        position: [-0.4338886, -0.8626685, -0.2598919],
+    },
+    Vertex {
+        position: [-0.1624598, -0.9510565, -0.2628656],
+    },
+    Vertex {
+        position: [0.160622, -0.6937805, -0.7020464],
+    },
+    Vertex {
+        position: [0.0, -0.8506508, -0.5257311],
+    },
+    Vertex {
+        position: [-0.160622, -0.6937805, -0.7020464],
+    },
+    Vertex {
+        position: [0.5877853, -0.688191, -0.4253254],
+    },
+    Vertex {
+        position: [0.6937805, -0.7020464, -0.160622],
+    },
+    Vertex {
+        position: [0.2598919, -0.4338886, -0.8626685],
+    },
+    Vertex {
+        position: [0.4253254, -0.5877853, -0.688191],
+    },
+    Vertex {
+        position: [0.8626685, -0.2598919, -0.4338886],
+    },
+    Vertex {
+        position: [0.688191, -0.4253254, -0.5877853],
+    },
+    Vertex {
+        position: [0.7020464, -0.160622, -0.6937805],
+    },
+    Vertex {
+        position: [0.8506508, -0.5257311, 0.0],
+    },
+    Vertex {
+        position: [0.9619384, 0.0, -0.2732665],
+    },
+    Vertex {
+        position: [0.9510565, -0.2628656, -0.1624598],
+    },
+    Vertex {
+        position: [0.9510565, -0.2628656, 0.1624598],
+    },
+    Vertex {
+        position: [0.9619384, 0.0, 0.2732665],
+    },
+    Vertex {
+        position: [0.2628656, -0.1624598, 0.9510565],
+    },
+    Vertex {
+        position: [0.5257311, 0.0, 0.8506508],
+    },
+    Vertex {
+        position: [0.2628656, 0.1624598, 0.9510565],
+    },
+    Vertex {
+        position: [-0.5877853, -0.688191, 0.4253254],
+    },
+    Vertex {
+        position: [-0.4253254, -0.5877853, 0.688191],
+    },
+    Vertex {
+        position: [-0.688191, -0.4253254, 0.5877853],
+    },
+    Vertex {
+        position: [-0.4253254, -0.5877853, -0.688191],
+    },
+    Vertex {
+        position: [-0.5877853, -0.688191, -0.4253254],
+    },
+    Vertex {
+        position: [-0.688191, -0.4253254, -0.5877853],
+    },
+    Vertex {
+        position: [0.5257311, 0.0, -0.8506508],
+    },
+    Vertex {
+        position: [0.2628656, -0.1624598, -0.9510565],
+    },
+    Vertex {
+        position: [0.2628656, 0.1624598, -0.9510565],
+    },
+    Vertex {
+        position: [0.9510565, 0.2628656, 0.1624598],
+    },
+    Vertex {
+        position: [0.9510565, 0.2628656, -0.1624598],
+    },
+    Vertex {
+        position: [0.8506508, 0.5257311, 0.0],
+    },
+];
+
+pub const ICOSPHERE_INDICES: &[u16] = &[
+    0, 42, 44, 12, 43, 42, 14, 44, 43, 42, 43, 44, 11, 45, 47, 13, 46, 45, 12, 47, 46, 45, 46, 47,
+    5, 48, 50, 14, 49, 48, 13, 50, 49, 48, 49, 50, 12, 46, 43, 13, 49, 46, 14, 43, 49, 46, 49, 43,
+    0, 44, 52, 14, 51, 44, 16, 52, 51, 44, 51, 52, 5, 53, 48, 15, 54, 53, 14, 48, 54, 53, 54, 48,
+    1, 55, 57, 16, 56, 55, 15, 57, 56, 55, 56, 57, 14, 54, 51, 15, 56, 54, 16, 51, 56, 54, 56, 51,
+    0, 52, 59, 16, 58, 52, 18, 59, 58, 52, 58, 59, 1, 60, 55, 17, 61, 60, 16, 55, 61, 60, 61, 55,
+    7, 62, 64, 18, 63, 62, 17, 64, 63, 62, 63, 64, 16, 61, 58, 17, 63, 61, 18, 58, 63, 61, 63, 58,
+    0, 59, 66, 18, 65, 59, 20, 66, 65, 59, 65, 66, 7, 67, 62, 19, 68, 67, 18, 62, 68, 67, 68, 62,
+    10, 69, 71, 20, 70, 69, 19, 71, 70, 69, 70, 71, 18, 68, 65, 19, 70, 68, 20, 65, 70, 68, 70, 65,
+    0, 66, 42, 20, 72, 66, 12, 42, 72, 66, 72, 42, 10, 73, 69, 21, 74, 73, 20, 69, 74, 73, 74, 69,
+    11, 47, 76, 12, 75, 47, 21, 76, 75, 47, 75, 76, 20, 74, 72, 21, 75, 74, 12, 72, 75, 74, 75, 72,
+    1, 57, 78, 15, 77, 57, 23, 78, 77, 57, 77, 78, 5, 79, 53, 22, 80, 79, 15, 53, 80, 79, 80, 53,
+    9, 81, 83, 23, 82, 81, 22, 83, 82, 81, 82, 83, 15, 80, 77, 22, 82, 80, 23, 77, 82, 80, 82, 77,
+    5, 50, 85, 13, 84, 50, 25, 85, 84, 50, 84, 85, 11, 86, 45, 24, 87, 86, 13, 45, 87, 86, 87, 45,
+    4, 88, 90, 25, 89, 88, 24, 90, 89, 88, 89, 90, 13, 87, 84, 24, 89, 87, 25, 84, 89, 87, 89, 84,
+    11, 76, 92, 21, 91, 76, 27, 92, 91, 76, 91, 92, 10, 93, 73, 26, 94, 93, 21, 73, 94, 93, 94, 73,
+    2, 95, 97, 27, 96, 95, 26, 97, 96, 95, 96, 97, 21, 94, 91, 26, 96, 94, 27, 91, 96, 94, 96, 91,
+    10, 71, 99, 19, 98, 71, 29, 99, 98, 71, 98, 99, 7, 100, 67, 28, 101, 100, 19, 67, 101, 100,
+    101, 67, 6, 102, 104, 29, 103, 102, 28, 104, 103, 102, 103, 104, 19, 101, 98, 28, 103, 101, 29,
+    98, 103, 101, 103, 98, 7, 64, 106, 17, 105, 64, 31, 106, 105, 64, 105, 106, 1, 107, 60, 30,
+    108, 107, 17, 60, 108, 107, 108, 60, 8, 109, 111, 31, 110, 109, 30, 111, 110, 109, 110, 111,
+    17, 108, 105, 30, 110, 108, 31, 105, 110, 108, 110, 105, 3, 112, 114, 32, 113, 112, 34, 114,
+    113, 112, 113, 114, 9, 115, 117, 33, 116, 115, 32, 117, 116, 115, 116, 117, 4, 118, 120, 34,
+    119, 118, 33, 120, 119, 118, 119, 120, 32, 116, 113, 33, 119, 116, 34, 113, 119, 116, 119, 113,
+    3, 114, 122, 34, 121, 114, 36, 122, 121, 114, 121, 122, 4, 123, 118, 35, 124, 123, 34, 118,
+    124, 123, 124, 118, 2, 125, 127, 36, 126, 125, 35, 127, 126, 125, 126, 127, 34, 124, 121, 35,
+    126, 124, 36, 121, 126, 124, 126, 121, 3, 122, 129, 36, 128, 122, 38, 129, 128, 122, 128, 129,
+    2, 130, 125, 37, 131, 130, 36, 125, 131, 130, 131, 125, 6, 132, 134, 38, 133, 132, 37, 134,
+    133, 132, 133, 134, 36, 131, 128, 37, 133, 131, 38, 128, 133, 131, 133, 128, 3, 129, 136, 38,
+    135, 129, 40, 136, 135, 129, 135, 136, 6, 137, 132, 39, 138, 137, 38, 132, 138, 137, 138, 132,
+    8, 139, 141, 40, 140, 139, 39, 141, 140, 139, 140, 141, 38, 138, 135, 39, 140, 138, 40, 135,
+    140, 138, 140, 135, 3, 136, 112, 40, 142, 136, 32, 112, 142, 136, 142, 112, 8, 143, 139, 41,
+    144, 143, 40, 139, 144, 143, 144, 139, 9, 117, 146, 32, 145, 117, 41, 146, 145, 117, 145, 146,
+    40, 144, 142, 41, 145, 144, 32, 142, 145, 144, 145, 142, 4, 120, 88, 33, 147, 120, 25, 88, 147,
+    120, 147, 88, 9, 83, 115, 22, 148, 83, 33, 115, 148, 83, 148, 115, 5, 85, 79, 25, 149, 85, 22,
+    79, 149, 85, 149, 79, 33, 148, 147, 22, 149, 148, 25, 147, 149, 148, 149, 147, 2, 127, 95, 35,
+    150, 127, 27, 95, 150, 127, 150, 95, 4, 90, 123, 24, 151, 90, 35, 123, 151, 90, 151, 123, 11,
+    92, 86, 27, 152, 92, 24, 86, 152, 92, 152, 86, 35, 151, 150, 24, 152, 151, 27, 150, 152, 151,
+    152, 150, 6, 134, 102, 37, 153, 134, 29, 102, 153, 134, 153, 102, 2, 97, 130, 26, 154, 97, 37,
+    130, 154, 97, 154, 130, 10, 99, 93, 29, 155, 99, 26, 93, 155, 99, 155, 93, 37, 154, 153, 26,
+    155, 154, 29, 153, 155, 154, 155, 153, 8, 141, 109, 39, 156, 141, 31, 109, 156, 141, 156, 109,
+    6, 104, 137, 28, 157, 104, 39, 137, 157, 104, 157, 137, 7, 106, 100, 31, 158, 106, 28, 100,
+    158, 106, 158, 100, 39, 157, 156, 28, 158, 157, 31, 156, 158, 157, 158, 156, 9, 146, 81, 41,
+    159, 146, 23, 81, 159, 146, 159, 81, 8, 111, 143, 30, 160, 111, 41, 143, 160, 111, 160, 143, 1,
+    78, 107, 23, 161, 78, 30, 107, 161, 78, 161, 107, 41, 160, 159, 30, 161, 160, 23, 159, 161,
+    160, 161, 159,
+];
+
+// Line list of the icosphere's 480 edges
+pub const ICOSPHERE_EDGE_INDICES: &[u16] = &[
+    0, 42, 0, 44, 0, 52, 0, 59, 0, 66, 1, 55, 1, 57, 1, 60, 1, 78, 1, 107, 2, 95, 2, 97, 2, 125, 2,
+    127, 2, 130, 3, 112, 3, 114, 3, 122, 3, 129, 3, 136, 4, 88, 4, 90, 4, 118, 4, 120, 4, 123, 5,
+    48, 5, 50, 5, 53, 5, 79, 5, 85, 6, 102, 6, 104, 6, 132, 6, 134, 6, 137, 7, 62, 7, 64, 7, 67, 7,
+    100, 7, 106, 8, 109, 8, 111, 8, 139, 8, 141, 8, 143, 9, 81, 9, 83, 9, 115, 9, 117, 9, 146, 10,
+    69, 10, 71, 10, 73, 10, 93, 10, 99, 11, 45, 11, 47, 11, 76, 11, 86, 11, 92, 12, 42, 12, 43, 12,
+    46, 12, 47, 12, 72, 12, 75, 13, 45, 13, 46, 13, 49, 13, 50, 13, 84, 13, 87, 14, 43, 14, 44, 14,
+    48, 14, 49, 14, 51, 14, 54, 15, 53, 15, 54, 15, 56, 15, 57, 15, 77, 15, 80, 16, 51, 16, 52, 16,
+    55, 16, 56, 16, 58, 16, 61, 17, 60, 17, 61, 17, 63, 17, 64, 17, 105, 17, 108, 18, 58, 18, 59,
+    18, 62, 18, 63, 18, 65, 18, 68, 19, 67, 19, 68, 19, 70, 19, 71, 19, 98, 19, 101, 20, 65, 20,
+    66, 20, 69, 20, 70, 20, 72, 20, 74, 21, 73, 21, 74, 21, 75, 21, 76, 21, 91, 21, 94, 22, 79, 22,
+    80, 22, 82, 22, 83, 22, 148, 22, 149, 23, 77, 23, 78, 23, 81, 23, 82, 23, 159, 23, 161, 24, 86,
+    24, 87, 24, 89, 24, 90, 24, 151, 24, 152, 25, 84, 25, 85, 25, 88, 25, 89, 25, 147, 25, 149, 26,
+    93, 26, 94, 26, 96, 26, 97, 26, 154, 26, 155, 27, 91, 27, 92, 27, 95, 27, 96, 27, 150, 27, 152,
+    28, 100, 28, 101, 28, 103, 28, 104, 28, 157, 28, 158, 29, 98, 29, 99, 29, 102, 29, 103, 29,
+    153, 29, 155, 30, 107, 30, 108, 30, 110, 30, 111, 30, 160, 30, 161, 31, 105, 31, 106, 31, 109,
+    31, 110, 31, 156, 31, 158, 32, 112, 32, 113, 32, 116, 32, 117, 32, 142, 32, 145, 33, 115, 33,
+    116, 33, 119, 33, 120, 33, 147, 33, 148, 34, 113, 34, 114, 34, 118, 34, 119, 34, 121, 34, 124,
+    35, 123, 35, 124, 35, 126, 35, 127, 35, 150, 35, 151, 36, 121, 36, 122, 36, 125, 36, 126, 36,
+    128, 36, 131, 37, 130, 37, 131, 37, 133, 37, 134, 37, 153, 37, 154, 38, 128, 38, 129, 38, 132,
+    38, 133, 38, 135, 38, 138, 39, 137, 39, 138, 39, 140, 39, 141, 39, 156, 39, 157, 40, 135, 40,
+    136, 40, 139, 40, 140, 40, 142, 40, 144, 41, 143, 41, 144, 41, 145, 41, 146, 41, 159, 41, 160,
+    42, 43, 42, 44, 42, 66, 42, 72, 43, 44, 43, 46, 43, 49, 44, 51, 44, 52, 45, 46, 45, 47, 45, 86,
+    45, 87, 46, 47, 46, 49, 47, 75, 47, 76, 48, 49, 48, 50, 48, 53, 48, 54, 49, 50, 50, 84, 50, 85,
+    51, 52, 51, 54, 51, 56, 52, 58, 52, 59, 53, 54, 53, 79, 53, 80, 54, 56, 55, 56, 55, 57, 55, 60,
+    55, 61, 56, 57, 57, 77, 57, 78, 58, 59, 58, 61, 58, 63, 59, 65, 59, 66, 60, 61, 60, 107, 60,
+    108, 61, 63, 62, 63, 62, 64, 62, 67, 62, 68, 63, 64, 64, 105, 64, 106, 65, 66, 65, 68, 65, 70,
+    66, 72, 67, 68, 67, 100, 67, 101, 68, 70, 69, 70, 69, 71, 69, 73, 69, 74, 70, 71, 71, 98, 71,
+    99, 72, 74, 72, 75, 73, 74, 73, 93, 73, 94, 74, 75, 75, 76, 76, 91, 76, 92, 77, 78, 77, 80, 77,
+    82, 78, 107, 78, 161, 79, 80, 79, 85, 79, 149, 80, 82, 81, 82, 81, 83, 81, 146, 81, 159, 82,
+    83, 83, 115, 83, 148, 84, 85, 84, 87, 84, 89, 85, 149, 86, 87, 86, 92, 86, 152, 87, 89, 88, 89,
+    88, 90, 88, 120, 88, 147, 89, 90, 90, 123, 90, 151, 91, 92, 91, 94, 91, 96, 92, 152, 93, 94,
+    93, 99, 93, 155, 94, 96, 95, 96, 95, 97, 95, 127, 95, 150, 96, 97, 97, 130, 97, 154, 98, 99,
+    98, 101, 98, 103, 99, 155, 100, 101, 100, 106, 100, 158, 101, 103, 102, 103, 102, 104, 102,
+    134, 102, 153, 103, 104, 104, 137, 104, 157, 105, 106, 105, 108, 105, 110, 106, 158, 107, 108,
+    107, 161, 108, 110, 109, 110, 109, 111, 109, 141, 109, 156, 110, 111, 111, 143, 111, 160, 112,
+    113, 112, 114, 112, 136, 112, 142, 113, 114, 113, 116, 113, 119, 114, 121, 114, 122, 115, 116,
+    115, 117, 115, 148, 116, 117, 116, 119, 117, 145, 117, 146, 118, 119, 118, 120, 118, 123, 118,
+    124, 119, 120, 120, 147, 121, 122, 121, 124, 121, 126, 122, 128, 122, 129, 123, 124, 123, 151,
+    124, 126, 125, 126, 125, 127, 125, 130, 125, 131, 126, 127, 127, 150, 128, 129, 128, 131, 128,
+    133, 129, 135, 129, 136, 130, 131, 130, 154, 131, 133, 132, 133, 132, 134, 132, 137, 132, 138,
+    133, 134, 134, 153, 135, 136, 135, 138, 135, 140, 136, 142, 137, 138, 137, 157, 138, 140, 139,
+    140, 139, 141, 139, 143, 139, 144, 140, 141, 141, 156, 142, 144, 142, 145, 143, 144, 143, 160,
+    144, 145, 145, 146, 146, 159, 147, 148, 147, 149, 148, 149, 150, 151, 150, 152, 151, 152, 153,
+    154, 153, 155, 154, 155, 156, 157, 156, 158, 157, 158, 159, 160, 159, 161, 160, 161,
+];
+
+/// Distance from the centre to the nearest icosphere face, rounded down. The proxy
+/// scales the icosphere by `0.5 / ICOSPHERE_INRADIUS` so its faces enclose the
+/// ellipsoid inscribed in the unit cube.
+pub const ICOSPHERE_INRADIUS: f32 = 0.9822;
+
+// UV sphere of diameter 1 (matching the unit cube) used as placeholder geometry for
+// far-away objects. Not drawn yet: there is no LOD selection to pick it.
+const SPHERE_LATITUDES: usize = 8;
+const SPHERE_LONGITUDES: usize = 16;
+const SPHERE_VERTEX_COUNT: usize = (SPHERE_LATITUDES + 1) * SPHERE_LONGITUDES;
+// Pole rows emit one triangle per quad, the others two
+const SPHERE_INDEX_COUNT: usize = (SPHERE_LATITUDES - 1) * SPHERE_LONGITUDES * 6;
+
+#[allow(dead_code)]
+pub const SPHERE_VERTICES: &[Vertex] = &sphere_vertices();
+#[allow(dead_code)]
+pub const SPHERE_INDICES: &[u16] = &sphere_indices();
+
+/// Camera-facing quad standing in for the sphere, in the same winding as the cube faces.
+#[allow(dead_code)]
+pub const SPHERE_BILLBOARD_VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [-0.5, -0.5, 0.0],
+    },
+    Vertex {
+        position: [0.5, -0.5, 0.0],
+    },
+    Vertex {
+        position: [0.5, 0.5, 0.0],
+    },
+    Vertex {
+        position: [-0.5, 0.5, 0.0],
+    },
+];
+
+#[allow(dead_code)]
+pub const SPHERE_BILLBOARD_INDICES: &[u16] = &[0, 1, 2, 0, 2, 3];
+
+/// Sine and cosine by Taylor series, for angles in `[0, 2π]`.
+const fn sin_cos(angle: f64) -> (f64, f64) {
+    let x = if angle > std::f64::consts::PI {
+        angle - std::f64::consts::TAU
+    } else {
+        angle
+    };
+    let (mut sin, mut cos) = (0.0, 0.0);
+    let (mut sin_term, mut cos_term) = (x, 1.0);
+    let mut n = 0;
+    while n < 12 {
+        sin += sin_term;
+        cos += cos_term;
+        let k = (2 * n + 2) as f64;
+        sin_term *= -x * x / (k * (k + 1.0));
+        cos_term *= -x * x / ((k - 1.0) * k);
+        n += 1;
+    }
+    (sin, cos)
+}
+
+/// Rings run from the +Y pole to the -Y pole; each repeats its pole vertex per
+/// longitude so every quad has its own corners.
+const fn sphere_vertices() -> [Vertex; SPHERE_VERTEX_COUNT] {
+    let mut vertices = [Vertex { position: [0.0; 3] }; SPHERE_VERTEX_COUNT];
+    let mut lat = 0;
+    while lat <= SPHERE_LATITUDES {
+        let theta = std::f64::consts::PI * lat as f64 / SPHERE_LATITUDES as f64;
+        let (sin_theta, cos_theta) = sin_cos(theta);
+        let mut lon = 0;
+        while lon < SPHERE_LONGITUDES {
+            let phi = std::f64::consts::TAU * lon as f64 / SPHERE_LONGITUDES as f64;
+            let (sin_phi, cos_phi) = sin_cos(phi);
+            vertices[lat * SPHERE_LONGITUDES + lon] = Vertex {
+                position: [
+                    (0.5 * sin_theta * cos_phi) as f32,
+                    (0.5 * cos_theta) as f32,
+                    (0.5 * sin_theta * sin_phi) as f32,
+                ],
+            };
+            lon += 1;
+        }
+        lat += 1;
+    }
+    vertices
+}
+
+/// Counter-clockwise seen from outside, skipping the degenerate triangles at the poles.
+const fn sphere_indices() -> [u16; SPHERE_INDEX_COUNT] {
+    let mut indices = [0; SPHERE_INDEX_COUNT];
+    let mut i = 0;
+    let mut lat = 0;
+    while lat < SPHERE_LATITUDES {
+        let mut lon = 0;
+        while lon < SPHERE_LONGITUDES {
+            let next = (lon + 1) % SPHERE_LONGITUDES;
+            let a = (lat * SPHERE_LONGITUDES + lon) as u16;
+            let b = (lat * SPHERE_LONGITUDES + next) as u16;
+            let c = ((lat + 1) * SPHERE_LONGITUDES + lon) as u16;
+            let d = ((lat + 1) * SPHERE_LONGITUDES + next) as u16;
+            if lat != 0 {
+                indices[i] = a;
+                indices[i + 1] = b;
+                indices[i + 2] = c;
+                i += 3;
+            }
+            if lat != SPHERE_LATITUDES - 1 {
+                indices[i] = b;
+                indices[i + 1] = d;
+                indices[i + 2] = c;
+                i += 3;
+            }
+            lon += 1;
+        }
+        lat += 1;
+    }
+    indices
+}
@@ -0,0 +1,162 @@
+//! Immediate-mode boxes for editor gizmos (selection cursors, brush previews), queued
+//! by `Renderer::draw_debug_box` and drawn over the next frame only.
+//!
+//! Each box is one instance of the proxy cube's edges (shaders/debug_box.wgsl),
+//! stretched from its min to its max corner in world space. Boxes are drawn without
+//! a depth test, so they stay visible behind the voxels they outline.
+
+use crate::constants::{Vertex, CUBE_EDGE_INDICES};
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DebugBox {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    pub color: [f32; 4],
+}
+
+pub struct DebugBoxPass {
+    pipeline: wgpu::RenderPipeline,
+    /// The renderer's proxy buffers, which start with `CUBE_VERTICES` and
+    /// `CUBE_EDGE_INDICES`
+    vertex_buffer: wgpu::Buffer,
+    edge_index_buffer: wgpu::Buffer,
+    /// Boxes of the frame, grown to the most queued in one frame.
+    instance_buffer: wgpu::Buffer,
+    capacity: u64,
+    boxes: Vec<DebugBox>,
+}
+
+impl DebugBoxPass {
+    /// `per_frame_layout` is the renderer's per-frame uniform layout, for the camera.
+    pub fn new(
+        device: &wgpu::Device,
+        (vertex_buffer, edge_index_buffer): (&wgpu::Buffer, &wgpu::Buffer),
+        per_frame_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Debug Box Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/debug_box.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Box Pipeline Layout"),
+            bind_group_layouts: &[per_frame_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Debug Box Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+                    },
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<DebugBox>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &wgpu::vertex_attr_array![
+                            1 => Float32x3, 2 => Float32x3, 3 => Float32x4
+                        ],
+                    },
+                ],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+            cache,
+        });
+
+        DebugBoxPass {
+            pipeline,
+            vertex_buffer: vertex_buffer.clone(),
+            edge_index_buffer: edge_index_buffer.clone(),
+            instance_buffer: create_instance_buffer(device, 1),
+            capacity: 1,
+            boxes: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, debug_box: DebugBox) {
+        self.boxes.push(debug_box);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boxes.is_empty()
+    }
+
+    /// Forgets the queued boxes, once their frame has been recorded.
+    pub fn clear(&mut self) {
+        self.boxes.clear();
+    }
+
+    /// Uploads the queued boxes for `encode`.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.boxes.len() as u64 > self.capacity {
+            self.capacity = (self.boxes.len() as u64).next_power_of_two();
+            self.instance_buffer = create_instance_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&self.boxes));
+    }
+
+    /// Draws the boxes uploaded by `prepare` over `output`.
+    pub fn encode(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        per_frame_bind_group: &wgpu::BindGroup,
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Debug Box Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            ..Default::default()
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, per_frame_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        pass.set_index_buffer(self.edge_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        pass.draw_indexed(
+            0..CUBE_EDGE_INDICES.len() as u32,
+            0,
+            0..self.boxes.len() as u32,
+        );
+    }
+}
+
+fn create_instance_buffer(device: &wgpu::Device, capacity: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Debug Box Instance Buffer"),
+        size: capacity * std::mem::size_of::<DebugBox>() as u64,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
@@ -0,0 +1,135 @@
+//! Vertex-only pipelines writing just depth, for `Renderer::render_depth_only`.
+
+use crate::constants::{MeshVertex, Vertex};
+use crate::scene::ColorMode;
+
+/// Depth format of `render_depth_only` targets, the same as the main depth buffer.
+pub const DEPTH_ONLY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+pub struct DepthOnlyPass {
+    pub uniform_buffer: wgpu::Buffer,
+    /// Binds only the view-projection matrix, unlike the camera's per-frame group.
+    pub bind_group: wgpu::BindGroup,
+    pub voxel_pipeline: wgpu::RenderPipeline,
+    /// `voxel_pipeline` for the per-draw bind groups of `ColorMode::Rgba` objects
+    pub rgba_voxel_pipeline: wgpu::RenderPipeline,
+    pub mesh_pipeline: wgpu::RenderPipeline,
+}
+
+impl DepthOnlyPass {
+    /// `per_draw_bind_group_layouts` are those of palette and of rgba objects.
+    pub fn new(
+        device: &wgpu::Device,
+        per_draw_bind_group_layouts: [&wgpu::BindGroupLayout; 2],
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Depth Only Uniform Buffer"),
+            size: std::mem::size_of::<[f32; 16]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Depth Only Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Depth Only Bind Group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Depth Only Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/depth_only.wgsl").into()),
+        });
+        let [palette_layout, rgba_layout] = per_draw_bind_group_layouts.map(|per_draw| {
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Depth Only Voxel Pipeline Layout"),
+                bind_group_layouts: &[&layout, per_draw],
+                push_constant_ranges: &[],
+            })
+        });
+        let mesh_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Depth Only Mesh Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let create_pipeline = |label: &str,
+                               layout: &wgpu::PipelineLayout,
+                               entry_point: &str,
+                               buffer: wgpu::VertexBufferLayout| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    buffers: &[buffer],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: None,
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DEPTH_ONLY_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache,
+            })
+        };
+
+        let voxel_pipeline = |layout| {
+            create_pipeline(
+                "Depth Only Voxel Pipeline",
+                layout,
+                "vs_voxel",
+                wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+                },
+            )
+        };
+        DepthOnlyPass {
+            voxel_pipeline: voxel_pipeline(&palette_layout),
+            rgba_voxel_pipeline: voxel_pipeline(&rgba_layout),
+            // Only the position of each mesh vertex is read
+            mesh_pipeline: create_pipeline(
+                "Depth Only Mesh Pipeline",
+                &mesh_layout,
+                "vs_mesh",
+                wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<MeshVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+                },
+            ),
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    pub fn voxel_pipeline_for(&self, color_mode: ColorMode) -> &wgpu::RenderPipeline {
+        match color_mode {
+            ColorMode::Palette => &self.voxel_pipeline,
+            ColorMode::Rgba => &self.rgba_voxel_pipeline,
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! Detection of a lost GPU device, and the token of `Renderer::reinitialize`.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::utils::log;
+
+/// Set once the device it watches is lost, other than by being destroyed.
+pub struct DeviceLostFlag(Arc<AtomicBool>);
+
+impl DeviceLostFlag {
+    pub fn watch(device: &wgpu::Device) -> Self {
+        let lost = Arc::new(AtomicBool::new(false));
+        let flag = lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // Dropping the device on purpose reports `Destroyed`
+            if reason != wgpu::DeviceLostReason::Destroyed {
+                log::error!("GPU device lost: {}", message);
+                flag.store(true, Ordering::Relaxed);
+            }
+        });
+        DeviceLostFlag(lost)
+    }
+
+    pub fn is_lost(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Held by the renderer while a reinitialization runs. Dropping it (another
+/// reinitialization or the renderer being freed) makes it give up before it touches
+/// the renderer.
+pub struct ReinitializeToken(Rc<Cell<bool>>);
+
+impl ReinitializeToken {
+    /// The token, and the flag it sets when dropped.
+    pub fn new() -> (Self, Rc<Cell<bool>>) {
+        let cancelled = Rc::new(Cell::new(false));
+        (ReinitializeToken(cancelled.clone()), cancelled)
+    }
+}
+
+impl Drop for ReinitializeToken {
+    fn drop(&mut self) {
+        self.0.set(true);
+    }
+}
@@ -0,0 +1,74 @@
+//! Render scale controller for `Renderer::enable_dynamic_resolution`.
+
+/// Lower and upper bounds of `Renderer::set_render_scale`.
+pub const MIN_RENDER_SCALE: f32 = 0.1;
+pub const MAX_RENDER_SCALE: f32 = 1.0;
+
+/// Scale change per adjustment.
+const STEP: f32 = 0.05;
+/// Frames averaged after a change before the next is decided, so the average
+/// reflects the new resolution.
+const SETTLE_FRAMES: u32 = 8;
+/// Longer gaps between frames (a paused loop, a hidden page, `render_if_needed`
+/// idling) say nothing about the cost of a frame and restart the average.
+const STALL_MS: f64 = 250.0;
+/// Average frame times within this share of the target interval keep the scale.
+const SLACK: f64 = 0.05;
+
+/// Steps the render scale down while frames take longer than the target interval,
+/// and back up while they are well within it.
+pub struct DynamicResolution {
+    target_ms: f64,
+    pub min_scale: f32,
+    pub max_scale: f32,
+    last_ms: Option<f64>,
+    average_ms: f64,
+    samples: u32,
+}
+
+impl DynamicResolution {
+    pub fn new(target_fps: f32, min_scale: f32, max_scale: f32) -> Self {
+        DynamicResolution {
+            target_ms: 1000.0 / target_fps as f64,
+            min_scale,
+            max_scale,
+            last_ms: None,
+            average_ms: 0.0,
+            samples: 0,
+        }
+    }
+
+    /// Scale for the frame starting at `now_ms`, given the current `scale`.
+    pub fn update(&mut self, now_ms: f64, scale: f32) -> f32 {
+        let Some(last) = self.last_ms.replace(now_ms) else {
+            return scale;
+        };
+        let elapsed = now_ms - last;
+        if !(0.0..=STALL_MS).contains(&elapsed) {
+            self.samples = 0;
+            return scale;
+        }
+        self.average_ms = if self.samples == 0 {
+            elapsed
+        } else {
+            self.average_ms + (elapsed - self.average_ms) * 0.25
+        };
+        self.samples += 1;
+        if self.samples < SETTLE_FRAMES {
+            return scale;
+        }
+
+        let next = if self.average_ms > self.target_ms * (1.0 + SLACK) {
+            scale - STEP
+        } else if self.average_ms < self.target_ms * (1.0 - SLACK) {
+            scale + STEP
+        } else {
+            scale
+        };
+        let next = next.clamp(self.min_scale, self.max_scale);
+        if next != scale {
+            self.samples = 0;
+        }
+        next
+    }
+}
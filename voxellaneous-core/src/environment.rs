@@ -0,0 +1,322 @@
+//! Equirectangular environment map for image-based lighting in the lighting pass.
+//!
+//! The image is uploaded with a box-filtered mip chain that stands in for a
+//! roughness prefilter (specular reflections sample it at a level picked from the
+//! roughness), and its irradiance is projected onto nine spherical harmonics on the
+//! CPU for diffuse ambient. Directions map to the image with +y at the top row and
+//! -z at the centre column; must match quad_lighting.wgsl.
+
+use std::f32::consts::PI;
+use wgpu::util::DeviceExt;
+
+const ENVIRONMENT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// The SH projection reads the first mip no larger than this on either axis.
+const SH_MAX_SIZE: u32 = 128;
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct EnvironmentUniforms {
+    /// Cosine-convolved SH of the radiance, scaled so that a uniform environment
+    /// evaluates to its radiance. rgb per coefficient.
+    sh: [[f32; 4]; 9],
+    /// 0 = no environment: flat ambient and a black background
+    enabled: u32,
+    /// Mip level sampled at roughness 1
+    max_lod: f32,
+    _padding: [u32; 2],
+}
+
+pub struct Environment {
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    pub uniform_buffer: wgpu::Buffer,
+}
+
+impl Environment {
+    /// No environment: a 1x1 black placeholder with the flat-ambient path selected.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Environment Sampler"),
+            // Longitude wraps around, latitude stops at the poles
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Environment Uniform Buffer"),
+            contents: bytemuck::bytes_of(&EnvironmentUniforms::default()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        Environment {
+            view: placeholder_view(device, queue),
+            sampler,
+            uniform_buffer,
+        }
+    }
+
+    /// Replaces the environment with the `width` x `height` equirectangular image
+    /// `pixels`: linear RGBA floats, rows from top to bottom.
+    pub fn set_image(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        pixels: &[f32],
+        width: u32,
+        height: u32,
+    ) -> Result<(), String> {
+        if width == 0 || height == 0 {
+            return Err("environment image is empty".to_string());
+        }
+        let expected = width as usize * height as usize * 4;
+        if pixels.len() != expected {
+            return Err(format!(
+                "expected {} floats (RGBA) for a {}x{} environment, got {}",
+                expected,
+                width,
+                height,
+                pixels.len()
+            ));
+        }
+        let max = device.limits().max_texture_dimension_2d;
+        if width > max || height > max {
+            return Err(format!(
+                "environment image is {}x{}, the device supports at most {}x{}",
+                width, height, max, max
+            ));
+        }
+
+        let mut levels = vec![Level {
+            width,
+            height,
+            texels: pixels
+                .chunks_exact(4)
+                .map(|texel| [texel[0], texel[1], texel[2], 1.0])
+                .collect(),
+        }];
+        while let Some(next) = levels.last().and_then(Level::downsample) {
+            levels.push(next);
+        }
+        let sh_level = levels
+            .iter()
+            .find(|level| level.width.max(level.height) <= SH_MAX_SIZE)
+            .unwrap_or(&levels[levels.len() - 1]);
+        let uniforms = EnvironmentUniforms {
+            sh: project_irradiance(sh_level),
+            enabled: 1,
+            max_lod: (levels.len() - 1) as f32,
+            _padding: [0; 2],
+        };
+
+        let texture = create_environment_texture(device, width, height, levels.len() as u32);
+        for (mip, level) in levels.iter().enumerate() {
+            let halves: Vec<u16> = level
+                .texels
+                .iter()
+                .flatten()
+                .map(|&v| f32_to_f16(v))
+                .collect();
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: mip as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                bytemuck::cast_slice(&halves),
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(level.width * 8),
+                    rows_per_image: None,
+                },
+                wgpu::Extent3d {
+                    width: level.width,
+                    height: level.height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        self.view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+        Ok(())
+    }
+
+    /// Removes the environment, restoring flat ambient.
+    pub fn clear(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.view = placeholder_view(device, queue);
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&EnvironmentUniforms::default()),
+        );
+    }
+}
+
+impl Default for EnvironmentUniforms {
+    fn default() -> Self {
+        EnvironmentUniforms {
+            sh: [[0.0; 4]; 9],
+            enabled: 0,
+            max_lod: 0.0,
+            _padding: [0; 2],
+        }
+    }
+}
+
+struct Level {
+    width: u32,
+    height: u32,
+    texels: Vec<[f32; 4]>,
+}
+
+impl Level {
+    /// The next mip: the 2x2 box average, or `None` at 1x1.
+    fn downsample(&self) -> Option<Level> {
+        if self.width == 1 && self.height == 1 {
+            return None;
+        }
+        let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let mut texels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0.0; 4];
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let sx = (x * 2 + dx).min(self.width - 1);
+                    let sy = (y * 2 + dy).min(self.height - 1);
+                    let texel = self.texels[(sy * self.width + sx) as usize];
+                    for (total, value) in sum.iter_mut().zip(texel) {
+                        *total += value * 0.25;
+                    }
+                }
+                texels.push(sum);
+            }
+        }
+        Some(Level {
+            width,
+            height,
+            texels,
+        })
+    }
+}
+
+/// Projects `level` onto the first nine real spherical harmonics, weighting each texel
+/// by its solid angle, and applies the clamped-cosine convolution (Ramamoorthi and
+/// Hanrahan 2001) divided by pi, so the result evaluates to the mean cosine-weighted
+/// radiance around a normal.
+fn project_irradiance(level: &Level) -> [[f32; 4]; 9] {
+    const BAND_SCALE: [f32; 9] = [
+        1.0,
+        2.0 / 3.0,
+        2.0 / 3.0,
+        2.0 / 3.0,
+        0.25,
+        0.25,
+        0.25,
+        0.25,
+        0.25,
+    ];
+    let mut sh = [[0.0; 4]; 9];
+    let texel_angle = (2.0 * PI / level.width as f32) * (PI / level.height as f32);
+    for y in 0..level.height {
+        let theta = (y as f32 + 0.5) / level.height as f32 * PI;
+        let weight = texel_angle * theta.sin();
+        for x in 0..level.width {
+            let phi = ((x as f32 + 0.5) / level.width as f32 - 0.5) * 2.0 * PI;
+            let dir = [
+                theta.sin() * phi.sin(),
+                theta.cos(),
+                -theta.sin() * phi.cos(),
+            ];
+            let texel = level.texels[(y * level.width + x) as usize];
+            for (coefficient, basis) in sh.iter_mut().zip(sh_basis(dir)) {
+                for channel in 0..3 {
+                    coefficient[channel] += texel[channel] * basis * weight;
+                }
+            }
+        }
+    }
+    for (coefficient, scale) in sh.iter_mut().zip(BAND_SCALE) {
+        for value in coefficient.iter_mut().take(3) {
+            *value *= scale;
+        }
+    }
+    sh
+}
+
+/// Real SH basis up to band 2; must match `environment_irradiance` in
+/// quad_lighting.wgsl.
+fn sh_basis([x, y, z]: [f32; 3]) -> [f32; 9] {
+    [
+        0.282095,
+        0.488603 * y,
+        0.488603 * z,
+        0.488603 * x,
+        1.092548 * x * y,
+        1.092548 * y * z,
+        0.315392 * (3.0 * z * z - 1.0),
+        1.092548 * x * z,
+        0.546274 * (x * x - y * y),
+    ]
+}
+
+/// IEEE half of `value`, truncated, with out-of-range values clamped to the largest
+/// finite half and NaN mapped to 0.
+fn f32_to_f16(value: f32) -> u16 {
+    let value = if value.is_nan() {
+        0.0
+    } else {
+        value.clamp(-65504.0, 65504.0)
+    };
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent <= 0 {
+        // Subnormal half, or zero below its range
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        return sign | (mantissa >> (14 - exponent)) as u16;
+    }
+    sign | (exponent as u16) << 10 | (mantissa >> 13) as u16
+}
+
+fn create_environment_texture(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+    mip_level_count: u32,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Environment Texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: ENVIRONMENT_FORMAT,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
+
+fn placeholder_view(device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::TextureView {
+    let texture = create_environment_texture(device, 1, 1, 1);
+    queue.write_texture(
+        texture.as_image_copy(),
+        &[0; 8],
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(8),
+            rows_per_image: None,
+        },
+        texture.size(),
+    );
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
@@ -0,0 +1,248 @@
+//! The error type of the public API.
+//!
+//! In JS every error is an object `{ code, message, details }`: `message` is
+//! human-readable and may change, `code` is stable and tells the cases apart, and
+//! `details` is an object whose fields depend on the code, or `null`:
+//!
+//! - `"surface"`: creating, configuring or acquiring the canvas surface failed.
+//! - `"device"`: no suitable adapter or device, or a GPU operation failed.
+//! - `"device_lost"`: the GPU device was lost; see `Renderer::reinitialize`.
+//! - `"validation"`: an argument or scene value is invalid. `details` is
+//!   `{ object_id, field }`: the object or slot and the argument or field at fault,
+//!   each `null` when it does not apply.
+//! - `"limit_exceeded"`: a size or count is over a device or renderer limit.
+//!   `details` is `{ limit, requested, max }`, `limit` naming it.
+//! - `"serde"`: a JS value does not have the expected shape.
+//! - `"destroyed"`: the operation was abandoned because the renderer was freed or
+//!   the operation superseded.
+//! - `"aborted"`: the operation was stopped by the `AbortSignal` passed to it.
+//! - `"js"`: a JS callback threw or a browser API failed. `details` is `{ cause }`,
+//!   the value thrown.
+//!
+//! Other codes have `null` details.
+
+use std::fmt;
+
+use serde::Serialize;
+use wasm_bindgen::{prelude::*, JsCast};
+
+#[derive(Debug)]
+pub enum RendererError {
+    Surface(String),
+    Device(String),
+    DeviceLost,
+    Validation {
+        message: String,
+        /// Index or name of the object or slot the value belongs to
+        object_id: Option<String>,
+        /// Argument or scene field holding the value
+        field: Option<&'static str>,
+    },
+    LimitExceeded {
+        message: String,
+        limit: &'static str,
+        requested: u64,
+        max: u64,
+    },
+    Serde(String),
+    Destroyed(String),
+    Aborted(String),
+    Js(JsValue),
+}
+
+impl RendererError {
+    /// Invalid value of argument or field `field`.
+    pub fn invalid(field: &'static str, message: impl Into<String>) -> Self {
+        RendererError::Validation {
+            message: message.into(),
+            object_id: None,
+            field: Some(field),
+        }
+    }
+
+    /// Invalid value of object or slot `object_id`, or no object or slot by that id.
+    pub fn invalid_object(
+        object_id: impl ToString,
+        field: Option<&'static str>,
+        message: impl Into<String>,
+    ) -> Self {
+        RendererError::Validation {
+            message: message.into(),
+            object_id: Some(object_id.to_string()),
+            field,
+        }
+    }
+
+    /// No object by index `id`.
+    pub fn no_object(id: u32) -> Self {
+        RendererError::invalid_object(id, None, format!("no object with id {}", id))
+    }
+
+    /// `requested` is over `max`, the limit named `limit`.
+    pub fn limit(
+        limit: &'static str,
+        requested: impl Into<u64>,
+        max: impl Into<u64>,
+        message: impl Into<String>,
+    ) -> Self {
+        RendererError::LimitExceeded {
+            message: message.into(),
+            limit,
+            requested: requested.into(),
+            max: max.into(),
+        }
+    }
+
+    /// The stable `code` of the JS error object.
+    pub fn code(&self) -> &'static str {
+        match self {
+            RendererError::Surface(_) => "surface",
+            RendererError::Device(_) => "device",
+            RendererError::DeviceLost => "device_lost",
+            RendererError::Validation { .. } => "validation",
+            RendererError::LimitExceeded { .. } => "limit_exceeded",
+            RendererError::Serde(_) => "serde",
+            RendererError::Destroyed(_) => "destroyed",
+            RendererError::Aborted(_) => "aborted",
+            RendererError::Js(_) => "js",
+        }
+    }
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererError::Surface(message)
+            | RendererError::Device(message)
+            | RendererError::Serde(message)
+            | RendererError::Destroyed(message)
+            | RendererError::Aborted(message)
+            | RendererError::Validation { message, .. }
+            | RendererError::LimitExceeded { message, .. } => f.write_str(message),
+            RendererError::DeviceLost => f.write_str("the GPU device was lost"),
+            RendererError::Js(value) => match js_error_message(value) {
+                Some(message) => f.write_str(&message),
+                None => write!(f, "{:?}", value),
+            },
+        }
+    }
+}
+
+fn js_error_message(value: &JsValue) -> Option<String> {
+    value
+        .as_string()
+        .or_else(|| value.dyn_ref::<js_sys::Error>().map(|e| e.message().into()))
+}
+
+#[derive(Serialize)]
+struct ValidationDetails<'a> {
+    object_id: &'a Option<String>,
+    field: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct LimitDetails {
+    limit: &'static str,
+    requested: u64,
+    max: u64,
+}
+
+impl From<RendererError> for JsValue {
+    fn from(error: RendererError) -> JsValue {
+        let serializer = serde_wasm_bindgen::Serializer::new().serialize_missing_as_null(true);
+        let details = match &error {
+            RendererError::Validation {
+                object_id, field, ..
+            } => ValidationDetails {
+                object_id,
+                field: *field,
+            }
+            .serialize(&serializer),
+            RendererError::LimitExceeded {
+                limit,
+                requested,
+                max,
+                ..
+            } => LimitDetails {
+                limit,
+                requested: *requested,
+                max: *max,
+            }
+            .serialize(&serializer),
+            RendererError::Js(cause) => {
+                let details = js_sys::Object::new();
+                let _ = js_sys::Reflect::set(&details, &"cause".into(), cause);
+                Ok(details.into())
+            }
+            _ => Ok(JsValue::NULL),
+        };
+        let object = js_sys::Object::new();
+        let _ = js_sys::Reflect::set(&object, &"code".into(), &error.code().into());
+        let _ = js_sys::Reflect::set(&object, &"message".into(), &error.to_string().into());
+        let _ = js_sys::Reflect::set(
+            &object,
+            &"details".into(),
+            &details.unwrap_or(JsValue::NULL),
+        );
+        object.into()
+    }
+}
+
+/// Messages of the internal modules, which all report invalid values.
+impl From<String> for RendererError {
+    fn from(message: String) -> Self {
+        RendererError::Validation {
+            message,
+            object_id: None,
+            field: None,
+        }
+    }
+}
+
+impl From<&str> for RendererError {
+    fn from(message: &str) -> Self {
+        RendererError::from(message.to_string())
+    }
+}
+
+impl From<JsValue> for RendererError {
+    fn from(value: JsValue) -> Self {
+        RendererError::Js(value)
+    }
+}
+
+impl From<serde_wasm_bindgen::Error> for RendererError {
+    fn from(error: serde_wasm_bindgen::Error) -> Self {
+        RendererError::Serde(error.to_string())
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for RendererError {
+    fn from(error: wgpu::CreateSurfaceError) -> Self {
+        RendererError::Surface(error.to_string())
+    }
+}
+
+impl From<wgpu::SurfaceError> for RendererError {
+    fn from(error: wgpu::SurfaceError) -> Self {
+        RendererError::Surface(error.to_string())
+    }
+}
+
+impl From<wgpu::RequestAdapterError> for RendererError {
+    fn from(error: wgpu::RequestAdapterError) -> Self {
+        RendererError::Device(error.to_string())
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for RendererError {
+    fn from(error: wgpu::RequestDeviceError) -> Self {
+        RendererError::Device(error.to_string())
+    }
+}
+
+impl From<wgpu::BufferAsyncError> for RendererError {
+    fn from(error: wgpu::BufferAsyncError) -> Self {
+        RendererError::Device(error.to_string())
+    }
+}
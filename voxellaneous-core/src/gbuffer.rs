@@ -0,0 +1,86 @@
+//! The G-buffer targets, with their textures kept next to the views so they can be
+//! handed to code that composites onto the G-buffer outside the renderer.
+
+use crate::constants::GBUFFER_NORMAL_FORMAT;
+use crate::{create_render_texture, GBufferProfile};
+
+/// One G-buffer target: `RENDER_ATTACHMENT | TEXTURE_BINDING` usage.
+pub struct GBufferTexture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl GBufferTexture {
+    fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let texture = create_render_texture(device, width, height, format, label);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        GBufferTexture { texture, view }
+    }
+}
+
+/// The targets written by the G-buffer pass and read by lighting and present.
+pub struct GBufferTextures {
+    /// `Rgba8Unorm`: palette colour, alpha the edge coverage
+    pub albedo: GBufferTexture,
+    /// `GBUFFER_NORMAL_FORMAT`: octahedral normal
+    pub normal: GBufferTexture,
+    /// `R16Uint` or `R32Uint`: camera distance over `LINEAR_Z_MAX_DISTANCE`, scaled to
+    /// 0..=65535, with 0 where nothing was drawn
+    pub linear_z: GBufferTexture,
+    /// The linear-Z format: palette index of the surface (its material, clamped to
+    /// 255) in bits 0-7 and the baked ambient occlusion, unorm8, in bits 8-15
+    pub material: GBufferTexture,
+}
+
+impl GBufferTextures {
+    /// Targets of `width` x `height`. Targets `profile` does not write are 1×1
+    /// placeholders so bindings stay valid.
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        profile: GBufferProfile,
+        linear_z_format: wgpu::TextureFormat,
+    ) -> Self {
+        let (aux_width, aux_height) = match profile {
+            GBufferProfile::Full => (width, height),
+            GBufferProfile::AlbedoOnly => (1, 1),
+        };
+        GBufferTextures {
+            albedo: GBufferTexture::new(
+                device,
+                width,
+                height,
+                wgpu::TextureFormat::Rgba8Unorm,
+                "GBuffer Albedo",
+            ),
+            normal: GBufferTexture::new(
+                device,
+                aux_width,
+                aux_height,
+                GBUFFER_NORMAL_FORMAT,
+                "GBuffer Normal",
+            ),
+            linear_z: GBufferTexture::new(
+                device,
+                aux_width,
+                aux_height,
+                linear_z_format,
+                "GBuffer LinearZ",
+            ),
+            material: GBufferTexture::new(
+                device,
+                aux_width,
+                aux_height,
+                linear_z_format,
+                "GBuffer Material",
+            ),
+        }
+    }
+}
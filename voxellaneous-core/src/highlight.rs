@@ -0,0 +1,196 @@
+//! Region-of-interest highlight, from `Renderer::set_highlight_region`.
+//!
+//! At the end of the G-buffer pass the region's box (the proxy cube, stretched from
+//! its min to its max corner) marks the stencil plane of every pixel whose surface
+//! lies inside it: its back faces set the stencil where they are behind the surface,
+//! then its front faces clear it again where they are behind the surface too, so the
+//! surface is in front of the box. Neither writes depth or colour. The lighting pass
+//! tints the marked pixels.
+
+use crate::constants::{Vertex, CUBE_INDICES};
+
+/// Stencil value of pixels inside the region.
+const MARKED: u32 = 1;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct HighlightRegion {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+pub struct HighlightPass {
+    shader: wgpu::ShaderModule,
+    layout: wgpu::PipelineLayout,
+    /// Back faces setting the stencil, then front faces clearing it
+    pipelines: [wgpu::RenderPipeline; 2],
+    /// The renderer's proxy buffers, which start with `CUBE_VERTICES` and
+    /// `CUBE_INDICES`
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    region_buffer: wgpu::Buffer,
+    /// `None` when nothing is highlighted
+    region: Option<HighlightRegion>,
+    /// RGBA; the lit colour is blended toward RGB by A
+    pub color: [f32; 4],
+}
+
+impl HighlightPass {
+    /// `per_frame_layout` is the renderer's per-frame uniform layout, for the camera;
+    /// `targets` are the colour targets of the G-buffer pass.
+    pub fn new(
+        device: &wgpu::Device,
+        (vertex_buffer, index_buffer): (&wgpu::Buffer, &wgpu::Buffer),
+        per_frame_layout: &wgpu::BindGroupLayout,
+        targets: &[Option<wgpu::ColorTargetState>],
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Highlight Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/highlight.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Highlight Pipeline Layout"),
+            bind_group_layouts: &[per_frame_layout],
+            push_constant_ranges: &[],
+        });
+        let pipelines = create_pipelines(device, &shader, &layout, targets, cache);
+        let region_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Highlight Region Buffer"),
+            size: std::mem::size_of::<HighlightRegion>() as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        HighlightPass {
+            shader,
+            layout,
+            pipelines,
+            vertex_buffer: vertex_buffer.clone(),
+            index_buffer: index_buffer.clone(),
+            region_buffer,
+            region: None,
+            color: [0.0; 4],
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.region.is_some()
+    }
+
+    /// Rebuilds the pipelines for new G-buffer colour targets.
+    pub fn set_targets(
+        &mut self,
+        device: &wgpu::Device,
+        targets: &[Option<wgpu::ColorTargetState>],
+        cache: Option<&wgpu::PipelineCache>,
+    ) {
+        self.pipelines = create_pipelines(device, &self.shader, &self.layout, targets, cache);
+    }
+
+    /// Sets the region `encode` marks; `None` marks nothing.
+    pub fn set_region(&mut self, queue: &wgpu::Queue, region: Option<HighlightRegion>) {
+        if let Some(region) = &region {
+            queue.write_buffer(&self.region_buffer, 0, bytemuck::bytes_of(region));
+        }
+        self.region = region;
+    }
+
+    /// Marks the region in the stencil of `pass`, a G-buffer pass with depth whose
+    /// bind group 0 holds the per-frame uniforms. Leaves the pass on the proxy buffers.
+    pub fn encode(&self, pass: &mut wgpu::RenderPass<'_>) {
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, self.region_buffer.slice(..));
+        pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        pass.set_stencil_reference(MARKED);
+        for pipeline in &self.pipelines {
+            pass.set_pipeline(pipeline);
+            pass.draw_indexed(0..CUBE_INDICES.len() as u32, 0, 0..1);
+        }
+    }
+}
+
+fn create_pipelines(
+    device: &wgpu::Device,
+    shader: &wgpu::ShaderModule,
+    layout: &wgpu::PipelineLayout,
+    targets: &[Option<wgpu::ColorTargetState>],
+    cache: Option<&wgpu::PipelineCache>,
+) -> [wgpu::RenderPipeline; 2] {
+    let targets: Vec<_> = targets
+        .iter()
+        .map(|target| {
+            target.clone().map(|target| wgpu::ColorTargetState {
+                write_mask: wgpu::ColorWrites::empty(),
+                ..target
+            })
+        })
+        .collect();
+    let pipeline = |label, cull_mode, depth_compare, pass_op| {
+        let face = wgpu::StencilFaceState {
+            compare: wgpu::CompareFunction::Always,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op,
+        };
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+                    },
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<HighlightRegion>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &wgpu::vertex_attr_array![1 => Float32x3, 2 => Float32x3],
+                    },
+                ],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                targets: &targets,
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(cull_mode),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth24PlusStencil8,
+                depth_write_enabled: false,
+                depth_compare,
+                stencil: wgpu::StencilState {
+                    front: face,
+                    back: face,
+                    read_mask: 0xff,
+                    write_mask: 0xff,
+                },
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache,
+        })
+    };
+    [
+        pipeline(
+            "Highlight Mark Pipeline",
+            wgpu::Face::Front,
+            wgpu::CompareFunction::GreaterEqual,
+            wgpu::StencilOperation::Replace,
+        ),
+        pipeline(
+            "Highlight Clear Pipeline",
+            wgpu::Face::Back,
+            wgpu::CompareFunction::Greater,
+            wgpu::StencilOperation::Zero,
+        ),
+    ]
+}
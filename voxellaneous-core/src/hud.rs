@@ -0,0 +1,374 @@
+//! Debug overlay of frame stats drawn as text over the final frame, for
+//! `Renderer::set_hud`. Built only with the `hud` feature, so embeds without it carry
+//! neither the font nor the pass.
+//!
+//! Text is laid out on a grid of fixed-size cells from the top-left corner and drawn
+//! as one instanced quad per character (shaders/hud.wgsl), each reading a bundled 5×7
+//! bitmap font over a dark backdrop that keeps it readable on any frame.
+
+use crate::timestamps::GpuTimestamps;
+
+/// Characters drawn at most per frame; the rest of the text is cut.
+const MAX_GLYPHS: usize = 512;
+/// Glyph size in font pixels; must match GLYPH_SIZE in hud.wgsl.
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+/// First character of `FONT`; characters outside it are drawn as `?`.
+const FIRST_CHAR: u8 = b' ';
+/// Weight of each new frame in the smoothed frame time.
+const FRAME_TIME_SMOOTHING: f64 = 0.1;
+
+/// Columns of each glyph from `FIRST_CHAR` to `_`, bit 0 the top row. Lower case is
+/// drawn as upper case.
+const FONT: [[u8; GLYPH_WIDTH as usize]; 64] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // '!'
+    [0x00, 0x07, 0x00, 0x07, 0x00], // '"'
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // '#'
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // '$'
+    [0x23, 0x13, 0x08, 0x64, 0x62], // '%'
+    [0x36, 0x49, 0x55, 0x22, 0x50], // '&'
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '\''
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // '('
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // ')'
+    [0x14, 0x08, 0x3E, 0x08, 0x14], // '*'
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // '+'
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ','
+    [0x08, 0x08, 0x08, 0x08, 0x08], // '-'
+    [0x00, 0x60, 0x60, 0x00, 0x00], // '.'
+    [0x20, 0x10, 0x08, 0x04, 0x02], // '/'
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // '0'
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // '1'
+    [0x42, 0x61, 0x51, 0x49, 0x46], // '2'
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // '3'
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // '4'
+    [0x27, 0x45, 0x45, 0x45, 0x39], // '5'
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // '6'
+    [0x01, 0x71, 0x09, 0x05, 0x03], // '7'
+    [0x36, 0x49, 0x49, 0x49, 0x36], // '8'
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // '9'
+    [0x00, 0x36, 0x36, 0x00, 0x00], // ':'
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ';'
+    [0x08, 0x14, 0x22, 0x41, 0x00], // '<'
+    [0x14, 0x14, 0x14, 0x14, 0x14], // '='
+    [0x00, 0x41, 0x22, 0x14, 0x08], // '>'
+    [0x02, 0x01, 0x51, 0x09, 0x06], // '?'
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // '@'
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // 'A'
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // 'B'
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // 'C'
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // 'D'
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // 'E'
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // 'F'
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // 'G'
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // 'H'
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // 'I'
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // 'J'
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // 'K'
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // 'L'
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // 'M'
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // 'N'
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // 'O'
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // 'P'
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // 'Q'
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // 'R'
+    [0x46, 0x49, 0x49, 0x49, 0x31], // 'S'
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // 'T'
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // 'U'
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // 'V'
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // 'W'
+    [0x63, 0x14, 0x08, 0x14, 0x63], // 'X'
+    [0x07, 0x08, 0x70, 0x08, 0x07], // 'Y'
+    [0x61, 0x51, 0x49, 0x45, 0x43], // 'Z'
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // '['
+    [0x02, 0x04, 0x08, 0x10, 0x20], // '\\'
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ']'
+    [0x04, 0x02, 0x01, 0x02, 0x04], // '^'
+    [0x40, 0x40, 0x40, 0x40, 0x40], // '_'
+];
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct HudUniforms {
+    target_size: [f32; 2],
+    /// Screen pixels per font pixel
+    scale: f32,
+    _padding: f32,
+}
+
+/// One character: its cell on the text grid and its glyph in `FONT`.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GlyphInstance {
+    cell: [u32; 2],
+    glyph: u32,
+}
+
+/// What `HudPass::encode` shows, gathered by the renderer each frame.
+pub struct HudStats {
+    /// Voxel objects and meshes drawn by the G-buffer pass
+    pub draw_calls: usize,
+    pub objects: usize,
+    /// `None` without `TIMESTAMP_QUERY` or before the first measured frame
+    pub gpu: Option<GpuTimestamps>,
+}
+
+pub struct HudPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    instance_buffer: wgpu::Buffer,
+    /// Smoothed time between frames, once two have been recorded
+    frame_ms: Option<f64>,
+    last_frame_at: Option<f64>,
+}
+
+impl HudPass {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HUD Uniform Buffer"),
+            size: std::mem::size_of::<HudUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HUD Glyph Buffer"),
+            size: (MAX_GLYPHS * std::mem::size_of::<GlyphInstance>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // The glyphs side by side in one row, glyph i at x = 5i
+        let atlas_size = wgpu::Extent3d {
+            width: FONT.len() as u32 * GLYPH_WIDTH,
+            height: GLYPH_HEIGHT,
+            depth_or_array_layers: 1,
+        };
+        let mut texels = vec![0u8; (atlas_size.width * atlas_size.height) as usize];
+        for (i, columns) in FONT.iter().enumerate() {
+            for (x, column) in columns.iter().enumerate() {
+                for y in 0..GLYPH_HEIGHT as usize {
+                    if column >> y & 1 != 0 {
+                        let texel = y * atlas_size.width as usize + i * GLYPH_WIDTH as usize + x;
+                        texels[texel] = 255;
+                    }
+                }
+            }
+        }
+        let atlas = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("HUD Font Atlas"),
+            size: atlas_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            atlas.as_image_copy(),
+            &texels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(atlas_size.width),
+                rows_per_image: Some(atlas_size.height),
+            },
+            atlas_size,
+        );
+        let atlas_view = atlas.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("HUD Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("HUD Bind Group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&atlas_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("HUD Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/hud.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("HUD Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("HUD Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<GlyphInstance>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![0 => Uint32x2, 1 => Uint32],
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+            cache,
+        });
+
+        HudPass {
+            pipeline,
+            bind_group,
+            uniform_buffer,
+            instance_buffer,
+            frame_ms: None,
+            last_frame_at: None,
+        }
+    }
+
+    /// Records a frame rendered at `now_ms`, for the frame rate shown.
+    pub fn record_frame(&mut self, now_ms: f64) {
+        if let Some(last) = self.last_frame_at.replace(now_ms) {
+            let elapsed = (now_ms - last).max(0.0);
+            self.frame_ms = Some(match self.frame_ms {
+                Some(ms) => ms + (elapsed - ms) * FRAME_TIME_SMOOTHING,
+                None => elapsed,
+            });
+        }
+    }
+
+    /// Lines of text shown for `stats`.
+    fn text(&self, stats: &HudStats) -> String {
+        let fps = match self.frame_ms {
+            Some(ms) if ms > 0.0 => format!("FPS {:.1} ({:.2} MS)", 1000.0 / ms, ms),
+            _ => "FPS -".to_string(),
+        };
+        let draws = format!(
+            "DRAW CALLS {} ({} OBJECTS)",
+            stats.draw_calls, stats.objects
+        );
+        let ms = |start: u64, end: u64| end.saturating_sub(start) as f64 / 1.0e6;
+        let gpu = match stats.gpu {
+            Some(t) => {
+                let mut gpu = format!(
+                    "GPU GBUFFER {:.2} PRESENT {:.2}",
+                    ms(t.gbuffer_start_ns, t.gbuffer_end_ns),
+                    ms(t.present_start_ns, t.present_end_ns)
+                );
+                if let (Some(start), Some(end)) = (t.post_start_ns, t.post_end_ns) {
+                    gpu += &format!(" POST {:.2}", ms(start, end));
+                }
+                gpu + " MS"
+            }
+            None => "GPU -".to_string(),
+        };
+        format!("{}\n{}\n{}", fps, draws, gpu)
+    }
+
+    /// Draws `stats` over `output`, a `target_size` texture of the pass's format.
+    pub fn encode(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        target_size: (u32, u32),
+        stats: &HudStats,
+    ) {
+        let text = self.text(stats);
+        let glyphs: Vec<GlyphInstance> = text
+            .lines()
+            .enumerate()
+            .flat_map(|(row, line)| {
+                line.bytes()
+                    .enumerate()
+                    .map(move |(column, byte)| GlyphInstance {
+                        cell: [column as u32, row as u32],
+                        glyph: glyph_index(byte),
+                    })
+            })
+            .take(MAX_GLYPHS)
+            .collect();
+        // Twice the size on targets tall enough that 7-pixel text is hard to read
+        let scale = if target_size.1 >= 720 { 2.0 } else { 1.0 };
+        let uniforms = HudUniforms {
+            target_size: [target_size.0 as f32, target_size.1 as f32],
+            scale,
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&glyphs));
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("HUD Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            ..Default::default()
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        pass.draw(0..6, 0..glyphs.len() as u32);
+    }
+}
+
+/// Glyph of `FONT` drawn for the ASCII character `byte`.
+fn glyph_index(byte: u8) -> u32 {
+    let byte = byte.to_ascii_uppercase();
+    let index = byte.wrapping_sub(FIRST_CHAR) as usize;
+    match index < FONT.len() {
+        true => index as u32,
+        false => (b'?' - FIRST_CHAR) as u32,
+    }
+}
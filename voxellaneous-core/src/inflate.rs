@@ -0,0 +1,392 @@
+//! Gzip decompression (RFC 1952 members around RFC 1951 DEFLATE data) for compressed
+//! binary scenes.
+//!
+//! A table-free canonical Huffman decoder in the style of zlib's `puff`: small rather
+//! than fast, as decoding is dwarfed by the texture uploads that follow. Errors name
+//! the byte offset into the gzip stream at which decoding failed.
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// Compression method byte of DEFLATE, the only one gzip defines.
+const METHOD_DEFLATE: u8 = 8;
+
+const FLAG_HEADER_CRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+const FLAG_RESERVED: u8 = 0xe0;
+
+const MAX_CODE_BITS: usize = 15;
+
+/// Base lengths and extra bits of length symbols 257..=285.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Base distances and extra bits of distance symbols 0..=29.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order in which dynamic blocks list the code lengths of the code length alphabet.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+pub fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&GZIP_MAGIC)
+}
+
+/// Decompresses a gzip stream of one or more members, checking each member's CRC-32
+/// and length. Fails once the output would pass `limit` bytes.
+pub fn gunzip(bytes: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let mut reader = BitReader {
+        bytes,
+        pos: 0,
+        bit_buf: 0,
+        bit_count: 0,
+    };
+    // The trailing size field of the last member, bounded in case it is corrupt
+    let size_hint = bytes
+        .get(bytes.len().saturating_sub(4)..)
+        .and_then(|tail| tail.try_into().ok())
+        .map_or(0, |tail| u32::from_le_bytes(tail) as usize)
+        .min(bytes.len().saturating_mul(64))
+        .min(limit);
+    let mut out = Vec::with_capacity(size_hint);
+    loop {
+        gunzip_member(&mut reader, &mut out, limit)?;
+        if reader.pos == bytes.len() {
+            return Ok(out);
+        }
+        if !is_gzip(&bytes[reader.pos..]) {
+            return Err(reader.error("trailing bytes after the gzip stream"));
+        }
+    }
+}
+
+fn gunzip_member(reader: &mut BitReader, out: &mut Vec<u8>, limit: usize) -> Result<(), String> {
+    let header = reader.take(10)?;
+    if header[..2] != GZIP_MAGIC {
+        return Err(reader.error_at(reader.pos - 10, "not a gzip stream"));
+    }
+    if header[2] != METHOD_DEFLATE {
+        return Err(reader.error_at(reader.pos - 8, "unknown compression method"));
+    }
+    let flags = header[3];
+    if flags & FLAG_RESERVED != 0 {
+        return Err(reader.error_at(reader.pos - 7, "reserved header flags are set"));
+    }
+    if flags & FLAG_EXTRA != 0 {
+        let len = reader.take(2)?;
+        let len = u16::from_le_bytes([len[0], len[1]]) as usize;
+        reader.take(len)?;
+    }
+    for flag in [FLAG_NAME, FLAG_COMMENT] {
+        if flag & flags != 0 {
+            // Zero-terminated
+            while reader.take(1)?[0] != 0 {}
+        }
+    }
+    if flags & FLAG_HEADER_CRC != 0 {
+        reader.take(2)?;
+    }
+
+    let start = out.len();
+    inflate(reader, out, limit)?;
+
+    reader.align();
+    let trailer = reader.take(8)?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc != crc32(&out[start..]) {
+        return Err(reader.error_at(reader.pos - 8, "CRC-32 mismatch"));
+    }
+    if size != (out.len() - start) as u32 {
+        return Err(reader.error_at(reader.pos - 4, "length mismatch"));
+    }
+    Ok(())
+}
+
+/// Decodes DEFLATE blocks up to and including the final one.
+fn inflate(reader: &mut BitReader, out: &mut Vec<u8>, limit: usize) -> Result<(), String> {
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => stored_block(reader, out, limit)?,
+            1 => {
+                let (literals, distances) = fixed_codes();
+                compressed_block(reader, out, limit, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(reader)?;
+                compressed_block(reader, out, limit, &literals, &distances)?;
+            }
+            _ => return Err(reader.error("invalid block type")),
+        }
+        if last {
+            return Ok(());
+        }
+    }
+}
+
+fn stored_block(reader: &mut BitReader, out: &mut Vec<u8>, limit: usize) -> Result<(), String> {
+    reader.align();
+    let header = reader.take(4)?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    let inverse = u16::from_le_bytes([header[2], header[3]]);
+    if len != !inverse {
+        return Err(reader.error_at(reader.pos - 4, "stored block length check failed"));
+    }
+    reserve(reader, out, len as usize, limit)?;
+    out.extend_from_slice(reader.take(len as usize)?);
+    Ok(())
+}
+
+fn compressed_block(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    limit: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => {
+                reserve(reader, out, 1, limit)?;
+                out.push(symbol as u8);
+            }
+            256 => return Ok(()),
+            257..=285 => {
+                let i = symbol - 257;
+                let len = LENGTH_BASE[i] as usize + reader.bits(LENGTH_EXTRA[i] as u32)? as usize;
+                let d = distances.decode(reader)? as usize;
+                if d >= DISTANCE_BASE.len() {
+                    return Err(reader.error("invalid distance symbol"));
+                }
+                let distance =
+                    DISTANCE_BASE[d] as usize + reader.bits(DISTANCE_EXTRA[d] as u32)? as usize;
+                if distance > out.len() {
+                    return Err(reader.error("distance reaches before the start of the output"));
+                }
+                reserve(reader, out, len, limit)?;
+                // Byte by byte, as the copy may overlap what it is writing
+                let from = out.len() - distance;
+                for k in 0..len {
+                    out.push(out[from + k]);
+                }
+            }
+            _ => return Err(reader.error("invalid length symbol")),
+        }
+    }
+}
+
+/// Errs unless `len` more bytes fit in `out` under `limit`, which bounds what a small
+/// stream of long matches can make the decoder allocate.
+fn reserve(reader: &BitReader, out: &[u8], len: usize, limit: usize) -> Result<(), String> {
+    if len > limit - out.len() {
+        return Err(reader.error(&format!("output past the {}-byte limit", limit)));
+    }
+    Ok(())
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    let literals = Huffman::new(&lengths).expect("fixed literal code");
+    let distances = Huffman::new(&[5; 30]).expect("fixed distance code");
+    (literals, distances)
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(reader.error("too many codes in a dynamic block"));
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &i in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[i] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths).map_err(|message| reader.error(message))?;
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = code_length_code.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let Some(&previous) = i.checked_sub(1).map(|p| &lengths[p]) else {
+                    return Err(reader.error("repeated code length with no previous length"));
+                };
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        if i + repeat > lengths.len() {
+            return Err(reader.error("code lengths overrun the alphabet"));
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(reader.error("dynamic block has no end-of-block code"));
+    }
+
+    let literals =
+        Huffman::new(&lengths[..literal_count]).map_err(|message| reader.error(message))?;
+    let distances =
+        Huffman::new(&lengths[literal_count..]).map_err(|message| reader.error(message))?;
+    Ok((literals, distances))
+}
+
+/// Canonical Huffman code: how many codes there are of each length, and the symbols
+/// ordered by code.
+struct Huffman {
+    counts: [u16; MAX_CODE_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Code of the symbols with code lengths `lengths` (0 for unused). Incomplete
+    /// codes are accepted, as DEFLATE allows a distance code of a single symbol; their
+    /// unused codes fail to decode.
+    fn new(lengths: &[u8]) -> Result<Huffman, &'static str> {
+        let mut counts = [0u16; MAX_CODE_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err("over-subscribed Huffman code");
+            }
+        }
+
+        let mut offsets = [0u16; MAX_CODE_BITS + 1];
+        for len in 1..MAX_CODE_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate().filter(|(_, &len)| len != 0) {
+            symbols[offsets[len as usize] as usize] = symbol as u16;
+            offsets[len as usize] += 1;
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    /// Reads one symbol, a bit at a time: codes are stored most significant bit first.
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(reader.error("invalid Huffman code"))
+    }
+}
+
+/// Reads DEFLATE's least-significant-bit-first bit stream and whole bytes.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    /// Next byte to load into `bit_buf`
+    pos: usize,
+    bit_buf: u32,
+    bit_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    /// The next `count` (at most 16) bits, the first in the lowest bit.
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        while self.bit_count < count {
+            let Some(&byte) = self.bytes.get(self.pos) else {
+                return Err(self.error_at(self.pos, "unexpected end of stream"));
+            };
+            self.bit_buf |= (byte as u32) << self.bit_count;
+            self.pos += 1;
+            self.bit_count += 8;
+        }
+        let value = self.bit_buf & ((1 << count) - 1);
+        self.bit_buf >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    /// Drops the rest of the partly read byte.
+    fn align(&mut self) {
+        self.bit_buf = 0;
+        self.bit_count = 0;
+    }
+
+    /// The next `len` whole bytes; the reader must be aligned.
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len());
+        let Some(end) = end else {
+            return Err(self.error_at(self.bytes.len(), "unexpected end of stream"));
+        };
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// Error at the byte holding the next unread bit.
+    fn error(&self, message: &str) -> String {
+        self.error_at(self.pos - (self.bit_count as usize).div_ceil(8), message)
+    }
+
+    fn error_at(&self, offset: usize, message: &str) -> String {
+        format!("corrupt gzip stream at byte {}: {}", offset, message)
+    }
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |c, &byte| {
+        CRC_TABLE[((c ^ byte as u32) & 0xff) as usize] ^ (c >> 8)
+    })
+}
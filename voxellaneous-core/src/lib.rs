@@ -1,14 +1,136 @@
+mod animation_loop;
+mod ao_bake;
+mod auto_pause;
+mod bounds;
+mod checkerboard;
 mod constants;
+mod debug_boxes;
+mod depth_only;
+mod device_lost;
+mod dynamic_resolution;
+mod environment;
+mod error;
+mod fxaa;
+mod gbuffer;
+mod highlight;
+#[cfg(feature = "hud")]
+mod hud;
+mod inflate;
+mod light_culling;
+mod light_probes;
+mod linear_z_resolve;
+mod matcap;
+mod math;
+mod morphology;
+mod occlusion;
+mod pacing;
+mod palette_animation;
+mod post_effect;
 mod primitives;
+mod raymarch;
+mod readback;
+mod render_graph;
 mod scene;
+mod shadows;
+mod ssr;
+mod svo;
+mod timestamps;
+mod update_batch;
+mod upload;
 mod utils;
+mod xr;
+#[cfg(feature = "zstd")]
+mod zstd;
 
-use constants::{Vertex, CUBE_EDGE_INDICES, CUBE_INDICES, CUBE_VERTICES};
-use scene::Scene;
+use animation_loop::AnimationLoop;
+use ao_bake::{AoBakePipelines, NeighbourAoPipeline, MAX_NEIGHBOUR_AO_RADIUS};
+use auto_pause::AutoPause;
+use bounds::{Aabb, Frustum, Sphere};
+use checkerboard::CheckerboardPass;
+use constants::{
+    MeshVertex, Vertex, BIND_GROUP_OVERHEAD_BYTES, CUBE_EDGE_INDICES, CUBE_INDICES, CUBE_VERTICES,
+    GBUFFER_NORMAL_FORMAT, ICOSPHERE_EDGE_INDICES, ICOSPHERE_INDICES, ICOSPHERE_INRADIUS,
+    ICOSPHERE_VERTICES,
+};
+use debug_boxes::{DebugBox, DebugBoxPass};
+use depth_only::DepthOnlyPass;
+use device_lost::{DeviceLostFlag, ReinitializeToken};
+use dynamic_resolution::{DynamicResolution, MAX_RENDER_SCALE, MIN_RENDER_SCALE};
+use environment::Environment;
+use fxaa::FxaaPass;
+use highlight::{HighlightPass, HighlightRegion};
+use light_culling::{LightCulling, PointLight};
+use light_probes::{LightProbeUniforms, ShProjection};
+use matcap::Matcap;
+use morphology::{MorphologyOp, MorphologyPipelines};
+use occlusion::OcclusionCuller;
+use pacing::FramePacer;
+use palette_animation::{gradient_palette, PaletteAnimation, PaletteCycle, PaletteTransition};
+use post_effect::PostEffects;
+use primitives::RGBA;
+use raymarch::{ComputeRaymarch, RaymarchFrame, RaymarchObject, SvoObject, MAX_RAYMARCH_OBJECTS};
+use readback::DepthReadback;
+use render_graph::{PassKind, RenderGraph, Resource};
+use scene::{ColorMode, Scene, VoxelFormat, VoxelObject};
 use serde::Serialize;
-use utils::map_wgpu_err;
+use shadows::CascadedShadows;
+use ssr::{SsrInputs, SsrPass, MAX_SSR_STEPS};
+use std::{cell::Cell, collections::HashMap, ops::Range, rc::Rc};
+use svo::SparseOctree;
+use timestamps::PassTimestamps;
+use update_batch::UpdateBatch;
+use upload::UploadToken;
+use utils::log;
 use wasm_bindgen::prelude::*;
 use wgpu::util::DeviceExt;
+use xr::{XrEye, XrView};
+
+pub use error::RendererError;
+pub use gbuffer::{GBufferTexture, GBufferTextures};
+pub use light_probes::LightProbe;
+
+#[derive(Serialize)]
+struct CameraFit {
+    eye: [f32; 3],
+    target: [f32; 3],
+}
+
+/// Benefit of the tight proxies: how much larger the full unit cube is than the
+/// box around the filled voxels, averaged over non-empty objects.
+#[derive(Serialize)]
+struct ProxyStats {
+    object_count: usize,
+    empty_object_count: usize,
+    average_cube_to_proxy_volume: f64,
+}
+
+/// An object of `Renderer::get_scene_info`.
+#[derive(Serialize)]
+struct ObjectInfo<'a> {
+    id: &'a str,
+    dims: [u32; 3],
+    order: i32,
+    /// In voxel coordinates
+    pivot: [f32; 3],
+    /// Includes the pivot
+    model_matrix: [f32; 16],
+}
+
+#[derive(Serialize)]
+struct ObjectMemoryStats {
+    voxel_texture_bytes: u64,
+    uniform_buffer_bytes: u64,
+    bind_group_overhead: u64,
+    total_bytes: u64,
+}
+
+/// Duration of the latest neighbour-counting AO bake on each path.
+#[derive(Serialize, Copy, Clone, Default)]
+struct AoBakeTimings {
+    cpu_ms: Option<f64>,
+    /// From submit until the GPU has finished.
+    gpu_ms: Option<f64>,
+}
 
 #[derive(Serialize)]
 struct SerializableAdapterInfo {
@@ -19,6 +141,21 @@ struct SerializableAdapterInfo {
     driver: String,
     driver_info: String,
     backend: String,
+    linear_z_format: String,
+}
+
+/// The subset of `wgpu::Limits` that constrains scenes and render settings.
+#[derive(Serialize)]
+struct SerializableDeviceLimits {
+    max_texture_dimension_2d: u32,
+    max_texture_dimension_3d: u32,
+    max_bind_groups: u32,
+    max_uniform_buffer_binding_size: u32,
+    max_storage_buffer_binding_size: u32,
+    max_storage_buffers_per_shader_stage: u32,
+    max_buffer_size: u64,
+    max_compute_workgroup_size_x: u32,
+    max_compute_workgroup_size_y: u32,
 }
 
 #[repr(C, align(16))]
@@ -26,7 +163,14 @@ struct SerializableAdapterInfo {
 struct PerFrameUniforms {
     vp_matrix: [f32; 16],
     camera_position: [f32; 3],
-    _padding: f32,
+    voxel_rounding: f32,
+    /// 0 renders every pixel, else 1 + the parity of the pixels to render.
+    checkerboard: u32,
+    /// Palette index treated as empty space.
+    empty_index: u32,
+    /// Angle between the view rays of adjacent pixels; 0 disables edge antialiasing.
+    pixel_angle: f32,
+    _padding: u32,
 }
 
 #[repr(C, align(16))]
@@ -35,11 +179,172 @@ struct StaticUniforms {
     color_palette: [u32; 256],
 }
 
+/// `performance.now()` of the page or worker, or 0 when unavailable (which disables
+/// the FPS cap).
+fn now_ms() -> f64 {
+    js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("performance"))
+        .ok()
+        .and_then(|performance| performance.dyn_into::<web_sys::Performance>().ok())
+        .map_or(0.0, |performance| performance.now())
+}
+
+/// Unit vector along (0.5, 0.5, 0.5), used when no valid light direction is given.
+const DEFAULT_LIGHT_DIR: [f32; 3] = [0.577_350_26; 3];
+
+/// Normalizes a light direction from JS, falling back to `DEFAULT_LIGHT_DIR` for
+/// wrong lengths and near-zero or non-finite vectors.
+fn light_direction(light_dir: &[f32]) -> [f32; 3] {
+    let Some(dir) = light_dir.try_into().ok().and_then(math::normalize) else {
+        log::warn_once!(
+            "light direction {:?} is unusable; using the default",
+            light_dir
+        );
+        return DEFAULT_LIGHT_DIR;
+    };
+    let length = light_dir.iter().map(|c| c * c).sum::<f32>().sqrt();
+    if (length - 1.0).abs() > 1e-3 {
+        log::warn_once!(
+            "light direction {:?} is not normalized; normalizing it",
+            light_dir
+        );
+    }
+    dir
+}
+
+/// Inputs of the last rendered frame, reused by the animation loop.
+#[derive(Copy, Clone, PartialEq)]
+struct FrameSettings {
+    vp_matrix: [f32; 16],
+    camera_position: [f32; 3],
+    present_target: usize,
+    light_dir: [f32; 3],
+    ambient: f32,
+    show_bboxes: bool,
+}
+
+impl Default for FrameSettings {
+    fn default() -> Self {
+        FrameSettings {
+            vp_matrix: [0.0; 16],
+            camera_position: [0.0; 3],
+            present_target: 4,
+            light_dir: DEFAULT_LIGHT_DIR,
+            ambient: 0.1,
+            show_bboxes: false,
+        }
+    }
+}
+
+impl FrameSettings {
+    /// Parses the arguments of `Renderer::render`.
+    fn new(
+        vp_matrix: &[f32],
+        view_position: &[f32],
+        present_target: usize,
+        light_dir: &[f32],
+        ambient: f32,
+        show_bboxes: bool,
+    ) -> Result<Self, RendererError> {
+        let (vp_matrix, camera_position) = parse_camera(vp_matrix, view_position)?;
+        Ok(FrameSettings {
+            vp_matrix,
+            camera_position,
+            present_target,
+            light_dir: light_direction(light_dir),
+            ambient,
+            show_bboxes,
+        })
+    }
+}
+
+/// The view-projection matrix and camera position of `Renderer::set_camera` and
+/// `Renderer::render`.
+fn parse_camera(
+    vp_matrix: &[f32],
+    view_position: &[f32],
+) -> Result<([f32; 16], [f32; 3]), RendererError> {
+    let vp_matrix = vp_matrix
+        .try_into()
+        .map_err(|_| RendererError::invalid("vp_matrix", "vp_matrix must have 16 elements"))?;
+    let camera_position = view_position.try_into().map_err(|_| {
+        RendererError::invalid("view_position", "view_position must have 3 elements")
+    })?;
+    Ok((vp_matrix, camera_position))
+}
+
+/// The corners and colour of `Renderer::draw_debug_box` and
+/// `Renderer::set_highlight_region`.
+fn parse_box(min: &[f32], max: &[f32], color: &[f32]) -> Result<DebugBox, RendererError> {
+    let corner = |field, values: &[f32]| -> Result<[f32; 3], RendererError> {
+        values
+            .try_into()
+            .ok()
+            .filter(|c: &[f32; 3]| c.iter().all(|v| v.is_finite()))
+            .ok_or_else(|| RendererError::invalid(field, "must be 3 finite numbers"))
+    };
+    let (min, max) = (corner("min", min)?, corner("max", max)?);
+    if (0..3).any(|i| min[i] > max[i]) {
+        return Err(RendererError::invalid(
+            "max",
+            "must not be below min on any axis",
+        ));
+    }
+    let color = color
+        .try_into()
+        .map_err(|_| RendererError::invalid("color", "color must have 4 components"))?;
+    Ok(DebugBox { min, max, color })
+}
+
+const MAX_CLIP_PLANES: usize = 4;
+
+/// Clip planes in a buffer of their own in the static bind group rather than in the
+/// per-frame uniforms: the shadow, wireframe, mesh and compute passes bind them
+/// without the G-buffer pass's camera, and they are only written when they change.
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ClipPlanesUniforms {
+    planes: [[f32; 4]; MAX_CLIP_PLANES],
+    count: u32,
+    /// Non-zero fills the cut faces of voxels with `cap_color`, see `set_clip_cap`.
+    cap: u32,
+    _padding: [u32; 2],
+    cap_color: [f32; 4],
+}
+
 #[repr(C, align(16))]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct PerDrawUniforms {
     model_matrix: [f32; 16],
     inverse_model_matrix: [f32; 16],
+    /// Object-space box around the filled voxels; the proxy cube is shrunk to it and
+    /// the raymarch enters and exits through it.
+    proxy_min: [f32; 3],
+    _padding: f32,
+    proxy_max: [f32; 3],
+    _padding2: f32,
+    /// Multiplies the albedo, from `Renderer::set_object_tint`.
+    tint: [f32; 4],
+    /// `VoxelObject::voxel_proportions`, for rounded voxels.
+    voxel_size: [f32; 3],
+    _padding3: f32,
+}
+
+impl PerDrawUniforms {
+    fn new(obj: &VoxelObject, proxy_bounds: Option<([f32; 3], [f32; 3])>, tint: [f32; 4]) -> Self {
+        // Empty objects collapse the proxy to a point, so nothing is rasterized
+        let (proxy_min, proxy_max) = proxy_bounds.unwrap_or_default();
+        PerDrawUniforms {
+            model_matrix: obj.model_matrix,
+            inverse_model_matrix: obj.inv_model_matrix,
+            proxy_min,
+            _padding: 0.0,
+            proxy_max,
+            _padding2: 0.0,
+            tint,
+            voxel_size: obj.voxel_proportions(),
+            _padding3: 0.0,
+        }
+    }
 }
 
 #[repr(C, align(16))]
@@ -47,227 +352,1217 @@ struct PerDrawUniforms {
 struct LightingUniforms {
     light_dir: [f32; 3],
     ambient: f32,
+    /// Unprojects pixels to view rays for the specular term.
+    inv_vp_matrix: [f32; 16],
+    specular_strength: f32,
+    shininess: f32,
+    /// 0 = stored, 1 = geometric; see `NormalSource`.
+    normal_source: u32,
+    /// Non-zero darkens the ambient term by the G-buffer occlusion.
+    ambient_occlusion: u32,
+    /// Camera right and up axes; the matcap is looked up by the normal in this basis.
+    view_right: [f32; 3],
+    lighting_model: u32,
+    view_up: [f32; 3],
+    /// 0 = off, else 1 + `EdgeAaQuality`.
+    edge_aa: u32,
+    rim_color: [f32; 3],
+    rim_power: f32,
+    /// 0 disables the rim term.
+    rim_intensity: f32,
+    _padding2: [f32; 3],
+    /// Rebuilds world positions from the linear-Z, for shadows and light probes.
+    camera_position: [f32; 3],
+    _padding3: f32,
+    /// Tint of the pixels inside the highlight region, blended by alpha; 0 alpha when
+    /// none is marked.
+    highlight_color: [f32; 4],
 }
 
-pub fn create_render_texture_view(
+/// Maps a present target pixel to the G-buffer: `uv * uv_scale + uv_offset`.
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PresentUniforms {
+    uv_scale: [f32; 2],
+    uv_offset: [f32; 2],
+}
+
+impl PresentUniforms {
+    const IDENTITY: PresentUniforms = PresentUniforms {
+        uv_scale: [1.0, 1.0],
+        uv_offset: [0.0, 0.0],
+    };
+}
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DepthResolveUniforms {
+    inv_vp_matrix: [f32; 16],
+    camera_position: [f32; 3],
+    _padding: f32,
+}
+
+/// Bytes per row of a `capture_frame` copy of a `width`-pixel canvas.
+fn capture_row_bytes(width: u32) -> u32 {
+    (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+}
+
+/// Material table entry read by quad_lighting.wgsl: unorm8 roughness, then metallic.
+fn pack_material(roughness: f32, metallic: f32) -> u32 {
+    let unorm8 = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u32;
+    unorm8(roughness) | unorm8(metallic) << 8
+}
+
+/// Picks the linear-Z G-buffer format: R16Uint where it is renderable, else R32Uint.
+/// Both store the same 0..=65535 encoding, so the shaders are shared. Integer formats
+/// cannot be multisample-resolved by the hardware, so a multisampled G-buffer has to
+/// resolve linear-Z through `linear_z_resolve::LinearZResolve`.
+fn choose_linear_z_format(adapter: &wgpu::Adapter) -> Result<wgpu::TextureFormat, RendererError> {
+    [wgpu::TextureFormat::R16Uint, wgpu::TextureFormat::R32Uint]
+        .into_iter()
+        .find(|&format| {
+            adapter
+                .get_texture_format_features(format)
+                .allowed_usages
+                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+        })
+        .ok_or_else(|| {
+            RendererError::Device("no renderable unsigned format for the linear-Z target".into())
+        })
+}
+
+pub fn create_render_texture(
     device: &wgpu::Device,
     width: u32,
     height: u32,
     format: wgpu::TextureFormat,
     label: &str,
-) -> wgpu::TextureView {
+) -> wgpu::Texture {
     let size = wgpu::Extent3d {
         width,
         height,
         depth_or_array_layers: 1,
     };
-    let texture = device.create_texture(&wgpu::TextureDescriptor {
+    device.create_texture(&wgpu::TextureDescriptor {
         label: Some(label),
         size,
         mip_level_count: 1,
         sample_count: 1,
         dimension: wgpu::TextureDimension::D2,
         format,
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC
+            | wgpu::TextureUsages::COPY_DST,
         view_formats: &[],
-    });
-    texture.create_view(&wgpu::TextureViewDescriptor::default())
+    })
 }
 
-fn create_depth_texture(
+pub fn create_render_texture_view(
     device: &wgpu::Device,
-    config: &wgpu::SurfaceConfiguration,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    label: &str,
 ) -> wgpu::TextureView {
+    create_render_texture(device, width, height, format, label)
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Ping-pong pair of surface-format targets for post-processing passes.
+fn create_post_targets(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+) -> [wgpu::TextureView; 2] {
+    [
+        create_render_texture_view(device, width, height, format, "Post Target A"),
+        create_render_texture_view(device, width, height, format, "Post Target B"),
+    ]
+}
+
+fn create_depth_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
     let size = wgpu::Extent3d {
-        width: config.width,
-        height: config.height,
+        width,
+        height,
         depth_or_array_layers: 1,
     };
 
-    let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
+    device.create_texture(&wgpu::TextureDescriptor {
         label: Some("Depth Texture"),
         size,
         mip_level_count: 1,
         sample_count: 1,
         dimension: wgpu::TextureDimension::D2,
         format: wgpu::TextureFormat::Depth24PlusStencil8, // Depth format
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,    // Used as a render target
+        // Render target, also sampled by the depth resolve for `capture_depth`
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
         view_formats: &[],
-    });
+    })
+}
 
-    depth_texture.create_view(&wgpu::TextureViewDescriptor::default())
+/// View of the stencil plane of a `create_depth_texture` texture, as `texture_2d<u32>`.
+fn create_stencil_view(depth_texture: &wgpu::Texture) -> wgpu::TextureView {
+    depth_texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("Stencil View"),
+        aspect: wgpu::TextureAspect::StencilOnly,
+        ..Default::default()
+    })
 }
 
-pub struct DrawCallData {
-    pub bind_group: wgpu::BindGroup,
-    pub texture: wgpu::Texture,
-    pub texture_view: wgpu::TextureView,
-    pub sampler: wgpu::Sampler,
-    pub uniform_buffer: wgpu::Buffer,
+/// Which G-buffer targets the raymarch and mesh pipelines write.
+#[wasm_bindgen]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GBufferProfile {
+    /// Albedo, normal, linear-Z and material; required for lighting and the debug
+    /// views.
+    Full = 0,
+    /// Albedo only, for unlit embeds on memory-constrained devices.
+    AlbedoOnly = 1,
 }
 
+/// How the G-buffer is produced; both paths feed the same lighting pass.
 #[wasm_bindgen]
-pub struct Renderer {
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    adapter_info: wgpu::AdapterInfo,
-    surface: wgpu::Surface<'static>,
-    surface_config: wgpu::SurfaceConfiguration,
-    render_pipeline: wgpu::RenderPipeline,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    static_uniform_buffer: wgpu::Buffer,
-    per_frame_uniform_buffer: wgpu::Buffer,
-    per_frame_bind_group_layout: wgpu::BindGroupLayout,
-    per_draw_bind_group_layout: wgpu::BindGroupLayout,
-    quad_layout_uint: wgpu::BindGroupLayout,
-    quad_layout_float: wgpu::BindGroupLayout,
-    quad_pipeline_uint: wgpu::RenderPipeline,
-    quad_pipeline_float: wgpu::RenderPipeline,
-    lighting_layout: wgpu::BindGroupLayout,
-    lighting_pipeline: wgpu::RenderPipeline,
-    lighting_uniform_buffer: wgpu::Buffer,
-    wireframe_pipeline: wgpu::RenderPipeline,
-    wireframe_bind_group_layout: wgpu::BindGroupLayout,
-    edge_index_buffer: wgpu::Buffer,
-    static_bind_group: wgpu::BindGroup,
-    gbuffer_albedo: wgpu::TextureView,
-    gbuffer_normal: wgpu::TextureView,
-    gbuffer_linear_z: wgpu::TextureView,
-    sampler: wgpu::Sampler,
-    depth_texture_view: wgpu::TextureView,
-    draw_call_array: Vec<DrawCallData>,
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RenderPath {
+    /// Proxy cubes rasterized and raymarched per fragment, plus meshes.
+    Raster = "raster",
+    /// One full-screen compute pass marching the nearest volumes per pixel, drawing no
+    /// meshes; there is no hardware depth, so depth views and `capture_depth` are
+    /// unavailable. Frames it cannot draw as the raster path would — more than
+    /// `MAX_RAYMARCH_OBJECTS` marched objects in view, RGBA objects in view, or object
+    /// scissors or slice views — are drawn by the raster path instead.
+    Compute = "compute",
 }
 
+/// How the lighting pass shades the G-buffer.
 #[wasm_bindgen]
-impl Renderer {
-    pub async fn new(html_canvas: web_sys::HtmlCanvasElement) -> Result<Renderer, JsValue> {
-        // Initialize the GPU
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LightingModel {
+    /// Ambient plus directional diffuse, with the optional specular highlight.
+    Directional = "directional",
+    /// Albedo multiplied by the matcap set with `set_matcap`, looked up by the
+    /// view-space normal; ignores the light. Without a matcap this is flat albedo.
+    Matcap = "matcap",
+    /// Cook-Torrance GGX with the roughness and metallic set per palette index by
+    /// `set_material`, shaded in linear space; the ambient term is a uniform white
+    /// environment. Costlier than `Directional`, whose specular settings it ignores.
+    Pbr = "pbr",
+}
 
-        let canvas_width = html_canvas.width();
-        let canvas_height = html_canvas.height();
+/// Normal the lighting pass shades with, from `Renderer::set_lighting_inputs`.
+#[wasm_bindgen]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum NormalSource {
+    /// The G-buffer normal written by the raymarch: the hit face, or the rounded or
+    /// filtered voxel surface.
+    Stored = 0,
+    /// Rebuilt from the linear-Z of neighbouring pixels, as the surface the depth
+    /// describes. Stepped at a distance by the 16-bit linear-Z, and flat across
+    /// rounded voxels smaller than a pixel.
+    Geometric = 1,
+}
 
-        let surface_target = wgpu::SurfaceTarget::Canvas(html_canvas);
-        let surface = instance
-            .create_surface(surface_target)
-            .map_err(map_wgpu_err)?;
+/// How far the lighting pass blends antialiased voxel edges.
+#[wasm_bindgen]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EdgeAaQuality {
+    /// Blend edge pixels toward the background only.
+    Low = 0,
+    /// Also blend toward farther geometry behind an edge, shading one extra pixel.
+    High = 1,
+}
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
+/// How the present pass maps the rendered image onto a target of another aspect ratio.
+#[wasm_bindgen]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PresentFit {
+    /// Fill the target, distorting the image.
+    Stretch = "stretch",
+    /// Show the whole image, with black bars on the sides it does not reach.
+    Contain = "contain",
+    /// Fill the target, cropping the sides of the image that overflow it.
+    Cover = "cover",
+}
+
+impl PresentFit {
+    /// UV transform presenting a `source` sized image centred on a `target`.
+    fn uniforms(self, source: (u32, u32), target: (u32, u32)) -> PresentUniforms {
+        let aspect = |(width, height): (u32, u32)| width.max(1) as f32 / height.max(1) as f32;
+        // > 1 when the source is wider than the target
+        let ratio = aspect(source) / aspect(target);
+        let uv_scale = match self {
+            PresentFit::Contain if ratio > 1.0 => [1.0, ratio],
+            PresentFit::Contain => [1.0 / ratio, 1.0],
+            PresentFit::Cover if ratio > 1.0 => [1.0 / ratio, 1.0],
+            PresentFit::Cover => [1.0, ratio],
+            _ => [1.0, 1.0],
+        };
+        PresentUniforms {
+            uv_scale,
+            uv_offset: uv_scale.map(|scale| 0.5 - 0.5 * scale),
+        }
+    }
+}
+
+impl GBufferProfile {
+    fn fragment_entry_point(self) -> &'static str {
+        match self {
+            GBufferProfile::Full => "fs_main",
+            GBufferProfile::AlbedoOnly => "fs_albedo",
+        }
+    }
+
+    fn color_targets(
+        self,
+        linear_z_format: wgpu::TextureFormat,
+    ) -> Vec<Option<wgpu::ColorTargetState>> {
+        let target = |format| {
+            Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
             })
-            .await
-            .map_err(map_wgpu_err)?;
+        };
+        match self {
+            GBufferProfile::Full => vec![
+                target(wgpu::TextureFormat::Rgba8Unorm),
+                target(GBUFFER_NORMAL_FORMAT),
+                target(linear_z_format),
+                // The material target shares the linear-Z format and its fallback
+                target(linear_z_format),
+            ],
+            GBufferProfile::AlbedoOnly => vec![target(wgpu::TextureFormat::Rgba8Unorm)],
+        }
+    }
+}
 
-        let adapter_info = adapter.get_info();
+/// Depth state of the G-buffer pipelines; `None` draws in painter's order.
+fn gbuffer_depth_stencil(depth_enabled: bool) -> Option<wgpu::DepthStencilState> {
+    depth_enabled.then_some(wgpu::DepthStencilState {
+        format: wgpu::TextureFormat::Depth24PlusStencil8,
+        depth_write_enabled: true,
+        depth_compare: wgpu::CompareFunction::Less,
+        stencil: wgpu::StencilState::default(),
+        bias: wgpu::DepthBiasState::default(),
+    })
+}
 
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor {
-                required_features: wgpu::Features::TEXTURE_FORMAT_16BIT_NORM,
+/// Layout of the per-draw bind group of objects of `color_mode`: the voxel texture,
+/// `PerDrawUniforms` and the baked AO texture.
+fn create_per_draw_bind_group_layout(
+    device: &wgpu::Device,
+    color_mode: ColorMode,
+) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some(match color_mode {
+            ColorMode::Palette => "Per Draw Call Bind Group Layout",
+            ColorMode::Rgba => "Rgba Per Draw Call Bind Group Layout",
+        }),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: color_mode.sample_type(),
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    multisampled: false,
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_voxel_pipeline(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    linear_z_format: wgpu::TextureFormat,
+    voxel_filter: VoxelFilterMode,
+    voxel_wrap: VoxelWrapMode,
+    profile: GBufferProfile,
+    depth_enabled: bool,
+    cull_mode: wgpu::Face,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(match cull_mode {
+            wgpu::Face::Back => "G-Buffer Render Pipeline",
+            wgpu::Face::Front => "G-Buffer Back Face Render Pipeline",
+        }),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+            }],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some(profile.fragment_entry_point()),
+            targets: &profile.color_targets(linear_z_format),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &[
+                    ("voxel_filter", voxel_filter as u32 as f64),
+                    ("voxel_wrap", voxel_wrap as u32 as f64),
+                ],
                 ..Default::default()
+            },
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(cull_mode),
+            ..Default::default()
+        },
+        depth_stencil: gbuffer_depth_stencil(depth_enabled),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache,
+    })
+}
+
+/// Palette-aware pipeline for user meshes (`upload_mesh`), same G-buffer targets
+fn create_mesh_pipeline(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    linear_z_format: wgpu::TextureFormat,
+    profile: GBufferProfile,
+    depth_enabled: bool,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Mesh G-Buffer Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &[MeshVertex::layout()],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some(profile.fragment_entry_point()),
+            targets: &profile.color_targets(linear_z_format),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: gbuffer_depth_stencil(depth_enabled),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache,
+    })
+}
+
+/// Identity of `Renderer::set_object_tint`.
+const WHITE_TINT: [f32; 4] = [1.0; 4];
+
+/// Camera an `encode_gbuffer_pass` draws from.
+struct GBufferCamera {
+    frustum: Frustum,
+    position: [f32; 3],
+    /// Farthest distance from `position` to a corner of the near plane
+    near_reach: f32,
+}
+
+impl GBufferCamera {
+    fn new(vp_matrix: &[f32; 16], position: [f32; 3]) -> Self {
+        let inv_vp_matrix = math::invert(vp_matrix).unwrap_or(math::IDENTITY);
+        let near_reach = [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0], [1.0, 1.0]]
+            .into_iter()
+            .map(|[x, y]| {
+                let corner = math::project_point(&inv_vp_matrix, [x, y, 0.0]);
+                let d: [f32; 3] = std::array::from_fn(|i| corner[i] - position[i]);
+                (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
             })
-            .await
-            .map_err(map_wgpu_err)?;
+            .fold(0.0, f32::max);
+        GBufferCamera {
+            frustum: Frustum::from_vp_matrix(vp_matrix),
+            position,
+            near_reach,
+        }
+    }
 
-        let supported_formats = surface.get_capabilities(&adapter).formats;
-        let surface_format = *supported_formats.first().unwrap();
+    /// Whether the near plane may cut `bounds`, clipping its front faces: the camera
+    /// is inside them or within `near_reach` of them.
+    fn is_near(&self, bounds: &Aabb) -> bool {
+        (0..3).all(|i| {
+            self.position[i] >= bounds.min[i] - self.near_reach
+                && self.position[i] <= bounds.max[i] + self.near_reach
+        })
+    }
+}
 
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: canvas_width,
-            height: canvas_height,
-            present_mode: wgpu::PresentMode::Fifo,
-            alpha_mode: wgpu::CompositeAlphaMode::Auto,
-            desired_maximum_frame_latency: 2,
-            view_formats: vec![],
-        };
-        surface.configure(&device, &surface_config);
+/// `[x, y, width, height]` cut to a target of `size` pixels, empty when the rectangle
+/// lies outside it.
+fn clamp_scissor([x, y, width, height]: [u32; 4], size: (u32, u32)) -> [u32; 4] {
+    let (x, y) = (x.min(size.0), y.min(size.1));
+    let width = width.min(size.0 - x);
+    let height = height.min(size.1 - y);
+    [x, y, width, height]
+}
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+/// Proxy geometry rasterized for an object, from `Renderer::set_object_bounding_shape`.
+///
+/// The raymarch still enters through the proxy box, so the shape only decides which
+/// pixels run it: `Icosphere` skips the box corners of round objects (characters,
+/// rocks), but clips any voxels outside the ellipsoid inscribed in the box.
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BoundingShape {
+    #[default]
+    Cube = 0,
+    Icosphere = 1,
+}
 
-        let depth_texture_view = create_depth_texture(&device, &surface_config);
+impl BoundingShape {
+    /// Range of the shape's triangles in the renderer's index buffer.
+    fn indices(self) -> std::ops::Range<u32> {
+        let cube = CUBE_INDICES.len() as u32;
+        match self {
+            BoundingShape::Cube => 0..cube,
+            BoundingShape::Icosphere => cube..cube + ICOSPHERE_INDICES.len() as u32,
+        }
+    }
 
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/shader.wgsl").into()),
-        });
+    /// Range of the shape's edges in the edge index buffer.
+    fn edges(self) -> std::ops::Range<u32> {
+        let cube = CUBE_EDGE_INDICES.len() as u32;
+        match self {
+            BoundingShape::Cube => 0..cube,
+            BoundingShape::Icosphere => cube..cube + ICOSPHERE_EDGE_INDICES.len() as u32,
+        }
+    }
+}
 
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(CUBE_VERTICES),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
+pub struct DrawCallData {
+    pub bind_group: wgpu::BindGroup,
+    pub texture: wgpu::Texture,
+    /// Second voxel texture for in-place compute passes, created on first use.
+    pub back_texture: Option<wgpu::Texture>,
+    pub texture_view: wgpu::TextureView,
+    pub uniform_buffer: wgpu::Buffer,
+    pub world_bounds: Aabb,
+    /// Object-space box around the filled voxels, `None` when the object is empty.
+    pub proxy_bounds: Option<([f32; 3], [f32; 3])>,
+    /// Coarse culling bounds, tested before `world_bounds`.
+    pub world_sphere: Sphere,
+    /// Draw priority; lower values are drawn first.
+    pub order: i32,
+    /// Proxy geometry drawn for the object, the cube by default.
+    pub bounding_shape: BoundingShape,
+    /// Albedo multiplier from `Renderer::set_object_tint`, `WHITE_TINT` by default.
+    pub tint: [f32; 4],
+    /// `[x, y, width, height]` from `Renderer::set_object_scissor`; `None` draws
+    /// across the whole target.
+    pub scissor: Option<[u32; 4]>,
+    /// From `Renderer::set_object_wireframe`: drawn as the edges of its bounding shape
+    /// instead of its voxels.
+    pub wireframe: bool,
+    /// From `Renderer::set_slice_view`: the axis and index of the only voxel layer drawn.
+    pub slice: Option<(usize, u32)>,
+    /// Object-space AO from `bake_object_space_ao` or `bake_ao`, bound for the
+    /// G-buffer; cleared when the voxels change.
+    pub ao_texture: Option<wgpu::Texture>,
+    /// Octree nodes from `build_svo`, marched by the compute path instead of `texture`;
+    /// cleared when the voxels change.
+    pub svo_buffer: Option<wgpu::Buffer>,
+    /// Voxels of the last erosion or dilation, filled in once read back from the GPU
+    /// and moved into `object` by `Renderer::sync_voxels`.
+    pub pending_voxels: Option<Rc<Cell<Option<Vec<u8>>>>>,
+    /// CPU copy of the object as uploaded (kept in sync by compute passes), for export.
+    pub object: VoxelObject,
+    /// Id of the object this draws, shared by the pieces of a split object.
+    pub object_id: u32,
+    /// Grid of the whole object, and the first voxel of this piece within it; `dims`
+    /// and zero unless the object was split.
+    pub object_dims: [u32; 3],
+    pub piece_origin: [u32; 3],
+}
 
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(CUBE_INDICES),
-            usage: wgpu::BufferUsages::INDEX,
-        });
+pub struct MeshDrawData {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub index_count: u32,
+}
 
-        let static_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Static Uniform Buffer"),
-            contents: &[0; std::mem::size_of::<StaticUniforms>()],
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
+/// Filtering applied to voxel colors at the raymarch hit.
+///
+/// Voxel textures hold palette indices (`R8Uint`), which the hardware cannot filter, so
+/// the blend happens in `shader.wgsl` over the palette colors of neighbouring occupied
+/// voxels. `Bilinear` blends across the hit face, `Trilinear` across the 2×2×2
+/// neighbourhood.
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum VoxelFilterMode {
+    #[default]
+    Nearest = 0,
+    Bilinear = 1,
+    Trilinear = 2,
+}
 
-        let per_frame_uniform_buffer =
-            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Per Frame Uniform Buffer"),
-                contents: &[0; std::mem::size_of::<PerFrameUniforms>()],
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            });
+/// What voxel lookups see past the edges of an object's grid.
+///
+/// `Clamp` treats the outside as empty. `Repeat` and `Mirror` tile the grid (mirrored
+/// for `Mirror`): the raymarch keeps walking past its faces into the copies, up to its
+/// 256-step limit, so the object's box shows the volume tiled behind it and the color
+/// filter and edge antialiasing at a face read the voxels across it. Copies of a
+/// tileable volume placed side by side join without seams. A slice view still stops
+/// the walk at its layer. The SVO path does not wrap.
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum VoxelWrapMode {
+    #[default]
+    Clamp = 0,
+    Repeat = 1,
+    Mirror = 2,
+}
 
-        let static_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Static Bind Group Layout"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-            });
+impl VoxelWrapMode {
+    /// Box the raymarch proxy of `object` covers: its filled voxels under `Clamp`, and
+    /// the whole grid when wrapped, where copies can show anywhere behind it.
+    fn proxy_bounds(self, object: &VoxelObject, empty_index: u8) -> Option<([f32; 3], [f32; 3])> {
+        let bounds = object.content_bounds(empty_index)?;
+        Some(match self {
+            VoxelWrapMode::Clamp => bounds,
+            _ => ([-0.5; 3], [0.5; 3]),
+        })
+    }
+}
 
-        let per_frame_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Per Frame Bind Group Layout"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
+/// What a `Renderer` draws into. Web Workers can only reach an `OffscreenCanvas`,
+/// transferred from the page with `transferControlToOffscreen`.
+#[derive(Clone, Debug)]
+pub enum RenderTarget {
+    Canvas(web_sys::HtmlCanvasElement),
+    OffscreenCanvas(web_sys::OffscreenCanvas),
+}
+
+impl RenderTarget {
+    fn size(&self) -> (u32, u32) {
+        match self {
+            RenderTarget::Canvas(canvas) => (canvas.width(), canvas.height()),
+            RenderTarget::OffscreenCanvas(offscreen) => (offscreen.width(), offscreen.height()),
+        }
+    }
+
+    fn surface_target(&self) -> wgpu::SurfaceTarget<'static> {
+        match self {
+            RenderTarget::Canvas(canvas) => wgpu::SurfaceTarget::Canvas(canvas.clone()),
+            RenderTarget::OffscreenCanvas(offscreen) => {
+                wgpu::SurfaceTarget::OffscreenCanvas(offscreen.clone())
+            }
+        }
+    }
+}
+
+impl From<web_sys::HtmlCanvasElement> for RenderTarget {
+    fn from(canvas: web_sys::HtmlCanvasElement) -> Self {
+        RenderTarget::Canvas(canvas)
+    }
+}
+
+impl From<web_sys::OffscreenCanvas> for RenderTarget {
+    fn from(offscreen: web_sys::OffscreenCanvas) -> Self {
+        RenderTarget::OffscreenCanvas(offscreen)
+    }
+}
+
+/// Construction-time options for `Renderer`.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct RendererBuilder {
+    voxel_filter: VoxelFilterMode,
+    pipeline_cache: Option<Vec<u8>>,
+    auto_pause: bool,
+}
+
+#[wasm_bindgen]
+impl RendererBuilder {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> RendererBuilder {
+        RendererBuilder::default()
+    }
+
+    pub fn with_voxel_filter(mut self, filter: VoxelFilterMode) -> RendererBuilder {
+        self.voxel_filter = filter;
+        self
+    }
+
+    /// Seeds pipeline compilation with data from `Renderer::serialize_pipeline_cache`
+    /// of an earlier session. Stale or foreign data is ignored.
+    pub fn with_pipeline_cache(mut self, cache_data: Option<Vec<u8>>) -> RendererBuilder {
+        self.pipeline_cache = cache_data;
+        self
+    }
+
+    /// Pauses the renderer (see `Renderer::pause`) while the document is hidden, on
+    /// top of any explicit pause.
+    pub fn with_auto_pause(mut self, enabled: bool) -> RendererBuilder {
+        self.auto_pause = enabled;
+        self
+    }
+
+    pub async fn build(
+        self,
+        html_canvas: web_sys::HtmlCanvasElement,
+    ) -> Result<Renderer, RendererError> {
+        Renderer::create(html_canvas.into(), self).await
+    }
+
+    /// Like `build`, for an `OffscreenCanvas`; see `Renderer::new_offscreen`.
+    pub async fn build_offscreen(
+        self,
+        canvas: web_sys::OffscreenCanvas,
+    ) -> Result<Renderer, RendererError> {
+        Renderer::create(canvas.into(), self).await
+    }
+}
+
+impl DrawCallData {
+    /// Fits the proxy to the voxels, or to the whole grid while the result of an
+    /// erosion or dilation is still being read back.
+    fn fit_proxy(&mut self, wrap: VoxelWrapMode, empty_index: u8) {
+        self.proxy_bounds = match self.pending_voxels {
+            Some(_) => Some(([-0.5; 3], [0.5; 3])),
+            None => wrap.proxy_bounds(&self.object, empty_index),
+        };
+    }
+
+    /// `proxy_bounds` narrowed to the slice's layer, `None` when that is empty.
+    fn drawn_proxy_bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        let (mut min, mut max) = self.proxy_bounds?;
+        if let Some((axis, index)) = self.slice {
+            // The layer of the whole object, outside this piece's grid if another holds it
+            let layer = index as f32 - self.piece_origin[axis] as f32;
+            let dim = self.object.dims[axis] as f32;
+            min[axis] = min[axis].max(layer / dim - 0.5);
+            max[axis] = max[axis].min((layer + 1.0) / dim - 0.5);
+            if min[axis] >= max[axis] {
+                return None;
+            }
+        }
+        Some((min, max))
+    }
+
+    fn uniforms(&self) -> PerDrawUniforms {
+        PerDrawUniforms::new(&self.object, self.drawn_proxy_bounds(), self.tint)
+    }
+
+    /// Size of the voxel texture.
+    pub fn texture_bytes(&self) -> u64 {
+        let size = self.texture.size();
+        let texel_bytes = self.object.bytes_per_voxel() as u64;
+        size.width as u64 * size.height as u64 * size.depth_or_array_layers as u64 * texel_bytes
+    }
+
+    /// Fails for `ColorMode::Rgba` objects, whose voxels `operation` of object `id`
+    /// would read as palette indices.
+    fn require_palette_voxels(&self, id: u32, operation: &str) -> Result<(), RendererError> {
+        if self.object.color_mode == ColorMode::Palette {
+            return Ok(());
+        }
+        Err(RendererError::invalid_object(
+            id,
+            Some("color_mode"),
+            format!(
+                "{} supports only palette voxels, object {} is rgba",
+                operation, id
+            ),
+        ))
+    }
+
+    /// Fails for `VoxelFormat::U16` and rgba objects, whose voxels `operation` of
+    /// object `id` cannot handle.
+    fn require_u8_voxels(&self, id: u32, operation: &str) -> Result<(), RendererError> {
+        self.require_palette_voxels(id, operation)?;
+        if self.object.format == VoxelFormat::U8 {
+            return Ok(());
+        }
+        Err(RendererError::invalid_object(
+            id,
+            Some("format"),
+            format!(
+                "{} supports only u8 voxels, object {} has u16 voxels",
+                operation, id
+            ),
+        ))
+    }
+}
+
+impl ObjectMemoryStats {
+    /// Footprint of the object drawn by `pieces`.
+    fn of(pieces: &[DrawCallData]) -> ObjectMemoryStats {
+        let count = pieces.len() as u64;
+        let voxel_texture_bytes = pieces.iter().map(DrawCallData::texture_bytes).sum();
+        // wgpu rounds uniform buffers up to 256-byte binding alignment
+        let uniform_buffer_bytes =
+            (std::mem::size_of::<PerDrawUniforms>() as u64).next_multiple_of(256) * count;
+        let bind_group_overhead = BIND_GROUP_OVERHEAD_BYTES * count;
+        ObjectMemoryStats {
+            voxel_texture_bytes,
+            uniform_buffer_bytes,
+            bind_group_overhead,
+            total_bytes: voxel_texture_bytes + uniform_buffer_bytes + bind_group_overhead,
+        }
+    }
+}
+
+/// Palette entries `u16` voxels can index.
+const MAX_PALETTE_ENTRIES: usize = PALETTE_TEXTURE_SIZE * PALETTE_TEXTURE_SIZE;
+/// Width and height of the palette texture, entry `i` at `(i % 256, i / 256)`.
+const PALETTE_TEXTURE_SIZE: usize = 256;
+
+/// Texture of the whole palette for indices past the 256 of `StaticUniforms`, read by
+/// the shaders for `VoxelFormat::U16` voxels; a 1×1 placeholder for palettes that fit
+/// the uniforms.
+fn create_palette_view(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    palette: &[RGBA],
+) -> wgpu::TextureView {
+    let size = if palette.len() > 256 {
+        PALETTE_TEXTURE_SIZE as u32
+    } else {
+        1
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Palette Texture"),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    if size > 1 {
+        let entries = &palette[..palette.len().min(MAX_PALETTE_ENTRIES)];
+        let rows = entries.len().div_ceil(PALETTE_TEXTURE_SIZE);
+        let mut texels = vec![0u8; rows * PALETTE_TEXTURE_SIZE * 4];
+        for (texel, color) in texels.chunks_exact_mut(4).zip(entries) {
+            texel.copy_from_slice(&[color.0, color.1, color.2, color.3]);
+        }
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &texels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(PALETTE_TEXTURE_SIZE as u32 * 4),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: PALETTE_TEXTURE_SIZE as u32,
+                height: rows as u32,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+fn bounds_of(draw_calls: &[DrawCallData]) -> Option<Aabb> {
+    draw_calls
+        .iter()
+        .map(|dc| dc.world_bounds)
+        .reduce(|a, b| a.union(&b))
+}
+
+/// GPU resources of a scene loaded with `load_scene_slot`.
+struct SceneSlot {
+    palette: Vec<RGBA>,
+    static_uniform_buffer: wgpu::Buffer,
+    static_bind_group: wgpu::BindGroup,
+    palette_view: wgpu::TextureView,
+    draw_call_array: Vec<DrawCallData>,
+    scene_bounds: Option<Aabb>,
+}
+
+impl SceneSlot {
+    fn compute_bounds(&self) -> Option<Aabb> {
+        bounds_of(&self.draw_call_array)
+    }
+}
+
+#[wasm_bindgen]
+pub struct Renderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    device_lost: DeviceLostFlag,
+    /// What the renderer was created from, for `reinitialize`
+    target: RenderTarget,
+    options: RendererBuilder,
+    /// Cancels the running `reinitialize`, if any, when replaced or dropped.
+    reinitialize_token: Option<ReinitializeToken>,
+    adapter_info: wgpu::AdapterInfo,
+    surface: wgpu::Surface<'static>,
+    surface_config: wgpu::SurfaceConfiguration,
+    shader: wgpu::ShaderModule,
+    /// `shader` for `ColorMode::Rgba` objects, and so on for the other `rgba_` fields
+    rgba_shader: wgpu::ShaderModule,
+    mesh_shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    rgba_pipeline_layout: wgpu::PipelineLayout,
+    mesh_pipeline_layout: wgpu::PipelineLayout,
+    render_pipeline: wgpu::RenderPipeline,
+    rgba_render_pipeline: wgpu::RenderPipeline,
+    /// Draw the back faces of objects whose proxy the near plane may cut, as when
+    /// the camera is inside them
+    inside_render_pipeline: wgpu::RenderPipeline,
+    rgba_inside_render_pipeline: wgpu::RenderPipeline,
+    mesh_pipeline: wgpu::RenderPipeline,
+    gbuffer_profile: GBufferProfile,
+    depth_enabled: bool,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    static_uniform_buffer: wgpu::Buffer,
+    clip_planes: ClipPlanesUniforms,
+    clip_planes_buffer: wgpu::Buffer,
+    per_frame_uniform_buffer: wgpu::Buffer,
+    per_frame_bind_group_layout: wgpu::BindGroupLayout,
+    per_draw_bind_group_layout: wgpu::BindGroupLayout,
+    rgba_per_draw_bind_group_layout: wgpu::BindGroupLayout,
+    quad_layout_uint: wgpu::BindGroupLayout,
+    quad_layout_float: wgpu::BindGroupLayout,
+    quad_pipeline_uint: wgpu::RenderPipeline,
+    quad_pipeline_float: wgpu::RenderPipeline,
+    /// Filtered float quad for the colour debug targets
+    quad_layout_sampled: wgpu::BindGroupLayout,
+    quad_pipeline_sampled: wgpu::RenderPipeline,
+    lighting_layout: wgpu::BindGroupLayout,
+    lighting_pipeline: wgpu::RenderPipeline,
+    lighting_uniform_buffer: wgpu::Buffer,
+    lighting_model: LightingModel,
+    /// `set_lighting_inputs`
+    normal_source: NormalSource,
+    ambient_occlusion: bool,
+    present_fit: PresentFit,
+    /// `present_fit` for the G-buffer and surface sizes, written every frame
+    present_uniform_buffer: wgpu::Buffer,
+    /// Identity present transform, for quads whose source matches their target
+    identity_present_buffer: wgpu::Buffer,
+    matcap: Matcap,
+    /// Packed roughness and metallic per palette index, see `set_material`.
+    material_buffer: wgpu::Buffer,
+    /// Image-based lighting and background of the lit target, see `set_environment`.
+    environment: Environment,
+    shadows: CascadedShadows,
+    /// Point lights and their per-tile lists, see `set_point_lights`.
+    light_culling: LightCulling,
+    /// `LightProbeUniforms`, see `set_light_probes`.
+    light_probe_buffer: wgpu::Buffer,
+    sh_projection: ShProjection,
+    wireframe_pipeline: wgpu::RenderPipeline,
+    wireframe_bind_group_layout: wgpu::BindGroupLayout,
+    edge_index_buffer: wgpu::Buffer,
+    debug_boxes: DebugBoxPass,
+    highlight: HighlightPass,
+    static_bind_group_layout: wgpu::BindGroupLayout,
+    static_bind_group: wgpu::BindGroup,
+    /// Palette entries past 256, from `create_palette_view`; bound with the static
+    /// uniforms.
+    palette_view: wgpu::TextureView,
+    /// Palette of the active scene, retained for `export_scene`.
+    palette: Vec<RGBA>,
+    /// Cancels the running `upload_scene_async`, if any, when replaced or dropped.
+    async_upload: Option<UploadToken>,
+    palette_animation: PaletteAnimation,
+    palette_transition: Option<PaletteTransition>,
+    scene_slots: HashMap<String, SceneSlot>,
+    active_scene_slot: Option<String>,
+    gbuffer: GBufferTextures,
+    linear_z_format: wgpu::TextureFormat,
+    depth_readback: DepthReadback,
+    /// Buffer the next rendered frame is copied into, set by `capture_frame`.
+    frame_capture: Option<wgpu::Buffer>,
+    nearest_sampler: wgpu::Sampler,
+    /// Used by the albedo present target, which is smoother upscaled linearly.
+    linear_sampler: wgpu::Sampler,
+    depth_texture: wgpu::Texture,
+    depth_texture_view: wgpu::TextureView,
+    /// Stencil plane of `depth_texture`, read by the lighting pass for the highlight
+    stencil_view: wgpu::TextureView,
+    depth_resolve_layout: wgpu::BindGroupLayout,
+    depth_resolve_pipeline: wgpu::RenderPipeline,
+    fxaa: FxaaPass,
+    ssr: SsrPass,
+    #[cfg(feature = "hud")]
+    hud: hud::HudPass,
+    /// Created by the first `set_render_pass_timestamps(true)`.
+    timestamps: Option<PassTimestamps>,
+    post_effects: PostEffects,
+    depth_only: DepthOnlyPass,
+    /// Shared by every pipeline; only available on native backends.
+    pipeline_cache: Option<wgpu::PipelineCache>,
+    raymarch: ComputeRaymarch,
+    render_path: RenderPath,
+    /// Set for frames `RenderPath::Compute` cannot draw, which the raster path draws.
+    compute_fallback: bool,
+    checkerboard: CheckerboardPass,
+    checkerboard_enabled: bool,
+    occlusion: OcclusionCuller,
+    occlusion_culling: bool,
+    /// Compute shaders and indirect draws, required by `occlusion`.
+    occlusion_supported: bool,
+    render_graph: RenderGraph,
+    morphology: MorphologyPipelines,
+    ao_bake: AoBakePipelines,
+    neighbour_ao: NeighbourAoPipeline,
+    /// Bound as the AO of objects with none baked
+    unoccluded_ao_view: wgpu::TextureView,
+    ao_bake_timings: Rc<Cell<AoBakeTimings>>,
+    post_targets: [wgpu::TextureView; 2],
+    xr_eyes: Vec<XrEye>,
+    frame: FrameSettings,
+    /// Set by any change that affects the image; cleared when a frame is presented.
+    dirty: bool,
+    pacer: FramePacer,
+    /// Share of the surface size the G-buffer is rendered at, see `set_render_scale`
+    render_scale: f32,
+    /// Longest side of the G-buffer in pixels, see `set_max_render_dimension`
+    max_render_dimension: Option<u32>,
+    dynamic_resolution: Option<DynamicResolution>,
+    post_submit_callback: Option<js_sys::Function>,
+    /// Uniform writes held by `begin_update_batch`
+    update_batch: UpdateBatch,
+    animation_loop: Option<AnimationLoop>,
+    /// Set by `pause`; no frames are drawn while set or while `auto_pause` is hidden.
+    paused: bool,
+    auto_pause: Option<AutoPause>,
+    draw_call_array: Vec<DrawCallData>,
+    /// Draw calls of each object by id, from `index_objects`: one, or the adjacent
+    /// pieces of an object split by `max_volume_size`
+    object_pieces: Vec<Range<usize>>,
+    /// Objects added by `create_voxel_from_sdf`, for unique ids
+    sdf_object_count: u32,
+    /// Cap on the side of object textures from `set_max_volume_size`; 0 for none
+    max_volume_size: u32,
+    /// Whether a scene's `inv_model_matrix` is used as given, see
+    /// `set_trust_inverse_matrices`
+    trust_inverse_matrices: bool,
+    /// `Scene::content_hash` of the last `upload_scene`, 0 once anything else replaced
+    /// or edited the active scene
+    last_scene_hash: u64,
+    meshes: Vec<MeshDrawData>,
+    scene_bounds: Option<Aabb>,
+    voxel_filter: VoxelFilterMode,
+    voxel_wrap: VoxelWrapMode,
+    voxel_rounding: f32,
+    /// Palette index of empty voxels; 0 unless changed by `set_empty_index`.
+    empty_index: u8,
+    edge_aa: Option<EdgeAaQuality>,
+    specular_strength: f32,
+    shininess: f32,
+    rim_color: [f32; 3],
+    rim_power: f32,
+    rim_intensity: f32,
+}
+
+#[wasm_bindgen]
+impl Renderer {
+    pub async fn new(html_canvas: web_sys::HtmlCanvasElement) -> Result<Renderer, RendererError> {
+        Renderer::create(html_canvas.into(), RendererBuilder::default()).await
+    }
+
+    /// Renders into an `OffscreenCanvas`, as from a Web Worker. Everything but
+    /// `start_loop` and `RendererBuilder::with_auto_pause` works there; both need the
+    /// page's window and document, so a worker drives `render_frame` from its own
+    /// `requestAnimationFrame` instead.
+    pub async fn new_offscreen(
+        canvas: web_sys::OffscreenCanvas,
+    ) -> Result<Renderer, RendererError> {
+        Renderer::create(canvas.into(), RendererBuilder::default()).await
+    }
+
+    async fn create(
+        target: RenderTarget,
+        options: RendererBuilder,
+    ) -> Result<Renderer, RendererError> {
+        log::install_panic_hook();
+        // Initialize the GPU
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+
+        let (canvas_width, canvas_height) = target.size();
+        let surface = instance.create_surface(target.surface_target())?;
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: Some(&surface),
+                force_fallback_adapter: false,
+            })
+            .await?;
+
+        let adapter_info = adapter.get_info();
+        log::info!("using {} ({:?})", adapter_info.name, adapter_info.backend);
+
+        // Pipeline caches are a native-only feature; WebGPU compiles without one
+        let cache_feature = adapter.features() & wgpu::Features::PIPELINE_CACHE;
+        // Requested when available, for `set_render_pass_timestamps`
+        let timestamp_feature = adapter.features() & wgpu::Features::TIMESTAMP_QUERY;
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                required_features: wgpu::Features::TEXTURE_FORMAT_16BIT_NORM
+                    | cache_feature
+                    | timestamp_feature,
+                ..Default::default()
+            })
+            .await?;
+        let device_lost = DeviceLostFlag::watch(&device);
+        let auto_pause = options.auto_pause.then(AutoPause::start).transpose()?;
+        let pipeline_cache = (!cache_feature.is_empty()).then(|| {
+            // SAFETY: the data is whatever `serialize_pipeline_cache` returned earlier;
+            // with `fallback` set, data wgpu does not recognise is discarded.
+            unsafe {
+                device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                    label: Some("Pipeline Cache"),
+                    data: options.pipeline_cache.as_deref(),
+                    fallback: true,
+                })
+            }
+        });
+
+        let linear_z_format = choose_linear_z_format(&adapter)?;
+
+        let surface_capabilities = surface.get_capabilities(&adapter);
+        let surface_format = *surface_capabilities.formats.first().unwrap();
+
+        let surface_config = wgpu::SurfaceConfiguration {
+            // Copies out of the canvas are only needed by `capture_frame`
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | (surface_capabilities.usages & wgpu::TextureUsages::COPY_SRC),
+            format: surface_format,
+            width: canvas_width,
+            height: canvas_height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            desired_maximum_frame_latency: 2,
+            view_formats: vec![],
+        };
+        surface.configure(&device, &surface_config);
+
+        let nearest_sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        let linear_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Linear Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let depth_texture =
+            create_depth_texture(&device, surface_config.width, surface_config.height);
+        let depth_texture_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let stencil_view = create_stencil_view(&depth_texture);
+
+        let voxel_shader = |label, color_mode: ColorMode| {
+            let source = color_mode.compose_shader(include_str!("shaders/shader.wgsl"));
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            })
+        };
+        let shader = voxel_shader("Shader", ColorMode::Palette);
+        let rgba_shader = voxel_shader("Rgba Shader", ColorMode::Rgba);
+
+        // The icosphere follows the cube, its indices offset past the cube's vertices
+        let proxy_vertices: Vec<Vertex> = CUBE_VERTICES
+            .iter()
+            .copied()
+            .chain(
+                ICOSPHERE_VERTICES
+                    .iter()
+                    .map(|v| v.scaled(0.5 / ICOSPHERE_INRADIUS)),
+            )
+            .collect();
+        let icosphere_index = |i: &u16| i + CUBE_VERTICES.len() as u16;
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: bytemuck::cast_slice(&proxy_vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let proxy_indices: Vec<u16> = CUBE_INDICES
+            .iter()
+            .copied()
+            .chain(ICOSPHERE_INDICES.iter().map(icosphere_index))
+            .collect();
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Index Buffer"),
+            contents: bytemuck::cast_slice(&proxy_indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let static_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Static Uniform Buffer"),
+            contents: &[0; std::mem::size_of::<StaticUniforms>()],
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let clip_planes_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Clip Planes Uniform Buffer"),
+            contents: &[0; std::mem::size_of::<ClipPlanesUniforms>()],
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let per_frame_uniform_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Per Frame Uniform Buffer"),
+                contents: &[0; std::mem::size_of::<PerFrameUniforms>()],
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             });
 
-        let per_draw_bind_group_layout =
+        let static_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Per Draw Call Bind Group Layout"),
+                label: Some("Static Bind Group Layout"),
                 entries: &[
                     wgpu::BindGroupLayoutEntry {
                         binding: 0,
                         visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Uint,
-                            view_dimension: wgpu::TextureViewDimension::D3,
-                            multisampled: false,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
                         },
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
                         binding: 1,
-                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Buffer {
                             ty: wgpu::BufferBindingType::Uniform,
                             has_dynamic_offset: false,
@@ -275,100 +1570,137 @@ impl Renderer {
                         },
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
                 ],
             });
 
+        let per_frame_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Per Frame Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let per_draw_bind_group_layout =
+            create_per_draw_bind_group_layout(&device, ColorMode::Palette);
+        let rgba_per_draw_bind_group_layout =
+            create_per_draw_bind_group_layout(&device, ColorMode::Rgba);
+
+        let palette_view = create_palette_view(&device, &queue, &[]);
         let static_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Static Bind Group"),
             layout: &static_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: static_uniform_buffer.as_entire_binding(),
-            }],
-        });
-
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Pipeline Layout"),
-            bind_group_layouts: &[
-                &static_bind_group_layout,
-                &per_frame_bind_group_layout,
-                &per_draw_bind_group_layout,
-            ],
-            push_constant_ranges: &[],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: static_uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: clip_planes_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&palette_view),
+                },
+            ],
         });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("G-Buffer Render Pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[wgpu::VertexBufferLayout {
-                    array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-                    step_mode: wgpu::VertexStepMode::Vertex,
-                    attributes: &wgpu::vertex_attr_array![0 => Float32x3],
-                }],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[
-                    Some(wgpu::ColorTargetState {
-                        format: wgpu::TextureFormat::Rgba8Unorm,
-                        blend: None,
-                        write_mask: wgpu::ColorWrites::ALL,
-                    }),
-                    Some(wgpu::ColorTargetState {
-                        format: wgpu::TextureFormat::Rgba8Unorm,
-                        blend: None,
-                        write_mask: wgpu::ColorWrites::ALL,
-                    }),
-                    Some(wgpu::ColorTargetState {
-                        format: wgpu::TextureFormat::R16Uint,
-                        blend: None,
-                        write_mask: wgpu::ColorWrites::ALL,
-                    }),
+        let voxel_pipeline_layout = |label, per_draw_layout| {
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[
+                    &static_bind_group_layout,
+                    &per_frame_bind_group_layout,
+                    per_draw_layout,
                 ],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            primitive: wgpu::PrimitiveState::default(),
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth24PlusStencil8,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-            cache: None,
-        });
+                push_constant_ranges: &[],
+            })
+        };
+        let pipeline_layout = voxel_pipeline_layout("Pipeline Layout", &per_draw_bind_group_layout);
+        let rgba_pipeline_layout =
+            voxel_pipeline_layout("Rgba Pipeline Layout", &rgba_per_draw_bind_group_layout);
 
-        let gbuffer_albedo = create_render_texture_view(
-            &device,
-            canvas_width,
-            canvas_height,
-            wgpu::TextureFormat::Rgba8Unorm,
-            "GBuffer Albedo",
-        );
-        let gbuffer_normal = create_render_texture_view(
+        let voxel_pipeline = |layout, shader, cull_mode| {
+            create_voxel_pipeline(
+                &device,
+                pipeline_cache.as_ref(),
+                layout,
+                shader,
+                linear_z_format,
+                options.voxel_filter,
+                VoxelWrapMode::Clamp,
+                GBufferProfile::Full,
+                true,
+                cull_mode,
+            )
+        };
+        let render_pipeline = voxel_pipeline(&pipeline_layout, &shader, wgpu::Face::Back);
+        let rgba_render_pipeline =
+            voxel_pipeline(&rgba_pipeline_layout, &rgba_shader, wgpu::Face::Back);
+        let inside_render_pipeline = voxel_pipeline(&pipeline_layout, &shader, wgpu::Face::Front);
+        let rgba_inside_render_pipeline =
+            voxel_pipeline(&rgba_pipeline_layout, &rgba_shader, wgpu::Face::Front);
+
+        let mesh_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mesh Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/mesh.wgsl").into()),
+        });
+        let mesh_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mesh Pipeline Layout"),
+            bind_group_layouts: &[&static_bind_group_layout, &per_frame_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let mesh_pipeline = create_mesh_pipeline(
             &device,
-            canvas_width,
-            canvas_height,
-            wgpu::TextureFormat::Rgba8Unorm,
-            "GBuffer Normal",
+            pipeline_cache.as_ref(),
+            &mesh_pipeline_layout,
+            &mesh_shader,
+            linear_z_format,
+            GBufferProfile::Full,
+            true,
         );
-        let gbuffer_linear_z = create_render_texture_view(
+
+        let gbuffer = GBufferTextures::new(
             &device,
             canvas_width,
             canvas_height,
-            wgpu::TextureFormat::R16Uint,
-            "GBuffer LinearZ",
+            GBufferProfile::Full,
+            linear_z_format,
         );
+        let depth_readback = DepthReadback::new(&device);
+        let present_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Present Uniform Buffer"),
+            contents: bytemuck::bytes_of(&PresentUniforms::IDENTITY),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let identity_present_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Identity Present Uniform Buffer"),
+                contents: bytemuck::bytes_of(&PresentUniforms::IDENTITY),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
 
         let (quad_layout_uint, quad_pipeline_uint, _) = Renderer::create_fullscreen_quad_pipeline(
             &device,
+            pipeline_cache.as_ref(),
             surface_format,
             include_str!("shaders/quad_uint.wgsl"),
             wgpu::TextureSampleType::Uint,
@@ -379,6 +1711,7 @@ impl Renderer {
         );
         let (quad_layout_float, quad_pipeline_float, _) = Renderer::create_fullscreen_quad_pipeline(
             &device,
+            pipeline_cache.as_ref(),
             surface_format,
             include_str!("shaders/quad_float.wgsl"),
             wgpu::TextureSampleType::Float { filterable: false },
@@ -387,60 +1720,241 @@ impl Renderer {
             "Quad Float Shader",
             "Quad Pipeline Float",
         );
+        let (quad_layout_sampled, quad_pipeline_sampled, _) =
+            Renderer::create_fullscreen_quad_pipeline(
+                &device,
+                pipeline_cache.as_ref(),
+                surface_format,
+                include_str!("shaders/quad_sampled.wgsl"),
+                wgpu::TextureSampleType::Float { filterable: true },
+                wgpu::SamplerBindingType::Filtering,
+                "Quad Layout Sampled",
+                "Quad Sampled Shader",
+                "Quad Pipeline Sampled",
+            );
 
         // Lighting pass pipeline
         let lighting_uniform_buffer =
             device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Lighting Uniform Buffer"),
-                contents: bytemuck::cast_slice(&[LightingUniforms {
-                    light_dir: [0.5, 0.5, 0.5],
-                    ambient: 0.1,
-                }]),
+                contents: &[0; std::mem::size_of::<LightingUniforms>()],
                 usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             });
 
-        let lighting_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Lighting Bind Group Layout"),
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            multisampled: false,
-                        },
-                        count: None,
+        let lighting_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Lighting Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
                     },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            multisampled: false,
-                        },
-                        count: None,
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
                     },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
-                        count: None,
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
                     },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 3,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
                     },
-                ],
-            });
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 9,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 10,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 11,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 12,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 13,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 14,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 15,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 16,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 17,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 18,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Stencil plane, marking the highlight region
+                wgpu::BindGroupLayoutEntry {
+                    binding: 19,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // Material target of the G-buffer
+                wgpu::BindGroupLayoutEntry {
+                    binding: 20,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let matcap = Matcap::new(&device, &queue);
+        let environment = Environment::new(&device, &queue);
+        let light_probe_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Light Probe Buffer"),
+            contents: bytemuck::bytes_of(&LightProbeUniforms::new(&[]).unwrap()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let sh_projection = ShProjection::new(&device, pipeline_cache.as_ref());
+        let material_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Material Buffer"),
+            contents: bytemuck::cast_slice(&[pack_material(0.5, 0.0); 256]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
 
         let lighting_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Lighting Shader"),
@@ -470,36 +1984,123 @@ impl Renderer {
                         blend: None,
                         write_mask: wgpu::ColorWrites::ALL,
                     })],
-                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    compilation_options: wgpu::PipelineCompilationOptions {
+                        constants: &[("encode_srgb", (!surface_format.is_srgb()) as u32 as f64)],
+                        ..Default::default()
+                    },
                 }),
                 primitive: Default::default(),
                 depth_stencil: None,
                 multisample: Default::default(),
                 multiview: None,
-                cache: None,
+                cache: pipeline_cache.as_ref(),
             })
         };
 
-        // Wireframe pipeline for bounding boxes
-        let edge_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Edge Index Buffer"),
-            contents: bytemuck::cast_slice(CUBE_EDGE_INDICES),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-
-        let wireframe_bind_group_layout =
+        // Depth resolve pipeline: hardware depth -> world-space distance (R32Float)
+        let depth_resolve_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Wireframe Bind Group Layout"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                label: Some("Depth Resolve Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Depth,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let depth_resolve_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Depth Resolve Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/depth_resolve.wgsl").into()),
+        });
+
+        let depth_resolve_pipeline = {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Depth Resolve Pipeline Layout"),
+                bind_group_layouts: &[&depth_resolve_layout],
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Depth Resolve Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &depth_resolve_shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &depth_resolve_shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: wgpu::TextureFormat::R32Float,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: Default::default(),
+                depth_stencil: None,
+                multisample: Default::default(),
+                multiview: None,
+                cache: pipeline_cache.as_ref(),
+            })
+        };
+
+        // Wireframe pipeline for bounding boxes
+        let edge_indices: Vec<u16> = CUBE_EDGE_INDICES
+            .iter()
+            .copied()
+            .chain(ICOSPHERE_EDGE_INDICES.iter().map(icosphere_index))
+            .collect();
+        let edge_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Edge Index Buffer"),
+            contents: bytemuck::cast_slice(&edge_indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let wireframe_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Wireframe Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Clip planes, which cut the edges as they cut the voxels
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
             });
 
         let wireframe_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -543,80 +2144,757 @@ impl Renderer {
                 depth_stencil: None,
                 multisample: Default::default(),
                 multiview: None,
-                cache: None,
+                cache: pipeline_cache.as_ref(),
             })
         };
 
+        let cache = pipeline_cache.as_ref();
+        let debug_boxes = DebugBoxPass::new(
+            &device,
+            (&vertex_buffer, &edge_index_buffer),
+            &per_frame_bind_group_layout,
+            surface_format,
+            cache,
+        );
+        let highlight = HighlightPass::new(
+            &device,
+            (&vertex_buffer, &index_buffer),
+            &per_frame_bind_group_layout,
+            &GBufferProfile::Full.color_targets(linear_z_format),
+            cache,
+        );
+        let fxaa = FxaaPass::new(&device, surface_format, cache);
+        let ssr = SsrPass::new(&device, surface_format, cache);
+        #[cfg(feature = "hud")]
+        let hud = hud::HudPass::new(&device, &queue, surface_format, cache);
+        let post_effects = PostEffects::new(&device, surface_format);
+        let morphology = MorphologyPipelines::new(&device, cache);
+        let ao_bake = AoBakePipelines::new(&device, cache);
+        let neighbour_ao = NeighbourAoPipeline::new(&device, cache);
+        let unoccluded_ao_view = ao_bake::create_unoccluded_view(&device, &queue);
+        let per_draw_layouts = [
+            &per_draw_bind_group_layout,
+            &rgba_per_draw_bind_group_layout,
+        ];
+        let depth_only = DepthOnlyPass::new(&device, per_draw_layouts, cache);
+        let shadows = CascadedShadows::new(&device, per_draw_layouts, &clip_planes_buffer, cache);
+        let light_culling = LightCulling::new(&device, cache);
+        let raymarch = ComputeRaymarch::new(
+            &device,
+            linear_z_format,
+            options.voxel_filter,
+            VoxelWrapMode::Clamp,
+            cache,
+        );
+        let checkerboard = CheckerboardPass::new(&device, linear_z_format, cache);
+        let occlusion = OcclusionCuller::new(&device, cache);
+        let post_targets =
+            create_post_targets(&device, canvas_width, canvas_height, surface_format);
+
         Ok(Renderer {
             device,
             queue,
             adapter_info,
             surface,
+            shader,
+            rgba_shader,
+            mesh_shader,
+            pipeline_layout,
+            rgba_pipeline_layout,
+            mesh_pipeline_layout,
             render_pipeline,
+            rgba_render_pipeline,
+            inside_render_pipeline,
+            rgba_inside_render_pipeline,
+            mesh_pipeline,
+            gbuffer_profile: GBufferProfile::Full,
+            depth_enabled: true,
             vertex_buffer,
             index_buffer,
             static_uniform_buffer,
+            clip_planes: bytemuck::Zeroable::zeroed(),
+            clip_planes_buffer,
             per_frame_uniform_buffer,
             per_frame_bind_group_layout,
             per_draw_bind_group_layout,
+            rgba_per_draw_bind_group_layout,
+            static_bind_group_layout,
             static_bind_group,
+            palette_view,
+            palette: Vec::new(),
+            async_upload: None,
+            palette_animation: PaletteAnimation::default(),
+            palette_transition: None,
+            scene_slots: HashMap::new(),
+            active_scene_slot: None,
+            depth_texture,
             depth_texture_view,
-            gbuffer_albedo,
-            gbuffer_normal,
-            gbuffer_linear_z,
+            stencil_view,
+            depth_resolve_layout,
+            depth_resolve_pipeline,
+            fxaa,
+            ssr,
+            #[cfg(feature = "hud")]
+            hud,
+            timestamps: None,
+            post_effects,
+            depth_only,
+            pipeline_cache,
+            raymarch,
+            render_path: RenderPath::Raster,
+            compute_fallback: false,
+            checkerboard,
+            checkerboard_enabled: false,
+            occlusion,
+            occlusion_culling: false,
+            occlusion_supported: occlusion::is_supported(&adapter.get_downlevel_capabilities()),
+            render_graph: RenderGraph::default(),
+            morphology,
+            ao_bake,
+            unoccluded_ao_view,
+            neighbour_ao,
+            ao_bake_timings: Rc::default(),
+            post_targets,
+            xr_eyes: Vec::new(),
+            frame: FrameSettings::default(),
+            dirty: true,
+            pacer: FramePacer::default(),
+            render_scale: 1.0,
+            max_render_dimension: None,
+            dynamic_resolution: None,
+            post_submit_callback: None,
+            update_batch: UpdateBatch::default(),
+            animation_loop: None,
+            paused: false,
+            auto_pause,
+            gbuffer,
+            linear_z_format,
+            depth_readback,
+            frame_capture: None,
             surface_config,
             quad_layout_uint,
             quad_layout_float,
             quad_pipeline_uint,
             quad_pipeline_float,
+            quad_layout_sampled,
+            quad_pipeline_sampled,
             lighting_layout,
             lighting_pipeline,
             lighting_uniform_buffer,
+            lighting_model: LightingModel::Directional,
+            normal_source: NormalSource::Stored,
+            ambient_occlusion: false,
+            present_fit: PresentFit::Stretch,
+            present_uniform_buffer,
+            identity_present_buffer,
+            matcap,
+            material_buffer,
+            environment,
+            shadows,
+            light_culling,
+            light_probe_buffer,
+            sh_projection,
             wireframe_pipeline,
             wireframe_bind_group_layout,
             edge_index_buffer,
-            sampler,
+            debug_boxes,
+            highlight,
+            nearest_sampler,
+            linear_sampler,
             draw_call_array: Vec::new(),
+            object_pieces: Vec::new(),
+            sdf_object_count: 0,
+            max_volume_size: 0,
+            trust_inverse_matrices: false,
+            last_scene_hash: 0,
+            meshes: Vec::new(),
+            scene_bounds: None,
+            voxel_filter: options.voxel_filter,
+            voxel_wrap: VoxelWrapMode::Clamp,
+            voxel_rounding: 0.0,
+            empty_index: 0,
+            edge_aa: None,
+            specular_strength: 0.0,
+            shininess: 32.0,
+            rim_color: [1.0; 3],
+            rim_power: 3.0,
+            rim_intensity: 0.0,
+            device_lost,
+            target,
+            options,
+            reinitialize_token: None,
         })
     }
 
-    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), JsValue> {
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), RendererError> {
+        log::debug!("resizing to {}x{}", width, height);
+        self.dirty = true;
         self.surface_config.width = width;
         self.surface_config.height = height;
         self.surface.configure(&self.device, &self.surface_config);
 
         // Recreate depth
-        self.depth_texture_view = create_depth_texture(&self.device, &self.surface_config);
+        self.create_depth_target();
 
         // Recreate G‑buffer targets
-        self.gbuffer_albedo = create_render_texture_view(
+        self.create_gbuffer_targets();
+
+        self.post_targets =
+            create_post_targets(&self.device, width, height, self.surface_config.format);
+
+        Ok(())
+    }
+
+    /// Resizes to the backing size of a `css_width`×`css_height` canvas at `dpr`
+    /// device pixels per CSS pixel, scaled down proportionally to stay within
+    /// `max_pixels` if given. Returns the `[width, height]` actually used, which the
+    /// host should assign to the canvas attributes.
+    pub fn resize_to_display(
+        &mut self,
+        css_width: f32,
+        css_height: f32,
+        dpr: f32,
+        max_pixels: Option<u32>,
+    ) -> Result<Vec<u32>, RendererError> {
+        let dpr = if dpr.is_finite() && dpr > 0.0 {
+            dpr
+        } else {
+            1.0
+        };
+        let mut width = (css_width * dpr).round().max(1.0);
+        let mut height = (css_height * dpr).round().max(1.0);
+        if let Some(budget) = max_pixels.filter(|&budget| budget > 0) {
+            let pixels = width * height;
+            if pixels > budget as f32 {
+                let scale = (budget as f32 / pixels).sqrt();
+                width = (width * scale).floor().max(1.0);
+                height = (height * scale).floor().max(1.0);
+            }
+        }
+
+        let (width, height) = (width as u32, height as u32);
+        if width != self.surface_config.width || height != self.surface_config.height {
+            self.resize(width, height)?;
+        }
+        Ok(vec![width, height])
+    }
+
+    /// Switches which G-buffer targets are rendered, rebuilding the G-buffer pipelines
+    /// and targets. `AlbedoOnly` skips normal and linear-Z generation and always
+    /// presents albedo, so lighting, debug views and depth queries are unavailable.
+    pub fn set_gbuffer_profile(&mut self, profile: GBufferProfile) {
+        self.dirty = true;
+        if profile == self.gbuffer_profile {
+            return;
+        }
+        self.gbuffer_profile = profile;
+        self.create_gbuffer_pipelines();
+        self.create_gbuffer_targets();
+    }
+
+    /// Turns the G-buffer depth test and depth buffer on or off. Without depth,
+    /// objects are drawn in painter's order (see `order`), the depth debug view shows
+    /// the lit image instead and `capture_depth` is unavailable.
+    pub fn set_depth_enabled(&mut self, enabled: bool) {
+        self.dirty = true;
+        if enabled == self.depth_enabled {
+            return;
+        }
+        self.depth_enabled = enabled;
+        self.create_gbuffer_pipelines();
+        self.create_depth_target();
+    }
+
+    /// Chooses between rasterized proxies and the full-screen compute raymarch.
+    pub fn set_render_path(&mut self, path: RenderPath) {
+        self.dirty = true;
+        self.render_path = path;
+    }
+
+    /// Raymarches half the pixels each frame in a checkerboard pattern and
+    /// reconstructs the rest from the previous frame, reprojected by depth, or from
+    /// rendered neighbours where that fails. Applies to the raster path with the full
+    /// G-buffer profile; depth views and `capture_depth` are unavailable meanwhile.
+    /// Present target 5 shows which pixels were reconstructed.
+    pub fn set_checkerboard(&mut self, enabled: bool) {
+        self.dirty = true;
+        if enabled && !self.checkerboard_enabled {
+            self.checkerboard.reset();
+        }
+        self.checkerboard_enabled = enabled;
+    }
+
+    /// Skips objects hidden behind the previous frame's surfaces: a compute pass tests
+    /// each object's bounds against a Hi-Z pyramid of the last linear-Z and writes the
+    /// indirect draws of the G-buffer pass, after CPU frustum culling. Objects revealed
+    /// by camera motion appear one frame late, and a follow-up frame is rendered once
+    /// the camera stops. Applies to the raster path with the full G-buffer profile.
+    /// Returns false, leaving culling off, when the device lacks compute shaders or
+    /// indirect draws.
+    pub fn set_occlusion_culling(&mut self, enabled: bool) -> bool {
+        self.dirty = true;
+        self.occlusion.invalidate();
+        self.occlusion_culling = enabled && self.occlusion_supported;
+        self.occlusion_culling
+    }
+
+    /// Objects occlusion culling skipped in a recent frame (the count is read back
+    /// asynchronously); 0 while culling is off.
+    pub fn get_occlusion_culled_count(&self) -> u32 {
+        match self.occlusion_active() {
+            true => self.occlusion.culled_count(),
+            false => 0,
+        }
+    }
+
+    /// Toggles the FXAA pass over the presented image.
+    pub fn set_fxaa_enabled(&mut self, enabled: bool) {
+        self.dirty = true;
+        self.render_graph.set_enabled(PassKind::Fxaa, enabled);
+    }
+
+    /// Runs FXAA before screen-space reflections instead of after them (the default),
+    /// so reflections sample the anti-aliased image but keep their own jagged edges.
+    pub fn set_fxaa_before_ssr(&mut self, before: bool) -> Result<(), RendererError> {
+        self.dirty = true;
+        match before {
+            true => self.render_graph.move_before(PassKind::Fxaa, PassKind::Ssr),
+            false => self.render_graph.move_before(PassKind::Ssr, PassKind::Fxaa),
+        }
+    }
+
+    /// Toggles a debug text overlay in the top-left corner of each frame: the frame
+    /// rate, the draw calls of the G-buffer pass and, while `set_render_pass_timestamps`
+    /// measures them, the GPU pass times. Only in builds with the `hud` feature.
+    #[cfg(feature = "hud")]
+    pub fn set_hud(&mut self, enabled: bool) {
+        self.dirty = true;
+        self.render_graph.set_enabled(PassKind::Hud, enabled);
+    }
+
+    /// Queues the edges of the world-space box from `min` to `max` (3 components each)
+    /// in `color` (RGBA, 0 to 1, blended by alpha), drawn over the next rendered frame
+    /// only, for gizmos such as selection cursors and brush previews. Calls accumulate
+    /// until that frame. Boxes are not depth tested, so they show through voxels.
+    pub fn draw_debug_box(
+        &mut self,
+        min: &[f32],
+        max: &[f32],
+        color: &[f32],
+    ) -> Result<(), RendererError> {
+        let debug_box = parse_box(min, max, color)?;
+        self.debug_boxes.push(debug_box);
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Tints the lit surfaces inside the world-space box from `min` to `max` (3
+    /// components each) toward `color` (RGBA, 0 to 1, blended by alpha), replacing any
+    /// previous region. The G-buffer pass marks the region in its stencil plane, so
+    /// this needs the raster path with depth and is skipped with checkerboard
+    /// rendering; the debug views are not tinted.
+    pub fn set_highlight_region(
+        &mut self,
+        min: &[f32],
+        max: &[f32],
+        color: &[f32],
+    ) -> Result<(), RendererError> {
+        let DebugBox { min, max, color } = parse_box(min, max, color)?;
+        self.highlight
+            .set_region(&self.queue, Some(HighlightRegion { min, max }));
+        self.highlight.color = color.map(|c| c.clamp(0.0, 1.0));
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Removes the region of `set_highlight_region`.
+    pub fn clear_highlight_region(&mut self) {
+        self.highlight.set_region(&self.queue, None);
+        self.dirty = true;
+    }
+
+    /// Toggles screen-space reflections over the lit image, marching each reflected
+    /// ray in `max_steps` steps (1 to 256) across the screen against the linear-Z.
+    /// Reflections are as strong as the palette entry's material is smooth (see
+    /// `set_material`) and follow Schlick Fresnel; they only show what is on screen and
+    /// fade out toward its edges. No effect on the G-buffer debug views.
+    pub fn set_ssr(&mut self, enabled: bool, max_steps: u32) -> Result<(), RendererError> {
+        if max_steps == 0 {
+            return Err(RendererError::invalid(
+                "max_steps",
+                "SSR needs at least one step",
+            ));
+        }
+        if max_steps > MAX_SSR_STEPS {
+            return Err(RendererError::limit(
+                "ssr_steps",
+                max_steps,
+                MAX_SSR_STEPS,
+                format!(
+                    "at most {} SSR steps are supported, got {}",
+                    MAX_SSR_STEPS, max_steps
+                ),
+            ));
+        }
+        self.ssr.enabled = enabled;
+        self.ssr.max_steps = max_steps;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Appends a fullscreen post effect, run after the present pass and FXAA in the
+    /// order added. `source` is WGSL defining
+    /// `@fragment fn fs_main(in: VSOut) -> @location(0) vec4<f32>`; it is compiled
+    /// after a prelude declaring `VSOut { position, uv }`, the image so far as
+    /// `@group(0) @binding(0) var input_tex: texture_2d<f32>` and its filtering sampler
+    /// `@group(0) @binding(1) var input_samp: sampler`. The effect may declare
+    /// `@group(0) @binding(2) var<uniform>` of any type to read the bytes of
+    /// `set_custom_uniform`. Shader errors are reported as device validation errors.
+    pub fn add_post_effect(&mut self, source: &str) {
+        self.post_effects.add(&self.device, source);
+        self.render_graph.set_enabled(PassKind::PostEffects, true);
+        self.dirty = true;
+    }
+
+    /// Removes every effect added with `add_post_effect`.
+    pub fn clear_post_effects(&mut self) {
+        self.post_effects.clear();
+        self.render_graph.set_enabled(PassKind::PostEffects, false);
+        self.dirty = true;
+    }
+
+    /// Sets the contents of the uniform buffer custom post effects see at
+    /// `@group(0) @binding(2)`, for per-frame parameters. It starts as 256 zero bytes
+    /// and grows when `bytes` is larger; a struct the effect declares must fit in it.
+    /// Fails when `bytes` exceed the device's uniform binding size limit.
+    pub fn set_custom_uniform(&mut self, bytes: &[u8]) -> Result<(), RendererError> {
+        self.post_effects
+            .set_custom_uniform(&self.device, &self.queue, bytes)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Rounds voxel corners in the raymarch by treating each voxel as a rounded box.
+    /// `radius` is in voxel units and clamped to [0, 0.5]; 0 gives hard voxels.
+    pub fn set_voxel_rounding(&mut self, radius: f32) {
+        self.dirty = true;
+        self.voxel_rounding = radius.clamp(0.0, 0.5);
+    }
+
+    /// Sets what voxel lookups see past the edges of each object's grid (see
+    /// `VoxelWrapMode`); `Clamp` by default. Wrapped objects raymarch their whole grid's
+    /// box rather than that of their filled voxels.
+    pub fn set_voxel_wrap(&mut self, wrap: VoxelWrapMode) {
+        if wrap == self.voxel_wrap {
+            return;
+        }
+        self.dirty = true;
+        self.last_scene_hash = 0;
+        self.voxel_wrap = wrap;
+        self.create_gbuffer_pipelines();
+        self.raymarch = ComputeRaymarch::new(
             &self.device,
-            width,
-            height,
-            wgpu::TextureFormat::Rgba8Unorm,
-            "GBuffer Albedo",
+            self.linear_z_format,
+            self.voxel_filter,
+            wrap,
+            self.pipeline_cache.as_ref(),
         );
-        self.gbuffer_normal = create_render_texture_view(
-            &self.device,
-            width,
-            height,
-            wgpu::TextureFormat::Rgba8Unorm,
-            "GBuffer Normal",
+        let slots = self
+            .scene_slots
+            .values_mut()
+            .map(|slot| &mut slot.draw_call_array);
+        for dc in slots.chain([&mut self.draw_call_array]).flatten() {
+            dc.fit_proxy(wrap, self.empty_index);
+            self.update_batch.write(
+                &self.queue,
+                &dc.uniform_buffer,
+                0,
+                bytemuck::bytes_of(&dc.uniforms()),
+            );
+        }
+    }
+
+    /// Smooths voxel silhouettes on the raster path: the raymarch writes how much of
+    /// each pixel the hit face covers, and the lighting pass blends the partially
+    /// covered pixels with what lies behind the edge (see `EdgeAaQuality`). Off by
+    /// default; not applied to rounded voxels or the compute path.
+    pub fn set_edge_antialiasing(&mut self, enabled: bool, quality: EdgeAaQuality) {
+        self.dirty = true;
+        self.edge_aa = enabled.then_some(quality);
+    }
+
+    /// Sets the palette index that marks empty voxels (default 0), for formats using
+    /// another sentinel. Rays pass through empty voxels and an all-empty object draws
+    /// nothing. AO bakes, erosion and dilation use it too; AO baked earlier keeps the
+    /// old index until baked again.
+    pub fn set_empty_index(&mut self, idx: u32) -> Result<(), RendererError> {
+        let idx = u8::try_from(idx).map_err(|_| {
+            let message = format!("empty index must be a palette index (0-255), got {}", idx);
+            RendererError::invalid("idx", message)
+        })?;
+        self.dirty = true;
+        self.last_scene_hash = 0;
+        self.empty_index = idx;
+        self.occlusion.invalidate();
+        let slots = self
+            .scene_slots
+            .values_mut()
+            .map(|slot| &mut slot.draw_call_array);
+        for dc in slots.chain([&mut self.draw_call_array]).flatten() {
+            dc.fit_proxy(self.voxel_wrap, idx);
+            if dc.svo_buffer.is_some() {
+                let octree = SparseOctree::build(dc.object.dims, &dc.object.voxels, idx)?;
+                dc.svo_buffer = Some(octree.upload(&self.device));
+            }
+            self.update_batch.write(
+                &self.queue,
+                &dc.uniform_buffer,
+                0,
+                bytemuck::bytes_of(&dc.uniforms()),
+            );
+        }
+        Ok(())
+    }
+
+    /// Adds a Blinn-Phong highlight from the directional light; `strength` 0 (the
+    /// default) leaves lighting diffuse-only. `shininess` is the specular exponent.
+    pub fn set_specular(&mut self, strength: f32, shininess: f32) {
+        self.dirty = true;
+        self.specular_strength = strength.max(0.0);
+        self.shininess = shininess.max(1.0);
+    }
+
+    /// Adds a rim light on top of the active lighting model: `color` (RGB,
+    /// scaled by `intensity`) where surfaces turn away from the camera, falling off as
+    /// `(1 - N·V)^power`. `intensity` 0 (the default) turns it off.
+    pub fn set_rim_light(
+        &mut self,
+        color: &[f32],
+        power: f32,
+        intensity: f32,
+    ) -> Result<(), RendererError> {
+        let color: [f32; 3] = color
+            .try_into()
+            .map_err(|_| RendererError::invalid("color", "rim color must have 3 components"))?;
+        self.dirty = true;
+        self.rim_color = color.map(|c| c.max(0.0));
+        self.rim_power = power.max(0.0);
+        self.rim_intensity = intensity.max(0.0);
+        Ok(())
+    }
+
+    /// Sets up to 4 world-space clip planes, passed as a flat array of `[a, b, c, d]`
+    /// plane equations. Geometry where `dot(p, abc) + d < 0` is discarded; an empty
+    /// array disables clipping. Voxel rays start past the clipped half-spaces on every
+    /// render path, so what is behind clipped voxels shows through with its own depth,
+    /// which `read_depth_at` also reads; voxels straddling a plane show their cut face
+    /// lit along it (see `set_clip_cap`). The bounding box wireframe is clipped too.
+    pub fn set_clip_planes(&mut self, planes: &[f32]) -> Result<(), RendererError> {
+        if !planes.len().is_multiple_of(4) {
+            return Err(RendererError::invalid(
+                "planes",
+                "clip planes must be a flat array of 4 floats per plane",
+            ));
+        }
+        let count = planes.len() / 4;
+        if count > MAX_CLIP_PLANES {
+            return Err(RendererError::limit(
+                "clip_planes",
+                count as u64,
+                MAX_CLIP_PLANES as u64,
+                format!(
+                    "at most {} clip planes are supported, got {}",
+                    MAX_CLIP_PLANES, count
+                ),
+            ));
+        }
+        if !planes.iter().all(|v| v.is_finite()) {
+            return Err(RendererError::invalid(
+                "planes",
+                "clip planes must be finite numbers",
+            ));
+        }
+
+        self.dirty = true;
+        self.clip_planes.planes = [[0.0; 4]; MAX_CLIP_PLANES];
+        self.clip_planes.count = count as u32;
+        for (dst, src) in self
+            .clip_planes
+            .planes
+            .iter_mut()
+            .zip(planes.chunks_exact(4))
+        {
+            dst.copy_from_slice(src);
+        }
+        self.write_clip_planes();
+        self.occlusion.invalidate();
+        Ok(())
+    }
+
+    /// Fills the faces that clip planes cut through voxels with a flat `color` (RGBA,
+    /// 0 to 1) in the G-buffer, as a solid cross-section; `None` (the default) keeps
+    /// the voxels' own colours. The compute render path does not cap.
+    pub fn set_clip_cap(&mut self, color: Option<Vec<f32>>) -> Result<(), RendererError> {
+        let color = color
+            .map(|color| <[f32; 4]>::try_from(color.as_slice()))
+            .transpose()
+            .map_err(|_| RendererError::invalid("color", "color must have 4 components"))?;
+        self.dirty = true;
+        self.clip_planes.cap = color.is_some() as u32;
+        self.clip_planes.cap_color = color.unwrap_or_default().map(|c| c.clamp(0.0, 1.0));
+        self.write_clip_planes();
+        Ok(())
+    }
+
+    fn write_clip_planes(&mut self) {
+        self.update_batch.write(
+            &self.queue,
+            &self.clip_planes_buffer,
+            0,
+            bytemuck::bytes_of(&self.clip_planes),
         );
-        self.gbuffer_linear_z = create_render_texture_view(
-            &self.device,
-            width,
-            height,
-            wgpu::TextureFormat::R16Uint,
-            "GBuffer LinearZ",
+    }
+
+    /// Cycles palette entries over time, e.g. for water or lava. `ranges` is an array
+    /// of `{ start, length, speed }`, `speed` in entries per second; ranges must not
+    /// overlap. Entries outside the ranges are never rewritten. Replaces the previous
+    /// ranges, restoring their colours; an empty array stops all animation.
+    pub fn set_palette_animation(&mut self, ranges: JsValue) -> Result<(), RendererError> {
+        let cycles: Vec<PaletteCycle> = serde_wasm_bindgen::from_value(ranges)?;
+        PaletteAnimation::validate(&cycles)?;
+        self.dirty = true;
+        self.palette_animation.set(
+            cycles,
+            &self.queue,
+            &self.static_uniform_buffer,
+            &self.palette,
         );
+        Ok(())
+    }
+
+    /// Advances palette cycling by `dt` seconds. Called automatically each frame by
+    /// `start_loop`.
+    pub fn advance_palette_animation(&mut self, dt: f64) {
+        if self.palette_animation.advance(
+            dt,
+            &self.queue,
+            &self.static_uniform_buffer,
+            &self.palette,
+        ) {
+            self.dirty = true;
+        }
+    }
+
+    /// Fades the whole palette to `target_palette` (256 RGBA8 colours, 1024 bytes)
+    /// over `duration_secs`, interpolating linearly in sRGB on every rendered frame,
+    /// e.g. for a day/night cycle. Cancels any transition in progress, starting from
+    /// the colours it had reached; palette cycling keeps rotating over the faded
+    /// colours. Loading or activating a scene cancels the transition.
+    pub fn animate_palette_to(
+        &mut self,
+        target_palette: &[u8],
+        duration_secs: f32,
+    ) -> Result<(), RendererError> {
+        let target = PaletteTransition::parse_palette(target_palette)?;
+        if !duration_secs.is_finite() {
+            return Err(RendererError::invalid(
+                "duration",
+                "duration must be finite",
+            ));
+        }
+        self.dirty = true;
+        self.last_scene_hash = 0;
+        self.step_palette_transition();
+        self.palette_transition = Some(PaletteTransition::new(
+            &self.palette,
+            target,
+            now_ms(),
+            duration_secs,
+        ));
+        Ok(())
+    }
 
+    /// Builds a 256-entry palette ramping linearly in sRGB between `stops`, an array
+    /// of `[index, [r, g, b, a]]` pairs in any order, e.g. for height or heat maps.
+    /// Entries before the first stop and after the last take its colour. Returns the
+    /// entries packed as by `upload_palette`, red in the low byte.
+    pub fn create_gradient_palette(stops: JsValue) -> Result<Vec<u32>, RendererError> {
+        let stops: Vec<(u8, [u8; 4])> = serde_wasm_bindgen::from_value(stops)?;
+        Ok(gradient_palette(&stops)?.to_vec())
+    }
+
+    /// Replaces the first 256 palette entries with `palette`, packed RGBA8 with red in
+    /// the low byte (see `create_gradient_palette`), without re-uploading the scene.
+    /// Cancels an `animate_palette_to` transition; palette cycling rotates the new
+    /// colours.
+    pub fn upload_palette(&mut self, palette: &[u32]) -> Result<(), RendererError> {
+        if palette.len() != 256 {
+            return Err(RendererError::invalid(
+                "palette",
+                format!("must have 256 entries, got {}", palette.len()),
+            ));
+        }
+        if self.palette.len() < palette.len() {
+            self.palette.resize(palette.len(), RGBA(0, 0, 0, 0));
+        }
+        for (color, &packed) in self.palette.iter_mut().zip(palette) {
+            let [r, g, b, a] = packed.to_le_bytes();
+            *color = RGBA(r, g, b, a);
+        }
+        self.palette_transition = None;
+        // The active scene no longer matches its upload
+        self.last_scene_hash = 0;
+        self.write_palette();
         Ok(())
     }
 
+    /// Whether no `animate_palette_to` transition is still running.
+    pub fn is_palette_animation_complete(&self) -> bool {
+        self.palette_transition
+            .as_ref()
+            .is_none_or(|transition| transition.is_finished(now_ms()))
+    }
+
+    /// Writes the palette the running transition has reached, ending it once done.
+    fn step_palette_transition(&mut self) {
+        let Some(transition) = &self.palette_transition else {
+            return;
+        };
+        let now = now_ms();
+        self.palette = transition.sample(now);
+        if transition.is_finished(now) {
+            self.palette_transition = None;
+        }
+        self.write_palette();
+    }
+
+    /// Writes the first 256 entries of `palette` to the palette uniforms.
+    fn write_palette(&mut self) {
+        let mut uniforms = StaticUniforms {
+            color_palette: [0; 256],
+        };
+        for (packed, color) in uniforms.color_palette.iter_mut().zip(&self.palette) {
+            *packed = utils::pack_rgba(color);
+        }
+        self.queue.write_buffer(
+            &self.static_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[uniforms]),
+        );
+        // Cycled ranges were overwritten unrotated
+        self.palette_animation.invalidate();
+        self.advance_palette_animation(0.0);
+        self.dirty = true;
+    }
+
+    fn update_scene_bounds(&mut self) {
+        self.scene_bounds = bounds_of(&self.draw_call_array);
+    }
+
     /// Helper to build a full‑screen quad pipeline + bind‑group layout
     fn create_fullscreen_quad_pipeline(
         device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
         surface_format: wgpu::TextureFormat,
         shader_src: &'static str,
         sample_type: wgpu::TextureSampleType,
@@ -649,6 +2927,16 @@ impl Renderer {
                     ty: wgpu::BindingType::Sampler(sampler_type),
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         });
 
@@ -688,277 +2976,2790 @@ impl Renderer {
                 depth_stencil: None,
                 multisample: Default::default(),
                 multiview: None,
-                cache: None,
+                cache,
+            })
+        };
+
+        (quad_layout, quad_pipeline, quad_shader)
+    }
+
+    pub fn render(
+        &mut self,
+        vp_matrix: &[f32],
+        view_position: &[f32],
+        present_target: usize,
+        light_dir: &[f32],
+        ambient: f32,
+        show_bboxes: bool,
+    ) -> Result<bool, RendererError> {
+        self.frame = FrameSettings::new(
+            vp_matrix,
+            view_position,
+            present_target,
+            light_dir,
+            ambient,
+            show_bboxes,
+        )?;
+        if self.is_paused() || !self.pacer.frame_due(now_ms()) {
+            return Ok(false);
+        }
+        let texture = self.acquire_frame()?;
+        self.render_to_surface_texture(
+            texture,
+            vp_matrix,
+            view_position,
+            present_target,
+            light_dir,
+            ambient,
+            show_bboxes,
+        )?;
+        Ok(true)
+    }
+
+    /// Like `render`, but skips the frame (no surface acquire or submit) and returns
+    /// `false` when nothing changed since the last rendered frame. Any state change,
+    /// a resize, or `invalidate` forces the next call to draw; so does a camera change
+    /// skipped by `set_max_fps` or `pause`.
+    pub fn render_if_needed(
+        &mut self,
+        vp_matrix: &[f32],
+        view_position: &[f32],
+        present_target: usize,
+        light_dir: &[f32],
+        ambient: f32,
+        show_bboxes: bool,
+    ) -> Result<bool, RendererError> {
+        let frame = FrameSettings::new(
+            vp_matrix,
+            view_position,
+            present_target,
+            light_dir,
+            ambient,
+            show_bboxes,
+        )?;
+        if !self.dirty && self.palette_transition.is_none() && frame == self.frame {
+            return Ok(false);
+        }
+        if !self.frame_due(now_ms()) {
+            return Ok(false);
+        }
+        self.frame = frame;
+        self.render_frame()?;
+        Ok(true)
+    }
+
+    /// Caps the frame rate of `render`, `render_if_needed` and the animation loop;
+    /// `None` removes the cap. Frames requested early are skipped, and the former two
+    /// return `false` for them.
+    pub fn set_max_fps(&mut self, fps: Option<f32>) {
+        self.pacer.set_max_fps(fps);
+    }
+
+    /// Renders the G-buffer at `scale` times the surface size, in [0.1, 1], and
+    /// stretches it over the canvas when lighting; 1 by default. Ends dynamic
+    /// resolution.
+    pub fn set_render_scale(&mut self, scale: f32) -> Result<(), RendererError> {
+        if !(MIN_RENDER_SCALE..=MAX_RENDER_SCALE).contains(&scale) {
+            return Err(RendererError::invalid(
+                "scale",
+                format!(
+                    "must be within [{}, {}], got {}",
+                    MIN_RENDER_SCALE, MAX_RENDER_SCALE, scale
+                ),
+            ));
+        }
+        self.dynamic_resolution = None;
+        self.apply_render_scale(scale);
+        Ok(())
+    }
+
+    /// Adjusts the render scale each frame to hold `target_fps`: down by 0.05 while
+    /// frames take longer than 1 / `target_fps`, up by 0.05 while they are well
+    /// within it, between `min_scale` and `max_scale` in [0.1, 1]. The G-buffer is
+    /// only recreated when its size in pixels changes. Frames are timed between
+    /// consecutive renders, so this needs a steady frame loop; long gaps are ignored.
+    pub fn enable_dynamic_resolution(
+        &mut self,
+        target_fps: f32,
+        min_scale: f32,
+        max_scale: f32,
+    ) -> Result<(), RendererError> {
+        if !(target_fps.is_finite() && target_fps > 0.0) {
+            return Err(RendererError::invalid(
+                "target_fps",
+                format!("must be positive, got {}", target_fps),
+            ));
+        }
+        if !(MIN_RENDER_SCALE..=MAX_RENDER_SCALE).contains(&min_scale)
+            || !(min_scale..=MAX_RENDER_SCALE).contains(&max_scale)
+        {
+            return Err(RendererError::invalid(
+                "min_scale",
+                format!(
+                    "and max_scale must satisfy {} <= min_scale <= max_scale <= {}, got {} \
+                     and {}",
+                    MIN_RENDER_SCALE, MAX_RENDER_SCALE, min_scale, max_scale
+                ),
+            ));
+        }
+        self.dynamic_resolution = Some(DynamicResolution::new(target_fps, min_scale, max_scale));
+        self.apply_render_scale(self.render_scale.clamp(min_scale, max_scale));
+        Ok(())
+    }
+
+    /// Keeps the current render scale from then on.
+    pub fn disable_dynamic_resolution(&mut self) {
+        self.dynamic_resolution = None;
+    }
+
+    /// Render scale of the next frame, from `set_render_scale` or dynamic resolution.
+    pub fn get_current_render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    /// Caps the longer side of the G-buffer at `max` pixels, whatever the canvas size
+    /// set by `resize`, keeping its aspect ratio; the lighting pass stretches it over
+    /// the canvas as for `set_render_scale`. The cap applies after the render scale,
+    /// so it bounds the pixel count of high-DPI canvases while dynamic resolution
+    /// still works below it. 0 removes the cap.
+    pub fn set_max_render_dimension(&mut self, max: u32) -> Result<(), RendererError> {
+        let limit = self.device.limits().max_texture_dimension_2d;
+        if max > limit {
+            return Err(RendererError::limit(
+                "max_texture_dimension_2d",
+                max,
+                limit,
+                format!("the render dimension cap is at most {}, got {}", limit, max),
+            ));
+        }
+        self.max_render_dimension = (max > 0).then_some(max);
+        self.dirty = true;
+        self.fit_render_targets();
+        Ok(())
+    }
+
+    /// Calls `callback` right after each frame's command buffer is submitted, before it
+    /// is presented, replacing any previous callback. Exceptions are logged, not
+    /// rethrown. The renderer is borrowed during the call, so it must not call back
+    /// into it.
+    pub fn register_post_submit_callback(&mut self, callback: js_sys::Function) {
+        self.post_submit_callback = Some(callback);
+    }
+
+    pub fn unregister_post_submit_callback(&mut self) {
+        self.post_submit_callback = None;
+    }
+
+    /// Forces the next `render_if_needed` to draw.
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Sets the camera used by frames rendered from the animation loop.
+    pub fn set_camera(
+        &mut self,
+        vp_matrix: &[f32],
+        view_position: &[f32],
+    ) -> Result<(), RendererError> {
+        let (vp_matrix, camera_position) = parse_camera(vp_matrix, view_position)?;
+        if vp_matrix != self.frame.vp_matrix || camera_position != self.frame.camera_position {
+            self.frame.vp_matrix = vp_matrix;
+            self.frame.camera_position = camera_position;
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    /// Sets the lighting used by frames rendered from the animation loop.
+    pub fn set_lighting(&mut self, light_dir: &[f32], ambient: f32) {
+        self.dirty = true;
+        self.frame.light_dir = light_direction(light_dir);
+        self.frame.ambient = ambient;
+    }
+
+    /// Selects the lighting model used by the lit present target (4).
+    pub fn set_lighting_model(&mut self, model: LightingModel) {
+        self.dirty = true;
+        self.lighting_model = model;
+    }
+
+    /// Chooses the G-buffer inputs of the lighting pass: the `normal_source` it
+    /// shades with, and whether the ambient term (the matcap for
+    /// `LightingModel::Matcap`) is darkened by the AO baked with `bake_ao` or
+    /// `bake_object_space_ao`, which objects without it leave unoccluded. Stored
+    /// normals without AO by default; AO is not read by the compute render path.
+    pub fn set_lighting_inputs(&mut self, normal_source: NormalSource, ambient_occlusion: bool) {
+        self.dirty = true;
+        self.normal_source = normal_source;
+        self.ambient_occlusion = ambient_occlusion;
+    }
+
+    /// Sets how the rendered image is fit to the canvas when their aspect ratios
+    /// differ; `Stretch` by default.
+    pub fn set_present_fit(&mut self, fit: PresentFit) {
+        self.dirty = true;
+        self.present_fit = fit;
+    }
+
+    /// Sets the surface of palette index `index` for `LightingModel::Pbr`: `roughness`
+    /// and `metallic` in [0, 1], clamped, stored with 8 bits each. Every index
+    /// starts at roughness 0.5 and metallic 0.
+    pub fn set_material(
+        &mut self,
+        index: u32,
+        roughness: f32,
+        metallic: f32,
+    ) -> Result<(), RendererError> {
+        if index > 255 {
+            return Err(RendererError::invalid(
+                "index",
+                format!(
+                    "material index must be a palette index (0-255), got {}",
+                    index
+                ),
+            ));
+        }
+        self.dirty = true;
+        self.update_batch.write(
+            &self.queue,
+            &self.material_buffer,
+            index as u64 * 4,
+            bytemuck::bytes_of(&pack_material(roughness, metallic)),
+        );
+        Ok(())
+    }
+
+    /// Uploads the matcap sampled by `LightingModel::Matcap`. `image` is an
+    /// `ImageBitmap`, `HTMLImageElement`, `HTMLCanvasElement`, `OffscreenCanvas` or
+    /// `ImageData`; the sphere it shows should fill the image.
+    pub fn set_matcap(&mut self, image: JsValue) -> Result<(), RendererError> {
+        self.matcap.set_image(&self.device, &self.queue, image)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Lights the lit present target (4) with an equirectangular environment map:
+    /// `pixels` holds `width * height` linear RGBA floats (alpha is ignored), rows from
+    /// top to bottom, with +y at the top and -z at the centre column. Its irradiance
+    /// replaces the flat ambient of the directional and PBR models, reflections sample
+    /// it by roughness (PBR) or shininess (directional specular), both scaled by the
+    /// `ambient` of `set_lighting`, and it is the background where rays hit nothing.
+    pub fn set_environment(
+        &mut self,
+        pixels: &[f32],
+        width: u32,
+        height: u32,
+    ) -> Result<(), RendererError> {
+        self.environment
+            .set_image(&self.device, &self.queue, pixels, width, height)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Removes the environment map: back to flat ambient and a black background.
+    pub fn clear_environment(&mut self) {
+        self.environment.clear(&self.device, &self.queue);
+        self.dirty = true;
+    }
+
+    /// Replaces the point lights (at most 1024) of the lit present target (4): an array
+    /// of `{ position: [x, y, z], radius, color: [r, g, b], intensity }`, `intensity`
+    /// defaulting to 1. A light fades out smoothly at `radius`. Lights are culled per
+    /// 16x16 pixel tile of the G-buffer, and a tile shades at most 63 of them, the
+    /// lowest indices first. An empty array removes them.
+    pub fn set_point_lights(&mut self, lights: JsValue) -> Result<(), RendererError> {
+        let lights: Vec<PointLight> = serde_wasm_bindgen::from_value(lights)?;
+        self.light_culling.set_lights(&self.queue, &lights)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Tints the lit present target by how many point lights each screen tile shades,
+    /// from blue (none) to red (the per-tile maximum).
+    pub fn set_light_tile_debug(&mut self, enabled: bool) {
+        self.dirty = true;
+        self.light_culling.debug = enabled;
+    }
+
+    /// Replaces the light probes (at most 8) that light the ambient term of the lit
+    /// present target (4): an array of `{ position: [x, y, z], sh_coeffs }` with nine
+    /// `[r, g, b]` irradiance coefficients, as resolved by `bake_light_probe`. Each
+    /// pixel uses the probe nearest to its surface, in place of the environment map's
+    /// irradiance or flat ambient, still scaled by `ambient`. An empty array removes
+    /// them.
+    pub fn set_light_probes(&mut self, probes: JsValue) -> Result<(), RendererError> {
+        let probes: Vec<LightProbe> = serde_wasm_bindgen::from_value(probes)?;
+        let uniforms = LightProbeUniforms::new(&probes)?;
+        self.update_batch.write(
+            &self.queue,
+            &self.light_probe_buffer,
+            0,
+            bytemuck::bytes_of(&uniforms),
+        );
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Bakes a light probe at `position` from a cubemap of `width`² texels per face:
+    /// `faces` holds linear RGBA floats for the +X, -X, +Y, -Y, +Z, -Z faces in turn.
+    /// Resolves with the probe object `set_light_probes` accepts.
+    pub fn bake_light_probe(&self, faces: &[f32], width: u32, position: &[f32]) -> js_sys::Promise {
+        let Ok(position) = <[f32; 3]>::try_from(position) else {
+            let error = RendererError::invalid("position", "position must have 3 elements");
+            return js_sys::Promise::reject(&error.into());
+        };
+        let cubemap = match light_probes::create_cubemap(&self.device, &self.queue, faces, width) {
+            Ok(cubemap) => cubemap,
+            Err(error) => return js_sys::Promise::reject(&RendererError::from(error).into()),
+        };
+        let (device, queue) = (self.device.clone(), self.queue.clone());
+        let projection = self.sh_projection.clone();
+        wasm_bindgen_futures::future_to_promise(async move {
+            let probe =
+                LightProbe::from_cubemap_texture(&device, &queue, &projection, &cubemap, position)
+                    .await?;
+            Ok(serde_wasm_bindgen::to_value(&probe)?)
+        })
+    }
+
+    /// Shadows the directional light in the lit present target (4) with `count`
+    /// cascaded shadow maps (0 disables shadows, at most 4) of `resolution`² texels
+    /// each. The view depths holding the scene are split between a uniform
+    /// (`split_lambda` 0) and a logarithmic (1) scheme. Voxel objects cast the shadow
+    /// of their voxels, meshes of their triangles. Shadows start disabled, and are
+    /// not drawn by `render_xr`.
+    pub fn set_shadow_cascades(
+        &mut self,
+        count: u32,
+        split_lambda: f32,
+        resolution: u32,
+    ) -> Result<(), RendererError> {
+        self.shadows
+            .configure(&self.device, count, split_lambda, resolution)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Fraction of each shadow cascade, at its far end, that fades into the next one
+    /// to hide the seam; clamped to [0, 1], 0 switches sharply. Defaults to 0.1.
+    pub fn set_shadow_cascade_blend(&mut self, fraction: f32) {
+        self.shadows.blend = fraction.clamp(0.0, 1.0);
+        self.dirty = true;
+    }
+
+    /// Shadow acne and peter-panning tuning: `constant` is in units of the shadow
+    /// map's depth precision, `slope_scaled` scales the depth change across a texel
+    /// of each caster, and `normal_offset` moves lookups off the surface by that many
+    /// texels. Clamped to [0, 1e6], [0, 16] and [0, 8]; defaults to (1000, 1.5, 1.5).
+    pub fn set_shadow_bias(
+        &mut self,
+        constant: f32,
+        slope_scaled: f32,
+        normal_offset: f32,
+    ) -> Result<(), RendererError> {
+        self.shadows.set_bias(
+            &self.device,
+            self.pipeline_cache.as_ref(),
+            constant,
+            slope_scaled,
+            normal_offset,
+        )?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Tints shadowed-lit pixels by their cascade: red, green, blue, then yellow.
+    pub fn set_shadow_debug(&mut self, enabled: bool) {
+        self.shadows.debug = enabled;
+        self.dirty = true;
+    }
+
+    /// Sets the most detailed messages written to the browser console: "off",
+    /// "error", "warn" (the default), "info" or "debug". The level is shared by
+    /// every renderer on the page.
+    pub fn set_log_level(&mut self, level: &str) -> Result<(), RendererError> {
+        let level = log::Level::parse(level).ok_or_else(|| {
+            RendererError::invalid("level", format!("unknown log level '{}'", level))
+        })?;
+        log::set_level(level);
+        Ok(())
+    }
+
+    pub fn set_present_target(&mut self, present_target: usize) {
+        self.dirty = true;
+        self.frame.present_target = present_target;
+    }
+
+    pub fn set_show_bboxes(&mut self, show_bboxes: bool) {
+        self.dirty = true;
+        self.frame.show_bboxes = show_bboxes;
+    }
+
+    /// Whether the FPS cap and pausing allow a frame at `now_ms` (used by the
+    /// animation loop).
+    fn frame_due(&mut self, now_ms: f64) -> bool {
+        !self.is_paused() && self.pacer.frame_due(now_ms)
+    }
+
+    /// Stops all rendering until `resume`: `render`, `render_if_needed`,
+    /// `render_to_surface_texture`, `render_xr` and the animation loop return without
+    /// GPU work, the former two returning `false`. State changes still apply and show
+    /// in the first frame after resuming.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Undoes `pause`. An auto-pause (`RendererBuilder::with_auto_pause`) still holds
+    /// while the document is hidden.
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.dirty = true;
+    }
+
+    /// Whether frames are skipped, by `pause` or by auto-pause while hidden.
+    pub fn is_paused(&self) -> bool {
+        self.paused || self.auto_pause.as_ref().is_some_and(AutoPause::is_hidden)
+    }
+
+    /// Starts a `requestAnimationFrame` loop that calls `callback(timestamp)` and then
+    /// renders with the stored camera and lighting (see `set_camera`, `set_lighting`).
+    /// The loop pauses while the document is hidden. If the callback throws or a frame
+    /// fails, the loop stops and the error is passed to `on_error` (or the console).
+    pub fn start_loop(
+        &mut self,
+        callback: js_sys::Function,
+        on_error: Option<js_sys::Function>,
+    ) -> Result<(), RendererError> {
+        self.stop_loop();
+        // SAFETY: the loop is owned by `self` and dropped with it.
+        let animation_loop = unsafe { AnimationLoop::start(self, callback, on_error)? };
+        self.animation_loop = Some(animation_loop);
+        Ok(())
+    }
+
+    pub fn stop_loop(&mut self) {
+        self.animation_loop = None;
+    }
+
+    /// Whether the GPU device was lost (a GPU reset or driver crash, say). Frames then
+    /// draw nothing until `reinitialize` succeeds.
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.is_lost()
+    }
+
+    /// Rebuilds the renderer on a new device, from the canvas and `RendererBuilder`
+    /// options it was created with, and re-uploads the active scene: its palette and
+    /// its objects under the same ids, including those added or edited since upload.
+    /// Resolves once the renderer has switched over; until then it keeps drawing on
+    /// the old device.
+    ///
+    /// Also kept: the canvas size set by `resize`, the camera and lighting of the
+    /// last frame, the FPS cap, the render scale, its cap and dynamic resolution,
+    /// pausing, the post-submit callback, the voxel filter and rounding, the empty
+    /// index, the lighting model and inputs, present fit, specular and rim settings.
+    /// Everything else starts over as in a new renderer: meshes, scene slots,
+    /// materials, matcap, environment, shadows, point lights, light probes, clip
+    /// planes, post effects and XR. The animation loop is stopped; call `start_loop`
+    /// again.
+    pub fn reinitialize(&mut self) -> js_sys::Promise {
+        self.stop_loop();
+        self.sync_voxels();
+        let scene = Scene {
+            palette: self.palette.clone(),
+            objects: self.scene_objects(),
+        };
+        let options = RendererBuilder {
+            voxel_filter: self.voxel_filter,
+            ..self.options.clone()
+        };
+        let target = self.target.clone();
+        let (token, cancelled) = ReinitializeToken::new();
+        self.reinitialize_token = Some(token);
+
+        let renderer: *mut Renderer = self;
+        wasm_bindgen_futures::future_to_promise(async move {
+            let mut fresh = Renderer::create(target, options).await?;
+            if cancelled.get() {
+                return Err(RendererError::Destroyed("reinitialization superseded".into()).into());
+            }
+            // SAFETY: the renderer owns the token and drops it before it is freed,
+            // checked above with no await since.
+            let renderer = unsafe { &mut *renderer };
+            fresh.take_settings(renderer)?;
+            fresh.upload_parsed_scene(scene)?;
+            fresh.last_scene_hash = renderer.last_scene_hash;
+            *renderer = fresh;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// The next surface texture, reconfiguring the surface once when it is outdated or
+    /// lost, as after the canvas was resized behind `resize`'s back.
+    fn acquire_frame(&self) -> Result<wgpu::SurfaceTexture, RendererError> {
+        match self.surface.get_current_texture() {
+            Err(error @ (wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost)) => {
+                log::warn!("surface {}; reconfiguring it", error);
+                self.surface.configure(&self.device, &self.surface_config);
+                Ok(self.surface.get_current_texture()?)
+            }
+            frame => Ok(frame?),
+        }
+    }
+
+    fn render_frame(&mut self) -> Result<(), RendererError> {
+        let frame = self.acquire_frame()?;
+        self.render_frame_to(frame)
+    }
+
+    /// Renders the current frame settings into `frame`, then presents it.
+    fn render_frame_to(&mut self, frame: wgpu::SurfaceTexture) -> Result<(), RendererError> {
+        self.render_frame_into(&frame.texture)?;
+        frame.present();
+        Ok(())
+    }
+
+    /// Renders the current frame settings into `frame`, a texture of the surface's
+    /// size and format.
+    fn render_frame_into(&mut self, frame: &wgpu::Texture) -> Result<(), RendererError> {
+        if self.device_lost.is_lost() {
+            return Err(RendererError::DeviceLost);
+        }
+        self.sync_voxels();
+        let size = frame.size();
+        if (size.width, size.height) != (self.surface_config.width, self.surface_config.height)
+            || frame.format() != self.surface_config.format
+        {
+            return Err(RendererError::Surface(format!(
+                "surface texture is {}x{} {:?}, the surface is configured for {}x{} {:?}",
+                size.width,
+                size.height,
+                frame.format(),
+                self.surface_config.width,
+                self.surface_config.height,
+                self.surface_config.format
+            )));
+        }
+        if let Some(dynamic) = self.dynamic_resolution.as_mut() {
+            let scale = dynamic.update(now_ms(), self.render_scale);
+            self.apply_render_scale(scale);
+        }
+        self.step_palette_transition();
+        #[cfg(feature = "hud")]
+        self.hud.record_frame(now_ms());
+        let FrameSettings {
+            vp_matrix,
+            camera_position,
+            present_target,
+            show_bboxes,
+            ..
+        } = self.frame;
+        self.compute_fallback = self.render_path == RenderPath::Compute
+            && !self.compute_can_draw(&Frustum::from_vp_matrix(&vp_matrix));
+        let render_size = self.render_size();
+        let checkerboard = if self.checkerboard_active() {
+            let parity = self.checkerboard.begin_frame(
+                &self.device,
+                &self.queue,
+                render_size,
+                &vp_matrix,
+                camera_position,
+            );
+            parity + 1
+        } else {
+            0
+        };
+        let per_frame_uniforms = PerFrameUniforms {
+            vp_matrix,
+            camera_position,
+            voxel_rounding: self.voxel_rounding,
+            checkerboard,
+            empty_index: self.empty_index as u32,
+            pixel_angle: self.edge_aa_pixel_angle(&vp_matrix, render_size.1),
+            _padding: 0,
+        };
+
+        self.queue.write_buffer(
+            &self.per_frame_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[per_frame_uniforms]),
+        );
+
+        // Update lighting uniforms
+        let lighting_uniforms = self.lighting_uniforms(&vp_matrix, camera_position);
+        self.queue.write_buffer(
+            &self.lighting_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[lighting_uniforms]),
+        );
+        let gbuffer_size = self.gbuffer.albedo.texture.size();
+        let present_uniforms = self.present_fit.uniforms(
+            (gbuffer_size.width, gbuffer_size.height),
+            (self.surface_config.width, self.surface_config.height),
+        );
+        self.queue.write_buffer(
+            &self.present_uniform_buffer,
+            0,
+            bytemuck::bytes_of(&present_uniforms),
+        );
+        let linear_z_size = self.gbuffer.linear_z.texture.size();
+        let inv_vp_matrix = math::invert(&vp_matrix).unwrap_or(math::IDENTITY);
+        self.light_culling.prepare(
+            &self.device,
+            &self.queue,
+            inv_vp_matrix,
+            camera_position,
+            [linear_z_size.width, linear_z_size.height],
+        );
+
+        let per_frame_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Per Frame Bind Group"),
+            layout: &self.per_frame_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: self.per_frame_uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let wire_objects = self.draw_call_array.iter().any(|dc| dc.wireframe);
+        self.render_graph
+            .set_enabled(PassKind::Wireframe, show_bboxes || wire_objects);
+        let debug_boxes = !self.debug_boxes.is_empty();
+        self.render_graph
+            .set_enabled(PassKind::DebugBoxes, debug_boxes);
+        if debug_boxes {
+            self.debug_boxes.prepare(&self.device, &self.queue);
+        }
+        self.render_graph
+            .set_enabled(PassKind::Checkerboard, checkerboard != 0);
+        let lit = present_target == 4 && self.gbuffer_profile == GBufferProfile::Full;
+        self.render_graph
+            .set_enabled(PassKind::Ssr, self.ssr.enabled && lit);
+        if self.ssr.enabled && lit {
+            self.ssr
+                .prepare(&self.queue, vp_matrix, inv_vp_matrix, camera_position);
+        }
+        if self.compute_active() {
+            let (width, height) = render_size;
+            self.raymarch.prepare(&self.device, width, height);
+        }
+
+        self.shadows.prepare(
+            &self.queue,
+            &vp_matrix,
+            camera_position,
+            self.frame.light_dir,
+            self.scene_bounds.as_ref(),
+            self.empty_index as u32,
+        );
+
+        let frame_view = frame.create_view(&Default::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.encode_shadow_passes(&mut encoder);
+        let camera = GBufferCamera::new(&vp_matrix, camera_position);
+        let occlusion_active = self.occlusion_active();
+        let occlusion_culled = occlusion_active && {
+            let objects: Vec<_> = self
+                .visible_draw_calls(&camera.frustum)
+                .map(|dc| (dc.world_bounds, dc.bounding_shape.indices()))
+                .collect();
+            self.occlusion.encode(
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                &self.gbuffer.linear_z.view,
+                render_size,
+                &objects,
+            )
+        };
+        let mut post = false;
+        for pass in self.render_graph.enabled_passes() {
+            let input = |i: usize| self.resource_view(pass.inputs[i], &frame_view);
+            let output = |i: usize| self.resource_view(pass.outputs[i], &frame_view);
+            if pass.kind.is_post() && !post {
+                post = true;
+                self.mark_timestamp(&mut encoder, timestamps::POST);
+            }
+            match pass.kind {
+                PassKind::GBuffer if self.compute_active() => {
+                    self.mark_timestamp(&mut encoder, timestamps::GBUFFER);
+                    self.encode_compute_gbuffer_pass(&mut encoder, &vp_matrix, camera_position);
+                    self.mark_timestamp(&mut encoder, timestamps::GBUFFER + 1);
+                }
+                PassKind::GBuffer => self.encode_gbuffer_pass(
+                    &mut encoder,
+                    &per_frame_bind_group,
+                    &camera,
+                    [output(0), output(1), output(2), output(3), output(4)],
+                    (gbuffer_size.width, gbuffer_size.height),
+                    occlusion_culled,
+                ),
+                PassKind::Checkerboard => self.checkerboard.encode(
+                    &self.device,
+                    &mut encoder,
+                    [
+                        (&self.gbuffer.albedo.texture, input(0)),
+                        (&self.gbuffer.normal.texture, input(1)),
+                        (&self.gbuffer.linear_z.texture, input(2)),
+                        (&self.gbuffer.material.texture, input(3)),
+                    ],
+                ),
+                PassKind::Present => {
+                    self.encode_present_pass(&mut encoder, present_target, output(0))
+                }
+                PassKind::Ssr => {
+                    let inputs = SsrInputs {
+                        lit: input(0),
+                        albedo: input(1),
+                        normal: input(2),
+                        linear_z: input(3),
+                        material: input(4),
+                        materials: &self.material_buffer,
+                        present: &self.present_uniform_buffer,
+                    };
+                    self.ssr
+                        .encode(&self.device, &mut encoder, &inputs, output(0));
+                    self.encode_blit(&mut encoder, output(0), output(1), "SSR Blit");
+                }
+                PassKind::Fxaa => {
+                    self.fxaa
+                        .encode(&self.device, &mut encoder, input(0), output(0));
+                    self.encode_blit(&mut encoder, output(0), output(1), "FXAA Blit");
+                }
+                PassKind::PostEffects => self.post_effects.encode(
+                    &self.device,
+                    &mut encoder,
+                    input(0),
+                    output(0),
+                    output(1),
+                ),
+                PassKind::Wireframe => {
+                    self.encode_wireframe_pass(&mut encoder, &per_frame_bind_group, output(0))
+                }
+                PassKind::DebugBoxes => {
+                    self.debug_boxes
+                        .encode(&mut encoder, output(0), &per_frame_bind_group)
+                }
+                #[cfg(feature = "hud")]
+                PassKind::Hud => {
+                    let visible = self.visible_draw_calls(&camera.frustum).count();
+                    let stats = hud::HudStats {
+                        draw_calls: visible + self.meshes.len(),
+                        objects: self.draw_call_array.len() + self.meshes.len(),
+                        gpu: self.timestamps.as_ref().and_then(PassTimestamps::latest),
+                    };
+                    let size = (self.surface_config.width, self.surface_config.height);
+                    self.hud
+                        .encode(&self.queue, &mut encoder, output(0), size, &stats);
+                }
+            }
+        }
+        if post {
+            self.mark_timestamp(&mut encoder, timestamps::POST + 1);
+        }
+        let timed = self
+            .timestamps
+            .as_ref()
+            .is_some_and(|timestamps| timestamps.resolve(&mut encoder));
+
+        if let Some(buffer) = self.frame_capture.take() {
+            encoder.copy_texture_to_buffer(
+                frame.as_image_copy(),
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &buffer,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(capture_row_bytes(frame.width())),
+                        rows_per_image: Some(frame.height()),
+                    },
+                },
+                frame.size(),
+            );
+        }
+
+        self.queue.submit(Some(encoder.finish()));
+        if let Some(timestamps) = self.timestamps.as_ref().filter(|_| timed) {
+            timestamps.read_back(post);
+        }
+        if let Some(callback) = &self.post_submit_callback {
+            if let Err(error) = callback.call0(&JsValue::UNDEFINED) {
+                log::error!("post-submit callback threw: {}", RendererError::from(error));
+            }
+        }
+        self.dirty = checkerboard != 0 && self.checkerboard.finish_frame();
+        // Boxes last one frame; the next one is drawn without them
+        self.debug_boxes.clear();
+        self.dirty |= debug_boxes;
+        if occlusion_active {
+            let stale = self
+                .occlusion
+                .finish_frame(occlusion_culled, &vp_matrix, camera_position);
+            self.dirty |= stale;
+        } else {
+            self.occlusion.invalidate();
+        }
+        self.depth_readback
+            .pump(&self.device, &self.queue, &self.gbuffer.linear_z.texture);
+        Ok(())
+    }
+
+    /// Renders a WebXR frame: the G-buffer and lighting passes run once per eye, and
+    /// each eye is written to its `viewport` of the canvas for the host to composite.
+    /// `views` is an array of `{ vp_matrix, camera_position, viewport: [x, y, w, h] }`.
+    /// Lighting uses the parameters of the last `render` call.
+    pub fn render_xr(&mut self, views: JsValue) -> Result<(), RendererError> {
+        let views: Vec<XrView> = serde_wasm_bindgen::from_value(views)?;
+        if self.is_paused() {
+            return Ok(());
+        }
+
+        self.xr_eyes.truncate(views.len());
+        let (canvas_width, canvas_height) = (self.surface_config.width, self.surface_config.height);
+        for (i, view) in views.iter().enumerate() {
+            let [x, y, width, height] = view.viewport;
+            // Checked, as viewports near u32::MAX would wrap past the canvas edge
+            if width == 0
+                || height == 0
+                || x.checked_add(width).is_none_or(|r| r > canvas_width)
+                || y.checked_add(height).is_none_or(|b| b > canvas_height)
+            {
+                return Err(RendererError::invalid(
+                    "viewport",
+                    format!(
+                        "XR viewport {:?} is empty or outside the {}x{} canvas",
+                        view.viewport, canvas_width, canvas_height
+                    ),
+                ));
+            }
+
+            // Eye targets follow the viewport size
+            if self
+                .xr_eyes
+                .get(i)
+                .is_none_or(|eye| eye.width != width || eye.height != height)
+            {
+                let eye = XrEye::new(
+                    &self.device,
+                    width,
+                    height,
+                    self.linear_z_format,
+                    &self.per_frame_bind_group_layout,
+                    std::mem::size_of::<PerFrameUniforms>() as u64,
+                    std::mem::size_of::<LightingUniforms>() as u64,
+                );
+                if i < self.xr_eyes.len() {
+                    self.xr_eyes[i] = eye;
+                } else {
+                    self.xr_eyes.push(eye);
+                }
+            }
+
+            self.queue.write_buffer(
+                &self.xr_eyes[i].per_frame_uniform_buffer,
+                0,
+                bytemuck::cast_slice(&[PerFrameUniforms {
+                    vp_matrix: view.vp_matrix,
+                    camera_position: view.camera_position,
+                    voxel_rounding: self.voxel_rounding,
+                    checkerboard: 0,
+                    empty_index: self.empty_index as u32,
+                    pixel_angle: self.edge_aa_pixel_angle(&view.vp_matrix, height),
+                    _padding: 0,
+                }]),
+            );
+            let lighting_uniforms = self.lighting_uniforms(&view.vp_matrix, view.camera_position);
+            self.queue.write_buffer(
+                &self.xr_eyes[i].lighting_uniform_buffer,
+                0,
+                bytemuck::cast_slice(&[lighting_uniforms]),
+            );
+        }
+
+        let frame = self.acquire_frame()?;
+        let frame_view = frame.texture.create_view(&Default::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("XR Encoder"),
+            });
+        for (i, (view, eye)) in views.iter().zip(&self.xr_eyes).enumerate() {
+            self.encode_gbuffer_pass(
+                &mut encoder,
+                &eye.per_frame_bind_group,
+                &GBufferCamera::new(&view.vp_matrix, view.camera_position),
+                [
+                    &eye.gbuffer_albedo,
+                    &eye.gbuffer_normal,
+                    &eye.gbuffer_linear_z,
+                    &eye.gbuffer_material,
+                    &eye.depth_texture_view,
+                ],
+                (eye.width, eye.height),
+                false,
+            );
+
+            let (pipeline, bind_group) = match self.gbuffer_profile {
+                GBufferProfile::Full => (
+                    &self.lighting_pipeline,
+                    self.create_lighting_bind_group(
+                        [
+                            &eye.gbuffer_albedo,
+                            &eye.gbuffer_normal,
+                            &eye.gbuffer_linear_z,
+                            &eye.gbuffer_material,
+                            &eye.stencil_view,
+                        ],
+                        &eye.lighting_uniform_buffer,
+                        false,
+                    ),
+                ),
+                // No normals to light with; show albedo
+                GBufferProfile::AlbedoOnly => (
+                    &self.quad_pipeline_float,
+                    self.create_quad_bind_group(
+                        &self.quad_layout_float,
+                        &eye.gbuffer_albedo,
+                        &self.nearest_sampler,
+                        &self.identity_present_buffer,
+                    ),
+                ),
+            };
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("XR Lighting Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &frame_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        // Later eyes must keep the ones already drawn
+                        load: if i == 0 {
+                            wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+                        } else {
+                            wgpu::LoadOp::Load
+                        },
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                ..Default::default()
+            });
+            let [x, y, width, height] = view.viewport;
+            pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        self.queue.submit(Some(encoder.finish()));
+        frame.present();
+        // The canvas no longer holds the single-view frame
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Reads the view distance under pixel (x, y) from the last rendered frame.
+    /// Resolves with the distance, or `null` for background. Requests made while a
+    /// readback is in flight are coalesced into the next one, so this is cheap to
+    /// call every frame. (x, y) is in canvas pixels, whatever the render scale.
+    pub fn read_depth_at(&self, x: u32, y: u32) -> js_sys::Promise {
+        let size = self.gbuffer.linear_z.texture.size();
+        let scaled =
+            |pixel: u32, from: u32, to: u32| (pixel as u64 * to as u64 / from.max(1) as u64) as u32;
+        let promise = self.depth_readback.request(
+            scaled(x, self.surface_config.width, size.width),
+            scaled(y, self.surface_config.height, size.height),
+        );
+        self.depth_readback
+            .pump(&self.device, &self.queue, &self.gbuffer.linear_z.texture);
+        promise
+    }
+
+    /// Captures the depth buffer of the last rendered frame as world-space distances
+    /// from the camera, row-major from the top-left. Background pixels are `Infinity`.
+    /// Resolves with a `Float32Array` of `width * height` values, at the render size
+    /// (see `set_render_scale` and `set_max_render_dimension`).
+    pub fn capture_depth(&mut self) -> js_sys::Promise {
+        let device = self.device.clone();
+        let queue = self.queue.clone();
+        let (width, height) = self.render_size();
+
+        if !self.has_depth() {
+            return js_sys::Promise::reject(
+                &RendererError::from(
+                    "capture_depth requires the raster path with depth; see set_depth_enabled",
+                )
+                .into(),
+            );
+        }
+        let Some(inv_vp_matrix) = math::invert(&self.frame.vp_matrix) else {
+            return js_sys::Promise::reject(
+                &RendererError::from(
+                    "capture_depth requires a rendered frame with an invertible view-projection \
+                     matrix",
+                )
+                .into(),
+            );
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Depth Resolve Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[DepthResolveUniforms {
+                inv_vp_matrix,
+                camera_position: self.frame.camera_position,
+                _padding: 0.0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let depth_view = self
+            .depth_texture
+            .create_view(&wgpu::TextureViewDescriptor {
+                aspect: wgpu::TextureAspect::DepthOnly,
+                ..Default::default()
+            });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Depth Resolve BG"),
+            layout: &self.depth_resolve_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let resolved = create_render_texture(
+            &device,
+            width,
+            height,
+            wgpu::TextureFormat::R32Float,
+            "Resolved Depth",
+        );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Depth Resolve Encoder"),
+        });
+        {
+            let resolved_view = resolved.create_view(&wgpu::TextureViewDescriptor::default());
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Depth Resolve Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &resolved_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                ..Default::default()
+            });
+            pass.set_pipeline(&self.depth_resolve_pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        queue.submit(Some(encoder.finish()));
+
+        wasm_bindgen_futures::future_to_promise(async move {
+            let bytes =
+                readback::read_texture(&device, &queue, &resolved, wgpu::TextureAspect::All, 4)
+                    .await?;
+            let depths: Vec<f32> = bytemuck::pod_collect_to_vec(&bytes);
+            Ok(js_sys::Float32Array::from(depths.as_slice()).into())
+        })
+    }
+
+    /// Renders a frame with the current settings and reads it back from the canvas.
+    /// Resolves with a `Uint8Array` of `width * height` RGBA8 pixels, row-major from
+    /// the top-left. Rejects when the canvas cannot be copied from.
+    pub fn capture_frame(&mut self) -> js_sys::Promise {
+        let swap_red_blue = match self.surface_config.format {
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            format => {
+                return js_sys::Promise::reject(
+                    &RendererError::Surface(format!(
+                        "capture_frame does not support the {:?} canvas format",
+                        format
+                    ))
+                    .into(),
+                );
+            }
+        };
+        if !self
+            .surface_config
+            .usage
+            .contains(wgpu::TextureUsages::COPY_SRC)
+        {
+            return js_sys::Promise::reject(
+                &RendererError::Surface(
+                    "the canvas does not support copies, which capture_frame requires".to_string(),
+                )
+                .into(),
+            );
+        }
+        let (width, height) = (self.surface_config.width, self.surface_config.height);
+        let padded_row = capture_row_bytes(width);
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Capture Buffer"),
+            size: padded_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        self.frame_capture = Some(buffer.clone());
+        if let Err(error) = self.render_frame() {
+            self.frame_capture = None;
+            return js_sys::Promise::reject(&error.into());
+        }
+
+        wasm_bindgen_futures::future_to_promise(async move {
+            readback::map_for_read(&buffer).await?;
+            let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+            {
+                let data = buffer.slice(..).get_mapped_range();
+                for row in data.chunks_exact(padded_row as usize) {
+                    pixels.extend_from_slice(&row[..(width * 4) as usize]);
+                }
+            }
+            buffer.unmap();
+            if swap_red_blue {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+            Ok(js_sys::Uint8Array::from(pixels.as_slice()).into())
+        })
+    }
+
+    /// Renders a frame with the parameters of `render` into an offscreen texture
+    /// instead of the canvas, and resolves with it as a WebCodecs `VideoFrame` at
+    /// `timestamp_us` microseconds, for a `VideoEncoder` exporting an animation. The
+    /// frame has the canvas size and its pixel order, `RGBA` or `BGRA`. Unlike
+    /// `render` it ignores pausing and the FPS cap, as every exported frame is
+    /// wanted. Callers must `close()` each frame once encoded.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_video_frame(
+        &mut self,
+        vp_matrix: &[f32],
+        view_position: &[f32],
+        present_target: usize,
+        light_dir: &[f32],
+        ambient: f32,
+        show_bboxes: bool,
+        timestamp_us: f64,
+    ) -> js_sys::Promise {
+        let pixel_format = match self.surface_config.format {
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+                web_sys::VideoPixelFormat::Bgra
+            }
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => {
+                web_sys::VideoPixelFormat::Rgba
+            }
+            format => {
+                return js_sys::Promise::reject(
+                    &RendererError::Surface(format!(
+                        "render_video_frame does not support the {:?} canvas format",
+                        format
+                    ))
+                    .into(),
+                );
+            }
+        };
+        self.frame = match FrameSettings::new(
+            vp_matrix,
+            view_position,
+            present_target,
+            light_dir,
+            ambient,
+            show_bboxes,
+        ) {
+            Ok(frame) => frame,
+            Err(error) => return js_sys::Promise::reject(&error.into()),
+        };
+        // The pipelines write the canvas format, so the target has it too
+        let (width, height) = (self.surface_config.width, self.surface_config.height);
+        let target = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Video Frame Target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.surface_config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let rendered = self.render_frame_into(&target);
+        // The canvas still shows the frame before
+        self.dirty = true;
+        if let Err(error) = rendered {
+            return js_sys::Promise::reject(&error.into());
+        }
+
+        let (device, queue) = (self.device.clone(), self.queue.clone());
+        wasm_bindgen_futures::future_to_promise(async move {
+            let mut pixels =
+                readback::read_texture(&device, &queue, &target, wgpu::TextureAspect::All, 4)
+                    .await?;
+            let init = web_sys::VideoFrameBufferInit::new_with_f64(
+                height,
+                width,
+                pixel_format,
+                timestamp_us,
+            );
+            Ok(
+                web_sys::VideoFrame::new_with_u8_slice_and_video_frame_buffer_init(
+                    &mut pixels,
+                    &init,
+                )?
+                .into(),
+            )
+        })
+    }
+
+    pub fn get_gpu_info(&self) -> JsValue {
+        let gpu_info = SerializableAdapterInfo {
+            name: self.adapter_info.name.clone(),
+            vendor: self.adapter_info.vendor,
+            device: self.adapter_info.device,
+            device_type: format!("{:?}", self.adapter_info.device_type),
+            driver: self.adapter_info.driver_info.clone(),
+            driver_info: self.adapter_info.driver_info.clone(),
+            backend: format!("{:?}", self.adapter_info.backend),
+            linear_z_format: format!("{:?}", self.linear_z_format),
+        };
+        serde_wasm_bindgen::to_value(&gpu_info).unwrap()
+    }
+
+    /// Limits of the device, for clients that adapt scene sizes or settings to it
+    /// (e.g. some mobile GPUs only allow 3D textures up to 256 per side).
+    pub fn get_device_limits(&self) -> JsValue {
+        let limits = self.device.limits();
+        let device_limits = SerializableDeviceLimits {
+            max_texture_dimension_2d: limits.max_texture_dimension_2d,
+            max_texture_dimension_3d: limits.max_texture_dimension_3d,
+            max_bind_groups: limits.max_bind_groups,
+            max_uniform_buffer_binding_size: limits.max_uniform_buffer_binding_size,
+            max_storage_buffer_binding_size: limits.max_storage_buffer_binding_size,
+            max_storage_buffers_per_shader_stage: limits.max_storage_buffers_per_shader_stage,
+            max_buffer_size: limits.max_buffer_size,
+            max_compute_workgroup_size_x: limits.max_compute_workgroup_size_x,
+            max_compute_workgroup_size_y: limits.max_compute_workgroup_size_y,
+        };
+        serde_wasm_bindgen::to_value(&device_limits).unwrap()
+    }
+
+    /// Names of the wgpu features enabled on the device, e.g. `"PIPELINE_CACHE"`.
+    pub fn get_device_features(&self) -> JsValue {
+        let features: Vec<&str> = self
+            .device
+            .features()
+            .iter_names()
+            .map(|(name, _)| name)
+            .collect();
+        serde_wasm_bindgen::to_value(&features).unwrap()
+    }
+
+    /// Picks up to `max_colors` (1 to 255) colours for an RGBA grid of
+    /// `dims_x × dims_y × dims_z` voxels (x fastest, then y, then z; four bytes each),
+    /// by median cut, returning `[voxels, palette]`: a palette index per voxel, 0 for
+    /// the voxels of alpha 0, and the `[r, g, b, a]` entries they index, entry 0 being
+    /// transparent black. Grids of at most `max_colors` distinct colours keep them
+    /// exactly. Upload an rgba object with `quantize: true` to have the scene's
+    /// palette extended this way instead.
+    pub fn quantize_voxels(
+        rgba: &[u8],
+        dims_x: u32,
+        dims_y: u32,
+        dims_z: u32,
+        max_colors: u32,
+    ) -> Result<JsValue, RendererError> {
+        let dims = [dims_x, dims_y, dims_z];
+        let expected = dims.iter().map(|&dim| dim as usize).product::<usize>() * 4;
+        if rgba.len() != expected {
+            return Err(RendererError::invalid(
+                "rgba",
+                format!(
+                    "{} rgba bytes given, expected {} (4 per voxel) for dims {:?}",
+                    rgba.len(),
+                    expected,
+                    dims
+                ),
+            ));
+        }
+        if !(1..=255).contains(&max_colors) {
+            return Err(RendererError::invalid(
+                "max_colors",
+                format!("max_colors must be 1 to 255, got {}", max_colors),
+            ));
+        }
+        let quantized = scene::quantize(rgba, dims, max_colors as usize);
+        Ok(serde_wasm_bindgen::to_value(&quantized)?)
+    }
+
+    /// Caps the side of the 3D textures objects are uploaded in at `size` voxels, for
+    /// hosts that keep textures small beyond the device's `max_texture_dimension_3d`;
+    /// 0 (the default) leaves the device limit. Objects larger than the cap or the
+    /// limit are split along the axes that exceed it into pieces sharing their id,
+    /// which meet without cracks or overlap; the voxel filter and edge antialiasing do
+    /// not see across the cuts. The per-object setters apply to every piece, while the
+    /// AO bakes, `read_ao` and erosion and dilation, which need the whole grid in one
+    /// texture, fail for split objects. Applies to the objects uploaded afterwards.
+    pub fn set_max_volume_size(&mut self, size: u32) {
+        self.max_volume_size = size;
+    }
+
+    /// By default the renderer inverts each object's `model_matrix` itself and ignores
+    /// any `inv_model_matrix` of a JSON scene, as a stale inverse silently breaks the
+    /// raymarch and the normals. Trusting the given inverses skips the inversion for
+    /// callers that already have them; objects without one are still inverted.
+    /// Binary scenes keep the inverses `export_scene` wrote either way.
+    pub fn set_trust_inverse_matrices(&mut self, trusted: bool) {
+        self.trust_inverse_matrices = trusted;
+    }
+
+    /// Replaces the active scene (including an active named slot) with `scene`.
+    /// Skips the upload when `scene` has the content and settings of the last one (see
+    /// `get_last_uploaded_scene_hash`) and the scene was not edited through the
+    /// renderer since, as by `set_object_pivot`, morphology or a palette change;
+    /// `force_upload_scene` uploads it regardless.
+    pub fn upload_scene(&mut self, scene: JsValue) -> Result<(), RendererError> {
+        let mut scene = self.parse_scene(scene)?;
+        scene.resolve_parents()?;
+        let mut settings = self.max_volume_size.to_le_bytes().to_vec();
+        settings.push(self.trust_inverse_matrices as u8);
+        let hash = scene.content_hash(&settings);
+        if hash == self.last_scene_hash {
+            log::debug!("skipped the upload of an unchanged scene");
+            return Ok(());
+        }
+        self.upload_parsed_scene(scene)?;
+        self.last_scene_hash = hash;
+        Ok(())
+    }
+
+    /// `upload_scene` even when `scene` is unchanged since the last upload.
+    pub fn force_upload_scene(&mut self, scene: JsValue) -> Result<(), RendererError> {
+        self.last_scene_hash = 0;
+        self.upload_scene(scene)
+    }
+
+    /// 64-bit FNV-1a hash of the palette, voxels and transforms of the scene last
+    /// uploaded with `upload_scene`, leaving out object ids, and of the settings it
+    /// was uploaded with (`set_max_volume_size`, `set_trust_inverse_matrices`), which
+    /// thus upload an unchanged scene again once changed. 0 before any, and after
+    /// another call, such as `upload_scene_binary` or `activate_scene`, replaced the
+    /// active scene or one such as `erode_object` or `upload_palette` edited it.
+    pub fn get_last_uploaded_scene_hash(&self) -> u64 {
+        self.last_scene_hash
+    }
+
+    /// Deserializes a JSON scene, inverting its model matrices unless the given
+    /// inverses are trusted.
+    fn parse_scene(&self, scene: JsValue) -> Result<Scene, RendererError> {
+        let mut scene: Scene = serde_wasm_bindgen::from_value(scene)?;
+        if !self.trust_inverse_matrices {
+            scene.invert_model_matrices()?;
+        }
+        Ok(scene)
+    }
+
+    /// Like `upload_scene`, with the voxels of all objects in one array instead of
+    /// each object's `voxels`, which the objects of `scene` leave out: object `i`'s
+    /// grid (x fastest, then y, then z; two little-endian bytes per voxel for the
+    /// `"u16"` format) starts at `voxels[offsets[i]]`. Each grid is
+    /// copied out of the array in one block, much faster for large scenes than
+    /// converting the number arrays `upload_scene` reads element by element. Fails,
+    /// naming the object index, when an offset is missing or its grid runs past the
+    /// end of `voxels`.
+    pub fn upload_scene_with_voxels(
+        &mut self,
+        scene: JsValue,
+        voxels: js_sys::Uint8Array,
+        offsets: &[u32],
+    ) -> Result<(), RendererError> {
+        let mut scene = self.parse_scene(scene)?;
+        if offsets.len() != scene.objects.len() {
+            return Err(RendererError::invalid(
+                "offsets",
+                format!(
+                    "{} voxel offsets given for {} objects",
+                    offsets.len(),
+                    scene.objects.len()
+                ),
+            ));
+        }
+        let available = voxels.length() as usize;
+        for (i, (obj, &offset)) in scene.objects.iter_mut().zip(offsets).enumerate() {
+            if !obj.voxels.is_empty() {
+                return Err(RendererError::invalid_object(
+                    i,
+                    Some("voxels"),
+                    format!(
+                        "object {} has voxels in the scene as well as in the array",
+                        i
+                    ),
+                ));
+            }
+            let bytes = obj.voxel_bytes().ok_or_else(|| {
+                RendererError::invalid_object(
+                    i,
+                    Some("dims"),
+                    format!("object {} has too many voxels: {:?}", i, obj.dims),
+                )
+            })?;
+            let start = offset as usize;
+            let Some(end) = start.checked_add(bytes).filter(|&end| end <= available) else {
+                return Err(RendererError::invalid_object(
+                    i,
+                    Some("offsets"),
+                    format!(
+                        "object {} needs {} bytes from offset {}, but the array has {}",
+                        i, bytes, start, available
+                    ),
+                ));
+            };
+            obj.voxels = vec![0; end - start];
+            voxels
+                .subarray(start as u32, end as u32)
+                .copy_to(&mut obj.voxels);
+        }
+        self.upload_parsed_scene(scene)
+    }
+
+    /// Like `upload_scene`, but deserializes and uploads the objects in batches,
+    /// yielding to the event loop in between; frames rendered meanwhile show the
+    /// objects uploaded so far. `on_progress`, if given, is called after each batch
+    /// with `{ objects_done, objects_total, bytes_done }`.
+    ///
+    /// The promise rejects with a `RendererError`: `"serde"` or `"validation"` for an
+    /// invalid scene, `object_id` holding the index in `scene.objects` of an object
+    /// that failed to deserialize; `"aborted"` when `signal` fired; `"destroyed"` when
+    /// another scene was uploaded or activated first; `"js"` when `on_progress` threw.
+    /// The objects uploaded until then stay in the scene.
+    pub fn upload_scene_async(
+        &mut self,
+        scene: JsValue,
+        on_progress: Option<js_sys::Function>,
+        signal: Option<web_sys::AbortSignal>,
+    ) -> js_sys::Promise {
+        let (pending, token) = match upload::begin(self, &scene) {
+            Ok(upload) => upload,
+            Err(error) => return js_sys::Promise::reject(&error.into()),
+        };
+        self.async_upload = Some(token);
+
+        let renderer: *mut Renderer = self;
+        wasm_bindgen_futures::future_to_promise(async move {
+            // SAFETY: the renderer owns the upload's token and drops it before it is
+            // freed, which the upload checks before every access.
+            unsafe { upload::run(renderer, pending, on_progress, signal) }.await?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Uploads a scene in the binary format produced by `export_scene`, either as is
+    /// or gzip-compressed, e.g. as cached from a gzip'd response, or zstd-compressed
+    /// with the `zstd` feature. Corrupt compressed data fails with the byte offset at
+    /// which decoding stopped, as does data decompressing past 512 MiB.
+    pub fn upload_scene_binary(&mut self, bytes: &[u8]) -> Result<(), RendererError> {
+        let scene = Scene::from_payload(bytes)?;
+        self.upload_parsed_scene(scene)
+    }
+
+    /// Returns the pipeline cache contents for `RendererBuilder::with_pipeline_cache`.
+    /// Fails where the device has no pipeline cache, which includes WebGPU.
+    pub fn serialize_pipeline_cache(&self) -> Result<Vec<u8>, RendererError> {
+        self.pipeline_cache
+            .as_ref()
+            .and_then(|cache| cache.get_data())
+            .ok_or_else(|| {
+                RendererError::Device("pipeline caching is not supported by this device".into())
+            })
+    }
+
+    /// Serializes the active scene (palette and objects by id, including
+    /// erosion / dilation read back since upload, split objects whole) for
+    /// `upload_scene_binary`.
+    pub fn export_scene(&mut self) -> Vec<u8> {
+        self.sync_voxels();
+        let scene = Scene {
+            palette: self.palette.clone(),
+            objects: self.scene_objects(),
+        };
+        scene.to_bytes()
+    }
+
+    /// Uploads `scene` into the named slot without making it active. Loading into the
+    /// active slot replaces what is currently drawn.
+    pub fn load_scene_slot(&mut self, name: &str, scene: JsValue) -> Result<(), RendererError> {
+        self.dirty = true;
+        let mut scene = self.parse_scene(scene)?;
+        scene.resolve_parents()?;
+        scene.quantize_objects()?;
+        let mut slot = self.create_scene_slot(name, scene)?;
+        if self.active_scene_slot.as_deref() == Some(name) {
+            self.swap_active_scene(&mut slot);
+        } else {
+            self.scene_slots.insert(name.to_string(), slot);
+        }
+        Ok(())
+    }
+
+    /// Uploads the palette and objects of `scene` into a new slot called `name`.
+    fn create_scene_slot(&mut self, name: &str, scene: Scene) -> Result<SceneSlot, RendererError> {
+        let static_uniform_buffer =
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("Static Uniform Buffer ({})", name)),
+                    contents: bytemuck::cast_slice(&[Renderer::pack_palette(&scene)]),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+        let palette_view = create_palette_view(&self.device, &self.queue, &scene.palette);
+        let static_bind_group =
+            self.create_static_bind_group(&static_uniform_buffer, &palette_view);
+        let draw_call_array = self.create_draw_calls(scene.objects, 0)?;
+        self.queue.submit([]);
+
+        let mut slot = SceneSlot {
+            palette: scene.palette,
+            static_uniform_buffer,
+            static_bind_group,
+            palette_view,
+            draw_call_array,
+            scene_bounds: None,
+        };
+        slot.scene_bounds = slot.compute_bounds();
+        Ok(slot)
+    }
+
+    /// Makes a slot loaded with `load_scene_slot` the active scene. No GPU resources
+    /// are re-uploaded. A scene uploaded with `upload_scene` outside of any slot is
+    /// released when another slot is activated.
+    pub fn activate_scene(&mut self, name: &str) -> Result<(), RendererError> {
+        self.dirty = true;
+        if self.active_scene_slot.as_deref() == Some(name) {
+            return Ok(());
+        }
+        let mut slot = self.scene_slots.remove(name).ok_or_else(|| {
+            RendererError::invalid_object(name, None, format!("no scene slot named '{}'", name))
+        })?;
+        self.swap_active_scene(&mut slot);
+        if let Some(previous) = self.active_scene_slot.replace(name.to_string()) {
+            self.scene_slots.insert(previous, slot);
+        }
+        Ok(())
+    }
+
+    /// Frees the GPU resources of a slot. Unloading the active slot leaves an empty scene.
+    pub fn unload_scene_slot(&mut self, name: &str) -> Result<(), RendererError> {
+        self.dirty = true;
+        if self.active_scene_slot.as_deref() == Some(name) {
+            // The slot's palette buffers go with its objects, swapped out for empty ones
+            let empty = Scene {
+                palette: Vec::new(),
+                objects: Vec::new(),
+            };
+            let mut empty = self.create_scene_slot(name, empty)?;
+            self.swap_active_scene(&mut empty);
+            self.active_scene_slot = None;
+            return Ok(());
+        }
+        self.scene_slots.remove(name).map(|_| ()).ok_or_else(|| {
+            RendererError::invalid_object(name, None, format!("no scene slot named '{}'", name))
+        })
+    }
+
+    /// Erodes object `id`: a voxel stays filled only if all 6 face neighbours are filled.
+    /// Runs on the GPU and draws from the next frame; the promise resolves once the
+    /// result is read back into the CPU copy used by `export_scene`, `find_collisions`
+    /// and the CPU bakes, and rejects when `id` cannot be eroded.
+    pub fn erode_object(&mut self, id: u32) -> js_sys::Promise {
+        self.dirty = true;
+        self.apply_morphology(id, MorphologyOp::Erode)
+            .unwrap_or_else(|error| js_sys::Promise::reject(&error.into()))
+    }
+
+    /// Dilates object `id`: an empty voxel with a filled face neighbour takes its colour.
+    /// Resolves like `erode_object`.
+    pub fn dilate_object(&mut self, id: u32) -> js_sys::Promise {
+        self.dirty = true;
+        self.apply_morphology(id, MorphologyOp::Dilate)
+            .unwrap_or_else(|error| js_sys::Promise::reject(&error.into()))
+    }
+
+    /// Holds the uniform writes of the per-object and material setters
+    /// (`set_object_tint`, `set_material`, `set_clip_planes`, ...) until
+    /// `end_update_batch`, keeping only the latest write of each, for hosts updating
+    /// many objects per frame. Frames rendered before the batch ends show the values
+    /// from before it. No effect while a batch is already open.
+    pub fn begin_update_batch(&mut self) {
+        self.update_batch.begin();
+    }
+
+    /// Issues the writes held since `begin_update_batch`; returns how many there were.
+    /// No effect without an open batch.
+    pub fn end_update_batch(&mut self) -> u32 {
+        self.update_batch.flush(&self.queue) as u32
+    }
+
+    /// Multiplies the palette colours of object `id` by `rgba`, for effects such as
+    /// damage flashes or team colours without editing the palette; white (the
+    /// default) leaves them unchanged. Alpha has no effect, voxels being opaque. Only
+    /// the object's uniforms are rewritten, and a scene upload resets the tint.
+    pub fn set_object_tint(&mut self, id: u32, rgba: &[f32]) -> Result<(), RendererError> {
+        let tint: [f32; 4] = rgba
+            .try_into()
+            .map_err(|_| RendererError::invalid("rgba", "tint must have 4 components"))?;
+        if tint.iter().any(|c| !c.is_finite() || *c < 0.0) {
+            return Err(RendererError::invalid(
+                "rgba",
+                "tint components must be finite and non-negative",
+            ));
+        }
+        let pieces = self.object_pieces(id)?;
+        for dc in &mut self.draw_call_array[pieces] {
+            dc.tint = tint;
+            self.update_batch.write(
+                &self.queue,
+                &dc.uniform_buffer,
+                0,
+                bytemuck::bytes_of(&dc.uniforms()),
+            );
+        }
+        self.dirty = true;
+        self.last_scene_hash = 0;
+        Ok(())
+    }
+
+    /// Moves the point object `id` rotates and scales about to `pivot`, in voxel
+    /// coordinates (0 to `dims` per axis), as a scene's `pivot` and `get_scene_info`
+    /// give it. The object's transform is kept, so the object shifts to put the new
+    /// pivot where the transform placed the old one.
+    pub fn set_object_pivot(&mut self, id: u32, pivot: &[f32]) -> Result<(), RendererError> {
+        let pivot: [f32; 3] = pivot
+            .try_into()
+            .ok()
+            .filter(|p: &[f32; 3]| p.iter().all(|c| c.is_finite()))
+            .ok_or_else(|| RendererError::invalid("pivot", "must be 3 finite numbers"))?;
+        let pieces = self.object_pieces(id)?;
+        for dc in &mut self.draw_call_array[pieces] {
+            // Each piece's pivot is relative to its own grid
+            let origin = dc.piece_origin;
+            dc.object
+                .set_pivot(std::array::from_fn(|a| pivot[a] - origin[a] as f32));
+            dc.world_bounds = Aabb::from_model_matrix(&dc.object.model_matrix);
+            dc.world_sphere = Sphere::from_model_matrix(&dc.object.model_matrix);
+            self.update_batch.write(
+                &self.queue,
+                &dc.uniform_buffer,
+                0,
+                bytemuck::bytes_of(&dc.uniforms()),
+            );
+        }
+        self.update_scene_bounds();
+        self.occlusion.invalidate();
+        self.dirty = true;
+        self.last_scene_hash = 0;
+        Ok(())
+    }
+
+    /// Limits object `id` to the pixels of `rect`, `[x, y, width, height]` from the
+    /// top left of the G-buffer (the render resolution, or each XR eye); `None` draws
+    /// it across the whole target again. Parts outside the target are ignored, and an
+    /// object whose rectangle misses it is not drawn. Objects sharing a rectangle in
+    /// draw order share a scissor change. While set, the compute render path falls
+    /// back to raster; a scene upload resets it.
+    pub fn set_object_scissor(
+        &mut self,
+        id: u32,
+        rect: Option<Vec<u32>>,
+    ) -> Result<(), RendererError> {
+        let rect = rect
+            .map(|rect| <[u32; 4]>::try_from(rect.as_slice()))
+            .transpose()
+            .map_err(|_| RendererError::invalid("rect", "scissor must have 4 components"))?;
+        let pieces = self.object_pieces(id)?;
+        for dc in &mut self.draw_call_array[pieces] {
+            dc.scissor = rect;
+        }
+        self.dirty = true;
+        self.last_scene_hash = 0;
+        Ok(())
+    }
+
+    /// Draws object `id` as the yellow edges of its bounding shape, like the
+    /// `show_bboxes` overlay and without depth testing, instead of its voxels, e.g.
+    /// for reference geometry in an editor; `false` draws it solid again. Wire objects
+    /// cast no shadows. A scene upload resets it.
+    pub fn set_object_wireframe(&mut self, id: u32, wire: bool) -> Result<(), RendererError> {
+        let pieces = self.object_pieces(id)?;
+        for dc in &mut self.draw_call_array[pieces] {
+            dc.wireframe = wire;
+        }
+        self.occlusion.invalidate();
+        self.dirty = true;
+        self.last_scene_hash = 0;
+        Ok(())
+    }
+
+    /// Draws only voxel layer `index` of object `id` along `axis` (0 = x, 1 = y, 2 = z),
+    /// lit as usual, for scrubbing through a volume one slice at a time (with an
+    /// orthographic camera, a basic slice viewer); `enabled` false draws the whole
+    /// object again. Moving the slice only rewrites the object's uniforms. While set,
+    /// the compute render path falls back to raster; a scene upload resets it.
+    pub fn set_slice_view(
+        &mut self,
+        id: u32,
+        axis: u32,
+        index: u32,
+        enabled: bool,
+    ) -> Result<(), RendererError> {
+        let pieces = self.object_pieces(id)?;
+        let slice = if enabled {
+            let dims = self.draw_call_array[pieces.start].object_dims;
+            if axis > 2 {
+                return Err(RendererError::invalid("axis", "must be 0, 1 or 2"));
+            }
+            if index >= dims[axis as usize] {
+                return Err(RendererError::invalid_object(
+                    id,
+                    Some("index"),
+                    format!(
+                        "slice {} is outside the {} voxels of axis {}",
+                        index, dims[axis as usize], axis
+                    ),
+                ));
+            }
+            Some((axis as usize, index))
+        } else {
+            None
+        };
+        // Pieces without the layer draw nothing
+        for dc in &mut self.draw_call_array[pieces] {
+            dc.slice = slice;
+            self.update_batch.write(
+                &self.queue,
+                &dc.uniform_buffer,
+                0,
+                bytemuck::bytes_of(&dc.uniforms()),
+            );
+        }
+        self.occlusion.invalidate();
+        self.dirty = true;
+        self.last_scene_hash = 0;
+        Ok(())
+    }
+
+    /// Rasterizes object `id` through `shape` instead of its proxy box; see
+    /// `BoundingShape`. The wireframe draws the same shape. A scene upload resets it.
+    pub fn set_object_bounding_shape(
+        &mut self,
+        id: u32,
+        shape: BoundingShape,
+    ) -> Result<(), RendererError> {
+        let pieces = self.object_pieces(id)?;
+        for dc in &mut self.draw_call_array[pieces] {
+            dc.bounding_shape = shape;
+        }
+        self.dirty = true;
+        self.last_scene_hash = 0;
+        Ok(())
+    }
+
+    /// Builds a sparse voxel octree of object `id` and uploads it, so the compute render
+    /// path skips the object's empty space instead of stepping through every voxel.
+    /// Octree objects are not limited by the compute path's object cap, but are drawn
+    /// without voxel filtering or rounding. Voxel edits drop the octree.
+    pub fn build_svo(&mut self, id: u32) -> Result<(), RendererError> {
+        self.sync_voxels();
+        let pieces = self.object_pieces(id)?;
+        // Split objects get an octree per piece, whose grids they cover exactly
+        for dc in &self.draw_call_array[pieces.clone()] {
+            dc.require_u8_voxels(id, "build_svo")?;
+        }
+        for dc in &mut self.draw_call_array[pieces] {
+            let octree = SparseOctree::build(dc.object.dims, &dc.object.voxels, self.empty_index)?;
+            dc.svo_buffer = Some(octree.upload(&self.device));
+        }
+        self.dirty = true;
+        self.last_scene_hash = 0;
+        Ok(())
+    }
+
+    /// Bakes object-space ambient occlusion for object `id` into an R8Unorm 3D texture:
+    /// each exposed face of every filled voxel traces `samples` cosine-weighted rays
+    /// through the object's voxels. The promise resolves once the GPU has finished.
+    pub fn bake_object_space_ao(&mut self, id: u32, samples: u32) -> js_sys::Promise {
+        if samples == 0 {
+            let error = RendererError::invalid("samples", "samples must be at least 1");
+            return js_sys::Promise::reject(&error.into());
+        }
+        let index = match self.whole_object(id, "bake_object_space_ao") {
+            Ok(index) => index,
+            Err(error) => return js_sys::Promise::reject(&error.into()),
+        };
+        let dc = &self.draw_call_array[index];
+        if let Err(error) = dc.require_palette_voxels(id, "bake_object_space_ao") {
+            return js_sys::Promise::reject(&error.into());
+        }
+        let (ao_texture, done) = self.ao_bake.bake(
+            &self.device,
+            &self.queue,
+            &dc.texture,
+            samples,
+            self.empty_index,
+        );
+        self.attach_ao_texture(index, ao_texture);
+
+        wasm_bindgen_futures::future_to_promise(async move {
+            readback::map_for_read(&done).await?;
+            done.unmap();
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Bakes neighbour-counting AO for object `id` on the GPU: each filled voxel's
+    /// occlusion is the share of filled voxels within `radius` (at most 6) on every
+    /// axis. Matches `bake_ao_cpu` to within 1 of 255; the promise resolves once the
+    /// GPU has finished, and the time taken is reported by `get_ao_bake_stats`.
+    pub fn bake_ao(&mut self, id: u32, radius: u32) -> js_sys::Promise {
+        let index = match self.check_ao_bake(id, radius) {
+            Ok(index) => index,
+            Err(error) => return js_sys::Promise::reject(&error.into()),
+        };
+        let dc = &self.draw_call_array[index];
+        let start = now_ms();
+        let (ao_texture, done) = self.neighbour_ao.bake(
+            &self.device,
+            &self.queue,
+            &dc.texture,
+            radius,
+            self.empty_index,
+        );
+        self.attach_ao_texture(index, ao_texture);
+
+        let timings = self.ao_bake_timings.clone();
+        wasm_bindgen_futures::future_to_promise(async move {
+            readback::map_for_read(&done).await?;
+            done.unmap();
+            timings.set(AoBakeTimings {
+                gpu_ms: Some(now_ms() - start),
+                ..timings.get()
+            });
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// CPU path of `bake_ao`, from the retained voxel data.
+    pub fn bake_ao_cpu(&mut self, id: u32, radius: u32) -> Result<(), RendererError> {
+        self.sync_voxels();
+        let start = now_ms();
+        let index = self.check_ao_bake(id, radius)?;
+        let dc = &self.draw_call_array[index];
+        let ao =
+            ao_bake::neighbour_ao_cpu(dc.object.dims, &dc.object.voxels, radius, self.empty_index);
+        let size = dc.texture.size();
+        let ao_texture = ao_bake::create_ao_texture(&self.device, size);
+        self.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &ao_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &ao,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(size.width),
+                rows_per_image: Some(size.height),
+            },
+            size,
+        );
+        self.attach_ao_texture(index, ao_texture);
+        self.ao_bake_timings.set(AoBakeTimings {
+            cpu_ms: Some(now_ms() - start),
+            ..self.ao_bake_timings.get()
+        });
+        Ok(())
+    }
+
+    /// Reads back object `id`'s baked AO, from any of the bakes: resolves with a
+    /// `Uint8Array` of one byte per voxel, x fastest, as `bake_ao_cpu` computes it.
+    /// Rejects when the object has no baked AO.
+    pub fn read_ao(&self, id: u32) -> js_sys::Promise {
+        let dc = match self.whole_object(id, "read_ao") {
+            Ok(index) => &self.draw_call_array[index],
+            Err(error) => return js_sys::Promise::reject(&error.into()),
+        };
+        let Some(ao_texture) = dc.ao_texture.clone() else {
+            let error =
+                RendererError::invalid_object(id, None, format!("object {} has no baked AO", id));
+            return js_sys::Promise::reject(&error.into());
+        };
+        let device = self.device.clone();
+        let queue = self.queue.clone();
+        wasm_bindgen_futures::future_to_promise(async move {
+            let ao =
+                readback::read_texture(&device, &queue, &ao_texture, wgpu::TextureAspect::All, 1)
+                    .await?;
+            Ok(js_sys::Uint8Array::from(ao.as_slice()).into())
+        })
+    }
+
+    /// Measures the G-buffer, present and post-processing passes of each frame with
+    /// GPU timestamps, read by `get_gpu_timestamps`. Fails when the device lacks
+    /// `TIMESTAMP_QUERY` (see `get_device_features`).
+    pub fn set_render_pass_timestamps(&mut self, enabled: bool) -> Result<(), RendererError> {
+        if !enabled {
+            if let Some(timestamps) = &mut self.timestamps {
+                timestamps.enabled = false;
+            }
+            return Ok(());
+        }
+        if !self
+            .device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+        {
+            return Err(RendererError::Device(
+                "timestamp queries are not supported by this device".into(),
+            ));
+        }
+        self.timestamps
+            .get_or_insert_with(|| PassTimestamps::new(&self.device, &self.queue))
+            .enabled = true;
+        Ok(())
+    }
+
+    /// `{ gbuffer_start_ns, gbuffer_end_ns, present_start_ns, present_end_ns,
+    /// post_start_ns, post_end_ns }` of the latest measured frame as `BigInt`s, the
+    /// post fields `null` without post-processing; `null` before any frame was measured.
+    /// Results arrive a frame or more after the frame they measure.
+    pub fn get_gpu_timestamps(&self) -> JsValue {
+        let serializer =
+            serde_wasm_bindgen::Serializer::new().serialize_large_number_types_as_bigints(true);
+        self.timestamps
+            .as_ref()
+            .and_then(PassTimestamps::latest)
+            .and_then(|latest| latest.serialize(&serializer).ok())
+            .unwrap_or(JsValue::NULL)
+    }
+
+    /// `{ cpu_ms, gpu_ms }` of the latest `bake_ao_cpu` / `bake_ao`, `null` for a path
+    /// that has not run yet.
+    pub fn get_ao_bake_stats(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.ao_bake_timings.get()).unwrap_or(JsValue::NULL)
+    }
+
+    /// Memory footprint of object `id`, over all its pieces if it was split, or `null`
+    /// if there is no such object. An object's id is its index in the uploaded scene's
+    /// `objects`, whatever its `order`; objects added later, such as by
+    /// `create_voxel_from_sdf`, take the next ids, and ids do not change until the
+    /// next upload.
+    pub fn get_object_memory_usage(&self, id: u32) -> JsValue {
+        match self.object_pieces.get(id as usize) {
+            Some(pieces) => {
+                let stats = ObjectMemoryStats::of(&self.draw_call_array[pieces.clone()]);
+                serde_wasm_bindgen::to_value(&stats).unwrap()
+            }
+            None => JsValue::NULL,
+        }
+    }
+
+    /// Objects of the active scene by id, as `{ id, dims, order, pivot,
+    /// model_matrix }`, split objects whole; see `set_object_pivot`.
+    pub fn get_scene_info(&self) -> JsValue {
+        let objects: Vec<ObjectInfo> = self
+            .object_pieces
+            .iter()
+            .map(|pieces| {
+                // The first piece starts at the object's first voxel
+                let dc = &self.draw_call_array[pieces.start];
+                let (_, inv_local) = scene::piece_transform([0; 3], dc.object.dims, dc.object_dims);
+                ObjectInfo {
+                    id: &dc.object.id,
+                    dims: dc.object_dims,
+                    order: dc.object.order,
+                    pivot: dc.object.pivot,
+                    model_matrix: math::multiply(&dc.object.model_matrix, &inv_local),
+                }
+            })
+            .collect();
+        serde_wasm_bindgen::to_value(&objects).unwrap()
+    }
+
+    /// Memory footprints of all objects in the active scene, by id.
+    pub fn get_all_object_memory_stats(&self) -> JsValue {
+        let stats: Vec<ObjectMemoryStats> = self
+            .object_pieces
+            .iter()
+            .map(|pieces| ObjectMemoryStats::of(&self.draw_call_array[pieces.clone()]))
+            .collect();
+        serde_wasm_bindgen::to_value(&stats).unwrap()
+    }
+
+    /// Proxy geometry of the active scene: each object rasterizes only the box around
+    /// its filled voxels instead of its whole unit cube.
+    pub fn get_proxy_stats(&self) -> JsValue {
+        let ratios: Vec<f64> = self
+            .draw_call_array
+            .iter()
+            .filter_map(|dc| dc.proxy_bounds)
+            .map(|(min, max)| {
+                let volume: f64 = (0..3).map(|axis| (max[axis] - min[axis]) as f64).product();
+                1.0 / volume
             })
+            .collect();
+        let stats = ProxyStats {
+            object_count: self.draw_call_array.len(),
+            empty_object_count: self.draw_call_array.len() - ratios.len(),
+            average_cube_to_proxy_volume: if ratios.is_empty() {
+                1.0
+            } else {
+                ratios.iter().sum::<f64>() / ratios.len() as f64
+            },
         };
+        serde_wasm_bindgen::to_value(&stats).unwrap()
+    }
 
-        (quad_layout, quad_pipeline, quad_shader)
+    /// Bytes of voxel texture memory held by the active scene and all loaded slots.
+    pub fn get_texture_memory_usage(&self) -> f64 {
+        let slots = self
+            .scene_slots
+            .values()
+            .flat_map(|slot| slot.draw_call_array.iter());
+        self.draw_call_array
+            .iter()
+            .chain(slots)
+            .map(|dc| dc.texture_bytes())
+            .sum::<u64>() as f64
     }
 
-    pub fn render(
+    /// Uploads world-space triangle geometry drawn into the G-buffer alongside voxel
+    /// objects, shaded through the scene palette. `vertices` are raw bytes laid out as
+    /// `MeshVertex` (28-byte stride: position `f32x3`, normal `f32x3`, palette index
+    /// `u32`); `indices` form a triangle list. Returns the mesh id.
+    pub fn upload_mesh(&mut self, vertices: &[u8], indices: &[u32]) -> Result<u32, RendererError> {
+        self.dirty = true;
+        let stride = std::mem::size_of::<MeshVertex>();
+        if vertices.is_empty() || !vertices.len().is_multiple_of(stride) {
+            return Err(RendererError::invalid(
+                "vertices",
+                format!(
+                    "mesh vertex data must be a non-empty multiple of {} bytes",
+                    stride
+                ),
+            ));
+        }
+        if indices.is_empty() || !indices.len().is_multiple_of(3) {
+            return Err(RendererError::invalid(
+                "indices",
+                "mesh indices must be a non-empty triangle list",
+            ));
+        }
+        let vertex_count = (vertices.len() / stride) as u32;
+        if let Some(bad) = indices.iter().find(|&&i| i >= vertex_count) {
+            return Err(RendererError::invalid(
+                "indices",
+                format!(
+                    "mesh index {} out of range for {} vertices",
+                    bad, vertex_count
+                ),
+            ));
+        }
+
+        let vertex_buffer = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Mesh Vertex Buffer"),
+                contents: vertices,
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+        let index_buffer = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Mesh Index Buffer"),
+                contents: bytemuck::cast_slice(indices),
+                usage: wgpu::BufferUsages::INDEX,
+            });
+        self.meshes.push(MeshDrawData {
+            vertex_buffer,
+            index_buffer,
+            index_count: indices.len() as u32,
+        });
+        Ok(self.meshes.len() as u32 - 1)
+    }
+
+    /// `create_voxel_from_sdf` with a JS callback: `sdf_fn(x, y, z)` returns the signed
+    /// distance at a voxel centre in [-1, 1]^3. Results that are not numbers count as
+    /// outside; an exception thrown by the callback aborts without adding the object.
+    pub fn create_voxel_from_sdf_js(
         &mut self,
-        vp_matrix: &[f32],
-        view_position: &[f32],
-        present_target: usize,
-        light_dir: &[f32],
-        ambient: f32,
-        show_bboxes: bool,
-    ) -> Result<(), JsValue> {
-        let vp_matrix = vp_matrix
-            .try_into()
-            .expect("mvp_matrix has incorrect length");
-        let per_frame_uniforms = PerFrameUniforms {
-            vp_matrix,
-            camera_position: view_position.try_into().unwrap(),
-            _padding: 0.0,
+        dims_x: u32,
+        dims_y: u32,
+        dims_z: u32,
+        sdf_fn: js_sys::Function,
+        palette_index: u8,
+    ) -> Result<u32, RendererError> {
+        let dims = [dims_x, dims_y, dims_z];
+        let mut sdf = |[x, y, z]: [f32; 3]| {
+            let distance = sdf_fn.call3(&JsValue::NULL, &x.into(), &y.into(), &z.into())?;
+            Ok(distance.as_f64().map_or(f32::INFINITY, |d| d as f32))
         };
+        let voxels = self.sample_sdf(dims, &mut sdf, palette_index)?;
+        self.add_sdf_object(dims, voxels)
+    }
 
-        self.queue.write_buffer(
-            &self.per_frame_uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[per_frame_uniforms]),
-        );
+    /// Removes all meshes added with `upload_mesh`.
+    pub fn clear_meshes(&mut self) {
+        self.dirty = true;
+        self.meshes.clear();
+    }
 
-        // Update lighting uniforms
-        let lighting_uniforms = LightingUniforms {
-            light_dir: light_dir.try_into().unwrap_or([0.5, 0.5, 0.5]),
-            ambient,
+    /// World-space bounds `{ min, max }` of all objects, or `null` for an empty scene.
+    pub fn get_scene_bounds(&self) -> JsValue {
+        match &self.scene_bounds {
+            Some(bounds) => serde_wasm_bindgen::to_value(bounds).unwrap(),
+            None => JsValue::NULL,
+        }
+    }
+
+    /// Pairs `[i, j]`, `i < j`, of objects whose filled voxels overlap, by id as in
+    /// `get_object_memory_usage`, with `set_empty_index`'s index empty. See
+    /// `Scene::objects_intersect`.
+    pub fn find_collisions(&mut self) -> JsValue {
+        self.sync_voxels();
+        let objects: Vec<&VoxelObject> = self.draw_call_array.iter().map(|dc| &dc.object).collect();
+        // Pieces of one object never share a voxel, so its pairs are with others
+        let ids = |i: usize| self.draw_call_array[i].object_id;
+        let mut pairs: Vec<(u32, u32)> = scene::colliding_pairs(&objects, self.empty_index)
+            .into_iter()
+            .map(|(i, j)| (ids(i).min(ids(j)), ids(i).max(ids(j))))
+            .filter(|(i, j)| i != j)
+            .collect();
+        pairs.sort_unstable();
+        pairs.dedup();
+        serde_wasm_bindgen::to_value(&pairs).unwrap()
+    }
+
+    /// Computes an `{ eye, target }` pair framing the scene bounds from a three-quarter
+    /// angle for a camera with vertical field of view `fov_y_deg`. `margin` scales the
+    /// distance (1.0 = tight fit). Returns `null` for an empty scene.
+    pub fn fit_camera(&self, fov_y_deg: f32, margin: f32) -> JsValue {
+        let Some(bounds) = &self.scene_bounds else {
+            return JsValue::NULL;
         };
+        let target = bounds.center();
+        let radius = bounds.half_diagonal().max(f32::EPSILON);
+        let half_fov = (fov_y_deg.clamp(1.0, 179.0) * 0.5).to_radians();
+        let distance = radius / half_fov.sin() * margin.max(f32::EPSILON);
+
+        // 45° azimuth, 30° elevation
+        let (azimuth, elevation) = (45f32.to_radians(), 30f32.to_radians());
+        let dir = [
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            elevation.cos() * azimuth.cos(),
+        ];
+        let fit = CameraFit {
+            eye: std::array::from_fn(|i| target[i] + dir[i] * distance),
+            target,
+        };
+        serde_wasm_bindgen::to_value(&fit).unwrap()
+    }
+}
+
+impl Renderer {
+    /// `Renderer::new` or `Renderer::new_offscreen` with `options`, for Rust callers
+    /// holding either kind of canvas.
+    pub async fn with_target(
+        target: impl Into<RenderTarget>,
+        options: RendererBuilder,
+    ) -> Result<Renderer, RendererError> {
+        Renderer::create(target.into(), options).await
+    }
+
+    /// Renders the depth of the voxel bounding cubes and meshes as seen through
+    /// `vp_matrix` into `target_depth_view`, for shadow maps or a depth pre-pass.
+    /// The target must be `Depth24PlusStencil8` and at least `width`×`height`.
+    pub fn render_depth_only(
+        &self,
+        vp_matrix: &[f32],
+        target_depth_view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) -> Result<(), RendererError> {
+        let vp_matrix: [f32; 16] = vp_matrix
+            .try_into()
+            .map_err(|_| RendererError::invalid("vp_matrix", "vp_matrix must have 16 elements"))?;
         self.queue.write_buffer(
-            &self.lighting_uniform_buffer,
+            &self.depth_only.uniform_buffer,
             0,
-            bytemuck::cast_slice(&[lighting_uniforms]),
+            bytemuck::cast_slice(&vp_matrix),
         );
 
-        let per_frame_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Per Frame Bind Group"),
-            layout: &self.per_frame_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: self.per_frame_uniform_buffer.as_entire_binding(),
-            }],
-        });
-
         let mut encoder = self
             .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Depth Only Encoder"),
+            });
         {
             let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("GBuffer Pass"),
-                color_attachments: &[
-                    Some(wgpu::RenderPassColorAttachment {
-                        view: &self.gbuffer_albedo,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                            store: wgpu::StoreOp::Store,
-                        },
-                    }),
-                    Some(wgpu::RenderPassColorAttachment {
-                        view: &self.gbuffer_normal,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                            store: wgpu::StoreOp::Store,
-                        },
-                    }),
-                    Some(wgpu::RenderPassColorAttachment {
-                        view: &self.gbuffer_linear_z,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                            store: wgpu::StoreOp::Store,
-                        },
-                    }),
-                ],
+                label: Some("Depth Only Pass"),
+                color_attachments: &[],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture_view,
+                    view: target_depth_view,
                     depth_ops: Some(wgpu::Operations {
                         load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Discard,
+                        store: wgpu::StoreOp::Store,
                     }),
                     stencil_ops: None,
                 }),
                 ..Default::default()
             });
-            pass.set_pipeline(&self.render_pipeline);
-            pass.set_bind_group(0, &self.static_bind_group, &[]);
-            pass.set_bind_group(1, &per_frame_bind_group, &[]);
+            pass.set_viewport(0.0, 0.0, width as f32, height as f32, 0.0, 1.0);
+            pass.set_bind_group(0, &self.depth_only.bind_group, &[]);
+
             pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
             pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            let frustum = Frustum::from_vp_matrix(&vp_matrix);
+            let mut color_mode = None;
             for dc in &self.draw_call_array {
-                pass.set_bind_group(2, &dc.bind_group, &[]);
-                pass.draw_indexed(0..CUBE_INDICES.len() as u32, 0, 0..1);
+                if dc.wireframe || !frustum.is_visible(dc.world_sphere, &dc.world_bounds) {
+                    continue;
+                }
+                if color_mode != Some(dc.object.color_mode) {
+                    color_mode = Some(dc.object.color_mode);
+                    pass.set_pipeline(self.depth_only.voxel_pipeline_for(dc.object.color_mode));
+                }
+                pass.set_bind_group(1, &dc.bind_group, &[]);
+                pass.draw_indexed(dc.bounding_shape.indices(), 0, 0..1);
+            }
+
+            if !self.meshes.is_empty() {
+                pass.set_pipeline(&self.depth_only.mesh_pipeline);
+                for mesh in &self.meshes {
+                    pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                    pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                    pass.draw_indexed(0..mesh.index_count, 0, 0..1);
+                }
             }
         }
+        self.queue.submit(Some(encoder.finish()));
+        Ok(())
+    }
 
-        // 2) Present pass: full‑screen quad sampling chosen G‑buffer
-        let frame = self.surface.get_current_texture().map_err(map_wgpu_err)?;
-        let frame_view = frame.texture.create_view(&Default::default());
-        {
-            // draw full‑screen
+    /// Renders the casters of each shadow cascade fit by `CascadedShadows::prepare`.
+    fn encode_shadow_passes(&self, encoder: &mut wgpu::CommandEncoder) {
+        for cascade in self.shadows.cascades() {
             let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Present Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &frame_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                label: Some("Shadow Cascade Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: cascade.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
                         store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
+                    }),
+                    stencil_ops: None,
+                }),
                 ..Default::default()
             });
+            pass.set_bind_group(0, &self.shadows.bind_group, &[cascade.offset]);
 
-            if present_target == 4 {
-                // Lit mode: use lighting pipeline
-                let lighting_bind = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    layout: &self.lighting_layout,
-                    entries: &[
-                        wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: wgpu::BindingResource::TextureView(&self.gbuffer_albedo),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 1,
-                            resource: wgpu::BindingResource::TextureView(&self.gbuffer_normal),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 2,
-                            resource: wgpu::BindingResource::Sampler(&self.sampler),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 3,
-                            resource: self.lighting_uniform_buffer.as_entire_binding(),
-                        },
-                    ],
-                    label: Some("Lighting BG"),
-                });
-                pass.set_pipeline(&self.lighting_pipeline);
-                pass.set_bind_group(0, &lighting_bind, &[]);
-            } else {
-                // G-buffer debug modes
-                let (pipeline, layout, view) = match present_target {
-                    0 => (
-                        &self.quad_pipeline_float,
-                        &self.quad_layout_float,
-                        &self.gbuffer_albedo,
-                    ),
-                    1 => (
-                        &self.quad_pipeline_float,
-                        &self.quad_layout_float,
-                        &self.gbuffer_normal,
-                    ),
-                    2 => (
-                        &self.quad_pipeline_uint,
-                        &self.quad_layout_uint,
-                        &self.gbuffer_linear_z,
-                    ),
-                    3 => (
-                        &self.quad_pipeline_float,
-                        &self.quad_layout_float,
-                        &self.depth_texture_view,
-                    ),
-                    _ => (
-                        &self.quad_pipeline_float,
-                        &self.quad_layout_float,
-                        &self.gbuffer_albedo,
-                    ),
-                };
+            pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            let frustum = Frustum::from_vp_matrix(&cascade.vp_matrix);
+            let mut color_mode = None;
+            for dc in &self.draw_call_array {
+                let visible = frustum.is_visible(dc.world_sphere, &dc.world_bounds);
+                if dc.proxy_bounds.is_none() || dc.wireframe || !visible {
+                    continue;
+                }
+                if color_mode != Some(dc.object.color_mode) {
+                    color_mode = Some(dc.object.color_mode);
+                    pass.set_pipeline(self.shadows.voxel_pipeline_for(dc.object.color_mode));
+                }
+                pass.set_bind_group(1, &dc.bind_group, &[]);
+                pass.draw_indexed(dc.bounding_shape.indices(), 0, 0..1);
+            }
 
-                let quad_bind = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    layout,
-                    entries: &[
-                        wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: wgpu::BindingResource::TextureView(view),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 1,
-                            resource: wgpu::BindingResource::Sampler(&self.sampler),
-                        },
-                    ],
-                    label: Some("Quad Present BG"),
-                });
-                pass.set_pipeline(pipeline);
-                pass.set_bind_group(0, &quad_bind, &[]);
+            if !self.meshes.is_empty() {
+                pass.set_pipeline(&self.shadows.mesh_pipeline);
+                for mesh in &self.meshes {
+                    pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                    pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                    pass.draw_indexed(0..mesh.index_count, 0, 0..1);
+                }
             }
-            pass.draw(0..3, 0..1);
         }
+    }
 
-        // 3) Optional wireframe bounding box pass
-        if show_bboxes {
-            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Wireframe Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &frame_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load, // Keep existing content
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                ..Default::default()
-            });
-            pass.set_pipeline(&self.wireframe_pipeline);
-            pass.set_bind_group(0, &per_frame_bind_group, &[]);
-            pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            pass.set_index_buffer(self.edge_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+    /// Adds a `dims` object with the unit transform, filling each voxel whose centre,
+    /// normalized to [-1, 1]^3, has `sdf <= 0` with `palette_index`. Returns the new
+    /// object's id, the next one (see `get_object_memory_usage`).
+    pub fn create_voxel_from_sdf(
+        &mut self,
+        dims: [u32; 3],
+        sdf: &dyn Fn([f32; 3]) -> f32,
+        palette_index: u8,
+    ) -> Result<u32, RendererError> {
+        let voxels = self.sample_sdf(dims, &mut |p| Ok(sdf(p)), palette_index)?;
+        self.add_sdf_object(dims, voxels)
+    }
 
-            for dc in &self.draw_call_array {
-                // Create wireframe bind group with per-draw uniforms
-                let wireframe_bg = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    layout: &self.wireframe_bind_group_layout,
-                    entries: &[wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: dc.uniform_buffer.as_entire_binding(),
-                    }],
-                    label: Some("Wireframe BG"),
-                });
-                pass.set_bind_group(1, &wireframe_bg, &[]);
-                pass.draw_indexed(0..CUBE_EDGE_INDICES.len() as u32, 0, 0..1);
+    /// Voxels of a `dims` grid (x fastest, then y, then z) set to `palette_index` where
+    /// `sdf` at the voxel centre in [-1, 1]^3 is <= 0, and empty elsewhere. Each side
+    /// must fit one object texture and the whole grid one buffer, as erosion and
+    /// dilation stage it through one; both are checked before anything is allocated.
+    fn sample_sdf(
+        &self,
+        dims: [u32; 3],
+        sdf: &mut dyn FnMut([f32; 3]) -> Result<f32, RendererError>,
+        palette_index: u8,
+    ) -> Result<Vec<u8>, RendererError> {
+        if dims.contains(&0) {
+            return Err(RendererError::invalid(
+                "dims",
+                "sdf object dims must be non-zero",
+            ));
+        }
+        if palette_index == self.empty_index {
+            return Err(RendererError::invalid(
+                "palette_index",
+                format!("palette index {} is the empty index", palette_index),
+            ));
+        }
+        let max_size = self.max_volume_size();
+        let largest = dims.into_iter().max().unwrap_or(0);
+        if largest > max_size {
+            let name = if self.max_volume_size == 0 {
+                "max_texture_dimension_3d"
+            } else {
+                "max_volume_size"
+            };
+            return Err(RendererError::limit(
+                name,
+                largest,
+                max_size,
+                format!(
+                    "sdf object dims {:?} exceed the volume size limit of {}",
+                    dims, max_size
+                ),
+            ));
+        }
+        let max_voxels = self.device.limits().max_buffer_size;
+        let voxel_count = dims
+            .into_iter()
+            .try_fold(1u64, |count, dim| count.checked_mul(dim as u64))
+            .unwrap_or(u64::MAX);
+        if voxel_count > max_voxels {
+            return Err(RendererError::limit(
+                "max_buffer_size",
+                voxel_count,
+                max_voxels,
+                format!(
+                    "sdf object dims {:?} hold {} voxels, at most {} are supported",
+                    dims, voxel_count, max_voxels
+                ),
+            ));
+        }
+        let [nx, ny, nz] = dims;
+        let centre = |i: u32, n: u32| (i as f32 + 0.5) / n as f32 * 2.0 - 1.0;
+        let mut voxels = Vec::new();
+        usize::try_from(voxel_count)
+            .ok()
+            .and_then(|count| voxels.try_reserve_exact(count).ok())
+            .ok_or_else(|| RendererError::invalid("dims", "out of memory for the sdf object"))?;
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    let inside = sdf([centre(x, nx), centre(y, ny), centre(z, nz)])? <= 0.0;
+                    voxels.push(if inside {
+                        palette_index
+                    } else {
+                        self.empty_index
+                    });
+                }
             }
         }
+        Ok(voxels)
+    }
 
-        self.queue.submit(Some(encoder.finish()));
-        frame.present();
+    fn add_sdf_object(&mut self, dims: [u32; 3], voxels: Vec<u8>) -> Result<u32, RendererError> {
+        let object_id = self.object_pieces.len() as u32;
+        self.sdf_object_count += 1;
+        let id = format!("sdf_{}", self.sdf_object_count);
+        self.append_objects(vec![VoxelObject {
+            id,
+            model_matrix: math::IDENTITY,
+            inv_model_matrix: math::IDENTITY,
+            dims,
+            format: VoxelFormat::U8,
+            color_mode: ColorMode::Palette,
+            voxels,
+            order: 0,
+            quantize: false,
+            parent: None,
+            voxel_size: [1.0; 3],
+            pivot: dims.map(|dim| dim as f32 * 0.5),
+        }])?;
+        Ok(object_id)
+    }
+
+    /// Like `render`, into `texture`, a texture the caller already acquired from this
+    /// renderer's surface with `get_current_texture`, so frame loops that acquire
+    /// first do not acquire twice. Always renders, regardless of `set_max_fps`, and
+    /// presents `texture`. It must match the surface's current size and format.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_to_surface_texture(
+        &mut self,
+        texture: wgpu::SurfaceTexture,
+        vp_matrix: &[f32],
+        view_position: &[f32],
+        present_target: usize,
+        light_dir: &[f32],
+        ambient: f32,
+        show_bboxes: bool,
+    ) -> Result<(), RendererError> {
+        self.frame = FrameSettings::new(
+            vp_matrix,
+            view_position,
+            present_target,
+            light_dir,
+            ambient,
+            show_bboxes,
+        )?;
+        if self.is_paused() {
+            return Ok(());
+        }
+        self.render_frame_to(texture)
+    }
+
+    /// The surface rendered into, for callers acquiring its texture themselves.
+    pub fn surface(&self) -> &wgpu::Surface<'static> {
+        &self.surface
+    }
+
+    /// The current G-buffer targets, for rendering that composites onto them. They are
+    /// replaced on resize and by `set_gbuffer_profile`, so fetch them again each frame.
+    pub fn gbuffer_textures(&self) -> &GBufferTextures {
+        &self.gbuffer
+    }
+
+    /// Bakes a light probe at `position` from `cubemap`, a six-layer float texture; see
+    /// `LightProbe::from_cubemap_texture`.
+    pub async fn light_probe_from_cubemap_texture(
+        &self,
+        cubemap: &wgpu::Texture,
+        position: [f32; 3],
+    ) -> Result<LightProbe, RendererError> {
+        LightProbe::from_cubemap_texture(
+            &self.device,
+            &self.queue,
+            &self.sh_projection,
+            cubemap,
+            position,
+        )
+        .await
+    }
+
+    /// Moves the settings that survive `reinitialize` over from `old`.
+    fn take_settings(&mut self, old: &mut Renderer) -> Result<(), RendererError> {
+        let (width, height) = (old.surface_config.width, old.surface_config.height);
+        if (width, height) != (self.surface_config.width, self.surface_config.height) {
+            self.resize(width, height)?;
+        }
+        self.frame = old.frame;
+        self.paused = old.paused;
+        self.pacer = std::mem::take(&mut old.pacer);
+        self.dynamic_resolution = old.dynamic_resolution.take();
+        self.render_scale = old.render_scale;
+        self.max_render_dimension = old.max_render_dimension;
+        self.fit_render_targets();
+        self.post_submit_callback = old.post_submit_callback.take();
+        self.voxel_rounding = old.voxel_rounding;
+        self.set_voxel_wrap(old.voxel_wrap);
+        self.empty_index = old.empty_index;
+        self.lighting_model = old.lighting_model;
+        self.normal_source = old.normal_source;
+        self.ambient_occlusion = old.ambient_occlusion;
+        self.present_fit = old.present_fit;
+        self.specular_strength = old.specular_strength;
+        self.shininess = old.shininess;
+        self.rim_color = old.rim_color;
+        self.rim_power = old.rim_power;
+        self.rim_intensity = old.rim_intensity;
         Ok(())
     }
 
-    pub fn get_gpu_info(&self) -> JsValue {
-        let gpu_info = SerializableAdapterInfo {
-            name: self.adapter_info.name.clone(),
-            vendor: self.adapter_info.vendor,
-            device: self.adapter_info.device,
-            device_type: format!("{:?}", self.adapter_info.device_type),
-            driver: self.adapter_info.driver_info.clone(),
-            driver_info: self.adapter_info.driver_info.clone(),
-            backend: format!("{:?}", self.adapter_info.backend),
-        };
-        serde_wasm_bindgen::to_value(&gpu_info).unwrap()
+    fn upload_parsed_scene(&mut self, mut scene: Scene) -> Result<(), RendererError> {
+        self.last_scene_hash = 0;
+        scene.resolve_parents()?;
+        scene.quantize_objects()?;
+        // Step 1: Upload objects as 3d textures; fails before anything is replaced
+        let static_uniforms = Renderer::pack_palette(&scene);
+        let draw_call_array = self.create_draw_calls(scene.objects, 0)?;
+        self.dirty = true;
+        self.async_upload = None;
+        self.palette_animation.invalidate();
+        self.palette_transition = None;
+        self.occlusion.invalidate();
+
+        // Step 2: Upload the color palette as a uniform buffer, and the entries past
+        // 256 as a texture
+        self.queue.write_buffer(
+            &self.static_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[static_uniforms]),
+        );
+        self.palette_view = create_palette_view(&self.device, &self.queue, &scene.palette);
+        self.static_bind_group =
+            self.create_static_bind_group(&self.static_uniform_buffer, &self.palette_view);
+
+        self.queue.submit([]);
+
+        log::debug!(
+            "uploaded {} objects with {} palette entries",
+            draw_call_array.len(),
+            scene.palette.len()
+        );
+        self.palette = scene.palette;
+        self.draw_call_array = draw_call_array;
+        self.index_objects();
+        self.update_scene_bounds();
+        Ok(())
+    }
+
+    /// Adds objects to the active scene under the next ids, keeping draw order sorted.
+    fn append_objects(&mut self, objects: Vec<VoxelObject>) -> Result<(), RendererError> {
+        let first_id = self.object_pieces.len() as u32;
+        let draw_call_array = self.create_draw_calls(objects, first_id)?;
+        self.dirty = true;
+        self.last_scene_hash = 0;
+        self.queue.submit([]);
+
+        self.draw_call_array.extend(draw_call_array);
+        // Stable, so equal orders keep upload order and pieces stay adjacent
+        self.draw_call_array.sort_by_key(|dc| dc.order);
+        self.index_objects();
+        self.update_scene_bounds();
+        Ok(())
+    }
+
+    /// Rebuilds `object_pieces` from `draw_call_array`.
+    fn index_objects(&mut self) {
+        self.object_pieces.clear();
+        for (index, dc) in self.draw_call_array.iter().enumerate() {
+            let id = dc.object_id as usize;
+            if id >= self.object_pieces.len() {
+                self.object_pieces.resize(id + 1, 0..0);
+            }
+            let pieces = &mut self.object_pieces[id];
+            if pieces.end == 0 {
+                *pieces = index..index + 1;
+            } else {
+                debug_assert_eq!(pieces.end, index, "pieces of object {} are apart", id);
+                pieces.end = index + 1;
+            }
+        }
+    }
+
+    /// Draw calls of object `id`.
+    fn object_pieces(&self, id: u32) -> Result<Range<usize>, RendererError> {
+        self.object_pieces
+            .get(id as usize)
+            .cloned()
+            .ok_or_else(|| RendererError::no_object(id))
+    }
+
+    /// The draw call of object `id` for `operation`, which needs the object's whole
+    /// grid in one texture and so fails for split objects.
+    fn whole_object(&self, id: u32, operation: &str) -> Result<usize, RendererError> {
+        let pieces = self.object_pieces(id)?;
+        if pieces.len() > 1 {
+            return Err(RendererError::invalid_object(
+                id,
+                None,
+                format!(
+                    "{} does not support split objects, object {} is in {} pieces",
+                    operation,
+                    id,
+                    pieces.len()
+                ),
+            ));
+        }
+        Ok(pieces.start)
+    }
+
+    /// The objects of the active scene by id, with split objects joined again.
+    fn scene_objects(&self) -> Vec<VoxelObject> {
+        self.object_pieces
+            .iter()
+            .map(|pieces| {
+                let pieces = &self.draw_call_array[pieces.clone()];
+                match pieces {
+                    [dc] => dc.object.clone(),
+                    _ => VoxelObject::join(
+                        pieces[0].object_dims,
+                        pieces.iter().map(|dc| (dc.piece_origin, &dc.object)),
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// The first 256 palette entries; `create_palette_view` holds the rest.
+    fn pack_palette(scene: &Scene) -> StaticUniforms {
+        let mut color_palette: [u32; 256] = [0; 256];
+        if scene.palette.len() > MAX_PALETTE_ENTRIES {
+            log::warn!(
+                "the palette has {} entries; only the first {} are used",
+                scene.palette.len(),
+                MAX_PALETTE_ENTRIES
+            );
+        }
+        for (packed, color) in color_palette.iter_mut().zip(&scene.palette) {
+            *packed = utils::pack_rgba(color);
+        }
+        StaticUniforms { color_palette }
+    }
+
+    fn create_static_bind_group(
+        &self,
+        static_uniform_buffer: &wgpu::Buffer,
+        palette_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Static Bind Group"),
+            layout: &self.static_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: static_uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.clip_planes_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(palette_view),
+                },
+            ],
+        })
+    }
+
+    /// Rejects objects that cannot be uploaded as they are.
+    fn check_object_dims(&self, objects: &[VoxelObject]) -> Result<(), RendererError> {
+        for obj in objects {
+            if obj.dims.contains(&0) {
+                return Err(RendererError::invalid_object(
+                    &obj.id,
+                    Some("dims"),
+                    format!("object '{}' has an empty dimension: {:?}", obj.id, obj.dims),
+                ));
+            }
+            let (Some(voxel_count), Some(voxel_bytes)) = (obj.voxel_count(), obj.voxel_bytes())
+            else {
+                return Err(RendererError::invalid_object(
+                    &obj.id,
+                    Some("dims"),
+                    format!("object '{}' has too many voxels: {:?}", obj.id, obj.dims),
+                ));
+            };
+            if obj.voxels.len() != voxel_bytes {
+                let message = match obj.color_mode {
+                    ColorMode::Palette => format!(
+                        "object '{}' has {} voxels, expected {} for dims {:?}",
+                        obj.id,
+                        obj.voxels.len() / obj.bytes_per_voxel(),
+                        voxel_count,
+                        obj.dims
+                    ),
+                    ColorMode::Rgba => format!(
+                        "object '{}' has {} rgba bytes, expected {} (4 per voxel) for dims {:?}",
+                        obj.id,
+                        obj.voxels.len(),
+                        voxel_bytes,
+                        obj.dims
+                    ),
+                };
+                return Err(RendererError::invalid_object(
+                    &obj.id,
+                    Some("voxels"),
+                    message,
+                ));
+            }
+            if let Some(parent) = &obj.parent {
+                return Err(RendererError::invalid_object(
+                    &obj.id,
+                    Some("parent"),
+                    format!(
+                        "object '{}' has parent '{}', which only whole-scene uploads resolve",
+                        obj.id, parent
+                    ),
+                ));
+            }
+            if obj.quantize {
+                return Err(RendererError::invalid_object(
+                    &obj.id,
+                    Some("quantize"),
+                    format!(
+                        "object '{}' is quantized, which only whole-scene uploads do",
+                        obj.id
+                    ),
+                ));
+            }
+        }
+        Ok(())
     }
 
-    pub fn upload_scene(&mut self, scene: JsValue) -> Result<(), JsValue> {
-        let scene: Scene = serde_wasm_bindgen::from_value(scene)?;
-
-        // Step 1: Upload the color palette as a uniform buffer
-        let mut color_palette: [u32; 256] = [0; 256];
-        for (i, color) in scene.palette.iter().enumerate() {
-            color_palette[i] = utils::pack_rgba(color);
+    /// Largest side of the 3D textures objects are uploaded in: `set_max_volume_size`,
+    /// within the device limit.
+    fn max_volume_size(&self) -> u32 {
+        let limit = self.device.limits().max_texture_dimension_3d;
+        match self.max_volume_size {
+            0 => limit,
+            size => size.min(limit),
         }
-        let static_uniforms = StaticUniforms { color_palette };
-        self.queue.write_buffer(
-            &self.static_uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[static_uniforms]),
-        );
+    }
 
-        // Step 2: Upload objects as 3d textures
-        let mut draw_call_array = Vec::with_capacity(scene.objects.len());
-        for obj in &scene.objects {
+    /// Uploads every object as 3D textures with their per-draw resources, splitting
+    /// those larger than `max_volume_size` into pieces that fit, in draw order. The
+    /// objects get ids from `first_id` on in the order given, whatever their `order`.
+    fn create_draw_calls(
+        &self,
+        objects: Vec<VoxelObject>,
+        first_id: u32,
+    ) -> Result<Vec<DrawCallData>, RendererError> {
+        self.check_object_dims(&objects)?;
+        let mut objects: Vec<(VoxelObject, u32)> = objects.into_iter().zip(first_id..).collect();
+        // Stable, so equal orders keep upload order
+        objects.sort_by_key(|(obj, _)| obj.order);
+        let max_size = self.max_volume_size();
+        let pieces: Vec<_> = objects
+            .into_iter()
+            .flat_map(|(obj, object_id)| {
+                let object_dims = obj.dims;
+                obj.split(max_size)
+                    .into_iter()
+                    .map(move |(origin, piece)| (object_id, object_dims, origin, piece))
+            })
+            .collect();
+        let mut draw_call_array = Vec::with_capacity(pieces.len());
+        for (object_id, object_dims, piece_origin, obj) in pieces {
             let [nx, ny, nz] = obj.dims;
+            // Only scanned when the warning would be written
+            let empty = self.empty_index as u16;
+            if log::enabled(log::Level::Warn) && !obj.filled(empty).any(|filled| filled) {
+                log::warn!("object '{}' has no filled voxels and draws nothing", obj.id);
+            }
             // create the texture
             let texture = self.device.create_texture(&wgpu::TextureDescriptor {
                 label: Some(&format!("object_{}", obj.id)),
@@ -970,7 +5771,7 @@ impl Renderer {
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D3,
-                format: wgpu::TextureFormat::R8Uint,
+                format: obj.texture_format(),
                 usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
                 view_formats: &[],
             });
@@ -985,7 +5786,7 @@ impl Renderer {
                 bytemuck::cast_slice(obj.voxels.as_slice()),
                 wgpu::TexelCopyBufferLayout {
                     offset: 0,
-                    bytes_per_row: Some(nx),
+                    bytes_per_row: Some(nx * obj.bytes_per_voxel() as u32),
                     rows_per_image: Some(ny),
                 },
                 wgpu::Extent3d {
@@ -995,49 +5796,990 @@ impl Renderer {
                 },
             );
             let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-            let sampler = self
-                .device
-                .create_sampler(&wgpu::SamplerDescriptor::default());
 
+            let proxy_bounds = self.voxel_wrap.proxy_bounds(&obj, self.empty_index);
+            let uniforms = PerDrawUniforms::new(&obj, proxy_bounds, WHITE_TINT);
             let uniform_buffer =
                 self.device
                     .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                         label: Some("Per Draw Uniform Buffer"),
-                        contents: bytemuck::cast_slice(&[PerDrawUniforms {
-                            model_matrix: obj.model_matrix,
-                            inverse_model_matrix: obj.inv_model_matrix,
-                        }]),
+                        contents: bytemuck::cast_slice(&[uniforms]),
                         usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
                     });
 
-            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Per Draw Call Bind Group"),
-                layout: &self.per_draw_bind_group_layout,
+            let bind_group =
+                self.create_draw_bind_group(obj.color_mode, &texture_view, &uniform_buffer, None);
+
+            draw_call_array.push(DrawCallData {
+                bind_group,
+                texture,
+                back_texture: None,
+                ao_texture: None,
+                svo_buffer: None,
+                pending_voxels: None,
+                texture_view,
+                uniform_buffer,
+                world_bounds: Aabb::from_model_matrix(&obj.model_matrix),
+                proxy_bounds,
+                world_sphere: Sphere::from_model_matrix(&obj.model_matrix),
+                order: obj.order,
+                bounding_shape: BoundingShape::Cube,
+                tint: WHITE_TINT,
+                scissor: None,
+                wireframe: false,
+                slice: None,
+                object: obj,
+                object_id,
+                object_dims,
+                piece_origin,
+            });
+        }
+        Ok(draw_call_array)
+    }
+
+    /// Draws the objects inside the frustum of `camera` and all meshes into the
+    /// albedo, normal, linear-Z, material and depth `targets`, of `size` pixels.
+    fn encode_gbuffer_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        per_frame_bind_group: &wgpu::BindGroup,
+        camera: &GBufferCamera,
+        [albedo, normal, linear_z, material, depth]: [&wgpu::TextureView; 5],
+        size: (u32, u32),
+        occlusion_culled: bool,
+    ) {
+        let attachment = |view| {
+            Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })
+        };
+        let attachments = [albedo, normal, linear_z, material].map(attachment);
+        let (attachment_count, depth_store) = match self.gbuffer_profile {
+            GBufferProfile::Full => (4, wgpu::StoreOp::Store),
+            GBufferProfile::AlbedoOnly => (1, wgpu::StoreOp::Discard),
+        };
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("GBuffer Pass"),
+            color_attachments: &attachments[..attachment_count],
+            depth_stencil_attachment: self.depth_enabled.then_some(
+                wgpu::RenderPassDepthStencilAttachment {
+                    view: depth,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        // Kept for `capture_depth`
+                        store: depth_store,
+                    }),
+                    // Marked by the highlight region for the lighting pass
+                    stencil_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                },
+            ),
+            timestamp_writes: self.pass_timestamp_writes(timestamps::GBUFFER),
+            ..Default::default()
+        });
+        pass.set_bind_group(0, &self.static_bind_group, &[]);
+        pass.set_bind_group(1, per_frame_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        // Draw order is kept, switching pipelines where the color mode changes or the
+        // near plane starts or stops cutting the proxy, and scissors where the
+        // rectangle changes. Proxies the near plane may cut draw their back faces,
+        // which it leaves whole while the camera is inside.
+        let full = [0, 0, size.0, size.1];
+        let mut pipeline = None;
+        let mut scissor = full;
+        for (i, dc) in self.visible_draw_calls(&camera.frustum).enumerate() {
+            let rect = dc.scissor.map_or(full, |rect| clamp_scissor(rect, size));
+            if rect[2] == 0 || rect[3] == 0 {
+                continue;
+            }
+            if rect != scissor {
+                scissor = rect;
+                pass.set_scissor_rect(rect[0], rect[1], rect[2], rect[3]);
+            }
+            let key = (dc.object.color_mode, camera.is_near(&dc.world_bounds));
+            if pipeline != Some(key) {
+                pipeline = Some(key);
+                pass.set_pipeline(self.voxel_pipeline(key.0, key.1));
+            }
+            pass.set_bind_group(2, &dc.bind_group, &[]);
+            if occlusion_culled {
+                let offset = i as u64 * occlusion::DRAW_ARGS_SIZE;
+                pass.draw_indexed_indirect(&self.occlusion.draws_buffer, offset);
+            } else {
+                pass.draw_indexed(dc.bounding_shape.indices(), 0, 0..1);
+            }
+        }
+
+        let highlight = self.highlight_active();
+        if scissor != full && (highlight || !self.meshes.is_empty()) {
+            pass.set_scissor_rect(0, 0, size.0, size.1);
+        }
+        if !self.meshes.is_empty() {
+            pass.set_pipeline(&self.mesh_pipeline);
+            for mesh in &self.meshes {
+                pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(0..mesh.index_count, 0, 0..1);
+            }
+        }
+        // After every surface, as it tests against their depth
+        if highlight {
+            self.highlight.encode(&mut pass);
+        }
+    }
+
+    /// `RenderPath::Compute` counterpart of `encode_gbuffer_pass` for the main view.
+    fn encode_compute_gbuffer_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        vp_matrix: &[f32; 16],
+        camera_position: [f32; 3],
+    ) {
+        let frustum = Frustum::from_vp_matrix(vp_matrix);
+        let svo_objects: Vec<_> = self
+            .visible_draw_calls(&frustum)
+            .filter_map(|dc| {
+                let record = SvoObject {
+                    model_matrix: dc.object.model_matrix,
+                    inverse_model_matrix: dc.object.inv_model_matrix,
+                    dims: dc.object.dims,
+                    root_size: svo::root_size(dc.object.dims),
+                    tint: dc.tint,
+                };
+                Some((record, dc.svo_buffer.as_ref()?))
+            })
+            .collect();
+        let objects: Vec<_> = self
+            .visible_draw_calls(&frustum)
+            .filter(|dc| dc.svo_buffer.is_none())
+            .map(|dc| {
+                let record = RaymarchObject {
+                    model_matrix: dc.object.model_matrix,
+                    inverse_model_matrix: dc.object.inv_model_matrix,
+                    dims: dc.object.dims,
+                    _padding: 0,
+                    tint: dc.tint,
+                    voxel_size: dc.object.voxel_proportions(),
+                    _padding2: 0.0,
+                };
+                (record, &dc.texture_view)
+            })
+            .collect();
+        let frame = RaymarchFrame {
+            // A singular matrix shows nothing, as in the raster path
+            inv_vp_matrix: math::invert(vp_matrix).unwrap_or_default(),
+            camera_position,
+            voxel_rounding: self.voxel_rounding,
+            empty_index: self.empty_index as u32,
+            objects: &objects,
+            svo_objects: &svo_objects,
+            static_uniform_buffer: &self.static_uniform_buffer,
+            palette_view: &self.palette_view,
+            clip_planes_buffer: &self.clip_planes_buffer,
+        };
+        self.raymarch.encode(
+            &self.device,
+            &self.queue,
+            encoder,
+            &frame,
+            [
+                &self.gbuffer.albedo.texture,
+                &self.gbuffer.normal.texture,
+                &self.gbuffer.linear_z.texture,
+                &self.gbuffer.material.texture,
+            ],
+            self.gbuffer_profile == GBufferProfile::Full,
+        );
+    }
+
+    /// Whether the compute raymarch can draw the objects in `frustum` as the raster
+    /// path would; see `RenderPath::Compute`.
+    fn compute_can_draw(&self, frustum: &Frustum) -> bool {
+        let mut marched = 0;
+        for dc in self.visible_draw_calls(frustum) {
+            if dc.object.color_mode != ColorMode::Palette {
+                log::warn_once!("the compute render path draws rgba objects by the raster path");
+                return false;
+            }
+            marched += dc.svo_buffer.is_none() as usize;
+        }
+        if marched > MAX_RAYMARCH_OBJECTS {
+            log::warn_once!(
+                "the compute render path draws more than {} objects by the raster path",
+                MAX_RAYMARCH_OBJECTS
+            );
+            return false;
+        }
+        if let Some(dc) = self
+            .draw_call_array
+            .iter()
+            .find(|dc| dc.scissor.is_some() || dc.slice.is_some())
+        {
+            log::warn_once!(
+                "the compute render path draws object {} by the raster path, for its {}",
+                dc.object_id,
+                if dc.scissor.is_some() {
+                    "scissor"
+                } else {
+                    "slice view"
+                }
+            );
+            return false;
+        }
+        true
+    }
+
+    /// Whether this frame's G-buffer comes from the compute raymarch.
+    fn compute_active(&self) -> bool {
+        self.render_path == RenderPath::Compute && !self.compute_fallback
+    }
+
+    /// Whether the depth buffer holds this frame's scene depth.
+    fn has_depth(&self) -> bool {
+        self.depth_enabled && !self.compute_active() && !self.checkerboard_active()
+    }
+
+    fn occlusion_active(&self) -> bool {
+        self.occlusion_culling
+            && !self.compute_active()
+            && self.gbuffer_profile == GBufferProfile::Full
+    }
+
+    /// Objects inside `frustum`, in draw order.
+    /// Solid objects in `frustum`, in draw order; wire objects are left to the
+    /// wireframe pass.
+    fn visible_draw_calls<'a>(
+        &'a self,
+        frustum: &'a Frustum,
+    ) -> impl Iterator<Item = &'a DrawCallData> + 'a {
+        self.draw_call_array
+            .iter()
+            .filter(|dc| !dc.wireframe && frustum.is_visible(dc.world_sphere, &dc.world_bounds))
+    }
+
+    fn checkerboard_active(&self) -> bool {
+        self.checkerboard_enabled
+            && !self.compute_active()
+            && self.gbuffer_profile == GBufferProfile::Full
+    }
+
+    /// Whether the G-buffer pass marks the highlight region for the lighting pass.
+    fn highlight_active(&self) -> bool {
+        self.highlight.is_active()
+            && self.depth_enabled
+            && !self.compute_active()
+            && self.gbuffer_profile == GBufferProfile::Full
+            && !self.checkerboard_active()
+    }
+
+    /// Full-screen quad: lighting, or the G-buffer view chosen by `present_target`.
+    fn encode_present_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        present_target: usize,
+        output: &wgpu::TextureView,
+    ) {
+        // Only albedo is rendered in the AlbedoOnly profile
+        let present_target = match self.gbuffer_profile {
+            GBufferProfile::Full => present_target,
+            GBufferProfile::AlbedoOnly => 0,
+        };
+        // Without a depth buffer the depth view falls back to lit
+        let present_target = match present_target {
+            3 if !self.has_depth() => 4,
+            target => target,
+        };
+        if present_target == 4 {
+            // After any checkerboard reconstruction, so every pixel has its depth
+            self.light_culling
+                .encode(&self.device, encoder, &self.gbuffer.linear_z.view);
+        }
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Present Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: self.pass_timestamp_writes(timestamps::PRESENT),
+            ..Default::default()
+        });
+
+        if present_target == 4 {
+            // Lit mode: use lighting pipeline
+            let lighting_bind = self.create_lighting_bind_group(
+                [
+                    &self.gbuffer.albedo.view,
+                    &self.gbuffer.normal.view,
+                    &self.gbuffer.linear_z.view,
+                    &self.gbuffer.material.view,
+                    &self.stencil_view,
+                ],
+                &self.lighting_uniform_buffer,
+                true,
+            );
+            pass.set_pipeline(&self.lighting_pipeline);
+            pass.set_bind_group(0, &lighting_bind, &[]);
+        } else {
+            // G-buffer debug modes. Colour targets are sampled, so a present fit that
+            // scales them filters linearly for albedo and keeps normals and the
+            // checkerboard status crisp; linear-Z and depth are loaded texel by texel.
+            let sampled = (&self.quad_pipeline_sampled, &self.quad_layout_sampled);
+            let ((pipeline, layout), view, sampler) = match present_target {
+                1 => (sampled, &self.gbuffer.normal.view, &self.nearest_sampler),
+                2 => (
+                    (&self.quad_pipeline_uint, &self.quad_layout_uint),
+                    &self.gbuffer.linear_z.view,
+                    &self.nearest_sampler,
+                ),
+                3 => (
+                    (&self.quad_pipeline_float, &self.quad_layout_float),
+                    &self.depth_texture_view,
+                    &self.nearest_sampler,
+                ),
+                5 if self.checkerboard_active() => (
+                    sampled,
+                    self.checkerboard
+                        .status_view()
+                        .unwrap_or(&self.gbuffer.albedo.view),
+                    &self.nearest_sampler,
+                ),
+                // Albedo, also for targets that do not apply
+                _ => (sampled, &self.gbuffer.albedo.view, &self.linear_sampler),
+            };
+
+            let quad_bind =
+                self.create_quad_bind_group(layout, view, sampler, &self.present_uniform_buffer);
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &quad_bind, &[]);
+        }
+        pass.draw(0..3, 0..1);
+    }
+
+    /// Start and end timestamp writes at queries `first` and `first + 1`, while pass
+    /// timestamps are measured.
+    fn pass_timestamp_writes(&self, first: u32) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        self.timestamps.as_ref()?.render_pass_writes(first)
+    }
+
+    fn mark_timestamp(&self, encoder: &mut wgpu::CommandEncoder, index: u32) {
+        if let Some(timestamps) = &self.timestamps {
+            timestamps.mark(encoder, index);
+        }
+    }
+
+    /// Copies `input`, a post target, to `output`, for passes that cannot write the
+    /// target they read.
+    fn encode_blit(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+        label: &str,
+    ) {
+        let blit_bind = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.quad_layout_float,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.nearest_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.identity_present_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some(label),
+        });
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            ..Default::default()
+        });
+        pass.set_pipeline(&self.quad_pipeline_float);
+        pass.set_bind_group(0, &blit_bind, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    /// Bounding box edges of every object, drawn over `output`.
+    fn encode_wireframe_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        per_frame_bind_group: &wgpu::BindGroup,
+        output: &wgpu::TextureView,
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Wireframe Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load, // Keep existing content
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            ..Default::default()
+        });
+        pass.set_pipeline(&self.wireframe_pipeline);
+        pass.set_bind_group(0, per_frame_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_index_buffer(self.edge_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+        // Every object for the `show_bboxes` overlay, else the wire objects
+        let show_bboxes = self.frame.show_bboxes;
+        for dc in self
+            .draw_call_array
+            .iter()
+            .filter(|dc| show_bboxes || dc.wireframe)
+        {
+            // Create wireframe bind group with per-draw uniforms
+            let wireframe_bg = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.wireframe_bind_group_layout,
                 entries: &[
                     wgpu::BindGroupEntry {
                         binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&texture_view),
+                        resource: dc.uniform_buffer.as_entire_binding(),
                     },
                     wgpu::BindGroupEntry {
                         binding: 1,
-                        resource: uniform_buffer.as_entire_binding(),
+                        resource: self.clip_planes_buffer.as_entire_binding(),
                     },
                 ],
+                label: Some("Wireframe BG"),
             });
+            pass.set_bind_group(1, &wireframe_bg, &[]);
+            pass.draw_indexed(dc.bounding_shape.edges(), 0, 0..1);
+        }
+    }
 
-            draw_call_array.push(DrawCallData {
-                bind_group,
-                texture,
-                texture_view,
-                sampler,
-                uniform_buffer,
-            });
+    fn resource_view<'a>(
+        &'a self,
+        resource: Resource,
+        frame_view: &'a wgpu::TextureView,
+    ) -> &'a wgpu::TextureView {
+        match resource {
+            Resource::GBufferAlbedo => &self.gbuffer.albedo.view,
+            Resource::GBufferNormal => &self.gbuffer.normal.view,
+            Resource::GBufferLinearZ => &self.gbuffer.linear_z.view,
+            Resource::GBufferMaterial => &self.gbuffer.material.view,
+            Resource::Depth => &self.depth_texture_view,
+            Resource::PostA => &self.post_targets[0],
+            Resource::PostB => &self.post_targets[1],
+            Resource::Frame => frame_view,
         }
+    }
 
-        self.queue.submit([]);
+    fn create_quad_bind_group(
+        &self,
+        layout: &wgpu::BindGroupLayout,
+        view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+        present_uniforms: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: present_uniforms.as_entire_binding(),
+                },
+            ],
+            label: Some("Quad Present BG"),
+        })
+    }
 
-        self.draw_call_array = draw_call_array;
+    /// The draw call of object `id`, if it can take a neighbour AO bake of `radius`.
+    fn check_ao_bake(&self, id: u32, radius: u32) -> Result<usize, RendererError> {
+        if radius > MAX_NEIGHBOUR_AO_RADIUS {
+            return Err(RendererError::limit(
+                "ao_radius",
+                radius,
+                MAX_NEIGHBOUR_AO_RADIUS,
+                format!(
+                    "AO radius {} exceeds the maximum of {}",
+                    radius, MAX_NEIGHBOUR_AO_RADIUS
+                ),
+            ));
+        }
+        let index = self.whole_object(id, "neighbour AO")?;
+        self.draw_call_array[index].require_u8_voxels(id, "neighbour AO")?;
+        Ok(index)
+    }
 
-        Ok(())
+    /// Per-pixel view ray angle for edge antialiasing at a target `height` pixels
+    /// tall, or 0 when it is disabled.
+    fn edge_aa_pixel_angle(&self, vp_matrix: &[f32; 16], height: u32) -> f32 {
+        if self.edge_aa.is_none() || height == 0 {
+            return 0.0;
+        }
+        let Some(inv_vp_matrix) = math::invert(vp_matrix) else {
+            return 0.0;
+        };
+        let ray = |y: f32| {
+            let near = math::project_point(&inv_vp_matrix, [0.0, y, 0.0]);
+            let far = math::project_point(&inv_vp_matrix, [0.0, y, 1.0]);
+            math::normalize([far[0] - near[0], far[1] - near[1], far[2] - near[2]])
+        };
+        // Chord length of the unit rays through two pixels at the screen centre
+        match (ray(0.0), ray(2.0 / height as f32)) {
+            (Some(a), Some(b)) => (0..3).map(|i| (a[i] - b[i]).powi(2)).sum::<f32>().sqrt(),
+            _ => 0.0,
+        }
+    }
+
+    /// Lighting uniforms for a view, using the light from the current frame settings.
+    fn lighting_uniforms(
+        &self,
+        vp_matrix: &[f32; 16],
+        camera_position: [f32; 3],
+    ) -> LightingUniforms {
+        let inv_vp_matrix = math::invert(vp_matrix).unwrap_or(math::IDENTITY);
+        // Near-plane points either side of the screen centre differ along the camera axes
+        let axis = |from: [f32; 3], to: [f32; 3], fallback| {
+            let from = math::project_point(&inv_vp_matrix, from);
+            let to = math::project_point(&inv_vp_matrix, to);
+            math::normalize([to[0] - from[0], to[1] - from[1], to[2] - from[2]]).unwrap_or(fallback)
+        };
+        let view_right = axis([-1.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        let view_up = axis([0.0, -1.0, 0.0], [0.0, 1.0, 0.0], [0.0, 1.0, 0.0]);
+        LightingUniforms {
+            light_dir: self.frame.light_dir,
+            ambient: self.frame.ambient,
+            inv_vp_matrix,
+            specular_strength: self.specular_strength,
+            shininess: self.shininess,
+            normal_source: self.normal_source as u32,
+            ambient_occlusion: self.ambient_occlusion as u32,
+            view_right,
+            lighting_model: match self.lighting_model {
+                LightingModel::Matcap => 1,
+                LightingModel::Pbr => 2,
+                _ => 0,
+            },
+            view_up,
+            edge_aa: self.edge_aa.map_or(0, |quality| quality as u32 + 1),
+            rim_color: self.rim_color,
+            rim_power: self.rim_power,
+            rim_intensity: self.rim_intensity,
+            _padding2: [0.0; 3],
+            camera_position,
+            _padding3: 0.0,
+            highlight_color: if self.highlight_active() {
+                self.highlight.color
+            } else {
+                [0.0; 4]
+            },
+        }
+    }
+
+    /// Lighting inputs over the G-buffer views: albedo, normal, linear-Z, material and
+    /// the stencil plane.
+    fn create_lighting_bind_group(
+        &self,
+        [albedo, normal, linear_z, material, stencil]: [&wgpu::TextureView; 5],
+        uniforms: &wgpu::Buffer,
+        main_view: bool,
+    ) -> wgpu::BindGroup {
+        // Shadow cascades, the present fit and the light tiles are set up for the
+        // `render` camera and G-buffer only
+        let (shadow_uniforms, present_uniforms, light_tile_uniforms) = if main_view {
+            (
+                &self.shadows.uniform_buffer,
+                &self.present_uniform_buffer,
+                &self.light_culling.uniform_buffer,
+            )
+        } else {
+            (
+                &self.shadows.off_uniform_buffer,
+                &self.identity_present_buffer,
+                &self.light_culling.off_uniform_buffer,
+            )
+        };
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.lighting_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(albedo),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(normal),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.nearest_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: uniforms.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&self.matcap.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(&self.matcap.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(linear_z),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: self.material_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::TextureView(&self.environment.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: wgpu::BindingResource::Sampler(&self.environment.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 10,
+                    resource: self.environment.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 11,
+                    resource: wgpu::BindingResource::TextureView(&self.shadows.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 12,
+                    resource: wgpu::BindingResource::Sampler(&self.shadows.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 13,
+                    resource: shadow_uniforms.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 14,
+                    resource: self.light_probe_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 15,
+                    resource: present_uniforms.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 16,
+                    resource: self.light_culling.light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 17,
+                    resource: self.light_culling.tile_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 18,
+                    resource: light_tile_uniforms.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 19,
+                    resource: wgpu::BindingResource::TextureView(stencil),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 20,
+                    resource: wgpu::BindingResource::TextureView(material),
+                },
+            ],
+            label: Some("Lighting BG"),
+        })
+    }
+
+    fn create_gbuffer_pipelines(&mut self) {
+        let voxel_pipeline = |layout, shader, cull_mode| {
+            create_voxel_pipeline(
+                &self.device,
+                self.pipeline_cache.as_ref(),
+                layout,
+                shader,
+                self.linear_z_format,
+                self.voxel_filter,
+                self.voxel_wrap,
+                self.gbuffer_profile,
+                self.depth_enabled,
+                cull_mode,
+            )
+        };
+        let (layout, rgba_layout) = (&self.pipeline_layout, &self.rgba_pipeline_layout);
+        self.render_pipeline = voxel_pipeline(layout, &self.shader, wgpu::Face::Back);
+        self.rgba_render_pipeline =
+            voxel_pipeline(rgba_layout, &self.rgba_shader, wgpu::Face::Back);
+        self.inside_render_pipeline = voxel_pipeline(layout, &self.shader, wgpu::Face::Front);
+        self.rgba_inside_render_pipeline =
+            voxel_pipeline(rgba_layout, &self.rgba_shader, wgpu::Face::Front);
+        self.mesh_pipeline = create_mesh_pipeline(
+            &self.device,
+            self.pipeline_cache.as_ref(),
+            &self.mesh_pipeline_layout,
+            &self.mesh_shader,
+            self.linear_z_format,
+            self.gbuffer_profile,
+            self.depth_enabled,
+        );
+        self.highlight.set_targets(
+            &self.device,
+            &self.gbuffer_profile.color_targets(self.linear_z_format),
+            self.pipeline_cache.as_ref(),
+        );
+    }
+
+    /// Without depth a 1×1 placeholder keeps the depth bindings valid.
+    fn create_depth_target(&mut self) {
+        let (width, height) = match self.depth_enabled {
+            true => self.render_size(),
+            false => (1, 1),
+        };
+        self.depth_texture = create_depth_texture(&self.device, width, height);
+        self.depth_texture_view = self
+            .depth_texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.stencil_view = create_stencil_view(&self.depth_texture);
+    }
+
+    /// Surface size times the render scale, at least 1×1.
+    fn render_size(&self) -> (u32, u32) {
+        let mut scale = self.render_scale;
+        if let Some(max) = self.max_render_dimension {
+            let longest = self.surface_config.width.max(self.surface_config.height) as f32;
+            scale = scale.min(max as f32 / longest);
+        }
+        let scaled = |size: u32| ((size as f32 * scale).round() as u32).max(1);
+        (
+            scaled(self.surface_config.width),
+            scaled(self.surface_config.height),
+        )
+    }
+
+    /// Recreates the G-buffer and depth targets if `scale` changes their size.
+    fn apply_render_scale(&mut self, scale: f32) {
+        if scale == self.render_scale {
+            return;
+        }
+        self.dirty = true;
+        self.render_scale = scale;
+        self.fit_render_targets();
+    }
+
+    /// Recreates the G-buffer and depth targets if they are not at the render size.
+    fn fit_render_targets(&mut self) {
+        let size = self.gbuffer.albedo.texture.size();
+        if self.render_size() != (size.width, size.height) {
+            self.create_depth_target();
+            self.create_gbuffer_targets();
+        }
+    }
+
+    /// (Re)creates the G-buffer targets at the render size.
+    fn create_gbuffer_targets(&mut self) {
+        let (width, height) = self.render_size();
+        self.gbuffer = GBufferTextures::new(
+            &self.device,
+            width,
+            height,
+            self.gbuffer_profile,
+            self.linear_z_format,
+        );
+    }
+
+    /// G-buffer pipeline of objects of `color_mode`.
+    fn voxel_pipeline(&self, color_mode: ColorMode, inside: bool) -> &wgpu::RenderPipeline {
+        match (color_mode, inside) {
+            (ColorMode::Palette, false) => &self.render_pipeline,
+            (ColorMode::Rgba, false) => &self.rgba_render_pipeline,
+            (ColorMode::Palette, true) => &self.inside_render_pipeline,
+            (ColorMode::Rgba, true) => &self.rgba_inside_render_pipeline,
+        }
+    }
+
+    fn create_draw_bind_group(
+        &self,
+        color_mode: ColorMode,
+        texture_view: &wgpu::TextureView,
+        uniform_buffer: &wgpu::Buffer,
+        ao_texture: Option<&wgpu::Texture>,
+    ) -> wgpu::BindGroup {
+        let ao_view = ao_texture.map(|texture| texture.create_view(&Default::default()));
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Per Draw Call Bind Group"),
+            layout: match color_mode {
+                ColorMode::Palette => &self.per_draw_bind_group_layout,
+                ColorMode::Rgba => &self.rgba_per_draw_bind_group_layout,
+            },
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        ao_view.as_ref().unwrap_or(&self.unoccluded_ao_view),
+                    ),
+                },
+            ],
+        })
+    }
+
+    /// Stores `ao_texture` as object `id`'s baked AO and binds it for the G-buffer.
+    fn attach_ao_texture(&mut self, id: usize, ao_texture: wgpu::Texture) {
+        let dc = &self.draw_call_array[id];
+        let bind_group = self.create_draw_bind_group(
+            dc.object.color_mode,
+            &dc.texture_view,
+            &dc.uniform_buffer,
+            Some(&ao_texture),
+        );
+        let dc = &mut self.draw_call_array[id];
+        dc.ao_texture = Some(ao_texture);
+        dc.bind_group = bind_group;
+        self.dirty = true;
+        self.last_scene_hash = 0;
+    }
+
+    /// Runs `op` on object `id`'s voxel texture, writing into its back texture and
+    /// then swapping the two.
+    fn apply_morphology(
+        &mut self,
+        id: u32,
+        op: MorphologyOp,
+    ) -> Result<js_sys::Promise, RendererError> {
+        let index = self.whole_object(id, "erosion and dilation")?;
+        let dc = &self.draw_call_array[index];
+        dc.require_u8_voxels(id, "erosion and dilation")?;
+
+        let back_texture = match &dc.back_texture {
+            Some(texture) => texture.clone(),
+            None => self.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("object_back"),
+                size: dc.texture.size(),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D3,
+                format: wgpu::TextureFormat::R8Uint,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            }),
+        };
+        let readback = self.morphology.apply(
+            &self.device,
+            &self.queue,
+            op,
+            &dc.texture,
+            &back_texture,
+            self.empty_index,
+        );
+
+        let texture_view = back_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = self.create_draw_bind_group(
+            dc.object.color_mode,
+            &texture_view,
+            &dc.uniform_buffer,
+            None,
+        );
+
+        let dc = &mut self.draw_call_array[index];
+        let voxels = Rc::new(Cell::new(None));
+        dc.pending_voxels = Some(voxels.clone());
+        dc.fit_proxy(self.voxel_wrap, self.empty_index);
+        // Eroded surfaces may no longer hide what they did last frame
+        self.occlusion.invalidate();
+        self.last_scene_hash = 0;
+        self.update_batch.write(
+            &self.queue,
+            &dc.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&dc.uniforms()),
+        );
+        dc.ao_texture = None;
+        dc.svo_buffer = None;
+        dc.back_texture = Some(std::mem::replace(&mut dc.texture, back_texture));
+        dc.texture_view = texture_view;
+        dc.bind_group = bind_group;
+        let dims = dc.object.dims;
+        Ok(wasm_bindgen_futures::future_to_promise(async move {
+            voxels.set(Some(morphology::read_result(&readback, dims).await?));
+            Ok(JsValue::UNDEFINED)
+        }))
+    }
+
+    /// Moves voxels read back after erosion or dilation into the CPU copies of their
+    /// objects and fits the proxies to them.
+    fn sync_voxels(&mut self) {
+        for dc in &mut self.draw_call_array {
+            let Some(voxels) = dc.pending_voxels.as_ref().and_then(|cell| cell.take()) else {
+                continue;
+            };
+            dc.pending_voxels = None;
+            dc.object.voxels = voxels;
+            dc.fit_proxy(self.voxel_wrap, self.empty_index);
+            self.update_batch.write(
+                &self.queue,
+                &dc.uniform_buffer,
+                0,
+                bytemuck::bytes_of(&dc.uniforms()),
+            );
+            self.occlusion.invalidate();
+            self.last_scene_hash = 0;
+            self.dirty = true;
+        }
+    }
+
+    /// Exchanges the active scene resources with `slot`.
+    fn swap_active_scene(&mut self, slot: &mut SceneSlot) {
+        self.async_upload = None;
+        self.last_scene_hash = 0;
+        self.palette_animation.invalidate();
+        self.palette_transition = None;
+        self.occlusion.invalidate();
+        std::mem::swap(&mut self.palette, &mut slot.palette);
+        std::mem::swap(
+            &mut self.static_uniform_buffer,
+            &mut slot.static_uniform_buffer,
+        );
+        std::mem::swap(&mut self.static_bind_group, &mut slot.static_bind_group);
+        std::mem::swap(&mut self.palette_view, &mut slot.palette_view);
+        std::mem::swap(&mut self.draw_call_array, &mut slot.draw_call_array);
+        std::mem::swap(&mut self.scene_bounds, &mut slot.scene_bounds);
+        self.index_objects();
     }
 }
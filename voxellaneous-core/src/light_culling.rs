@@ -0,0 +1,255 @@
+//! Point lights for the lit present target, culled per screen tile.
+//!
+//! A compute pass (shaders/light_cull.wgsl) runs once per 16x16 pixel tile of the
+//! G-buffer: it takes the tile's linear-Z range and keeps the lights whose sphere of
+//! influence can reach the tile's slice of the view frustum. The lighting pass then
+//! shades each pixel with its tile's list only. The test is conservative, so a list
+//! may hold lights that light none of its pixels, never the other way round.
+
+use serde::Deserialize;
+
+/// Tile edge in pixels; must match light_cull.wgsl and quad_lighting.wgsl.
+const TILE_SIZE: u32 = 16;
+pub const MAX_POINT_LIGHTS: usize = 1024;
+/// Lights kept per tile; the rest are dropped in index order.
+const MAX_LIGHTS_PER_TILE: u32 = 63;
+/// Words per tile list: the count, then the light indices.
+const TILE_WORDS: u32 = MAX_LIGHTS_PER_TILE + 1;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Deserialize, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PointLight {
+    pub position: [f32; 3],
+    /// Distance at which the light fades out entirely
+    pub radius: f32,
+    pub color: [f32; 3],
+    #[serde(default = "default_intensity")]
+    pub intensity: f32,
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightTileUniforms {
+    inv_vp_matrix: [f32; 16],
+    camera_position: [f32; 3],
+    /// 0 = no point lights; the tile lists are not read
+    light_count: u32,
+    /// G-buffer size in pixels
+    size: [u32; 2],
+    tiles_x: u32,
+    /// Tint lit pixels by the length of their tile's list
+    debug: u32,
+}
+
+pub struct LightCulling {
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+    pub light_buffer: wgpu::Buffer,
+    pub tile_buffer: wgpu::Buffer,
+    /// Bound by the lighting pass of the `render` camera
+    pub uniform_buffer: wgpu::Buffer,
+    /// No point lights, for views the tile lists were not built for
+    pub off_uniform_buffer: wgpu::Buffer,
+    light_count: u32,
+    pub debug: bool,
+    /// Tiles of the last `prepare`
+    tiles: [u32; 2],
+}
+
+impl LightCulling {
+    pub fn new(device: &wgpu::Device, cache: Option<&wgpu::PipelineCache>) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Light Cull Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Light Cull Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/light_cull.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Light Cull Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Light Cull Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache,
+        });
+
+        let light_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Point Light Buffer"),
+            size: (MAX_POINT_LIGHTS * std::mem::size_of::<PointLight>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let uniform_buffer = |label| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: std::mem::size_of::<LightTileUniforms>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        LightCulling {
+            layout,
+            pipeline,
+            light_buffer,
+            tile_buffer: create_tile_buffer(device, 1),
+            uniform_buffer: uniform_buffer("Light Tile Uniform Buffer"),
+            off_uniform_buffer: uniform_buffer("Light Tile Off Uniform Buffer"),
+            light_count: 0,
+            debug: false,
+            tiles: [0, 0],
+        }
+    }
+
+    /// Replaces the point lights.
+    pub fn set_lights(&mut self, queue: &wgpu::Queue, lights: &[PointLight]) -> Result<(), String> {
+        if lights.len() > MAX_POINT_LIGHTS {
+            return Err(format!(
+                "at most {} point lights are supported, got {}",
+                MAX_POINT_LIGHTS,
+                lights.len()
+            ));
+        }
+        let invalid = |light: &&PointLight| !light.radius.is_finite() || light.radius <= 0.0;
+        if let Some(light) = lights.iter().find(invalid) {
+            return Err(format!(
+                "point light radius must be positive, got {}",
+                light.radius
+            ));
+        }
+        if !lights.is_empty() {
+            queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(lights));
+        }
+        self.light_count = lights.len() as u32;
+        Ok(())
+    }
+
+    /// Writes the uniforms for a `size` G-buffer seen through `inv_vp_matrix`, growing
+    /// the tile lists to fit. Call before creating the frame's lighting bind group.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        inv_vp_matrix: [f32; 16],
+        camera_position: [f32; 3],
+        size: [u32; 2],
+    ) {
+        self.tiles = size.map(|pixels| pixels.div_ceil(TILE_SIZE));
+        let tile_count = (self.tiles[0] * self.tiles[1]).max(1);
+        let needed = u64::from(tile_count * TILE_WORDS) * 4;
+        if self.tile_buffer.size() < needed {
+            self.tile_buffer = create_tile_buffer(device, tile_count);
+        }
+        let uniforms = LightTileUniforms {
+            inv_vp_matrix,
+            camera_position,
+            light_count: self.light_count,
+            size,
+            tiles_x: self.tiles[0],
+            debug: self.debug as u32,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Builds the tile lists from `linear_z`, the G-buffer of the last `prepare`.
+    /// Nothing to do without lights.
+    pub fn encode(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        linear_z: &wgpu::TextureView,
+    ) {
+        if self.light_count == 0 || self.tiles.contains(&0) {
+            return;
+        }
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Light Cull BG"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(linear_z),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.tile_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Light Cull Pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(self.tiles[0], self.tiles[1], 1);
+    }
+}
+
+fn create_tile_buffer(device: &wgpu::Device, tile_count: u32) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Light Tile Buffer"),
+        size: u64::from(tile_count * TILE_WORDS) * 4,
+        usage: wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    })
+}
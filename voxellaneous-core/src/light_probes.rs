@@ -0,0 +1,250 @@
+//! Light probes: irradiance samples at user positions for the indirect (ambient)
+//! light of the lit present target.
+//!
+//! Each probe holds nine RGB spherical harmonic coefficients in the convention of
+//! environment.rs (cosine-convolved and divided by pi, so a uniform environment of
+//! radiance L evaluates to L). The lighting pass shades each pixel with the probe
+//! nearest to its surface.
+
+use serde::{Deserialize, Serialize};
+use wgpu::util::DeviceExt;
+
+use crate::readback;
+use crate::RendererError;
+
+pub const MAX_LIGHT_PROBES: usize = 8;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LightProbe {
+    pub position: [f32; 3],
+    /// RGB per coefficient, bands 0 to 2
+    pub sh_coeffs: [[f32; 3]; 9],
+}
+
+/// Probes for quad_lighting.wgsl. Vectors are padded to 16 bytes as uniform arrays
+/// require.
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightProbeUniforms {
+    pub positions: [[f32; 4]; MAX_LIGHT_PROBES],
+    /// 9 per probe
+    pub sh_coeffs: [[f32; 4]; 9 * MAX_LIGHT_PROBES],
+    /// 0 = no probes: flat (or environment) ambient
+    pub count: u32,
+    _padding: [u32; 3],
+}
+
+impl LightProbeUniforms {
+    pub fn new(probes: &[LightProbe]) -> Result<Self, String> {
+        if probes.len() > MAX_LIGHT_PROBES {
+            return Err(format!(
+                "at most {} light probes are supported, got {}",
+                MAX_LIGHT_PROBES,
+                probes.len()
+            ));
+        }
+        let mut uniforms = LightProbeUniforms {
+            count: probes.len() as u32,
+            ..bytemuck::Zeroable::zeroed()
+        };
+        for (i, probe) in probes.iter().enumerate() {
+            let [x, y, z] = probe.position;
+            uniforms.positions[i] = [x, y, z, 0.0];
+            for (k, [r, g, b]) in probe.sh_coeffs.into_iter().enumerate() {
+                uniforms.sh_coeffs[i * 9 + k] = [r, g, b, 0.0];
+            }
+        }
+        Ok(uniforms)
+    }
+}
+
+/// Compute pipeline of shaders/sh_project.wgsl.
+#[derive(Clone)]
+pub struct ShProjection {
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl ShProjection {
+    pub fn new(device: &wgpu::Device, cache: Option<&wgpu::PipelineCache>) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("SH Projection Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("SH Projection Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/sh_project.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("SH Projection Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("SH Projection Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache,
+        });
+        ShProjection { layout, pipeline }
+    }
+}
+
+impl LightProbe {
+    /// Bakes a probe at `position` from `cubemap`: a square six-layer float texture
+    /// (e.g. `Rgba16Float`, `TEXTURE_BINDING` usage) holding linear radiance in the
+    /// +X, -X, +Y, -Y, +Z, -Z layer order, integrated on the GPU.
+    pub async fn from_cubemap_texture(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        projection: &ShProjection,
+        cubemap: &wgpu::Texture,
+        position: [f32; 3],
+    ) -> Result<LightProbe, RendererError> {
+        if cubemap.depth_or_array_layers() != 6 || cubemap.width() != cubemap.height() {
+            return Err(RendererError::invalid(
+                "cubemap",
+                format!(
+                    "a cubemap needs 6 square layers, got {}x{}x{}",
+                    cubemap.width(),
+                    cubemap.height(),
+                    cubemap.depth_or_array_layers()
+                ),
+            ));
+        }
+        let size = (6 * 9 * std::mem::size_of::<[f32; 4]>()) as u64;
+        let coeffs = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("SH Projection Buffer"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("SH Projection Staging Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let view = cubemap.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("SH Projection BG"),
+            layout: &projection.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: coeffs.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("SH Projection Encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("SH Projection Pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&projection.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(6, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&coeffs, 0, &staging, 0, size);
+        queue.submit(Some(encoder.finish()));
+
+        readback::map_for_read(&staging).await?;
+        let mut sh_coeffs = [[0.0; 3]; 9];
+        {
+            let data = staging.slice(..).get_mapped_range();
+            let faces: &[[f32; 4]] = bytemuck::cast_slice(&data);
+            for face in faces.chunks_exact(9) {
+                for (sum, coefficient) in sh_coeffs.iter_mut().zip(face) {
+                    for channel in 0..3 {
+                        sum[channel] += coefficient[channel];
+                    }
+                }
+            }
+        }
+        staging.unmap();
+        Ok(LightProbe {
+            position,
+            sh_coeffs,
+        })
+    }
+}
+
+/// Uploads `width` x `width` x 6 RGBA floats (`faces`, layer by layer) as a cubemap
+/// for `LightProbe::from_cubemap_texture`.
+pub fn create_cubemap(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    faces: &[f32],
+    width: u32,
+) -> Result<wgpu::Texture, String> {
+    let expected = width as usize * width as usize * 6 * 4;
+    if width == 0 || faces.len() != expected {
+        return Err(format!(
+            "expected {} floats (RGBA, 6 faces) for a {}x{} cubemap, got {}",
+            expected,
+            width,
+            width,
+            faces.len()
+        ));
+    }
+    let max = device.limits().max_texture_dimension_2d;
+    if width > max {
+        return Err(format!(
+            "cubemap faces are {}x{}, the device supports at most {}x{}",
+            width, width, max, max
+        ));
+    }
+    Ok(device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some("Light Probe Cubemap"),
+            size: wgpu::Extent3d {
+                width,
+                height: width,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        },
+        wgpu::util::TextureDataOrder::LayerMajor,
+        bytemuck::cast_slice(faces),
+    ))
+}
@@ -0,0 +1,137 @@
+//! Matcap (material capture) texture for the matcap lighting model.
+
+use wasm_bindgen::{JsCast, JsValue};
+
+use crate::RendererError;
+
+const MATCAP_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+pub struct Matcap {
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+}
+
+impl Matcap {
+    /// A 1x1 white matcap, so the matcap model shades flat with the albedo until an
+    /// image is set.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let texture = create_matcap_texture(device, 1, 1);
+        queue.write_texture(
+            texture.as_image_copy(),
+            &[255; 4],
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4),
+                rows_per_image: None,
+            },
+            texture.size(),
+        );
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Matcap Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Matcap {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            sampler,
+        }
+    }
+
+    /// Replaces the matcap with `image`, an `ImageBitmap`, `HTMLImageElement`,
+    /// `HTMLCanvasElement`, `OffscreenCanvas` or `ImageData`.
+    pub fn set_image(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: JsValue,
+    ) -> Result<(), RendererError> {
+        let source = external_image_source(image)?;
+        let (width, height) = (source.width(), source.height());
+        if width == 0 || height == 0 {
+            return Err(RendererError::invalid(
+                "image",
+                "matcap image is empty or not loaded yet",
+            ));
+        }
+        let max = device.limits().max_texture_dimension_2d;
+        if width > max || height > max {
+            return Err(RendererError::limit(
+                "max_texture_dimension_2d",
+                width.max(height),
+                max,
+                format!(
+                    "matcap image is {}x{}, the device supports at most {}x{}",
+                    width, height, max, max
+                ),
+            ));
+        }
+
+        let texture = create_matcap_texture(device, width, height);
+        queue.copy_external_image_to_texture(
+            &wgpu::CopyExternalImageSourceInfo {
+                source,
+                origin: wgpu::Origin2d::ZERO,
+                flip_y: false,
+            },
+            wgpu::CopyExternalImageDestInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+                color_space: wgpu::PredefinedColorSpace::Srgb,
+                premultiplied_alpha: false,
+            },
+            texture.size(),
+        );
+        self.view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Ok(())
+    }
+}
+
+fn create_matcap_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Matcap Texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: MATCAP_FORMAT,
+        // External image copies require RENDER_ATTACHMENT on the destination
+        usage: wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    })
+}
+
+fn external_image_source(image: JsValue) -> Result<wgpu::ExternalImageSource, RendererError> {
+    let image = match image.dyn_into::<web_sys::ImageBitmap>() {
+        Ok(bitmap) => return Ok(wgpu::ExternalImageSource::ImageBitmap(bitmap)),
+        Err(image) => image,
+    };
+    let image = match image.dyn_into::<web_sys::HtmlImageElement>() {
+        Ok(element) => return Ok(wgpu::ExternalImageSource::HTMLImageElement(element)),
+        Err(image) => image,
+    };
+    let image = match image.dyn_into::<web_sys::HtmlCanvasElement>() {
+        Ok(canvas) => return Ok(wgpu::ExternalImageSource::HTMLCanvasElement(canvas)),
+        Err(image) => image,
+    };
+    let image = match image.dyn_into::<web_sys::OffscreenCanvas>() {
+        Ok(canvas) => return Ok(wgpu::ExternalImageSource::OffscreenCanvas(canvas)),
+        Err(image) => image,
+    };
+    match image.dyn_into::<web_sys::ImageData>() {
+        Ok(data) => Ok(wgpu::ExternalImageSource::ImageData(data)),
+        Err(_) => Err(RendererError::invalid(
+            "image",
+            "matcap must be an ImageBitmap, HTMLImageElement, HTMLCanvasElement, \
+             OffscreenCanvas or ImageData",
+        )),
+    }
+}
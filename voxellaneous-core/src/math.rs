@@ -0,0 +1,202 @@
+//! Small column-major 4×4 matrix helpers (same layout as gl-matrix on the TS side).
+
+pub type Mat4 = [f32; 16];
+
+#[rustfmt::skip]
+pub const IDENTITY: Mat4 = [
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 1.0, 0.0,
+    0.0, 0.0, 0.0, 1.0,
+];
+
+/// `a * b`: applies `b`, then `a`.
+pub fn multiply(a: &Mat4, b: &Mat4) -> Mat4 {
+    std::array::from_fn(|i| {
+        let (column, row) = (i / 4, i % 4);
+        (0..4).map(|k| a[k * 4 + row] * b[column * 4 + k]).sum()
+    })
+}
+
+pub fn translation(t: [f32; 3]) -> Mat4 {
+    let mut m = IDENTITY;
+    m[12..15].copy_from_slice(&t);
+    m
+}
+
+pub fn scaling(s: [f32; 3]) -> Mat4 {
+    let mut m = IDENTITY;
+    m[0] = s[0];
+    m[5] = s[1];
+    m[10] = s[2];
+    m
+}
+
+/// Inverts a 4×4 matrix, returning `None` when it is singular.
+pub fn invert(m: &Mat4) -> Option<Mat4> {
+    let mut inv = [0.0f32; 16];
+
+    inv[0] = m[5] * m[10] * m[15] - m[5] * m[11] * m[14] - m[9] * m[6] * m[15]
+        + m[9] * m[7] * m[14]
+        + m[13] * m[6] * m[11]
+        - m[13] * m[7] * m[10];
+    inv[4] = -m[4] * m[10] * m[15] + m[4] * m[11] * m[14] + m[8] * m[6] * m[15]
+        - m[8] * m[7] * m[14]
+        - m[12] * m[6] * m[11]
+        + m[12] * m[7] * m[10];
+    inv[8] = m[4] * m[9] * m[15] - m[4] * m[11] * m[13] - m[8] * m[5] * m[15]
+        + m[8] * m[7] * m[13]
+        + m[12] * m[5] * m[11]
+        - m[12] * m[7] * m[9];
+    inv[12] = -m[4] * m[9] * m[14] + m[4] * m[10] * m[13] + m[8] * m[5] * m[14]
+        - m[8] * m[6] * m[13]
+        - m[12] * m[5] * m[10]
+        + m[12] * m[6] * m[9];
+    inv[1] = -m[1] * m[10] * m[15] + m[1] * m[11] * m[14] + m[9] * m[2] * m[15]
+        - m[9] * m[3] * m[14]
+        - m[13] * m[2] * m[11]
+        + m[13] * m[3] * m[10];
+    inv[5] = m[0] * m[10] * m[15] - m[0] * m[11] * m[14] - m[8] * m[2] * m[15]
+        + m[8] * m[3] * m[14]
+        + m[12] * m[2] * m[11]
+        - m[12] * m[3] * m[10];
+    inv[9] = -m[0] * m[9] * m[15] + m[0] * m[11] * m[13] + m[8] * m[1] * m[15]
+        - m[8] * m[3] * m[13]
+        - m[12] * m[1] * m[11]
+        + m[12] * m[3] * m[9];
+    inv[13] = m[0] * m[9] * m[14] - m[0] * m[10] * m[13] - m[8] * m[1] * m[14]
+        + m[8] * m[2] * m[13]
+        + m[12] * m[1] * m[10]
+        - m[12] * m[2] * m[9];
+    inv[2] = m[1] * m[6] * m[15] - m[1] * m[7] * m[14] - m[5] * m[2] * m[15]
+        + m[5] * m[3] * m[14]
+        + m[13] * m[2] * m[7]
+        - m[13] * m[3] * m[6];
+    inv[6] = -m[0] * m[6] * m[15] + m[0] * m[7] * m[14] + m[4] * m[2] * m[15]
+        - m[4] * m[3] * m[14]
+        - m[12] * m[2] * m[7]
+        + m[12] * m[3] * m[6];
+    inv[10] = m[0] * m[5] * m[15] - m[0] * m[7] * m[13] - m[4] * m[1] * m[15]
+        + m[4] * m[3] * m[13]
+        + m[12] * m[1] * m[7]
+        - m[12] * m[3] * m[5];
+    inv[14] = -m[0] * m[5] * m[14] + m[0] * m[6] * m[13] + m[4] * m[1] * m[14]
+        - m[4] * m[2] * m[13]
+        - m[12] * m[1] * m[6]
+        + m[12] * m[2] * m[5];
+    inv[3] = -m[1] * m[6] * m[11] + m[1] * m[7] * m[10] + m[5] * m[2] * m[11]
+        - m[5] * m[3] * m[10]
+        - m[9] * m[2] * m[7]
+        + m[9] * m[3] * m[6];
+    inv[7] = m[0] * m[6] * m[11] - m[0] * m[7] * m[10] - m[4] * m[2] * m[11]
+        + m[4] * m[3] * m[10]
+        + m[8] * m[2] * m[7]
+        - m[8] * m[3] * m[6];
+    inv[11] = -m[0] * m[5] * m[11] + m[0] * m[7] * m[9] + m[4] * m[1] * m[11]
+        - m[4] * m[3] * m[9]
+        - m[8] * m[1] * m[7]
+        + m[8] * m[3] * m[5];
+    inv[15] = m[0] * m[5] * m[10] - m[0] * m[6] * m[9] - m[4] * m[1] * m[10]
+        + m[4] * m[2] * m[9]
+        + m[8] * m[1] * m[6]
+        - m[8] * m[2] * m[5];
+
+    let det = m[0] * inv[0] + m[1] * inv[4] + m[2] * inv[8] + m[3] * inv[12];
+    if det == 0.0 || !det.is_finite() {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    for v in inv.iter_mut() {
+        *v *= inv_det;
+    }
+    Some(inv)
+}
+
+/// Transforms a point (w = 1), without perspective divide.
+pub fn transform_point(m: &Mat4, p: [f32; 3]) -> [f32; 3] {
+    [
+        m[0] * p[0] + m[4] * p[1] + m[8] * p[2] + m[12],
+        m[1] * p[0] + m[5] * p[1] + m[9] * p[2] + m[13],
+        m[2] * p[0] + m[6] * p[1] + m[10] * p[2] + m[14],
+    ]
+}
+
+/// Transforms a point (w = 1) and divides by the resulting w, e.g. to unproject
+/// NDC with an inverse view-projection.
+pub fn project_point(m: &Mat4, p: [f32; 3]) -> [f32; 3] {
+    let w = m[3] * p[0] + m[7] * p[1] + m[11] * p[2] + m[15];
+    transform_point(m, p).map(|c| c / w)
+}
+
+/// Normalizes `v`, or returns `None` when it is near-zero or not finite.
+pub fn normalize(v: [f32; 3]) -> Option<[f32; 3]> {
+    let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    if !len.is_finite() || len < 1e-6 {
+        return None;
+    }
+    Some(v.map(|c| c / len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn assert_near(actual: Mat4, expected: Mat4) {
+        for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+            assert!(
+                (a - e).abs() < 1e-6,
+                "element {} is {}, expected {}",
+                i,
+                a,
+                e
+            );
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn inverts_identity() {
+        assert_eq!(invert(&IDENTITY), Some(IDENTITY));
+    }
+
+    #[wasm_bindgen_test]
+    fn inverts_translation() {
+        let inverse = invert(&translation([1.0, -2.0, 3.0])).expect("invertible");
+        assert_near(inverse, translation([-1.0, 2.0, -3.0]));
+    }
+
+    #[wasm_bindgen_test]
+    fn inverts_non_uniform_scale() {
+        let inverse = invert(&scaling([2.0, 4.0, 0.5])).expect("invertible");
+        assert_near(inverse, scaling([0.5, 0.25, 2.0]));
+    }
+
+    #[wasm_bindgen_test]
+    fn inverts_rotation_and_translation() {
+        // A quarter turn about z (x to y), then a move by (1, 2, 3)
+        #[rustfmt::skip]
+        let m = [
+            0.0, 1.0, 0.0, 0.0,
+            -1.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            1.0, 2.0, 3.0, 1.0,
+        ];
+        // The transposed rotation, then its turn of the negated move: (-2, 1, -3)
+        #[rustfmt::skip]
+        let expected = [
+            0.0, -1.0, 0.0, 0.0,
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            -2.0, 1.0, -3.0, 1.0,
+        ];
+        assert_near(invert(&m).expect("invertible"), expected);
+    }
+
+    #[wasm_bindgen_test]
+    fn singular_matrices_have_no_inverse() {
+        assert_eq!(invert(&scaling([1.0, 0.0, 1.0])), None);
+        assert_eq!(invert(&[0.0; 16]), None);
+    }
+}
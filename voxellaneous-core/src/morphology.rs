@@ -0,0 +1,215 @@
+//! Compute-shader erosion / dilation of R8Uint voxel textures.
+
+use wasm_bindgen::JsValue;
+use wgpu::util::DeviceExt;
+
+use crate::readback;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MorphologyOp {
+    /// A voxel stays filled only if all 6 face neighbours are filled.
+    Erode = 0,
+    /// An empty voxel takes the colour of a filled face neighbour.
+    Dilate = 1,
+}
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct MorphologyParams {
+    dims: [u32; 3],
+    row_words: u32,
+    empty_index: u32,
+    _padding: [u32; 3],
+}
+
+pub struct MorphologyPipelines {
+    layout: wgpu::BindGroupLayout,
+    erode: wgpu::ComputePipeline,
+    dilate: wgpu::ComputePipeline,
+}
+
+impl MorphologyPipelines {
+    pub fn new(device: &wgpu::Device, cache: Option<&wgpu::PipelineCache>) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Morphology Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Morphology Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Morphology Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/morphology.wgsl").into()),
+        });
+        let create_pipeline = |op: MorphologyOp, label: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some("cs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &[("operation", op as u32 as f64)],
+                    ..Default::default()
+                },
+                cache,
+            })
+        };
+
+        MorphologyPipelines {
+            erode: create_pipeline(MorphologyOp::Erode, "Erode Pipeline"),
+            dilate: create_pipeline(MorphologyOp::Dilate, "Dilate Pipeline"),
+            layout,
+        }
+    }
+
+    /// Applies `op` to `src` and writes the result into `dst`, `empty_index` marking
+    /// empty voxels; both must be R8Uint 3D textures of the same size, `dst` with
+    /// `COPY_DST` usage. Also copies the result to the returned buffer, for
+    /// `read_result`.
+    pub fn apply(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        op: MorphologyOp,
+        src: &wgpu::Texture,
+        dst: &wgpu::Texture,
+        empty_index: u8,
+    ) -> wgpu::Buffer {
+        let size = src.size();
+        let row_bytes = size.width.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let row_words = row_bytes / 4;
+
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Morphology Params"),
+            contents: bytemuck::cast_slice(&[MorphologyParams {
+                dims: [size.width, size.height, size.depth_or_array_layers],
+                row_words,
+                empty_index: empty_index as u32,
+                _padding: [0; 3],
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let output_size = row_bytes as u64 * size.height as u64 * size.depth_or_array_layers as u64;
+        let output = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Morphology Output"),
+            size: output_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Morphology Readback"),
+            size: output_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let src_view = src.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Morphology BG"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&src_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: output.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Morphology Encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Morphology Pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(match op {
+                MorphologyOp::Erode => &self.erode,
+                MorphologyOp::Dilate => &self.dilate,
+            });
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(
+                row_words.div_ceil(4),
+                size.height.div_ceil(4),
+                size.depth_or_array_layers.div_ceil(4),
+            );
+        }
+        encoder.copy_buffer_to_texture(
+            wgpu::TexelCopyBufferInfo {
+                buffer: &output,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(row_bytes),
+                    rows_per_image: Some(size.height),
+                },
+            },
+            wgpu::TexelCopyTextureInfo {
+                texture: dst,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            size,
+        );
+        encoder.copy_buffer_to_buffer(&output, 0, &readback, 0, output_size);
+        queue.submit(Some(encoder.finish()));
+        readback
+    }
+}
+
+/// Voxels of `dims`, x-fastest as uploaded, from the buffer `apply` returned.
+pub async fn read_result(readback: &wgpu::Buffer, dims: [u32; 3]) -> Result<Vec<u8>, JsValue> {
+    readback::map_for_read(readback).await?;
+    let row_bytes =
+        dims[0].div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let voxels = readback
+        .slice(..)
+        .get_mapped_range()
+        .chunks_exact(row_bytes as usize)
+        .flat_map(|row| &row[..dims[0] as usize])
+        .copied()
+        .collect();
+    readback.unmap();
+    Ok(voxels)
+}
@@ -0,0 +1,445 @@
+//! GPU occlusion culling: a Hi-Z pyramid of the previous frame's linear-Z, against
+//! which a compute pass tests object bounds and writes the indirect draws of the
+//! raster G-buffer pass.
+
+use std::{cell::Cell, ops::Range, rc::Rc};
+
+use crate::bounds::Aabb;
+
+const HIZ_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+pub const DRAW_ARGS_SIZE: u64 = std::mem::size_of::<wgpu::util::DrawIndexedIndirectArgs>() as u64;
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullUniforms {
+    vp_matrix: [f32; 16],
+    camera_position: [f32; 3],
+    object_count: u32,
+    size: [u32; 2],
+    level_count: u32,
+    _padding: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullObject {
+    min: [f32; 3],
+    /// Range of the object's proxy in the index buffer
+    index_count: u32,
+    max: [f32; 3],
+    first_index: u32,
+}
+
+struct HiZPyramid {
+    width: u32,
+    height: u32,
+    view: wgpu::TextureView,
+    /// One view per level, written by the build passes.
+    levels: Vec<wgpu::TextureView>,
+}
+
+pub struct OcclusionCuller {
+    build_layout: wgpu::BindGroupLayout,
+    downsample_layout: wgpu::BindGroupLayout,
+    cull_layout: wgpu::BindGroupLayout,
+    build_pipeline: wgpu::ComputePipeline,
+    downsample_pipeline: wgpu::ComputePipeline,
+    cull_pipeline: wgpu::ComputePipeline,
+    uniform_buffer: wgpu::Buffer,
+    /// Object bounds and their draws, grown to the largest visible object count.
+    objects_buffer: wgpu::Buffer,
+    pub draws_buffer: wgpu::Buffer,
+    capacity: u64,
+    counter_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    readback_in_flight: Rc<Cell<bool>>,
+    /// Set when this frame's count was copied and should be mapped after submit.
+    readback_pending: bool,
+    culled_count: Rc<Cell<u32>>,
+    pyramid: Option<HiZPyramid>,
+    /// View the linear-Z target was last rendered with; `None` when it holds no
+    /// usable frame.
+    history: Option<([f32; 16], [f32; 3])>,
+}
+
+impl OcclusionCuller {
+    pub fn new(device: &wgpu::Device, cache: Option<&wgpu::PipelineCache>) -> Self {
+        let texture_entry = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let storage_texture_entry = wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: HIZ_FORMAT,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        let buffer_entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let float = wgpu::TextureSampleType::Float { filterable: false };
+        let build_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Hi-Z Build Bind Group Layout"),
+            entries: &[
+                texture_entry(0, wgpu::TextureSampleType::Uint),
+                storage_texture_entry,
+            ],
+        });
+        let downsample_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Hi-Z Downsample Bind Group Layout"),
+            entries: &[storage_texture_entry, texture_entry(2, float)],
+        });
+        let cull_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Occlusion Cull Bind Group Layout"),
+            entries: &[
+                texture_entry(0, float),
+                buffer_entry(1, wgpu::BufferBindingType::Uniform),
+                buffer_entry(2, wgpu::BufferBindingType::Storage { read_only: true }),
+                buffer_entry(3, wgpu::BufferBindingType::Storage { read_only: false }),
+                buffer_entry(4, wgpu::BufferBindingType::Storage { read_only: false }),
+            ],
+        });
+
+        let hiz_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Hi-Z Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/hiz.wgsl").into()),
+        });
+        let cull_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Occlusion Cull Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/occlusion_cull.wgsl").into()),
+        });
+        let pipeline = |label, layout, module, entry_point| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache,
+            })
+        };
+        let build_pipeline = pipeline(
+            "Hi-Z Build Pipeline",
+            &build_layout,
+            &hiz_shader,
+            "build_first",
+        );
+        let downsample_pipeline = pipeline(
+            "Hi-Z Downsample Pipeline",
+            &downsample_layout,
+            &hiz_shader,
+            "downsample",
+        );
+        let cull_pipeline = pipeline(
+            "Occlusion Cull Pipeline",
+            &cull_layout,
+            &cull_shader,
+            "cs_main",
+        );
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Occlusion Cull Uniform Buffer"),
+            size: std::mem::size_of::<CullUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let (objects_buffer, draws_buffer) = create_object_buffers(device, 1);
+        let counter_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Occlusion Culled Count Buffer"),
+            size: 4,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Occlusion Culled Count Readback Buffer"),
+            size: 4,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        OcclusionCuller {
+            build_layout,
+            downsample_layout,
+            cull_layout,
+            build_pipeline,
+            downsample_pipeline,
+            cull_pipeline,
+            uniform_buffer,
+            objects_buffer,
+            draws_buffer,
+            capacity: 1,
+            counter_buffer,
+            readback_buffer,
+            readback_in_flight: Rc::default(),
+            readback_pending: false,
+            culled_count: Rc::default(),
+            pyramid: None,
+            history: None,
+        }
+    }
+
+    /// Forgets the previous frame, e.g. after the scene or its geometry changed, so
+    /// the next frame draws every object.
+    pub fn invalidate(&mut self) {
+        self.history = None;
+    }
+
+    /// Objects culled by the latest frame whose count has been read back.
+    pub fn culled_count(&self) -> u32 {
+        self.culled_count.get()
+    }
+
+    /// Builds the pyramid from `linear_z`, which still holds the previous frame, and
+    /// writes one indirect draw per entry of `objects`, a world AABB and the index
+    /// range of the object's proxy, into `draws_buffer`. Returns
+    /// false, encoding nothing, when there is no usable previous frame; the caller
+    /// then draws directly.
+    pub fn encode(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        linear_z: &wgpu::TextureView,
+        (width, height): (u32, u32),
+        objects: &[(Aabb, Range<u32>)],
+    ) -> bool {
+        let Some((vp_matrix, camera_position)) = self.history else {
+            self.culled_count.set(0);
+            return false;
+        };
+        if objects.is_empty() {
+            self.culled_count.set(0);
+            return true;
+        }
+        let pyramid_size = (width.div_ceil(2), height.div_ceil(2));
+        if self
+            .pyramid
+            .as_ref()
+            .is_none_or(|pyramid| (pyramid.width, pyramid.height) != pyramid_size)
+        {
+            self.pyramid = Some(create_pyramid(device, pyramid_size));
+        }
+        if objects.len() as u64 > self.capacity {
+            self.capacity = (objects.len() as u64).next_power_of_two();
+            (self.objects_buffer, self.draws_buffer) = create_object_buffers(device, self.capacity);
+        }
+
+        let records: Vec<CullObject> = objects
+            .iter()
+            .map(|(bounds, indices)| CullObject {
+                min: bounds.min,
+                index_count: indices.len() as u32,
+                max: bounds.max,
+                first_index: indices.start,
+            })
+            .collect();
+        queue.write_buffer(&self.objects_buffer, 0, bytemuck::cast_slice(&records));
+        let pyramid = self.pyramid.as_ref().unwrap();
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[CullUniforms {
+                vp_matrix,
+                camera_position,
+                object_count: objects.len() as u32,
+                size: [width, height],
+                level_count: pyramid.levels.len() as u32,
+                _padding: 0,
+            }]),
+        );
+        encoder.clear_buffer(&self.counter_buffer, 0, None);
+
+        let build_groups: Vec<_> = pyramid
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(level, view)| {
+                let (layout, source) = match level {
+                    0 => (&self.build_layout, (0, linear_z)),
+                    _ => (&self.downsample_layout, (2, &pyramid.levels[level - 1])),
+                };
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Hi-Z Level BG"),
+                    layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: source.0,
+                            resource: wgpu::BindingResource::TextureView(source.1),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(view),
+                        },
+                    ],
+                })
+            })
+            .collect();
+        let cull_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Occlusion Cull BG"),
+            layout: &self.cull_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&pyramid.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.objects_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.draws_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.counter_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Occlusion Cull Pass"),
+                timestamp_writes: None,
+            });
+            let (mut level_width, mut level_height) = pyramid_size;
+            for (level, bind_group) in build_groups.iter().enumerate() {
+                pass.set_pipeline(match level {
+                    0 => &self.build_pipeline,
+                    _ => &self.downsample_pipeline,
+                });
+                pass.set_bind_group(0, bind_group, &[]);
+                pass.dispatch_workgroups(level_width.div_ceil(8), level_height.div_ceil(8), 1);
+                level_width = (level_width / 2).max(1);
+                level_height = (level_height / 2).max(1);
+            }
+            pass.set_pipeline(&self.cull_pipeline);
+            pass.set_bind_group(0, &cull_group, &[]);
+            pass.dispatch_workgroups((objects.len() as u32).div_ceil(64), 1, 1);
+        }
+
+        if !self.readback_in_flight.get() {
+            encoder.copy_buffer_to_buffer(&self.counter_buffer, 0, &self.readback_buffer, 0, 4);
+            self.readback_pending = true;
+        }
+        true
+    }
+
+    /// Records the view the linear-Z target now holds, and maps the culled count
+    /// copied this frame. Returns whether another frame is needed: objects were
+    /// tested against an older view than `vp_matrix` and may have been revealed.
+    pub fn finish_frame(
+        &mut self,
+        culled: bool,
+        vp_matrix: &[f32; 16],
+        camera_position: [f32; 3],
+    ) -> bool {
+        let stale = culled && self.history != Some((*vp_matrix, camera_position));
+        self.history = Some((*vp_matrix, camera_position));
+
+        if std::mem::take(&mut self.readback_pending) {
+            self.readback_in_flight.set(true);
+            let buffer = self.readback_buffer.clone();
+            let in_flight = self.readback_in_flight.clone();
+            let culled_count = self.culled_count.clone();
+            self.readback_buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    if result.is_ok() {
+                        let count = {
+                            let data = buffer.slice(..).get_mapped_range();
+                            u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+                        };
+                        buffer.unmap();
+                        culled_count.set(count);
+                    }
+                    in_flight.set(false);
+                });
+        }
+        stale
+    }
+}
+
+fn create_object_buffers(device: &wgpu::Device, capacity: u64) -> (wgpu::Buffer, wgpu::Buffer) {
+    let objects = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Occlusion Cull Objects Buffer"),
+        size: capacity * std::mem::size_of::<CullObject>() as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let draws = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Occlusion Cull Draws Buffer"),
+        size: capacity * DRAW_ARGS_SIZE,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT,
+        mapped_at_creation: false,
+    });
+    (objects, draws)
+}
+
+fn create_pyramid(device: &wgpu::Device, (width, height): (u32, u32)) -> HiZPyramid {
+    let level_count = 32 - width.max(height).leading_zeros();
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Hi-Z Pyramid"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: HIZ_FORMAT,
+        usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let levels = (0..level_count)
+        .map(|level| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        })
+        .collect();
+    HiZPyramid {
+        width,
+        height,
+        view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+        levels,
+    }
+}
+
+/// Whether the device can run the culling pass and consume its indirect draws.
+pub fn is_supported(downlevel: &wgpu::DownlevelCapabilities) -> bool {
+    downlevel
+        .flags
+        .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS | wgpu::DownlevelFlags::INDIRECT_EXECUTION)
+}
@@ -0,0 +1,41 @@
+//! Frame rate cap for `Renderer::set_max_fps`.
+
+/// Decides which frames to draw under an FPS cap using a time accumulator, so a cap
+/// that does not divide the display rate evenly still gives a steady cadence.
+#[derive(Default)]
+pub struct FramePacer {
+    interval_ms: Option<f64>,
+    last_ms: Option<f64>,
+    accumulator_ms: f64,
+}
+
+impl FramePacer {
+    pub fn set_max_fps(&mut self, fps: Option<f32>) {
+        self.interval_ms = fps
+            .filter(|fps| fps.is_finite() && *fps > 0.0)
+            .map(|fps| 1000.0 / fps as f64);
+        self.last_ms = None;
+        self.accumulator_ms = 0.0;
+    }
+
+    /// Returns whether a frame should be drawn at time `now_ms`.
+    pub fn frame_due(&mut self, now_ms: f64) -> bool {
+        let Some(interval) = self.interval_ms else {
+            return true;
+        };
+        let Some(last) = self.last_ms.replace(now_ms) else {
+            return true;
+        };
+        let elapsed = (now_ms - last).max(0.0);
+        self.accumulator_ms += elapsed;
+
+        // Half a display frame of slack keeps vsync jitter from dropping a due frame
+        if self.accumulator_ms < interval - elapsed * 0.5 {
+            return false;
+        }
+        // Keep the remainder for a steady cadence; clamp so a stall does not cause a
+        // burst and a long run of early frames does not build up a deficit.
+        self.accumulator_ms = (self.accumulator_ms - interval).clamp(-interval * 0.5, interval);
+        true
+    }
+}
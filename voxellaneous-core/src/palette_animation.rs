@@ -0,0 +1,235 @@
+//! Palette colour cycling for `Renderer::set_palette_animation`, whole-palette
+//! transitions for `Renderer::animate_palette_to`, and the colour ramps of
+//! `Renderer::create_gradient_palette`.
+
+use serde::Deserialize;
+
+use crate::primitives::RGBA;
+use crate::utils::pack_rgba;
+
+/// Number of entries in the packed palette uniform.
+const PALETTE_SIZE: u32 = 256;
+
+/// A run of palette entries rotated over time, as passed from JS.
+#[derive(Deserialize)]
+pub struct PaletteCycle {
+    pub start: u32,
+    pub length: u32,
+    /// Entries advanced per second; negative values cycle the other way.
+    pub speed: f32,
+}
+
+struct CycleState {
+    cycle: PaletteCycle,
+    /// Accumulated rotation in entries.
+    phase: f64,
+    /// Rotation last written to the palette buffer, `None` when it must be rewritten.
+    written_offset: Option<u32>,
+}
+
+impl CycleState {
+    fn offset(&self) -> u32 {
+        self.phase.floor().rem_euclid(self.cycle.length as f64) as u32
+    }
+
+    /// Writes the cycle's entries rotated by `offset`, leaving all other entries alone.
+    fn write(&self, queue: &wgpu::Queue, buffer: &wgpu::Buffer, palette: &[RGBA], offset: u32) {
+        let PaletteCycle { start, length, .. } = self.cycle;
+        let entries: Vec<u32> = (0..length)
+            .map(|i| {
+                let source = start + (i + offset) % length;
+                palette.get(source as usize).map_or(0, pack_rgba)
+            })
+            .collect();
+        queue.write_buffer(
+            buffer,
+            (start as usize * std::mem::size_of::<u32>()) as wgpu::BufferAddress,
+            bytemuck::cast_slice(&entries),
+        );
+    }
+}
+
+#[derive(Default)]
+pub struct PaletteAnimation {
+    cycles: Vec<CycleState>,
+}
+
+impl PaletteAnimation {
+    /// Checks that cycles are non-empty, inside the palette and disjoint.
+    pub fn validate(cycles: &[PaletteCycle]) -> Result<(), String> {
+        for (i, cycle) in cycles.iter().enumerate() {
+            if cycle.length == 0 || cycle.start.saturating_add(cycle.length) > PALETTE_SIZE {
+                return Err(format!(
+                    "palette cycle {} ({}..{}) must be non-empty and within {} entries",
+                    i,
+                    cycle.start,
+                    cycle.start.saturating_add(cycle.length),
+                    PALETTE_SIZE
+                ));
+            }
+            if !cycle.speed.is_finite() {
+                return Err(format!("palette cycle {} has a non-finite speed", i));
+            }
+            let overlaps = cycles[..i].iter().any(|other| {
+                cycle.start < other.start + other.length && other.start < cycle.start + cycle.length
+            });
+            if overlaps {
+                return Err(format!("palette cycle {} overlaps an earlier cycle", i));
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the cycles, restoring the entries of the previous ones; `cycles` must
+    /// have passed `validate`.
+    pub fn set(
+        &mut self,
+        cycles: Vec<PaletteCycle>,
+        queue: &wgpu::Queue,
+        buffer: &wgpu::Buffer,
+        palette: &[RGBA],
+    ) {
+        for state in &self.cycles {
+            state.write(queue, buffer, palette, 0);
+        }
+        self.cycles = cycles
+            .into_iter()
+            .map(|cycle| CycleState {
+                cycle,
+                phase: 0.0,
+                // Already unrotated
+                written_offset: Some(0),
+            })
+            .collect();
+    }
+
+    /// The palette buffer was rewritten (scene upload or swap); rotations must be
+    /// written again on the next `advance`.
+    pub fn invalidate(&mut self) {
+        for state in &mut self.cycles {
+            state.written_offset = None;
+        }
+    }
+
+    /// Advances every cycle by `dt` seconds and rewrites the cycles whose rotation
+    /// changed. Returns whether anything was written.
+    pub fn advance(
+        &mut self,
+        dt: f64,
+        queue: &wgpu::Queue,
+        buffer: &wgpu::Buffer,
+        palette: &[RGBA],
+    ) -> bool {
+        let mut written = false;
+        for state in &mut self.cycles {
+            // Wrapped so the phase keeps its precision over long sessions
+            state.phase =
+                (state.phase + dt * state.cycle.speed as f64).rem_euclid(state.cycle.length as f64);
+            let offset = state.offset();
+            if state.written_offset != Some(offset) {
+                state.write(queue, buffer, palette, offset);
+                state.written_offset = Some(offset);
+                written = true;
+            }
+        }
+        written
+    }
+}
+
+/// Interpolation of the whole palette toward a target, for
+/// `Renderer::animate_palette_to`.
+pub struct PaletteTransition {
+    from: Vec<RGBA>,
+    to: Vec<RGBA>,
+    start_ms: f64,
+    duration_ms: f64,
+}
+
+impl PaletteTransition {
+    /// Starts at `start_ms` from `from`, padded with transparent black to the full
+    /// palette; `to` must hold `PALETTE_SIZE` entries.
+    pub fn new(from: &[RGBA], to: Vec<RGBA>, start_ms: f64, duration_secs: f32) -> Self {
+        let from = (0..PALETTE_SIZE as usize)
+            .map(|i| from.get(i).copied().unwrap_or(RGBA(0, 0, 0, 0)))
+            .collect();
+        PaletteTransition {
+            from,
+            to,
+            start_ms,
+            duration_ms: duration_secs.max(0.0) as f64 * 1000.0,
+        }
+    }
+
+    /// Parses a target palette of `PALETTE_SIZE` RGBA8 colours.
+    pub fn parse_palette(bytes: &[u8]) -> Result<Vec<RGBA>, String> {
+        if bytes.len() != PALETTE_SIZE as usize * 4 {
+            return Err(format!(
+                "target palette must be {} bytes ({} RGBA colours), got {}",
+                PALETTE_SIZE * 4,
+                PALETTE_SIZE,
+                bytes.len()
+            ));
+        }
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|c| RGBA(c[0], c[1], c[2], c[3]))
+            .collect())
+    }
+
+    pub fn is_finished(&self, now_ms: f64) -> bool {
+        now_ms - self.start_ms >= self.duration_ms
+    }
+
+    /// The palette at `now_ms`, linearly interpolated per channel in sRGB.
+    pub fn sample(&self, now_ms: f64) -> Vec<RGBA> {
+        if self.is_finished(now_ms) {
+            return self.to.clone();
+        }
+        let t = ((now_ms - self.start_ms) / self.duration_ms).clamp(0.0, 1.0) as f32;
+        let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        self.from
+            .iter()
+            .zip(&self.to)
+            .map(|(a, b)| {
+                RGBA(
+                    lerp(a.0, b.0),
+                    lerp(a.1, b.1),
+                    lerp(a.2, b.2),
+                    lerp(a.3, b.3),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Packed palette ramping linearly in sRGB between `stops` of (palette index, RGBA),
+/// in any order. Entries before the first stop and after the last take its colour.
+pub fn gradient_palette(stops: &[(u8, [u8; 4])]) -> Result<[u32; 256], String> {
+    let mut stops = stops.to_vec();
+    stops.sort_by_key(|&(index, _)| index);
+    if stops.is_empty() {
+        return Err("a gradient needs at least one stop".to_string());
+    }
+    if let Some(pair) = stops.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        return Err(format!(
+            "palette index {} has more than one stop",
+            pair[0].0
+        ));
+    }
+
+    let mut palette = [0; PALETTE_SIZE as usize];
+    for (i, packed) in palette.iter_mut().enumerate() {
+        let next = stops.partition_point(|&(index, _)| (index as usize) < i);
+        let color = match (next.checked_sub(1).map(|prev| stops[prev]), stops.get(next)) {
+            (Some((from_index, from)), Some(&(to_index, to))) if to_index as usize != i => {
+                let t = (i - from_index as usize) as f32 / (to_index - from_index) as f32;
+                let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+                [0, 1, 2, 3].map(|c| lerp(from[c], to[c]))
+            }
+            (_, Some(&(_, color))) | (Some((_, color)), None) => color,
+            (None, None) => unreachable!("stops is not empty"),
+        };
+        *packed = pack_rgba(&RGBA(color[0], color[1], color[2], color[3]));
+    }
+    Ok(palette)
+}
@@ -0,0 +1,213 @@
+//! User-supplied fullscreen post effects, run in order after the present pass and
+//! FXAA, ping-ponging between the post targets and ending on the frame.
+//!
+//! Each effect is a WGSL fragment shader appended to shaders/post_effect_prelude.wgsl,
+//! which provides `vs_main`, `VSOut` and the input bindings 0 and 1. Binding 2 is the
+//! custom uniform buffer shared by all effects and filled by `set_custom_uniform`.
+
+use wgpu::util::DeviceExt;
+
+/// Initial size of the custom uniform buffer, zero-filled until set.
+const MIN_CUSTOM_UNIFORM_SIZE: u64 = 256;
+
+pub struct PostEffects {
+    layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    sampler: wgpu::Sampler,
+    pipelines: Vec<wgpu::RenderPipeline>,
+    custom_uniform: wgpu::Buffer,
+}
+
+impl PostEffects {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post Effect Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Effect Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Effect Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        PostEffects {
+            layout,
+            pipeline_layout,
+            format,
+            sampler,
+            pipelines: Vec::new(),
+            custom_uniform: create_custom_uniform(device, &[0; MIN_CUSTOM_UNIFORM_SIZE as usize]),
+        }
+    }
+
+    /// Compiles `source` after the prelude and appends it to the chain. Shader errors
+    /// surface as device validation errors, like those of the built-in pipelines.
+    pub fn add(&mut self, device: &wgpu::Device, source: &str) {
+        let source = format!(
+            "{}\n{}",
+            include_str!("shaders/post_effect_prelude.wgsl"),
+            source
+        );
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Post Effect Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Post Effect Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: self.format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+            cache: None,
+        });
+        self.pipelines.push(pipeline);
+    }
+
+    pub fn clear(&mut self) {
+        self.pipelines.clear();
+    }
+
+    /// Writes `bytes` to the start of the custom uniform buffer, replacing it with a
+    /// larger one when they do not fit. Uniform buffers are read in 16-byte rows, so
+    /// the bytes are zero-padded to a multiple of 16.
+    pub fn set_custom_uniform(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+    ) -> Result<(), String> {
+        let max = device.limits().max_uniform_buffer_binding_size as usize;
+        if bytes.len() > max {
+            return Err(format!(
+                "custom uniform is {} bytes, the device binds at most {}",
+                bytes.len(),
+                max
+            ));
+        }
+        let mut padded = bytes.to_vec();
+        padded.resize(bytes.len().div_ceil(16).max(1) * 16, 0);
+        if padded.len() as u64 > self.custom_uniform.size() {
+            self.custom_uniform = create_custom_uniform(device, &padded);
+        } else {
+            queue.write_buffer(&self.custom_uniform, 0, &padded);
+        }
+        Ok(())
+    }
+
+    /// Runs the effects in order: the first reads `input`, each writes the other of
+    /// `scratch` and `input`, and the last writes `output`.
+    pub fn encode(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::TextureView,
+        scratch: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) {
+        let mut source = input;
+        let mut target = scratch;
+        for (i, pipeline) in self.pipelines.iter().enumerate() {
+            let view = if i + 1 == self.pipelines.len() {
+                output
+            } else {
+                target
+            };
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Post Effect BG"),
+                layout: &self.layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(source),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: self.custom_uniform.as_entire_binding(),
+                    },
+                ],
+            });
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Post Effect Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                ..Default::default()
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+            drop(pass);
+            std::mem::swap(&mut source, &mut target);
+        }
+    }
+}
+
+fn create_custom_uniform(device: &wgpu::Device, contents: &[u8]) -> wgpu::Buffer {
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Custom Uniform Buffer"),
+        contents,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    })
+}
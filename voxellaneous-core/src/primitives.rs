@@ -1,4 +1,4 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
-pub struct RGBA(pub u8, pub u8, pub u8, pub u8);
\ No newline at end of file
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct RGBA(pub u8, pub u8, pub u8, pub u8);
@@ -0,0 +1,494 @@
+//! Full-screen compute raymarch, the `RenderPath::Compute` alternative to rasterized
+//! proxy cubes. Writes the same G-buffer as the raster path, so lighting is shared.
+
+use wgpu::util::DeviceExt;
+
+use crate::constants::GBUFFER_NORMAL_FORMAT;
+
+/// Volumes one compute dispatch can march; the shader binds one texture per object.
+pub const MAX_RAYMARCH_OBJECTS: usize = 8;
+
+const WORKGROUP_SIZE: u32 = 8;
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct RaymarchParams {
+    inv_vp_matrix: [f32; 16],
+    camera_position: [f32; 3],
+    voxel_rounding: f32,
+    size: [u32; 2],
+    object_count: u32,
+    color_row_words: u32,
+    normal_row_words: u32,
+    linear_z_row_words: u32,
+    empty_index: u32,
+    _padding: u32,
+}
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct RaymarchObject {
+    pub model_matrix: [f32; 16],
+    pub inverse_model_matrix: [f32; 16],
+    pub dims: [u32; 3],
+    pub _padding: u32,
+    pub tint: [f32; 4],
+    pub voxel_size: [f32; 3],
+    pub _padding2: f32,
+}
+
+/// Object marched through its sparse voxel octree by svo_raymarch.wgsl.
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SvoObject {
+    pub model_matrix: [f32; 16],
+    pub inverse_model_matrix: [f32; 16],
+    pub dims: [u32; 3],
+    /// `svo::root_size` of `dims`.
+    pub root_size: u32,
+    pub tint: [f32; 4],
+}
+
+/// Per-frame inputs of `ComputeRaymarch::encode`.
+pub struct RaymarchFrame<'a> {
+    pub inv_vp_matrix: [f32; 16],
+    pub camera_position: [f32; 3],
+    pub voxel_rounding: f32,
+    pub empty_index: u32,
+    /// Objects past `MAX_RAYMARCH_OBJECTS` are ignored.
+    pub objects: &'a [(RaymarchObject, &'a wgpu::TextureView)],
+    /// Objects with an octree, marched one dispatch each after `objects`.
+    pub svo_objects: &'a [(SvoObject, &'a wgpu::Buffer)],
+    pub static_uniform_buffer: &'a wgpu::Buffer,
+    /// Palette entries past 256, as bound with the static uniforms of the raster path
+    pub palette_view: &'a wgpu::TextureView,
+    pub clip_planes_buffer: &'a wgpu::Buffer,
+}
+
+/// Packed outputs of one frame size, copied into the G-buffer textures.
+struct RaymarchTargets {
+    width: u32,
+    height: u32,
+    albedo: wgpu::Buffer,
+    normal: wgpu::Buffer,
+    linear_z: wgpu::Buffer,
+    /// In the linear-Z layout, as the material target shares its format
+    material: wgpu::Buffer,
+}
+
+pub struct ComputeRaymarch {
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+    svo_layout: wgpu::BindGroupLayout,
+    svo_pipeline: wgpu::ComputePipeline,
+    linear_z_format: wgpu::TextureFormat,
+    objects_buffer: wgpu::Buffer,
+    params_buffer: wgpu::Buffer,
+    /// Bound to object slots left unused this frame; textures start zeroed (empty).
+    empty_volume: wgpu::TextureView,
+    targets: Option<RaymarchTargets>,
+}
+
+impl ComputeRaymarch {
+    pub fn new(
+        device: &wgpu::Device,
+        linear_z_format: wgpu::TextureFormat,
+        voxel_filter: crate::VoxelFilterMode,
+        voxel_wrap: crate::VoxelWrapMode,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Uint,
+                view_dimension: wgpu::TextureViewDimension::D3,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let buffer_entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage = |read_only| wgpu::BufferBindingType::Storage { read_only };
+        let entries: Vec<_> = (0..MAX_RAYMARCH_OBJECTS as u32)
+            .map(texture_entry)
+            .chain([
+                buffer_entry(8, storage(true)),
+                buffer_entry(9, wgpu::BufferBindingType::Uniform),
+                buffer_entry(10, wgpu::BufferBindingType::Uniform),
+                buffer_entry(11, wgpu::BufferBindingType::Uniform),
+                buffer_entry(12, storage(false)),
+                buffer_entry(13, storage(false)),
+                buffer_entry(14, storage(false)),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 15,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                buffer_entry(16, storage(false)),
+            ])
+            .collect();
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Compute Raymarch Bind Group Layout"),
+            entries: &entries,
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Compute Raymarch Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Compute Raymarch Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/raymarch_compute.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Compute Raymarch Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &[
+                    ("voxel_filter", voxel_filter as u32 as f64),
+                    ("voxel_wrap", voxel_wrap as u32 as f64),
+                    ("linear_z_bytes", texel_bytes(linear_z_format) as f64),
+                ],
+                ..Default::default()
+            },
+            cache,
+        });
+
+        let svo_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("SVO Raymarch Bind Group Layout"),
+            entries: &[
+                buffer_entry(0, storage(true)),
+                buffer_entry(1, wgpu::BufferBindingType::Uniform),
+                buffer_entry(2, wgpu::BufferBindingType::Uniform),
+                buffer_entry(3, wgpu::BufferBindingType::Uniform),
+                buffer_entry(4, wgpu::BufferBindingType::Uniform),
+                buffer_entry(5, storage(false)),
+                buffer_entry(6, storage(false)),
+                buffer_entry(7, storage(false)),
+                buffer_entry(8, storage(false)),
+            ],
+        });
+        let svo_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("SVO Raymarch Pipeline Layout"),
+            bind_group_layouts: &[&svo_layout],
+            push_constant_ranges: &[],
+        });
+        let svo_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("SVO Raymarch Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/svo_raymarch.wgsl").into()),
+        });
+        let svo_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("SVO Raymarch Pipeline"),
+            layout: Some(&svo_pipeline_layout),
+            module: &svo_shader,
+            entry_point: Some("cs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &[("linear_z_bytes", texel_bytes(linear_z_format) as f64)],
+                ..Default::default()
+            },
+            cache,
+        });
+
+        let objects_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Compute Raymarch Objects"),
+            size: (std::mem::size_of::<RaymarchObject>() * MAX_RAYMARCH_OBJECTS) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Compute Raymarch Params"),
+            size: std::mem::size_of::<RaymarchParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let empty_volume = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Compute Raymarch Empty Volume"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D3,
+                format: wgpu::TextureFormat::R8Uint,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        ComputeRaymarch {
+            layout,
+            pipeline,
+            svo_layout,
+            svo_pipeline,
+            linear_z_format,
+            objects_buffer,
+            params_buffer,
+            empty_volume,
+            targets: None,
+        }
+    }
+
+    /// (Re)creates the packed output buffers for a `width`×`height` frame.
+    pub fn prepare(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if self
+            .targets
+            .as_ref()
+            .is_some_and(|targets| targets.width == width && targets.height == height)
+        {
+            return;
+        }
+        let create_buffer = |label, texel_bytes| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: padded_row_bytes(width, texel_bytes) as u64 * height as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            })
+        };
+        self.targets = Some(RaymarchTargets {
+            width,
+            height,
+            albedo: create_buffer("Compute Raymarch Albedo", 4),
+            normal: create_buffer(
+                "Compute Raymarch Normal",
+                texel_bytes(GBUFFER_NORMAL_FORMAT),
+            ),
+            linear_z: create_buffer(
+                "Compute Raymarch LinearZ",
+                texel_bytes(self.linear_z_format),
+            ),
+            material: create_buffer(
+                "Compute Raymarch Material",
+                texel_bytes(self.linear_z_format),
+            ),
+        });
+    }
+
+    /// Marches `frame.objects` and copies the result into the G-buffer `targets`
+    /// (albedo, normal, linear-Z, material). `normal_and_linear_z` is false for the
+    /// AlbedoOnly profile, whose auxiliary targets are placeholders. Call `prepare`
+    /// first.
+    pub fn encode(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        frame: &RaymarchFrame,
+        targets: [&wgpu::Texture; 4],
+        normal_and_linear_z: bool,
+    ) {
+        let Some(output) = &self.targets else {
+            return;
+        };
+        let (width, height) = (output.width, output.height);
+        let normal_bytes = texel_bytes(GBUFFER_NORMAL_FORMAT);
+        let linear_z_bytes = texel_bytes(self.linear_z_format);
+
+        let objects = &frame.objects[..frame.objects.len().min(MAX_RAYMARCH_OBJECTS)];
+        let records: Vec<RaymarchObject> = objects.iter().map(|(record, _)| *record).collect();
+        if !records.is_empty() {
+            queue.write_buffer(&self.objects_buffer, 0, bytemuck::cast_slice(&records));
+        }
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[RaymarchParams {
+                inv_vp_matrix: frame.inv_vp_matrix,
+                camera_position: frame.camera_position,
+                voxel_rounding: frame.voxel_rounding,
+                size: [width, height],
+                object_count: objects.len() as u32,
+                color_row_words: padded_row_bytes(width, 4) / 4,
+                normal_row_words: padded_row_bytes(width, normal_bytes) / 4,
+                linear_z_row_words: padded_row_bytes(width, linear_z_bytes) / 4,
+                empty_index: frame.empty_index,
+                _padding: 0,
+            }]),
+        );
+
+        let mut entries: Vec<_> = (0..MAX_RAYMARCH_OBJECTS)
+            .map(|slot| wgpu::BindGroupEntry {
+                binding: slot as u32,
+                resource: wgpu::BindingResource::TextureView(
+                    objects
+                        .get(slot)
+                        .map_or(&self.empty_volume, |(_, view)| *view),
+                ),
+            })
+            .collect();
+        entries.extend(
+            [
+                (8, &self.objects_buffer),
+                (9, &self.params_buffer),
+                (10, frame.static_uniform_buffer),
+                (11, frame.clip_planes_buffer),
+                (12, &output.albedo),
+                (13, &output.normal),
+                (14, &output.linear_z),
+            ]
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding,
+                resource: buffer.as_entire_binding(),
+            }),
+        );
+        entries.push(wgpu::BindGroupEntry {
+            binding: 15,
+            resource: wgpu::BindingResource::TextureView(frame.palette_view),
+        });
+        entries.push(wgpu::BindGroupEntry {
+            binding: 16,
+            resource: output.material.as_entire_binding(),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Compute Raymarch BG"),
+            layout: &self.layout,
+            entries: &entries,
+        });
+
+        let svo_bind_groups = self.svo_bind_groups(device, frame, output);
+
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Compute Raymarch Pass"),
+                timestamp_writes: None,
+            });
+            let workgroups = (
+                width.div_ceil(2).div_ceil(WORKGROUP_SIZE),
+                height.div_ceil(WORKGROUP_SIZE),
+            );
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(workgroups.0, workgroups.1, 1);
+            if !svo_bind_groups.is_empty() {
+                pass.set_pipeline(&self.svo_pipeline);
+            }
+            for svo_bind_group in &svo_bind_groups {
+                pass.set_bind_group(0, svo_bind_group, &[]);
+                pass.dispatch_workgroups(workgroups.0, workgroups.1, 1);
+            }
+        }
+
+        let copies = [
+            (&output.albedo, targets[0], 4),
+            (&output.normal, targets[1], normal_bytes),
+            (&output.linear_z, targets[2], linear_z_bytes),
+            (&output.material, targets[3], linear_z_bytes),
+        ];
+        let copy_count = if normal_and_linear_z { 4 } else { 1 };
+        for (buffer, texture, texel_bytes) in &copies[..copy_count] {
+            encoder.copy_buffer_to_texture(
+                wgpu::TexelCopyBufferInfo {
+                    buffer,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(padded_row_bytes(width, *texel_bytes)),
+                        rows_per_image: Some(height),
+                    },
+                },
+                wgpu::TexelCopyTextureInfo {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+
+    /// One bind group per octree object in `frame`, over the packed `output` buffers.
+    fn svo_bind_groups(
+        &self,
+        device: &wgpu::Device,
+        frame: &RaymarchFrame,
+        output: &RaymarchTargets,
+    ) -> Vec<wgpu::BindGroup> {
+        if frame.svo_objects.is_empty() {
+            return Vec::new();
+        }
+        // One record per uniform slot, so each bind group views its own
+        let record_size = std::mem::size_of::<SvoObject>() as u64;
+        let stride = record_size
+            .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64);
+        let mut records = vec![0u8; stride as usize * frame.svo_objects.len()];
+        for (slot, (record, _)) in records
+            .chunks_exact_mut(stride as usize)
+            .zip(frame.svo_objects)
+        {
+            slot[..record_size as usize].copy_from_slice(bytemuck::bytes_of(record));
+        }
+        let records_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("SVO Raymarch Objects"),
+            contents: &records,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        fn buffer(binding: u32, buffer: &wgpu::Buffer) -> wgpu::BindGroupEntry<'_> {
+            wgpu::BindGroupEntry {
+                binding,
+                resource: buffer.as_entire_binding(),
+            }
+        }
+        frame
+            .svo_objects
+            .iter()
+            .enumerate()
+            .map(|(i, (_, nodes))| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("SVO Raymarch BG"),
+                    layout: &self.svo_layout,
+                    entries: &[
+                        buffer(0, nodes),
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                                buffer: &records_buffer,
+                                offset: i as u64 * stride,
+                                size: wgpu::BufferSize::new(record_size),
+                            }),
+                        },
+                        buffer(2, &self.params_buffer),
+                        buffer(3, frame.static_uniform_buffer),
+                        buffer(4, frame.clip_planes_buffer),
+                        buffer(5, &output.albedo),
+                        buffer(6, &output.normal),
+                        buffer(7, &output.linear_z),
+                        buffer(8, &output.material),
+                    ],
+                })
+            })
+            .collect()
+    }
+}
+
+fn texel_bytes(format: wgpu::TextureFormat) -> u32 {
+    format.block_copy_size(None).unwrap_or(4)
+}
+
+fn padded_row_bytes(width: u32, texel_bytes: u32) -> u32 {
+    (width * texel_bytes).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+}
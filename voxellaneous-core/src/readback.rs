@@ -0,0 +1,210 @@
+use std::{cell::RefCell, rc::Rc};
+
+use wasm_bindgen::JsValue;
+
+use crate::constants::{LINEAR_Z_MAX_DISTANCE, LINEAR_Z_MAX_VALUE};
+use crate::RendererError;
+
+#[derive(Default)]
+struct DepthReadbackState {
+    in_flight: bool,
+    /// Latest requested pixel; older pending requests are coalesced into it.
+    next: Option<(u32, u32)>,
+    /// Resolvers waiting for the readback of `next`.
+    waiting: Vec<js_sys::Function>,
+}
+
+/// Single-pixel readback of the linear-Z target with at most one copy in flight.
+pub struct DepthReadback {
+    staging_buffer: wgpu::Buffer,
+    state: Rc<RefCell<DepthReadbackState>>,
+}
+
+impl DepthReadback {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Depth Readback Buffer"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        DepthReadback {
+            staging_buffer,
+            state: Rc::new(RefCell::new(DepthReadbackState::default())),
+        }
+    }
+
+    /// Queues a readback of pixel (x, y); the returned promise resolves with the
+    /// view distance or `null` for background.
+    pub fn request(&self, x: u32, y: u32) -> js_sys::Promise {
+        let mut state = self.state.borrow_mut();
+        state.next = Some((x, y));
+        js_sys::Promise::new(&mut |resolve, _reject| state.waiting.push(resolve))
+    }
+
+    /// Issues the pending readback, if any, unless one is already in flight.
+    pub fn pump(&self, device: &wgpu::Device, queue: &wgpu::Queue, linear_z: &wgpu::Texture) {
+        let (x, y, waiting) = {
+            let mut state = self.state.borrow_mut();
+            if state.in_flight {
+                return;
+            }
+            let Some((x, y)) = state.next.take() else {
+                return;
+            };
+            state.in_flight = true;
+            (x, y, std::mem::take(&mut state.waiting))
+        };
+
+        if x >= linear_z.width() || y >= linear_z.height() {
+            resolve_all(&waiting, &JsValue::NULL);
+            self.state.borrow_mut().in_flight = false;
+            return;
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Depth Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: linear_z,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.staging_buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: Some(1),
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let texel_size = linear_z.format().block_copy_size(None).unwrap_or(2);
+        let buffer = self.staging_buffer.clone();
+        let state = self.state.clone();
+        self.staging_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let value = match result {
+                    Ok(()) => {
+                        let stored = {
+                            let data = buffer.slice(..).get_mapped_range();
+                            match texel_size {
+                                2 => u16::from_le_bytes([data[0], data[1]]) as u32,
+                                _ => u32::from_le_bytes([data[0], data[1], data[2], data[3]]),
+                            }
+                        };
+                        buffer.unmap();
+                        decode_linear_z(stored)
+                    }
+                    Err(_) => JsValue::NULL,
+                };
+                state.borrow_mut().in_flight = false;
+                resolve_all(&waiting, &value);
+            });
+    }
+}
+
+/// Converts a stored linear-Z value back to a view-space distance (`null` for background).
+fn decode_linear_z(stored: u32) -> JsValue {
+    if stored == 0 {
+        return JsValue::NULL;
+    }
+    JsValue::from_f64((stored as f32 / LINEAR_Z_MAX_VALUE * LINEAR_Z_MAX_DISTANCE) as f64)
+}
+
+fn resolve_all(waiting: &[js_sys::Function], value: &JsValue) {
+    for resolve in waiting {
+        let _ = resolve.call1(&JsValue::UNDEFINED, value);
+    }
+}
+
+/// Maps `buffer` for reading and waits for the browser to complete the mapping.
+pub async fn map_for_read(buffer: &wgpu::Buffer) -> Result<(), JsValue> {
+    let mut callbacks = None;
+    let promise = js_sys::Promise::new(&mut |resolve, reject| callbacks = Some((resolve, reject)));
+    let (resolve, reject) = callbacks.expect("Promise executor runs synchronously");
+    buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| match result {
+            Ok(()) => {
+                let _ = resolve.call0(&JsValue::UNDEFINED);
+            }
+            Err(e) => {
+                let _ = reject.call1(&JsValue::UNDEFINED, &RendererError::from(e).into());
+            }
+        });
+    wasm_bindgen_futures::JsFuture::from(promise).await?;
+    Ok(())
+}
+
+/// Copies one aspect of a 2D texture, or every slice of a 3D one, to the CPU,
+/// stripping the 256-byte row padding. The texture must have `COPY_SRC` usage.
+pub async fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    aspect: wgpu::TextureAspect,
+    bytes_per_texel: u32,
+) -> Result<Vec<u8>, JsValue> {
+    let (width, height) = (texture.width(), texture.height());
+    let depth = match texture.dimension() {
+        wgpu::TextureDimension::D3 => texture.depth_or_array_layers(),
+        _ => 1,
+    };
+    let unpadded_row = width * bytes_per_texel;
+    let padded_row = unpadded_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Texture Readback Buffer"),
+        size: padded_row as u64 * height as u64 * depth as u64,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Texture Readback Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        wgpu::TexelCopyTextureInfo {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect,
+        },
+        wgpu::TexelCopyBufferInfo {
+            buffer: &staging_buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row),
+                rows_per_image: Some(height),
+            },
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: depth,
+        },
+    );
+    queue.submit(Some(encoder.finish()));
+
+    map_for_read(&staging_buffer).await?;
+    let mut pixels = Vec::with_capacity((unpadded_row * height * depth) as usize);
+    {
+        let data = staging_buffer.slice(..).get_mapped_range();
+        for row in data.chunks_exact(padded_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_row as usize]);
+        }
+    }
+    staging_buffer.unmap();
+    Ok(pixels)
+}
@@ -0,0 +1,307 @@
+//! Ordered list of the passes `Renderer::render` records each frame.
+
+use std::collections::HashMap;
+
+use crate::RendererError;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PassKind {
+    GBuffer,
+    /// Fills the pixels a checkerboard G-buffer pass skipped.
+    Checkerboard,
+    /// Lighting, or one of the G-buffer debug views.
+    Present,
+    /// Screen-space reflections over the lit image.
+    Ssr,
+    Fxaa,
+    /// Custom effects from `Renderer::add_post_effect`.
+    PostEffects,
+    Wireframe,
+    /// Boxes queued by `Renderer::draw_debug_box` for this frame.
+    DebugBoxes,
+    /// Debug text over the finished frame, from `Renderer::set_hud`.
+    #[cfg(feature = "hud")]
+    Hud,
+}
+
+impl PassKind {
+    /// Passes after lighting that rework the presented image.
+    pub fn is_post(self) -> bool {
+        matches!(self, PassKind::Ssr | PassKind::Fxaa | PassKind::PostEffects)
+    }
+}
+
+/// Views passes read and write, resolved by the renderer each frame.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Resource {
+    GBufferAlbedo,
+    GBufferNormal,
+    GBufferLinearZ,
+    GBufferMaterial,
+    Depth,
+    PostA,
+    PostB,
+    /// The current surface texture.
+    Frame,
+}
+
+const GBUFFER: &[Resource] = &[
+    Resource::GBufferAlbedo,
+    Resource::GBufferNormal,
+    Resource::GBufferLinearZ,
+    Resource::GBufferMaterial,
+    Resource::Depth,
+];
+
+/// Overlays draw over the frame, so they read it as well as write it.
+const OVERLAY: &[Resource] = &[Resource::Frame];
+
+#[derive(Clone)]
+pub struct Pass {
+    pub kind: PassKind,
+    pub enabled: bool,
+    pub inputs: &'static [Resource],
+    pub outputs: &'static [Resource],
+}
+
+pub struct RenderGraph {
+    passes: Vec<Pass>,
+}
+
+impl Default for RenderGraph {
+    fn default() -> Self {
+        let mut graph = RenderGraph {
+            passes: vec![
+                Pass {
+                    kind: PassKind::GBuffer,
+                    enabled: true,
+                    inputs: &[],
+                    outputs: GBUFFER,
+                },
+                Pass {
+                    kind: PassKind::Checkerboard,
+                    enabled: false,
+                    inputs: GBUFFER,
+                    outputs: GBUFFER,
+                },
+                Pass {
+                    kind: PassKind::Present,
+                    enabled: true,
+                    inputs: GBUFFER,
+                    outputs: &[Resource::Frame],
+                },
+                Pass {
+                    kind: PassKind::Ssr,
+                    enabled: false,
+                    inputs: &[
+                        Resource::PostA,
+                        Resource::GBufferAlbedo,
+                        Resource::GBufferNormal,
+                        Resource::GBufferLinearZ,
+                        Resource::GBufferMaterial,
+                    ],
+                    // Reflected into B, then copied to the frame
+                    outputs: &[Resource::PostB, Resource::Frame],
+                },
+                Pass {
+                    kind: PassKind::Fxaa,
+                    enabled: false,
+                    inputs: &[Resource::PostA],
+                    // Anti-aliased into B, then copied to the frame
+                    outputs: &[Resource::PostB, Resource::Frame],
+                },
+                Pass {
+                    kind: PassKind::PostEffects,
+                    enabled: false,
+                    inputs: &[Resource::PostA],
+                    // Ping-pong through B and A, the last effect writing the frame
+                    outputs: &[Resource::PostB, Resource::Frame],
+                },
+                Pass {
+                    kind: PassKind::Wireframe,
+                    enabled: false,
+                    inputs: OVERLAY,
+                    outputs: &[Resource::Frame],
+                },
+                Pass {
+                    kind: PassKind::DebugBoxes,
+                    enabled: false,
+                    inputs: OVERLAY,
+                    outputs: &[Resource::Frame],
+                },
+                #[cfg(feature = "hud")]
+                Pass {
+                    kind: PassKind::Hud,
+                    enabled: false,
+                    inputs: OVERLAY,
+                    outputs: &[Resource::Frame],
+                },
+            ],
+        };
+        graph.link();
+        graph
+    }
+}
+
+impl RenderGraph {
+    pub fn set_enabled(&mut self, kind: PassKind, enabled: bool) {
+        if let Some(pass) = self.passes.iter_mut().find(|pass| pass.kind == kind) {
+            pass.enabled = enabled;
+        }
+        self.link();
+    }
+
+    pub fn enabled_passes(&self) -> impl Iterator<Item = &Pass> {
+        self.passes.iter().filter(|pass| pass.enabled)
+    }
+
+    /// Moves pass `kind` to just before pass `before`. Fails, keeping the order, when
+    /// with every pass enabled a pass would then read a view no earlier pass writes,
+    /// or one written before the frame, which only overlays may follow.
+    pub fn move_before(&mut self, kind: PassKind, before: PassKind) -> Result<(), RendererError> {
+        let position = |passes: &[Pass], kind| passes.iter().position(|pass| pass.kind == kind);
+        let mut passes = self.passes.clone();
+        let Some(from) = position(&passes, kind).filter(|_| kind != before) else {
+            return Ok(());
+        };
+        let pass = passes.remove(from);
+        let to = position(&passes, before).unwrap_or(passes.len());
+        passes.insert(to, pass);
+
+        let mut all = RenderGraph {
+            passes: passes.clone(),
+        };
+        all.passes.iter_mut().for_each(|pass| pass.enabled = true);
+        all.link();
+        all.check_dependencies()?;
+        self.passes = passes;
+        self.link();
+        Ok(())
+    }
+
+    /// Checks the order of the enabled passes, as `move_before` describes.
+    fn check_dependencies(&self) -> Result<(), RendererError> {
+        let mut written = HashMap::new();
+        for (index, pass) in self.enabled_passes().enumerate() {
+            let frame = written.get(&Resource::Frame).copied();
+            for input in pass.inputs {
+                let error = match written.get(input) {
+                    None => "before any pass writes it",
+                    Some(&at) if *input != Resource::Frame && frame.is_some_and(|f| at < f) => {
+                        "after the frame is written"
+                    }
+                    Some(_) => continue,
+                };
+                let message = format!("{:?} would read {:?} {}", pass.kind, input, error);
+                return Err(RendererError::invalid("kind", message));
+            }
+            for output in pass.outputs {
+                written.insert(*output, index);
+            }
+        }
+        Ok(())
+    }
+
+    /// Points the present pass and each post pass but the last enabled one at the post
+    /// pass that follows it, the last writing the frame. Effects always end the chain.
+    fn link(&mut self) {
+        let last_post = self
+            .passes
+            .iter()
+            .rposition(|pass| pass.enabled && pass.kind.is_post());
+        for (index, pass) in self.passes.iter_mut().enumerate() {
+            let chained = last_post.is_some_and(|last| index < last);
+            match pass.kind {
+                PassKind::Present if chained => pass.outputs = &[Resource::PostA],
+                PassKind::Present => pass.outputs = &[Resource::Frame],
+                PassKind::Ssr | PassKind::Fxaa if chained => {
+                    pass.outputs = &[Resource::PostB, Resource::PostA]
+                }
+                PassKind::Ssr | PassKind::Fxaa => {
+                    pass.outputs = &[Resource::PostB, Resource::Frame]
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn order(graph: &RenderGraph) -> Vec<PassKind> {
+        graph.passes.iter().map(|pass| pass.kind).collect()
+    }
+
+    fn outputs(graph: &RenderGraph, kind: PassKind) -> &'static [Resource] {
+        let pass = graph.passes.iter().find(|pass| pass.kind == kind);
+        pass.expect("pass").outputs
+    }
+
+    fn enable_post(graph: &mut RenderGraph) {
+        for kind in [PassKind::Ssr, PassKind::Fxaa, PassKind::PostEffects] {
+            graph.set_enabled(kind, true);
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn default_order_is_valid() {
+        let mut graph = RenderGraph::default();
+        graph.passes.iter_mut().for_each(|pass| pass.enabled = true);
+        graph.link();
+        assert!(graph.check_dependencies().is_ok());
+    }
+
+    #[wasm_bindgen_test]
+    fn swapped_post_passes_chain_in_their_new_order() {
+        let mut graph = RenderGraph::default();
+        graph.set_enabled(PassKind::Ssr, true);
+        graph.set_enabled(PassKind::Fxaa, true);
+        graph
+            .move_before(PassKind::Fxaa, PassKind::Ssr)
+            .expect("FXAA before SSR");
+        let post = [PassKind::Present, PassKind::Fxaa, PassKind::Ssr];
+        assert_eq!(order(&graph)[2..5], post);
+        assert_eq!(outputs(&graph, PassKind::Present), [Resource::PostA]);
+        let chained = [Resource::PostB, Resource::PostA];
+        assert_eq!(outputs(&graph, PassKind::Fxaa), chained);
+        let last = [Resource::PostB, Resource::Frame];
+        assert_eq!(outputs(&graph, PassKind::Ssr), last);
+    }
+
+    #[wasm_bindgen_test]
+    fn post_passes_cannot_follow_the_effects() {
+        let mut graph = RenderGraph::default();
+        enable_post(&mut graph);
+        let before = order(&graph);
+        let error = graph.move_before(PassKind::Fxaa, PassKind::Wireframe);
+        assert!(matches!(
+            error,
+            Err(RendererError::Validation {
+                field: Some("kind"),
+                ..
+            })
+        ));
+        assert_eq!(order(&graph), before);
+    }
+
+    #[wasm_bindgen_test]
+    fn passes_cannot_read_views_before_they_are_written() {
+        let mut graph = RenderGraph::default();
+        let before = order(&graph);
+        for (kind, target) in [
+            (PassKind::Wireframe, PassKind::Present),
+            (PassKind::Ssr, PassKind::Present),
+            (PassKind::Present, PassKind::GBuffer),
+            (PassKind::Checkerboard, PassKind::GBuffer),
+        ] {
+            let moved = graph.move_before(kind, target);
+            assert!(moved.is_err(), "{:?} before {:?}", kind, target);
+            assert_eq!(order(&graph), before);
+        }
+    }
+}
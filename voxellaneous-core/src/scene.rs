@@ -1,15 +1,501 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::bounds::Aabb;
+use crate::inflate;
+use crate::math;
 use crate::primitives::RGBA;
+use crate::utils::compose_trs;
+#[cfg(feature = "zstd")]
+use crate::zstd;
 
-/// A voxel object: an 8×8×8 grid of palette indices.
-#[derive(Serialize, Deserialize)]
+/// How an object stores its palette indices.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VoxelFormat {
+    /// One byte per voxel, an `R8Uint` texture: the first 256 palette entries.
+    #[default]
+    U8,
+    /// Two little-endian bytes per voxel, an `R16Uint` texture: up to 65536 palette
+    /// entries, those past 256 read from the palette texture. Indices past 255 share
+    /// the PBR material of index 255, which the G-buffer stores in 8 bits.
+    U16,
+}
+
+impl VoxelFormat {
+    pub fn bytes_per_voxel(self) -> usize {
+        match self {
+            VoxelFormat::U8 => 1,
+            VoxelFormat::U16 => 2,
+        }
+    }
+
+    pub fn texture_format(self) -> wgpu::TextureFormat {
+        match self {
+            VoxelFormat::U8 => wgpu::TextureFormat::R8Uint,
+            VoxelFormat::U16 => wgpu::TextureFormat::R16Uint,
+        }
+    }
+}
+
+/// How an object's voxels give their colour.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    /// Voxels are palette indices, in the object's `VoxelFormat`.
+    #[default]
+    Palette,
+    /// Voxels are four bytes of RGBA drawn as they are, an `Rgba8Unorm` texture; alpha
+    /// 0 is empty, whatever the empty index. With no palette index, they take the PBR
+    /// material of palette entry 0.
+    Rgba,
+}
+
+impl ColorMode {
+    /// Voxel access of the shaders marching voxel textures (shader.wgsl and
+    /// shadow_cascade.wgsl), appended to their source by `compose_shader`.
+    fn shader_source(self) -> &'static str {
+        match self {
+            ColorMode::Palette => include_str!("shaders/voxel_palette.wgsl"),
+            ColorMode::Rgba => include_str!("shaders/voxel_rgba.wgsl"),
+        }
+    }
+
+    /// `source` of a voxel-marching shader, completed for this mode's voxel texture.
+    pub fn compose_shader(self, source: &str) -> String {
+        format!("{}\n{}", source, self.shader_source())
+    }
+
+    /// Sample type of the voxel texture binding of the per-draw bind group layout.
+    pub fn sample_type(self) -> wgpu::TextureSampleType {
+        match self {
+            ColorMode::Palette => wgpu::TextureSampleType::Uint,
+            ColorMode::Rgba => wgpu::TextureSampleType::Float { filterable: false },
+        }
+    }
+}
+
+/// A voxel object: an 8×8×8 grid of palette indices or RGBA colours.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "VoxelObjectDesc")]
 pub struct VoxelObject {
     pub id: String,
     pub model_matrix: [f32; 16],
     pub inv_model_matrix: [f32; 16],
     pub dims: [u32; 3],
+    pub format: VoxelFormat,
+    pub color_mode: ColorMode,
+    /// Texel bytes of the grid, x fastest, then y, then z: one per voxel, two
+    /// (little-endian) for `VoxelFormat::U16`, or four for `ColorMode::Rgba`.
     pub voxels: Vec<u8>,
+    /// Draw priority; lower values are drawn first, ties keep scene order.
+    pub order: i32,
+    /// An rgba object to be turned into palette indices by `Scene::quantize_objects`.
+    pub quantize: bool,
+    /// Id of the object the transform is relative to, until `Scene::resolve_parents`
+    /// composes the matrices with the parent's.
+    pub parent: Option<String>,
+    /// Relative extent of a voxel along each axis, for anisotropic grids such as
+    /// scans; `[1; 3]` by default. The stretch is baked into the matrices, and keeps
+    /// rounded voxels in proportion.
+    pub voxel_size: [f32; 3],
+    /// Point the transform rotates and scales about, in voxel coordinates (`[0, dims]`
+    /// per axis; the centre, `dims / 2`, by default). It is baked into the matrices;
+    /// `set_pivot` moves it.
+    pub pivot: [f32; 3],
+}
+
+/// Wire form of `VoxelObject`: the transform is either an explicit `model_matrix`
+/// or `translation` / `rotation_quat` / `scale`, with the matrix taking precedence.
+/// Either is applied about `pivot`, a point of the grid in voxel coordinates as
+/// `VoxelObject::pivot` (default the centre, `dims / 2`), which is baked into the
+/// stored matrices, as is the stretch of
+/// `voxel_size` applied between the two. With a `parent`, the
+/// transform places the object in the parent's object space, the unit cube spanning
+/// the parent's grid.
+#[derive(Deserialize)]
+struct VoxelObjectDesc {
+    id: String,
+    model_matrix: Option<[f32; 16]>,
+    inv_model_matrix: Option<[f32; 16]>,
+    translation: Option<[f32; 3]>,
+    rotation_quat: Option<[f32; 4]>,
+    scale: Option<[f32; 3]>,
+    pivot: Option<[f32; 3]>,
+    dims: [u32; 3],
+    #[serde(default)]
+    format: VoxelFormat,
+    #[serde(default)]
+    color_mode: ColorMode,
+    /// Palette indices, or the RGBA bytes of each voxel; left out when the voxels come
+    /// separately
+    /// (`Renderer::upload_scene_with_voxels`)
+    #[serde(default)]
+    voxels: Vec<u16>,
+    #[serde(default)]
+    order: i32,
+    /// For rgba voxels: store them as palette indices of colours picked by `quantize`
+    #[serde(default)]
+    quantize: bool,
+    parent: Option<String>,
+    voxel_size: Option<[f32; 3]>,
+}
+
+impl TryFrom<VoxelObjectDesc> for VoxelObject {
+    type Error = String;
+
+    fn try_from(desc: VoxelObjectDesc) -> Result<Self, Self::Error> {
+        let (model_matrix, inv_model_matrix) = match desc.model_matrix {
+            Some(model_matrix) => (model_matrix, desc.inv_model_matrix),
+            None => (
+                compose_trs(
+                    desc.translation.unwrap_or([0.0; 3]),
+                    desc.rotation_quat.unwrap_or([0.0, 0.0, 0.0, 1.0]),
+                    desc.scale.unwrap_or([1.0; 3]),
+                ),
+                None,
+            ),
+        };
+        let inv_model_matrix = match inv_model_matrix {
+            Some(inv_model_matrix) => inv_model_matrix,
+            None => math::invert(&model_matrix)
+                .ok_or_else(|| format!("object '{}' has a singular model matrix", desc.id))?,
+        };
+        let voxel_size = desc.voxel_size.unwrap_or([1.0; 3]);
+        if voxel_size.iter().any(|s| !s.is_finite() || *s <= 0.0) {
+            return Err(format!(
+                "object '{}' has a voxel_size that is not finite and positive",
+                desc.id
+            ));
+        }
+        // Skipped for cubic voxels, which keeps their matrices bit for bit
+        let (model_matrix, inv_model_matrix) = if voxel_size == [1.0; 3] {
+            (model_matrix, inv_model_matrix)
+        } else {
+            let inv_size = voxel_size.map(|s| 1.0 / s);
+            (
+                math::multiply(&model_matrix, &math::scaling(voxel_size)),
+                math::multiply(&math::scaling(inv_size), &inv_model_matrix),
+            )
+        };
+        // The unit cube spans [-0.5, 0.5]^3, so the pivot moves to the transform's origin
+        let pivot = desc.pivot.unwrap_or(desc.dims.map(|dim| dim as f32 * 0.5));
+        let offset: [f32; 3] = std::array::from_fn(|a| pivot[a] / desc.dims[a] as f32 - 0.5);
+        let model_matrix = math::multiply(&model_matrix, &math::translation(offset.map(|p| -p)));
+        let inv_model_matrix = math::multiply(&math::translation(offset), &inv_model_matrix);
+
+        if desc.color_mode == ColorMode::Rgba && desc.format != VoxelFormat::U8 {
+            return Err(format!(
+                "object '{}' is rgba, whose voxels have no palette index format",
+                desc.id
+            ));
+        }
+        if desc.quantize && desc.color_mode != ColorMode::Rgba {
+            return Err(format!(
+                "object '{}' is quantized, which only rgba objects can be",
+                desc.id
+            ));
+        }
+        let voxels = match desc.format {
+            VoxelFormat::U8 => desc
+                .voxels
+                .iter()
+                .map(|&v| u8::try_from(v))
+                .collect::<Result<_, _>>()
+                .map_err(|_| match desc.color_mode {
+                    ColorMode::Palette => format!(
+                        "object '{}' has voxels above 255; give it the \"u16\" format",
+                        desc.id
+                    ),
+                    ColorMode::Rgba => {
+                        format!("object '{}' has colour bytes above 255", desc.id)
+                    }
+                })?,
+            VoxelFormat::U16 => desc.voxels.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        };
+
+        Ok(VoxelObject {
+            id: desc.id,
+            model_matrix,
+            inv_model_matrix,
+            dims: desc.dims,
+            format: desc.format,
+            color_mode: desc.color_mode,
+            voxels,
+            order: desc.order,
+            quantize: desc.quantize,
+            parent: desc.parent,
+            voxel_size,
+            pivot,
+        })
+    }
+}
+
+impl VoxelObject {
+    /// Centre of the grid in voxel coordinates, the default pivot.
+    pub fn centre(&self) -> [f32; 3] {
+        self.dims.map(|dim| dim as f32 * 0.5)
+    }
+
+    /// Point of the unit cube at voxel coordinates `voxel`.
+    fn object_space(&self, voxel: [f32; 3]) -> [f32; 3] {
+        std::array::from_fn(|a| voxel[a] / self.dims[a] as f32 - 0.5)
+    }
+
+    /// Moves the pivot to `pivot`, in voxel coordinates, keeping the transform: the
+    /// object shifts so that the new pivot is where the transform puts the old one.
+    pub fn set_pivot(&mut self, pivot: [f32; 3]) {
+        let (old, new) = (self.object_space(self.pivot), self.object_space(pivot));
+        let shift: [f32; 3] = std::array::from_fn(|a| old[a] - new[a]);
+        self.model_matrix = math::multiply(&self.model_matrix, &math::translation(shift));
+        self.inv_model_matrix = math::multiply(
+            &math::translation(shift.map(|s| -s)),
+            &self.inv_model_matrix,
+        );
+        self.pivot = pivot;
+    }
+
+    /// `voxel_size` over its smallest component, so the shortest voxel side is 1.
+    pub fn voxel_proportions(&self) -> [f32; 3] {
+        let shortest = self
+            .voxel_size
+            .iter()
+            .copied()
+            .fold(f32::INFINITY, f32::min);
+        self.voxel_size.map(|s| s / shortest)
+    }
+
+    /// Voxels of the grid, from `dims`; `None` past `usize`.
+    pub fn voxel_count(&self) -> Option<usize> {
+        self.dims
+            .iter()
+            .try_fold(1usize, |count, &dim| count.checked_mul(dim as usize))
+    }
+
+    /// Length `voxels` must have; `None` past `usize`.
+    pub fn voxel_bytes(&self) -> Option<usize> {
+        self.voxel_count()?.checked_mul(self.bytes_per_voxel())
+    }
+
+    pub fn bytes_per_voxel(&self) -> usize {
+        match self.color_mode {
+            ColorMode::Palette => self.format.bytes_per_voxel(),
+            ColorMode::Rgba => 4,
+        }
+    }
+
+    pub fn texture_format(&self) -> wgpu::TextureFormat {
+        match self.color_mode {
+            ColorMode::Palette => self.format.texture_format(),
+            ColorMode::Rgba => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+
+    /// Palette index of each voxel, in `voxels` order; only for `ColorMode::Palette`.
+    pub fn indices(&self) -> impl Iterator<Item = u16> + '_ {
+        self.voxels
+            .chunks_exact(self.format.bytes_per_voxel())
+            .map(texel_index)
+    }
+
+    /// Whether each voxel is filled, in `voxels` order: not `empty_index`, or of
+    /// non-zero alpha for `ColorMode::Rgba`.
+    pub fn filled(&self, empty_index: u16) -> impl Iterator<Item = bool> + '_ {
+        let color_mode = self.color_mode;
+        self.voxels
+            .chunks_exact(self.bytes_per_voxel())
+            .map(move |texel| texel_filled(color_mode, texel, empty_index))
+    }
+
+    /// `filled` of voxel `i` alone; false past the end of the grid.
+    pub fn is_filled(&self, i: usize, empty_index: u16) -> bool {
+        let width = self.bytes_per_voxel();
+        self.voxels
+            .get(i * width..(i + 1) * width)
+            .is_some_and(|texel| texel_filled(self.color_mode, texel, empty_index))
+    }
+
+    /// The object cut into a grid of objects of at most `max_dim` voxels a side, or
+    /// itself when it fits. Each piece keeps the id and order and covers its block of
+    /// voxels exactly where the object did: its unit cube is scaled and moved onto the
+    /// block, whose faces lie on voxel boundaries, so neighbouring pieces share their
+    /// faces and no voxel is in two. The pieces follow the voxel order, x fastest, each
+    /// with the first voxel of its block; `join` puts them back together.
+    pub fn split(self, max_dim: u32) -> Vec<([u32; 3], VoxelObject)> {
+        let max_dim = max_dim.max(1);
+        if self.dims.iter().all(|&dim| dim <= max_dim) {
+            return vec![([0; 3], self)];
+        }
+        let counts = self.dims.map(|dim| dim.div_ceil(max_dim));
+        let width = self.bytes_per_voxel();
+        let [nx, ny, _] = self.dims.map(|dim| dim as usize);
+        let mut pieces = Vec::with_capacity(counts.iter().product::<u32>() as usize);
+        for k in 0..counts[2] {
+            for j in 0..counts[1] {
+                for i in 0..counts[0] {
+                    let start = [i, j, k].map(|c| c * max_dim);
+                    let dims: [u32; 3] =
+                        std::array::from_fn(|a| max_dim.min(self.dims[a] - start[a]));
+                    let (local, inv_local) = piece_transform(start, dims, self.dims);
+
+                    let [sx, sy, sz] = start.map(|s| s as usize);
+                    let row = dims[0] as usize * width;
+                    let mut voxels = Vec::with_capacity(row * (dims[1] * dims[2]) as usize);
+                    for z in sz..sz + dims[2] as usize {
+                        for y in sy..sy + dims[1] as usize {
+                            let offset = ((z * ny + y) * nx + sx) * width;
+                            voxels.extend_from_slice(&self.voxels[offset..offset + row]);
+                        }
+                    }
+                    pieces.push((
+                        start,
+                        VoxelObject {
+                            id: self.id.clone(),
+                            model_matrix: math::multiply(&self.model_matrix, &local),
+                            inv_model_matrix: math::multiply(&inv_local, &self.inv_model_matrix),
+                            dims,
+                            format: self.format,
+                            color_mode: self.color_mode,
+                            voxels,
+                            order: self.order,
+                            quantize: false,
+                            parent: None,
+                            voxel_size: self.voxel_size,
+                            pivot: std::array::from_fn(|a| self.pivot[a] - start[a] as f32),
+                        },
+                    ));
+                }
+            }
+        }
+        pieces
+    }
+
+    /// The `dims` object that `split` cut into `pieces`, each with the first voxel of
+    /// its block, the one at the origin first.
+    pub fn join<'a>(
+        dims: [u32; 3],
+        pieces: impl IntoIterator<Item = ([u32; 3], &'a VoxelObject)>,
+    ) -> VoxelObject {
+        let mut pieces = pieces.into_iter().peekable();
+        let (_, first) = *pieces.peek().expect("an object has at least one piece");
+        let (local, inv_local) = piece_transform([0; 3], first.dims, dims);
+        let width = first.bytes_per_voxel();
+        let [nx, ny, nz] = dims.map(|dim| dim as usize);
+        let mut object = VoxelObject {
+            id: first.id.clone(),
+            model_matrix: math::multiply(&first.model_matrix, &inv_local),
+            inv_model_matrix: math::multiply(&local, &first.inv_model_matrix),
+            dims,
+            format: first.format,
+            color_mode: first.color_mode,
+            voxels: vec![0; nx * ny * nz * width],
+            order: first.order,
+            quantize: false,
+            parent: None,
+            voxel_size: first.voxel_size,
+            pivot: first.pivot,
+        };
+        for ([sx, sy, sz], piece) in pieces {
+            let row = piece.dims[0] as usize * width;
+            let (sx, sy, sz) = (sx as usize, sy as usize, sz as usize);
+            for (r, src) in piece.voxels.chunks_exact(row).enumerate() {
+                let y = sy + r % piece.dims[1] as usize;
+                let z = sz + r / piece.dims[1] as usize;
+                let offset = ((z * ny + y) * nx + sx) * width;
+                object.voxels[offset..offset + row].copy_from_slice(src);
+            }
+        }
+        object
+    }
+
+    /// Object-space box `(min, max)` around the voxels that are not `empty_index`,
+    /// inside the unit cube [-0.5, 0.5]^3 that spans the whole grid; `None` when the
+    /// object is empty.
+    pub fn content_bounds(&self, empty_index: u8) -> Option<([f32; 3], [f32; 3])> {
+        let [nx, ny, _] = self.dims.map(|dim| dim as usize);
+        let mut min = [usize::MAX; 3];
+        let mut max = [0usize; 3];
+        let filled = self
+            .filled(empty_index as u16)
+            .enumerate()
+            .filter(|&(_, f)| f);
+        for (i, _) in filled {
+            let voxel = [i % nx, i / nx % ny, i / (nx * ny)];
+            for axis in 0..3 {
+                min[axis] = min[axis].min(voxel[axis]);
+                max[axis] = max[axis].max(voxel[axis] + 1);
+            }
+        }
+        if min[0] == usize::MAX {
+            return None;
+        }
+        let to_object = |v: [usize; 3]| {
+            std::array::from_fn(|axis| v[axis] as f32 / self.dims[axis] as f32 - 0.5)
+        };
+        Some((to_object(min), to_object(max)))
+    }
+
+    /// Mean colour of the object's filled voxels, for the far-LOD placeholder;
+    /// transparent black when the object is empty.
+    #[allow(dead_code)]
+    pub fn average_color(&self, palette: &[RGBA]) -> RGBA {
+        let mut sum = [0u64; 4];
+        let mut count = 0u64;
+        let colors: Box<dyn Iterator<Item = RGBA>> = match self.color_mode {
+            ColorMode::Palette => Box::new(
+                self.indices()
+                    .filter(|&v| v != 0)
+                    .filter_map(|v| palette.get(v as usize).copied()),
+            ),
+            ColorMode::Rgba => Box::new(
+                self.voxels
+                    .chunks_exact(4)
+                    .filter(|c| c[3] != 0)
+                    .map(|c| RGBA(c[0], c[1], c[2], c[3])),
+            ),
+        };
+        for color in colors {
+            for (total, channel) in sum.iter_mut().zip([color.0, color.1, color.2, color.3]) {
+                *total += channel as u64;
+            }
+            count += 1;
+        }
+        if count == 0 {
+            return RGBA(0, 0, 0, 0);
+        }
+        let [r, g, b, a] = sum.map(|total| (total / count) as u8);
+        RGBA(r, g, b, a)
+    }
+}
+
+/// Matrix taking the unit cube of the `dims` block at voxel `start` of a `whole` grid
+/// onto the block within the grid's unit cube, and its inverse.
+pub fn piece_transform(start: [u32; 3], dims: [u32; 3], whole: [u32; 3]) -> ([f32; 16], [f32; 16]) {
+    let centre: [f32; 3] =
+        std::array::from_fn(|a| (start[a] as f32 + dims[a] as f32 * 0.5) / whole[a] as f32 - 0.5);
+    let size: [f32; 3] = std::array::from_fn(|a| dims[a] as f32 / whole[a] as f32);
+    let local = math::multiply(&math::translation(centre), &math::scaling(size));
+    let inv_local = math::multiply(
+        &math::scaling(size.map(|s| 1.0 / s)),
+        &math::translation(centre.map(|c| -c)),
+    );
+    (local, inv_local)
+}
+
+/// Little-endian palette index of one texel of `VoxelObject::voxels`.
+fn texel_index(texel: &[u8]) -> u16 {
+    texel
+        .iter()
+        .rev()
+        .fold(0, |index, &byte| index << 8 | byte as u16)
+}
+
+fn texel_filled(color_mode: ColorMode, texel: &[u8], empty_index: u16) -> bool {
+    match color_mode {
+        ColorMode::Palette => texel_index(texel) != empty_index,
+        ColorMode::Rgba => texel[3] != 0,
+    }
 }
 
 /// The scene containing a shared palette and multiple voxel objects.
@@ -18,3 +504,576 @@ pub struct Scene {
     pub palette: Vec<RGBA>,
     pub objects: Vec<VoxelObject>,
 }
+
+impl Scene {
+    /// Composes the transform of each object that has a `parent` with its parent's
+    /// world transform, parents first, so the stored matrices are world matrices and
+    /// no object has a parent left. Fails on a parent id no object has, or on a
+    /// cycle of parents. Of objects sharing an id, the first is the parent.
+    pub fn resolve_parents(&mut self) -> Result<(), String> {
+        if self.objects.iter().all(|obj| obj.parent.is_none()) {
+            return Ok(());
+        }
+        let mut index_of = HashMap::new();
+        for (i, obj) in self.objects.iter().enumerate() {
+            index_of.entry(obj.id.as_str()).or_insert(i);
+        }
+        let parents = self
+            .objects
+            .iter()
+            .map(|obj| match &obj.parent {
+                Some(parent) => match index_of.get(parent.as_str()) {
+                    Some(&index) => Ok(Some(index)),
+                    None => Err(format!(
+                        "object '{}' has parent '{}', which is not in the scene",
+                        obj.id, parent
+                    )),
+                },
+                None => Ok(None),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // (model, inverse) world matrices, filled in from the roots down
+        let mut world: Vec<Option<([f32; 16], [f32; 16])>> = vec![None; self.objects.len()];
+        for i in 0..self.objects.len() {
+            // Objects from `i` up to the first resolved ancestor or root
+            let mut chain: Vec<usize> = Vec::new();
+            let mut next = Some(i);
+            while let Some(j) = next.filter(|&j| world[j].is_none()) {
+                if let Some(start) = chain.iter().position(|&k| k == j) {
+                    let cycle: Vec<String> = chain[start..]
+                        .iter()
+                        .chain([&j])
+                        .map(|&k| format!("'{}'", self.objects[k].id))
+                        .collect();
+                    return Err(format!(
+                        "objects {} form a cycle of parents",
+                        cycle.join(" -> ")
+                    ));
+                }
+                chain.push(j);
+                next = parents[j];
+            }
+            for &j in chain.iter().rev() {
+                let local = &self.objects[j];
+                world[j] = Some(match parents[j].and_then(|p| world[p]) {
+                    Some((parent, inv_parent)) => (
+                        math::multiply(&parent, &local.model_matrix),
+                        math::multiply(&local.inv_model_matrix, &inv_parent),
+                    ),
+                    None => (local.model_matrix, local.inv_model_matrix),
+                });
+            }
+        }
+        for (obj, world) in self.objects.iter_mut().zip(world) {
+            (obj.model_matrix, obj.inv_model_matrix) = world.expect("every object is resolved");
+            obj.parent = None;
+        }
+        Ok(())
+    }
+}
+
+/// Palette entries `VoxelFormat::U16` indices reach.
+const MAX_INDEXED_COLORS: usize = u16::MAX as usize + 1;
+
+/// Running 64-bit FNV-1a hash.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+impl Scene {
+    /// Replaces each object's inverse with that of its model matrix, for inverses
+    /// that cannot be trusted. Fails on a singular matrix.
+    pub fn invert_model_matrices(&mut self) -> Result<(), String> {
+        for obj in &mut self.objects {
+            obj.inv_model_matrix = math::invert(&obj.model_matrix)
+                .ok_or_else(|| format!("object '{}' has a singular model matrix", obj.id))?;
+        }
+        Ok(())
+    }
+
+    /// 64-bit FNV-1a hash of `settings`, the renderer settings that change how the
+    /// scene uploads, the palette and each object's voxels and the fields that place
+    /// and draw them, but not of ids or parent ids, which only matter through the
+    /// matrices `resolve_parents` composes. Equal scenes hash alike.
+    pub fn content_hash(&self, settings: &[u8]) -> u64 {
+        let mut hash = Fnv1a::default();
+        hash.write(settings);
+        hash.write(&(self.palette.len() as u64).to_le_bytes());
+        for color in &self.palette {
+            hash.write(&[color.0, color.1, color.2, color.3]);
+        }
+        for obj in &self.objects {
+            hash.write(bytemuck::cast_slice(&obj.model_matrix));
+            hash.write(bytemuck::cast_slice(&obj.inv_model_matrix));
+            hash.write(bytemuck::cast_slice(&obj.dims));
+            hash.write(bytemuck::cast_slice(&obj.voxel_size));
+            hash.write(bytemuck::cast_slice(&obj.pivot));
+            hash.write(&obj.order.to_le_bytes());
+            hash.write(&[obj.format as u8, obj.color_mode as u8, obj.quantize as u8]);
+            hash.write(&(obj.voxels.len() as u64).to_le_bytes());
+            hash.write(&obj.voxels);
+        }
+        hash.0
+    }
+
+    /// Turns the objects with `quantize` set into palette objects, appending the
+    /// colours `quantize` picks for each (up to 255) to the palette. Objects whose
+    /// indices then pass 255 take the `VoxelFormat::U16` format. Objects whose voxels
+    /// do not match their dims are left for the upload to reject.
+    pub fn quantize_objects(&mut self) -> Result<(), String> {
+        for obj in self.objects.iter_mut().filter(|obj| obj.quantize) {
+            if obj.voxel_bytes() != Some(obj.voxels.len()) {
+                continue;
+            }
+            let (indices, colors) = quantize(&obj.voxels, obj.dims, 255);
+            // Index 0 stays empty, so the colours follow at least a placeholder entry
+            if self.palette.is_empty() {
+                self.palette.push(RGBA(0, 0, 0, 0));
+            }
+            let offset = self.palette.len() as u32 - 1;
+            if self.palette.len() + colors.len() - 1 > MAX_INDEXED_COLORS {
+                return Err(format!(
+                    "object '{}' needs {} more palette entries, past the {} a palette holds",
+                    obj.id,
+                    colors.len() - 1,
+                    MAX_INDEXED_COLORS
+                ));
+            }
+            let index = |i: u8| if i == 0 { 0 } else { offset + i as u32 };
+            obj.format = match offset + colors.len() as u32 - 1 {
+                0..=255 => VoxelFormat::U8,
+                _ => VoxelFormat::U16,
+            };
+            obj.voxels = match obj.format {
+                VoxelFormat::U8 => indices.iter().map(|&i| index(i) as u8).collect(),
+                VoxelFormat::U16 => indices
+                    .iter()
+                    .flat_map(|&i| (index(i) as u16).to_le_bytes())
+                    .collect(),
+            };
+            obj.color_mode = ColorMode::Palette;
+            obj.quantize = false;
+            self.palette
+                .extend(colors[1..].iter().map(|&[r, g, b, a]| RGBA(r, g, b, a)));
+        }
+        Ok(())
+    }
+}
+
+/// Up to `max_colors` (at most 255) colours standing for the filled voxels of the RGBA
+/// grid `rgba_voxels` of `dims`, by median cut: the distinct colours, weighted by their
+/// voxel counts, are split at the weighted median of the widest channel of the box of
+/// widest range until there are `max_colors` boxes, each taking the mean colour of its
+/// voxels. Returns one palette index per voxel, 0 for the voxels of alpha 0, and the
+/// palette they index, whose entry 0 is transparent black. Grids of at most
+/// `max_colors` distinct colours keep them exactly.
+pub fn quantize(rgba_voxels: &[u8], dims: [u32; 3], max_colors: usize) -> (Vec<u8>, Vec<[u8; 4]>) {
+    let voxel_count = dims.iter().map(|&dim| dim as usize).product::<usize>();
+    let texels = rgba_voxels.chunks_exact(4).take(voxel_count);
+    let mut counts: HashMap<[u8; 4], u64> = HashMap::new();
+    for texel in texels.clone().filter(|texel| texel[3] != 0) {
+        *counts
+            .entry([texel[0], texel[1], texel[2], texel[3]])
+            .or_default() += 1;
+    }
+    let mut colors: Vec<([u8; 4], u64)> = counts.into_iter().collect();
+    // Sorted so the palette does not depend on the hash order
+    colors.sort_unstable();
+
+    // Ranges of `colors`, each a box of the cut, and the palette index of each colour
+    let max_colors = max_colors.clamp(1, 255);
+    let mut boxes = Vec::with_capacity(max_colors);
+    boxes.push(0..colors.len());
+    while boxes.len() < max_colors {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, range)| range.len() > 1)
+            .map(|(i, range)| (i, widest_channel(&colors[range.clone()])))
+            .max_by_key(|&(_, (_, extent))| extent);
+        let Some((i, (channel, _))) = widest else {
+            break;
+        };
+        let range = boxes[i].clone();
+        let part = &mut colors[range.clone()];
+        part.sort_unstable_by_key(|&(color, _)| color[channel]);
+        // Weighted median, leaving at least one colour on either side
+        let total: u64 = part.iter().map(|&(_, count)| count).sum();
+        let mut below = 0;
+        let mut split = 1;
+        for (j, &(_, count)) in part.iter().enumerate().take(part.len() - 1) {
+            below += count;
+            split = j + 1;
+            if below * 2 >= total {
+                break;
+            }
+        }
+        boxes[i] = range.start..range.start + split;
+        boxes.push(range.start + split..range.end);
+    }
+
+    let mut palette = vec![[0u8; 4]];
+    let mut index_of: HashMap<[u8; 4], u8> = HashMap::with_capacity(colors.len());
+    for range in boxes.into_iter().filter(|range| !range.is_empty()) {
+        let part = &colors[range];
+        let total: u64 = part.iter().map(|&(_, count)| count).sum();
+        let mean = std::array::from_fn(|channel| {
+            let sum: u64 = part
+                .iter()
+                .map(|&(color, count)| color[channel] as u64 * count)
+                .sum();
+            ((sum + total / 2) / total) as u8
+        });
+        let index = palette.len() as u8;
+        palette.push(mean);
+        index_of.extend(part.iter().map(|&(color, _)| (color, index)));
+    }
+    let indices = texels
+        .map(|texel| match texel[3] {
+            0 => 0,
+            _ => index_of[&[texel[0], texel[1], texel[2], texel[3]]],
+        })
+        .collect();
+    (indices, palette)
+}
+
+/// Channel of `colors` with the widest range, and that range.
+fn widest_channel(colors: &[([u8; 4], u64)]) -> (usize, u8) {
+    (0..4)
+        .map(|channel| {
+            let values = colors.iter().map(|&(color, _)| color[channel]);
+            let extent = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+            (channel, extent)
+        })
+        .max_by_key(|&(_, extent)| extent)
+        .unwrap_or((0, 0))
+}
+
+/// Collision tests between the filled voxels of objects, for game logic. Voxels of
+/// palette index `empty_index` are empty, as in `VoxelObject::is_filled`, as are rgba
+/// voxels of alpha 0.
+impl Scene {
+    /// Whether the filled voxels of `a` and `b` overlap. After a test of their world
+    /// bounds, the centre of each filled voxel of `b` inside the overlap of the bounds
+    /// is carried into `a`'s grid (`b.model_matrix`, then `a.inv_model_matrix`) and
+    /// tested against the voxel there, so contacts thinner than a voxel of `b` can
+    /// be missed.
+    pub fn objects_intersect(a: &VoxelObject, b: &VoxelObject, empty_index: u8) -> bool {
+        let bounds_a = Aabb::from_model_matrix(&a.model_matrix);
+        let Some(overlap) = bounds_a.intersection(&Aabb::from_model_matrix(&b.model_matrix)) else {
+            return false;
+        };
+        // Only b's voxels whose centres can fall in the overlap
+        let local = overlap.transformed(&b.inv_model_matrix);
+        let range = |axis: usize| {
+            let n = b.dims[axis] as f32;
+            let lo = ((local.min[axis] + 0.5) * n - 0.5).floor().max(0.0) as usize;
+            let hi = ((local.max[axis] + 0.5) * n + 0.5).ceil().clamp(0.0, n) as usize;
+            lo..hi
+        };
+        let [bx, by, _] = b.dims.map(|dim| dim as usize);
+        let [ax, ay, _] = a.dims.map(|dim| dim as usize);
+        for z in range(2) {
+            for y in range(1) {
+                for x in range(0) {
+                    if !b.is_filled(x + bx * (y + by * z), empty_index as u16) {
+                        continue;
+                    }
+                    let center = std::array::from_fn(|axis| {
+                        ([x, y, z][axis] as f32 + 0.5) / b.dims[axis] as f32 - 0.5
+                    });
+                    let world = math::transform_point(&b.model_matrix, center);
+                    let in_a = math::transform_point(&a.inv_model_matrix, world);
+                    let voxel: [f32; 3] =
+                        std::array::from_fn(|axis| (in_a[axis] + 0.5) * a.dims[axis] as f32);
+                    if (0..3).any(|axis| !(0.0..a.dims[axis] as f32).contains(&voxel[axis])) {
+                        continue;
+                    }
+                    let [vx, vy, vz] = voxel.map(|c| c as usize);
+                    if a.is_filled(vx + ax * (vy + ay * vz), empty_index as u16) {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    /// Index pairs `(i, j)`, `i < j`, of all colliding objects.
+    #[allow(dead_code)]
+    pub fn find_collisions(&self, empty_index: u8) -> Vec<(usize, usize)> {
+        let objects: Vec<&VoxelObject> = self.objects.iter().collect();
+        colliding_pairs(&objects, empty_index)
+    }
+}
+
+/// `Scene::find_collisions` over `objects`. Pairs are found by sweeping the world
+/// bounds along x, so only objects overlapping in x are tested further.
+pub fn colliding_pairs(objects: &[&VoxelObject], empty_index: u8) -> Vec<(usize, usize)> {
+    let bounds: Vec<Aabb> = objects
+        .iter()
+        .map(|obj| Aabb::from_model_matrix(&obj.model_matrix))
+        .collect();
+    let mut by_min_x: Vec<usize> = (0..objects.len()).collect();
+    by_min_x.sort_by(|&i, &j| bounds[i].min[0].total_cmp(&bounds[j].min[0]));
+    let mut pairs = Vec::new();
+    for (k, &i) in by_min_x.iter().enumerate() {
+        for &j in &by_min_x[k + 1..] {
+            if bounds[j].min[0] >= bounds[i].max[0] {
+                break;
+            }
+            if Scene::objects_intersect(objects[i], objects[j], empty_index) {
+                pairs.push((i.min(j), i.max(j)));
+            }
+        }
+    }
+    pairs.sort_unstable();
+    pairs
+}
+
+/// Leading bytes of the binary scene format (`Scene::to_bytes`).
+const BINARY_MAGIC: &[u8; 4] = b"VXSC";
+/// Version 2 adds each object's `VoxelFormat`, version 3 its `ColorMode`, version 4
+/// its `voxel_size` and version 5 its `pivot`; scenes are written in the oldest
+/// version that holds them.
+const BINARY_VERSION: u32 = 5;
+/// Leading bytes of a zstd frame, recognized to reject it clearly without the `zstd`
+/// feature.
+const ZSTD_MAGIC: &[u8; 4] = &[0x28, 0xb5, 0x2f, 0xfd];
+/// Largest decompressed binary scene, so that a small corrupt or malicious stream
+/// cannot grow the output until wasm runs out of memory.
+const MAX_DECOMPRESSED_BYTES: usize = 512 << 20;
+
+/// Binary scene format, all integers and floats little-endian:
+///
+/// ```text
+/// magic "VXSC", version: u32
+/// palette_len: u32, palette_len × [r, g, b, a]: u8
+/// object_count: u32, then per object:
+///     id_len: u32, id: UTF-8 bytes
+///     model_matrix: 16 × f32, inv_model_matrix: 16 × f32
+///     dims: 3 × u32, order: i32
+///     format: u32, 0 for u8 or 1 for u16 (version 2 on)
+///     color_mode: u32, 0 for palette or 1 for rgba (version 3 on)
+///     voxel_size: 3 × f32 (version 4 on)
+///     pivot: 3 × f32, in voxel coordinates (version 5 on)
+///     voxels: dims[0] × dims[1] × dims[2] × (1, 2 or 4) bytes
+/// ```
+impl Scene {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let voxel_bytes: usize = self.objects.iter().map(|obj| obj.voxels.len()).sum();
+        let mut bytes = Vec::with_capacity(16 + self.palette.len() * 4 + voxel_bytes);
+        bytes.extend_from_slice(BINARY_MAGIC);
+        let rgba = self
+            .objects
+            .iter()
+            .any(|obj| obj.color_mode != ColorMode::Palette);
+        let wide = self.objects.iter().any(|obj| obj.format != VoxelFormat::U8);
+        let anisotropic = self.objects.iter().any(|obj| obj.voxel_size != [1.0; 3]);
+        let pivoted = self.objects.iter().any(|obj| obj.pivot != obj.centre());
+        let version: u32 = match (pivoted, anisotropic, rgba, wide) {
+            (true, ..) => 5,
+            (false, true, ..) => 4,
+            (false, false, true, _) => 3,
+            (false, false, false, true) => 2,
+            (false, false, false, false) => 1,
+        };
+        bytes.extend_from_slice(&version.to_le_bytes());
+
+        bytes.extend_from_slice(&(self.palette.len() as u32).to_le_bytes());
+        for color in &self.palette {
+            bytes.extend_from_slice(&[color.0, color.1, color.2, color.3]);
+        }
+
+        bytes.extend_from_slice(&(self.objects.len() as u32).to_le_bytes());
+        for obj in &self.objects {
+            bytes.extend_from_slice(&(obj.id.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(obj.id.as_bytes());
+            for value in obj.model_matrix.iter().chain(&obj.inv_model_matrix) {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            for dim in obj.dims {
+                bytes.extend_from_slice(&dim.to_le_bytes());
+            }
+            bytes.extend_from_slice(&obj.order.to_le_bytes());
+            if version >= 2 {
+                bytes.extend_from_slice(&(obj.format as u32).to_le_bytes());
+            }
+            if version >= 3 {
+                bytes.extend_from_slice(&(obj.color_mode as u32).to_le_bytes());
+            }
+            if version >= 4 {
+                for size in obj.voxel_size {
+                    bytes.extend_from_slice(&size.to_le_bytes());
+                }
+            }
+            if version >= 5 {
+                for p in obj.pivot {
+                    bytes.extend_from_slice(&p.to_le_bytes());
+                }
+            }
+            bytes.extend_from_slice(&obj.voxels);
+        }
+        bytes
+    }
+
+    /// `from_bytes` of a binary scene the host may have gzip- or zstd-compressed, told
+    /// apart by the leading bytes. Uncompressed scenes are parsed in place, without a
+    /// copy.
+    pub fn from_payload(bytes: &[u8]) -> Result<Scene, String> {
+        if inflate::is_gzip(bytes) {
+            Scene::from_bytes(&inflate::gunzip(bytes, MAX_DECOMPRESSED_BYTES)?)
+        } else if bytes.starts_with(ZSTD_MAGIC) {
+            Scene::from_zstd(bytes)
+        } else {
+            Scene::from_bytes(bytes)
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    fn from_zstd(bytes: &[u8]) -> Result<Scene, String> {
+        Scene::from_bytes(&zstd::decompress(bytes, MAX_DECOMPRESSED_BYTES)?)
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    fn from_zstd(_bytes: &[u8]) -> Result<Scene, String> {
+        Err("zstd-compressed scenes need the `zstd` feature of voxellaneous-core".to_string())
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Scene, String> {
+        let mut reader = ByteReader { bytes };
+        if reader.take(4)? != BINARY_MAGIC {
+            return Err("not a binary scene".to_string());
+        }
+        let version = reader.u32()?;
+        if !(1..=BINARY_VERSION).contains(&version) {
+            return Err(format!("unsupported binary scene version {}", version));
+        }
+
+        let palette_len = reader.u32()? as usize;
+        let palette = reader
+            .take(palette_len.checked_mul(4).ok_or("palette too large")?)?
+            .chunks_exact(4)
+            .map(|c| RGBA(c[0], c[1], c[2], c[3]))
+            .collect();
+
+        let object_count = reader.u32()?;
+        let mut objects = Vec::new();
+        for _ in 0..object_count {
+            let id_len = reader.u32()? as usize;
+            let id = String::from_utf8(reader.take(id_len)?.to_vec())
+                .map_err(|_| "object id is not valid UTF-8".to_string())?;
+            let model_matrix = reader.matrix()?;
+            let inv_model_matrix = reader.matrix()?;
+            let dims = [reader.u32()?, reader.u32()?, reader.u32()?];
+            let order = reader.u32()? as i32;
+            let format = match if version >= 2 { reader.u32()? } else { 0 } {
+                0 => VoxelFormat::U8,
+                1 => VoxelFormat::U16,
+                other => {
+                    return Err(format!(
+                        "object '{}' has unknown voxel format {}",
+                        id, other
+                    ))
+                }
+            };
+            let color_mode = match if version >= 3 { reader.u32()? } else { 0 } {
+                0 => ColorMode::Palette,
+                1 => ColorMode::Rgba,
+                other => return Err(format!("object '{}' has unknown color mode {}", id, other)),
+            };
+            if color_mode == ColorMode::Rgba && format != VoxelFormat::U8 {
+                return Err(format!("object '{}' is rgba with a u16 voxel format", id));
+            }
+            let voxel_size = if version >= 4 {
+                [reader.f32()?, reader.f32()?, reader.f32()?]
+            } else {
+                [1.0; 3]
+            };
+            if voxel_size.iter().any(|s| !s.is_finite() || *s <= 0.0) {
+                return Err(format!(
+                    "object '{}' has a voxel_size that is not finite and positive",
+                    id
+                ));
+            }
+            let pivot = if version >= 5 {
+                [reader.f32()?, reader.f32()?, reader.f32()?]
+            } else {
+                dims.map(|dim| dim as f32 * 0.5)
+            };
+            let texel_bytes = match color_mode {
+                ColorMode::Palette => format.bytes_per_voxel(),
+                ColorMode::Rgba => 4,
+            };
+            let voxel_bytes = dims
+                .iter()
+                .try_fold(texel_bytes, |count, &dim| count.checked_mul(dim as usize))
+                .ok_or_else(|| format!("object '{}' is too large", id))?;
+            let voxels = reader.take(voxel_bytes)?.to_vec();
+            objects.push(VoxelObject {
+                id,
+                model_matrix,
+                inv_model_matrix,
+                dims,
+                format,
+                color_mode,
+                voxels,
+                order,
+                quantize: false,
+                parent: None,
+                voxel_size,
+                pivot,
+            });
+        }
+
+        if !reader.bytes.is_empty() {
+            return Err("trailing bytes after binary scene".to_string());
+        }
+        Ok(Scene { palette, objects })
+    }
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < len {
+            return Err("binary scene is truncated".to_string());
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    fn matrix(&mut self) -> Result<[f32; 16], String> {
+        let mut matrix = [0.0; 16];
+        for value in &mut matrix {
+            *value = self.f32()?;
+        }
+        Ok(matrix)
+    }
+}
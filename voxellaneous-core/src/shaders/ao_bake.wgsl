@@ -0,0 +1,165 @@
+// Object-space ambient occlusion: cosine-weighted hemisphere rays from every exposed
+// face of every filled voxel, marched through the voxel grid with a DDA.
+// `cs_accumulate` adds one batch of samples to per-voxel hit counts; `cs_resolve`
+// turns the counts into R8Unorm occlusion, packed 4 voxels per u32 in rows padded to
+// whole words for a buffer-to-texture copy.
+
+struct AoParams {
+    dims:          vec3<u32>,
+    row_words:     u32,
+    sample_offset: u32,
+    sample_count:  u32,
+    total_samples: u32,
+    max_distance:  f32,
+    empty_index:   u32,
+};
+
+@group(0) @binding(0) var voxels: texture_3d<u32>;
+@group(0) @binding(1) var<storage, read_write> hits: array<u32>;
+@group(0) @binding(2) var<storage, read_write> ao_out: array<u32>;
+@group(0) @binding(3) var<uniform> u_params: AoParams;
+
+const FACE_NORMALS = array<vec3<i32>, 6>(
+    vec3<i32>( 1,  0,  0),
+    vec3<i32>(-1,  0,  0),
+    vec3<i32>( 0,  1,  0),
+    vec3<i32>( 0, -1,  0),
+    vec3<i32>( 0,  0,  1),
+    vec3<i32>( 0,  0, -1)
+);
+
+const PI = 3.14159265358979;
+
+// Voxels outside the grid count as empty, so rays leaving it are unoccluded
+fn is_filled(p: vec3<i32>) -> bool {
+    if any(p < vec3<i32>(0)) || any(p >= vec3<i32>(u_params.dims)) {
+        return false;
+    }
+    return textureLoad(voxels, p, 0).r != u_params.empty_index;
+}
+
+fn voxel_index(p: vec3<u32>) -> u32 {
+    return (p.z * u_params.dims.y + p.y) * u_params.dims.x + p.x;
+}
+
+fn pcg(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random_unit(seed: u32) -> f32 {
+    return f32(pcg(seed)) / 4294967296.0;
+}
+
+// Amanatides & Woo traversal starting in the (empty) cell containing `origin`
+fn ray_occluded(origin: vec3<f32>, dir: vec3<f32>) -> bool {
+    var cell = vec3<i32>(floor(origin));
+    let step = vec3<i32>(sign(dir));
+    let t_delta = 1.0 / max(abs(dir), vec3<f32>(1e-6));
+    let upper = select(vec3<f32>(0.0), vec3<f32>(1.0), dir > vec3<f32>(0.0));
+    let next_boundary = vec3<f32>(cell) + upper;
+    var t_max = abs(next_boundary - origin) * t_delta;
+
+    loop {
+        var t: f32;
+        if t_max.x < t_max.y && t_max.x < t_max.z {
+            t = t_max.x;
+            cell.x += step.x;
+            t_max.x += t_delta.x;
+        } else if t_max.y < t_max.z {
+            t = t_max.y;
+            cell.y += step.y;
+            t_max.y += t_delta.y;
+        } else {
+            t = t_max.z;
+            cell.z += step.z;
+            t_max.z += t_delta.z;
+        }
+        if t > u_params.max_distance
+            || any(cell < vec3<i32>(0))
+            || any(cell >= vec3<i32>(u_params.dims)) {
+            return false;
+        }
+        if is_filled(cell) {
+            return true;
+        }
+    }
+    return false;
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn cs_accumulate(@builtin(global_invocation_id) id: vec3<u32>) {
+    if any(id >= u_params.dims) {
+        return;
+    }
+    let p = vec3<i32>(id);
+    if !is_filled(p) {
+        return;
+    }
+
+    let index = voxel_index(id);
+    var occluded = 0u;
+    for (var face = 0u; face < 6u; face = face + 1u) {
+        let n = FACE_NORMALS[face];
+        if is_filled(p + n) {
+            continue;
+        }
+        let normal = vec3<f32>(n);
+        let tangent = normal.zxy;
+        let bitangent = cross(normal, tangent);
+        let origin = vec3<f32>(p) + vec3<f32>(0.5) + normal * 0.501;
+
+        for (var s = 0u; s < u_params.sample_count; s = s + 1u) {
+            let seed = pcg((index * 6u + face) ^ pcg(u_params.sample_offset + s));
+            let u1 = random_unit(seed);
+            let u2 = random_unit(seed ^ 0x9e3779b9u);
+            let r = sqrt(u1);
+            let phi = 2.0 * PI * u2;
+            let dir = tangent * (r * cos(phi))
+                + bitangent * (r * sin(phi))
+                + normal * sqrt(1.0 - u1);
+            if ray_occluded(origin, dir) {
+                occluded += 1u;
+            }
+        }
+    }
+    hits[index] += occluded;
+}
+
+// 1 = unoccluded; buried voxels resolve to 0 and empty ones to 1
+fn resolve_voxel(p: vec3<u32>) -> f32 {
+    let pi = vec3<i32>(p);
+    if !is_filled(pi) {
+        return 1.0;
+    }
+    var exposed = 0u;
+    for (var face = 0u; face < 6u; face = face + 1u) {
+        if !is_filled(pi + FACE_NORMALS[face]) {
+            exposed += 1u;
+        }
+    }
+    if exposed == 0u {
+        return 0.0;
+    }
+    let rays = f32(exposed * u_params.total_samples);
+    return 1.0 - f32(hits[voxel_index(p)]) / rays;
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn cs_resolve(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= u_params.row_words || id.y >= u_params.dims.y || id.z >= u_params.dims.z {
+        return;
+    }
+
+    var word = 0u;
+    for (var i = 0u; i < 4u; i = i + 1u) {
+        let x = id.x * 4u + i;
+        if x >= u_params.dims.x {
+            break;
+        }
+        let ao = resolve_voxel(vec3<u32>(x, id.y, id.z));
+        word = word | (u32(round(saturate(ao) * 255.0)) << (8u * i));
+    }
+    ao_out[(id.z * u_params.dims.y + id.y) * u_params.row_words + id.x] = word;
+}
@@ -0,0 +1,79 @@
+// Neighbour-counting voxel AO: occlusion is the share of filled voxels in the
+// (2r+1)³ cube around each filled voxel. Each workgroup caches its 4³ tile plus an
+// r-voxel apron in shared memory, then packs 4 voxels per u32 into rows padded to
+// whole words for a buffer-to-texture copy. The integer maths matches
+// `ao_bake::neighbour_ao_cpu` exactly.
+
+struct NeighbourAoParams {
+    dims:        vec3<u32>,
+    row_words:   u32,
+    radius:      u32,
+    empty_index: u32,
+};
+
+@group(0) @binding(0) var voxels: texture_3d<u32>;
+@group(0) @binding(1) var<storage, read_write> ao_out: array<u32>;
+@group(0) @binding(2) var<uniform> u_params: NeighbourAoParams;
+
+const TILE = 4u;
+// Tile plus apron for the largest radius (6): 16³ occupancy flags
+const MAX_SIDE = 16u;
+
+var<workgroup> occupancy: array<u32, 4096>;
+var<workgroup> packed: array<u32, 64>;
+
+fn is_filled(p: vec3<i32>) -> u32 {
+    if any(p < vec3<i32>(0)) || any(p >= vec3<i32>(u_params.dims)) {
+        return 0u;
+    }
+    return select(0u, 1u, textureLoad(voxels, p, 0).r != u_params.empty_index);
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn cs_main(
+    @builtin(workgroup_id) group: vec3<u32>,
+    @builtin(local_invocation_id) local: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    let r = u_params.radius;
+    let side = TILE + 2u * r;
+    let origin = vec3<i32>(group * TILE) - vec3<i32>(i32(r));
+
+    for (var i = local_index; i < side * side * side; i = i + 64u) {
+        let offset = vec3<u32>(i % side, (i / side) % side, i / (side * side));
+        occupancy[(offset.z * MAX_SIDE + offset.y) * MAX_SIDE + offset.x] =
+            is_filled(origin + vec3<i32>(offset));
+    }
+    workgroupBarrier();
+
+    let voxel = group * TILE + local;
+    var value = 255u;
+    let centre = local + vec3<u32>(r);
+    let filled = occupancy[(centre.z * MAX_SIDE + centre.y) * MAX_SIDE + centre.x];
+    if all(voxel < u_params.dims) && filled != 0u {
+        var occupied = 0u;
+        for (var z = local.z; z <= local.z + 2u * r; z = z + 1u) {
+            for (var y = local.y; y <= local.y + 2u * r; y = y + 1u) {
+                for (var x = local.x; x <= local.x + 2u * r; x = x + 1u) {
+                    occupied += occupancy[(z * MAX_SIDE + y) * MAX_SIDE + x];
+                }
+            }
+        }
+        let width = 2u * r + 1u;
+        let total = width * width * width - 1u;
+        // Excludes the voxel itself; radius 0 means no neighbours and no occlusion
+        occupied -= 1u;
+        if total > 0u {
+            value = (255u * (total - occupied) + total / 2u) / total;
+        }
+    }
+
+    packed[local_index] = value << (8u * local.x);
+    workgroupBarrier();
+
+    if local.x == 0u && voxel.y < u_params.dims.y && voxel.z < u_params.dims.z {
+        let word = packed[local_index] | packed[local_index + 1u]
+            | packed[local_index + 2u] | packed[local_index + 3u];
+        ao_out[(voxel.z * u_params.dims.y + voxel.y) * u_params.row_words + group.x] = word;
+    }
+}
@@ -0,0 +1,148 @@
+// Fills the G-buffer pixels skipped by the checkerboard raymarch. A skipped pixel is
+// reprojected into the previous frame's resolved G-buffer using the depth of its
+// rendered neighbours; when the history there does not match (disocclusion, or the
+// pixel was off-screen) it copies the nearest rendered neighbour instead.
+
+struct VSOut {
+    @builtin(position) position: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vi: u32) -> VSOut {
+    var corners = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>( 3.0, -1.0),
+        vec2<f32>(-1.0,  3.0)
+    );
+    var out: VSOut;
+    out.position = vec4<f32>(corners[vi], 0.0, 1.0);
+    return out;
+}
+
+struct ResolveUniforms {
+    inv_vp_matrix:  mat4x4<f32>,
+    prev_vp_matrix: mat4x4<f32>,
+    cam_pos_ws:     vec3<f32>,
+    // Pixels with (x + y) % 2 == parity were rendered this frame
+    parity:         u32,
+    prev_cam_pos_ws: vec3<f32>,
+    has_history:    u32,
+};
+
+@group(0) @binding(0) var cur_albedo:    texture_2d<f32>;
+@group(0) @binding(1) var cur_normal:    texture_2d<f32>;
+@group(0) @binding(2) var cur_linear_z:  texture_2d<u32>;
+@group(0) @binding(3) var cur_material:  texture_2d<u32>;
+@group(0) @binding(4) var hist_albedo:   texture_2d<f32>;
+@group(0) @binding(5) var hist_normal:   texture_2d<f32>;
+@group(0) @binding(6) var hist_linear_z: texture_2d<u32>;
+@group(0) @binding(7) var hist_material: texture_2d<u32>;
+@group(0) @binding(8) var<uniform> u_resolve: ResolveUniforms;
+
+struct GBuffer {
+    @location(0) albedo:   vec4<f32>,
+    @location(1) normal:   vec4<f32>,
+    @location(2) linear_z: u32,
+    @location(3) material: u32,
+    // Debug view: black = rendered, green = reprojected, red = interpolated
+    @location(4) status:   vec4<f32>,
+};
+
+// Encoding must match LINEAR_Z_MAX_DISTANCE / LINEAR_Z_MAX_VALUE in constants.rs
+fn decode_linear_z(v: u32) -> f32 {
+    return f32(v) / 65535.0 * 100.0;
+}
+
+fn encode_linear_z(distance: f32) -> u32 {
+    return u32(clamp(distance / 100.0, 0.0, 1.0) * 65535.0);
+}
+
+fn view_dir(pixel: vec2<f32>, size: vec2<f32>) -> vec3<f32> {
+    let ndc = vec2<f32>(pixel.x / size.x * 2.0 - 1.0, 1.0 - pixel.y / size.y * 2.0);
+    let near = u_resolve.inv_vp_matrix * vec4<f32>(ndc, 0.0, 1.0);
+    let far = u_resolve.inv_vp_matrix * vec4<f32>(ndc, 1.0, 1.0);
+    return normalize(far.xyz / far.w - near.xyz / near.w);
+}
+
+@fragment
+fn fs_main(in: VSOut) -> GBuffer {
+    let coord = vec2<i32>(in.position.xy);
+    let dims = vec2<i32>(textureDimensions(cur_albedo, 0));
+
+    if u32(coord.x + coord.y) % 2u == u_resolve.parity {
+        return GBuffer(
+            textureLoad(cur_albedo, coord, 0),
+            textureLoad(cur_normal, coord, 0),
+            textureLoad(cur_linear_z, coord, 0).r,
+            textureLoad(cur_material, coord, 0).r,
+            vec4<f32>(0.0, 0.0, 0.0, 1.0)
+        );
+    }
+
+    // The four edge neighbours all have the rendered parity
+    var offsets = array<vec2<i32>, 4>(
+        vec2<i32>(-1, 0), vec2<i32>(1, 0), vec2<i32>(0, -1), vec2<i32>(0, 1)
+    );
+    var nearest = vec2<i32>(-1);
+    var nearest_z = 0u;
+    var z_sum = 0.0;
+    var z_count = 0.0;
+    for (var i = 0; i < 4; i = i + 1) {
+        let n = coord + offsets[i];
+        if any(n < vec2<i32>(0)) || any(n >= dims) {
+            continue;
+        }
+        let z = textureLoad(cur_linear_z, n, 0).r;
+        if z == 0u {
+            continue;
+        }
+        z_sum += decode_linear_z(z);
+        z_count += 1.0;
+        if nearest.x < 0 || z < nearest_z {
+            nearest = n;
+            nearest_z = z;
+        }
+    }
+    let interpolated = vec4<f32>(1.0, 0.0, 0.0, 1.0);
+    if nearest.x < 0 {
+        // Surrounded by background
+        return GBuffer(vec4<f32>(0.0), vec4<f32>(0.0), 0u, 0u, interpolated);
+    }
+
+    if u_resolve.has_history != 0u {
+        let size = vec2<f32>(dims);
+        let distance = z_sum / z_count;
+        let world = u_resolve.cam_pos_ws + view_dir(in.position.xy, size) * distance;
+        let prev_clip = u_resolve.prev_vp_matrix * vec4<f32>(world, 1.0);
+        if prev_clip.w > 0.0 {
+            let prev_ndc = prev_clip.xy / prev_clip.w;
+            let prev_pixel = vec2<i32>(floor(
+                vec2<f32>(prev_ndc.x * 0.5 + 0.5, 0.5 - prev_ndc.y * 0.5) * size
+            ));
+            if all(prev_pixel >= vec2<i32>(0)) && all(prev_pixel < dims) {
+                let hist_z = textureLoad(hist_linear_z, prev_pixel, 0).r;
+                let expected = length(world - u_resolve.prev_cam_pos_ws);
+                let hist_distance = decode_linear_z(hist_z);
+                // Accept history that saw the same surface, within 5% + 2 cm
+                if hist_z != 0u && abs(hist_distance - expected) < expected * 0.05 + 0.02 {
+                    let current = hist_distance + length(world - u_resolve.cam_pos_ws) - expected;
+                    return GBuffer(
+                        textureLoad(hist_albedo, prev_pixel, 0),
+                        textureLoad(hist_normal, prev_pixel, 0),
+                        encode_linear_z(current),
+                        textureLoad(hist_material, prev_pixel, 0).r,
+                        vec4<f32>(0.0, 1.0, 0.0, 1.0)
+                    );
+                }
+            }
+        }
+    }
+
+    return GBuffer(
+        textureLoad(cur_albedo, nearest, 0),
+        textureLoad(cur_normal, nearest, 0),
+        nearest_z,
+        textureLoad(cur_material, nearest, 0).r,
+        interpolated
+    );
+}
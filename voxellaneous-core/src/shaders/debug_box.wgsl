@@ -0,0 +1,36 @@
+// Immediate-mode box edges over the final frame, see debug_boxes.rs
+
+struct PerFrameUniforms {
+    vp_matrix:  mat4x4<f32>,
+    cam_pos_ws: vec3<f32>,
+    voxel_rounding: f32,
+};
+@group(0) @binding(0) var<uniform> u_frame: PerFrameUniforms;
+
+struct VertexInput {
+    // Corner of the unit cube, in [-0.5, 0.5]
+    @location(0) position: vec3<f32>,
+    // World-space corners of the box
+    @location(1) box_min:  vec3<f32>,
+    @location(2) box_max:  vec3<f32>,
+    @location(3) color:    vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let ws = mix(in.box_min, in.box_max, in.position + vec3<f32>(0.5));
+    out.position = u_frame.vp_matrix * vec4<f32>(ws, 1.0);
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
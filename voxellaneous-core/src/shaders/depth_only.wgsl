@@ -0,0 +1,28 @@
+// Depth-only rendering of the voxel proxy cubes and user meshes, for shadow maps and
+// the depth pre-pass. There is no fragment stage: only rasterized depth is written.
+
+struct LightUniforms {
+    vp_matrix: mat4x4<f32>,
+};
+@group(0) @binding(0) var<uniform> u_light: LightUniforms;
+
+struct PerDrawUniforms {
+    model_matrix:     mat4x4<f32>,
+    inv_model_matrix: mat4x4<f32>,
+    proxy_min:        vec3<f32>,
+    proxy_max:        vec3<f32>,
+};
+@group(1) @binding(1) var<uniform> u_draw: PerDrawUniforms;
+
+@vertex
+fn vs_voxel(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    // Same proxy box as vs_main in shader.wgsl
+    let obj_pos = mix(u_draw.proxy_min, u_draw.proxy_max, position + vec3<f32>(0.5));
+    return u_light.vp_matrix * u_draw.model_matrix * vec4<f32>(obj_pos, 1.0);
+}
+
+// Mesh vertices are already in world space
+@vertex
+fn vs_mesh(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return u_light.vp_matrix * vec4<f32>(position, 1.0);
+}
@@ -0,0 +1,44 @@
+struct VSOut {
+    @builtin(position) Position: vec4<f32>,
+    @location(0)       uv:       vec2<f32>,
+};
+
+struct DepthResolveUniforms {
+    inv_vp_matrix: mat4x4<f32>,
+    cam_pos_ws:    vec3<f32>,
+    _padding:      f32,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vi: u32) -> VSOut {
+    var corners = array<vec2<f32>,3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>( 3.0, -1.0),
+        vec2<f32>(-1.0,  3.0)
+    );
+    var out: VSOut;
+    out.Position = vec4<f32>(corners[vi], 0.0, 1.0);
+    out.uv       = corners[vi] * 0.5 + vec2<f32>(0.5);
+    return out;
+}
+
+@group(0) @binding(0) var depth_tex: texture_depth_2d;
+@group(0) @binding(1) var<uniform> u_resolve: DepthResolveUniforms;
+
+// Writes the world-space distance from the camera, or +inf for background.
+@fragment
+fn fs_main(in: VSOut) -> @location(0) f32 {
+    let dims = textureDimensions(depth_tex, 0);
+    let coord = vec2<i32>(
+        i32(in.uv.x * f32(dims.x)),
+        i32((1.0 - in.uv.y) * f32(dims.y))
+    );
+    let depth = textureLoad(depth_tex, coord, 0);
+    if depth >= 1.0 {
+        return bitcast<f32>(0x7f800000u);
+    }
+
+    let ndc = vec4<f32>(in.uv * 2.0 - 1.0, depth, 1.0);
+    let ws = u_resolve.inv_vp_matrix * ndc;
+    return length(ws.xyz / ws.w - u_resolve.cam_pos_ws);
+}
@@ -0,0 +1,181 @@
+// FXAA 3.11 (quality variant), after Timothy Lottes' reference implementation.
+
+struct VSOut {
+    @builtin(position) Position: vec4<f32>,
+    @location(0)       uv:       vec2<f32>,
+};
+
+struct FxaaUniforms {
+    subpixel_quality:   f32,
+    edge_threshold:     f32,
+    edge_threshold_min: f32,
+    _padding:           f32,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vi: u32) -> VSOut {
+    var corners = array<vec2<f32>,3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>( 3.0, -1.0),
+        vec2<f32>(-1.0,  3.0)
+    );
+    var out: VSOut;
+    out.Position = vec4<f32>(corners[vi], 0.0, 1.0);
+    // Texture-space uv (y down)
+    out.uv = vec2<f32>(corners[vi].x * 0.5 + 0.5, 0.5 - corners[vi].y * 0.5);
+    return out;
+}
+
+@group(0) @binding(0) var u_tex: texture_2d<f32>;
+@group(0) @binding(1) var u_samp: sampler;
+@group(0) @binding(2) var<uniform> u_fxaa: FxaaUniforms;
+
+const EDGE_SEARCH_STEPS: u32 = 12u;
+
+fn edge_step(i: u32) -> f32 {
+    var steps = array<f32, 12>(1.0, 1.0, 1.0, 1.0, 1.0, 1.5, 2.0, 2.0, 2.0, 2.0, 4.0, 8.0);
+    return steps[i];
+}
+
+fn luma(rgb: vec3<f32>) -> f32 {
+    return sqrt(dot(rgb, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+fn luma_at(uv: vec2<f32>) -> f32 {
+    return luma(textureSampleLevel(u_tex, u_samp, uv, 0.0).rgb);
+}
+
+fn luma_off(uv: vec2<f32>, off: vec2<f32>, texel: vec2<f32>) -> f32 {
+    return luma_at(uv + off * texel);
+}
+
+@fragment
+fn fs_main(in: VSOut) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(u_tex, 0));
+    let uv = in.uv;
+    let center = textureSampleLevel(u_tex, u_samp, uv, 0.0);
+
+    let luma_c = luma(center.rgb);
+    let luma_d = luma_off(uv, vec2<f32>( 0.0,  1.0), texel);
+    let luma_u = luma_off(uv, vec2<f32>( 0.0, -1.0), texel);
+    let luma_l = luma_off(uv, vec2<f32>(-1.0,  0.0), texel);
+    let luma_r = luma_off(uv, vec2<f32>( 1.0,  0.0), texel);
+
+    let luma_min = min(luma_c, min(min(luma_d, luma_u), min(luma_l, luma_r)));
+    let luma_max = max(luma_c, max(max(luma_d, luma_u), max(luma_l, luma_r)));
+    let range = luma_max - luma_min;
+
+    // Early exit on low-contrast pixels
+    if range < max(u_fxaa.edge_threshold_min, luma_max * u_fxaa.edge_threshold) {
+        return center;
+    }
+
+    let luma_dl = luma_off(uv, vec2<f32>(-1.0,  1.0), texel);
+    let luma_ur = luma_off(uv, vec2<f32>( 1.0, -1.0), texel);
+    let luma_ul = luma_off(uv, vec2<f32>(-1.0, -1.0), texel);
+    let luma_dr = luma_off(uv, vec2<f32>( 1.0,  1.0), texel);
+
+    let luma_du = luma_d + luma_u;
+    let luma_lr = luma_l + luma_r;
+    let luma_left_corners = luma_dl + luma_ul;
+    let luma_down_corners = luma_dl + luma_dr;
+    let luma_right_corners = luma_dr + luma_ur;
+    let luma_up_corners = luma_ur + luma_ul;
+
+    let edge_h = abs(-2.0 * luma_l + luma_left_corners)
+        + abs(-2.0 * luma_c + luma_du) * 2.0
+        + abs(-2.0 * luma_r + luma_right_corners);
+    let edge_v = abs(-2.0 * luma_u + luma_up_corners)
+        + abs(-2.0 * luma_c + luma_lr) * 2.0
+        + abs(-2.0 * luma_d + luma_down_corners);
+    let is_horizontal = edge_h >= edge_v;
+
+    // Pick the side of the edge with the steepest gradient
+    let luma1 = select(luma_l, luma_u, is_horizontal);
+    let luma2 = select(luma_r, luma_d, is_horizontal);
+    let gradient1 = luma1 - luma_c;
+    let gradient2 = luma2 - luma_c;
+    let is_1_steepest = abs(gradient1) >= abs(gradient2);
+    let gradient_scaled = 0.25 * max(abs(gradient1), abs(gradient2));
+
+    var step_length = select(texel.x, texel.y, is_horizontal);
+    var luma_local_avg = 0.0;
+    if is_1_steepest {
+        step_length = -step_length;
+        luma_local_avg = 0.5 * (luma1 + luma_c);
+    } else {
+        luma_local_avg = 0.5 * (luma2 + luma_c);
+    }
+
+    var current_uv = uv;
+    if is_horizontal {
+        current_uv.y += step_length * 0.5;
+    } else {
+        current_uv.x += step_length * 0.5;
+    }
+
+    // Walk along the edge in both directions until its ends are found
+    let offset = select(vec2<f32>(0.0, texel.y), vec2<f32>(texel.x, 0.0), is_horizontal);
+    var uv1 = current_uv - offset;
+    var uv2 = current_uv + offset;
+    var luma_end1 = luma_at(uv1) - luma_local_avg;
+    var luma_end2 = luma_at(uv2) - luma_local_avg;
+    var reached1 = abs(luma_end1) >= gradient_scaled;
+    var reached2 = abs(luma_end2) >= gradient_scaled;
+    if !reached1 {
+        uv1 -= offset;
+    }
+    if !reached2 {
+        uv2 += offset;
+    }
+
+    for (var i = 2u; i < EDGE_SEARCH_STEPS; i = i + 1u) {
+        if reached1 && reached2 {
+            break;
+        }
+        if !reached1 {
+            luma_end1 = luma_at(uv1) - luma_local_avg;
+            reached1 = abs(luma_end1) >= gradient_scaled;
+            if !reached1 {
+                uv1 -= offset * edge_step(i);
+            }
+        }
+        if !reached2 {
+            luma_end2 = luma_at(uv2) - luma_local_avg;
+            reached2 = abs(luma_end2) >= gradient_scaled;
+            if !reached2 {
+                uv2 += offset * edge_step(i);
+            }
+        }
+    }
+
+    let distance1 = select(uv.y - uv1.y, uv.x - uv1.x, is_horizontal);
+    let distance2 = select(uv2.y - uv.y, uv2.x - uv.x, is_horizontal);
+    let is_direction1 = distance1 < distance2;
+    let distance_final = min(distance1, distance2);
+    let edge_thickness = distance1 + distance2;
+
+    // Only blend when the pixel is on the side the edge's end bends towards
+    let is_luma_center_smaller = luma_c < luma_local_avg;
+    let correct_variation1 = (luma_end1 < 0.0) != is_luma_center_smaller;
+    let correct_variation2 = (luma_end2 < 0.0) != is_luma_center_smaller;
+    let correct_variation = select(correct_variation2, correct_variation1, is_direction1);
+
+    let pixel_offset = -distance_final / edge_thickness + 0.5;
+    var final_offset = select(0.0, pixel_offset, correct_variation);
+
+    // Sub-pixel anti-aliasing
+    let luma_average = (1.0 / 12.0) * (2.0 * (luma_du + luma_lr) + luma_left_corners + luma_right_corners);
+    let sub_pixel_offset1 = clamp(abs(luma_average - luma_c) / range, 0.0, 1.0);
+    let sub_pixel_offset2 = (-2.0 * sub_pixel_offset1 + 3.0) * sub_pixel_offset1 * sub_pixel_offset1;
+    let sub_pixel_offset_final = sub_pixel_offset2 * sub_pixel_offset2 * u_fxaa.subpixel_quality;
+    final_offset = max(final_offset, sub_pixel_offset_final);
+
+    var final_uv = uv;
+    if is_horizontal {
+        final_uv.y += final_offset * step_length;
+    } else {
+        final_uv.x += final_offset * step_length;
+    }
+    return textureSampleLevel(u_tex, u_samp, final_uv, 0.0);
+}
@@ -0,0 +1,26 @@
+// Stencil marking of the highlight region's box, see highlight.rs
+
+struct PerFrameUniforms {
+    vp_matrix:  mat4x4<f32>,
+    cam_pos_ws: vec3<f32>,
+    voxel_rounding: f32,
+};
+@group(0) @binding(0) var<uniform> u_frame: PerFrameUniforms;
+
+struct VertexInput {
+    // Corner of the unit cube, in [-0.5, 0.5]
+    @location(0) position:   vec3<f32>,
+    // World-space corners of the region
+    @location(1) region_min: vec3<f32>,
+    @location(2) region_max: vec3<f32>,
+};
+
+@vertex
+fn vs_main(in: VertexInput) -> @builtin(position) vec4<f32> {
+    let ws = mix(in.region_min, in.region_max, in.position + vec3<f32>(0.5));
+    return u_frame.vp_matrix * vec4<f32>(ws, 1.0);
+}
+
+// Only the depth and stencil tests matter; the colour targets are masked
+@fragment
+fn fs_main() {}
@@ -0,0 +1,48 @@
+// Hi-Z pyramid of the previous frame's linear-Z: each texel holds the farthest view
+// distance of the pixels it covers, so a box nearer than that is hidden behind them.
+// Level 0 is half the G-buffer resolution; empty pixels count as infinitely far.
+
+@group(0) @binding(0) var src_linear_z: texture_2d<u32>;
+@group(0) @binding(1) var dst_level: texture_storage_2d<r32float, write>;
+@group(0) @binding(2) var src_level: texture_2d<f32>;
+
+const FAR: f32 = 1e30;
+
+// Encoding must match LINEAR_Z_MAX_DISTANCE / LINEAR_Z_MAX_VALUE in constants.rs.
+// Saturated values are beyond the encodable range, so they are treated as far too.
+fn decode_linear_z(v: u32) -> f32 {
+    if v == 0u || v >= 65535u {
+        return FAR;
+    }
+    return f32(v) / 65535.0 * 100.0;
+}
+
+@compute @workgroup_size(8, 8)
+fn build_first(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dst_dims = textureDimensions(dst_level);
+    if any(id.xy >= dst_dims) {
+        return;
+    }
+    let src_max = textureDimensions(src_linear_z, 0) - vec2<u32>(1u);
+    var farthest = 0.0;
+    for (var i = 0u; i < 4u; i = i + 1u) {
+        let p = min(id.xy * 2u + vec2<u32>(i & 1u, i >> 1u), src_max);
+        farthest = max(farthest, decode_linear_z(textureLoad(src_linear_z, p, 0).r));
+    }
+    textureStore(dst_level, id.xy, vec4<f32>(farthest, 0.0, 0.0, 0.0));
+}
+
+@compute @workgroup_size(8, 8)
+fn downsample(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dst_dims = textureDimensions(dst_level);
+    if any(id.xy >= dst_dims) {
+        return;
+    }
+    let src_max = textureDimensions(src_level, 0) - vec2<u32>(1u);
+    var farthest = 0.0;
+    for (var i = 0u; i < 4u; i = i + 1u) {
+        let p = min(id.xy * 2u + vec2<u32>(i & 1u, i >> 1u), src_max);
+        farthest = max(farthest, textureLoad(src_level, p, 0).r);
+    }
+    textureStore(dst_level, id.xy, vec4<f32>(farthest, 0.0, 0.0, 0.0));
+}
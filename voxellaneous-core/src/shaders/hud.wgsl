@@ -0,0 +1,58 @@
+// Debug HUD text over the final frame, see hud.rs
+
+struct HudUniforms {
+    target_size: vec2<f32>,
+    // Screen pixels per font pixel
+    scale:       f32,
+    _padding:    f32,
+};
+
+// The glyphs of hud.rs's FONT side by side, glyph i at x = 5i
+@group(0) @binding(0) var font_tex: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> u_hud: HudUniforms;
+
+// GLYPH_WIDTH / GLYPH_HEIGHT in hud.rs
+const GLYPH_SIZE: vec2<i32> = vec2<i32>(5, 7);
+// Each glyph is padded by one font pixel on every side
+const CELL_SIZE: vec2<f32> = vec2<f32>(7.0, 9.0);
+// Distance of the text from the top-left corner, in screen pixels
+const MARGIN: f32 = 4.0;
+
+struct VSOut {
+    @builtin(position) position: vec4<f32>,
+    // Position in the cell in font pixels, from its top-left corner
+    @location(0) local: vec2<f32>,
+    @location(1) @interpolate(flat) glyph: u32,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vi: u32,
+    @location(0) cell: vec2<u32>,
+    @location(1) glyph: u32,
+) -> VSOut {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0), vec2<f32>(1.0, 0.0), vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0), vec2<f32>(1.0, 0.0), vec2<f32>(1.0, 1.0)
+    );
+    let local = corners[vi] * CELL_SIZE;
+    let pixel = vec2<f32>(MARGIN) + (vec2<f32>(cell) * CELL_SIZE + local) * u_hud.scale;
+    let ndc = pixel / u_hud.target_size * 2.0 - vec2<f32>(1.0);
+    var out: VSOut;
+    out.position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.local = local;
+    out.glyph = glyph;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VSOut) -> @location(0) vec4<f32> {
+    let texel = vec2<i32>(floor(in.local)) - vec2<i32>(1);
+    var ink = 0.0;
+    if all(texel >= vec2<i32>(0)) && all(texel < GLYPH_SIZE) {
+        let atlas = vec2<i32>(i32(in.glyph) * GLYPH_SIZE.x + texel.x, texel.y);
+        ink = textureLoad(font_tex, atlas, 0).r;
+    }
+    // White text on a translucent black backdrop
+    return mix(vec4<f32>(0.0, 0.0, 0.0, 0.6), vec4<f32>(1.0), ink);
+}
@@ -0,0 +1,149 @@
+// Per-tile point light lists for quad_lighting.wgsl, see light_culling.rs. One
+// workgroup per 16x16 tile: the tile's linear-Z range bounds its slice of the view
+// frustum, each thread tests a share of the lights against it, and the hits are kept
+// in light index order so the lighting sums are the same every frame.
+
+struct LightTiles {
+    inv_vp_matrix:   mat4x4<f32>,
+    camera_position: vec3<f32>,
+    light_count:     u32,
+    size:            vec2<u32>,
+    tiles_x:         u32,
+    debug:           u32,
+};
+
+struct PointLight {
+    position:  vec3<f32>,
+    radius:    f32,
+    color:     vec3<f32>,
+    intensity: f32,
+};
+
+@group(0) @binding(0) var<uniform> u_tiles: LightTiles;
+@group(0) @binding(1) var<storage, read> lights: array<PointLight>;
+@group(0) @binding(2) var linear_z_tex: texture_2d<u32>;
+@group(0) @binding(3) var<storage, read_write> tile_lights: array<u32>;
+
+const TILE_SIZE: u32 = 16u;
+// Count, then up to 63 light indices; must match light_culling.rs
+const TILE_WORDS: u32 = 64u;
+const MAX_LIGHTS_PER_TILE: u32 = 63u;
+// One bit per light of MAX_POINT_LIGHTS
+const MASK_WORDS: u32 = 32u;
+
+var<workgroup> z_min: atomic<u32>;
+var<workgroup> z_max: atomic<u32>;
+var<workgroup> hits: array<atomic<u32>, 32>;
+
+// Decoding must match LINEAR_Z_MAX_DISTANCE / LINEAR_Z_MAX_VALUE in constants.rs
+fn decode_linear_z(z: f32) -> f32 {
+    return z / 65535.0 * 100.0;
+}
+
+struct Ray {
+    origin: vec3<f32>,
+    dir:    vec3<f32>,
+};
+
+// View ray through pixel corner `pixel` of the G-buffer
+fn corner_ray(pixel: vec2<u32>) -> Ray {
+    let uv = vec2<f32>(pixel) / vec2<f32>(u_tiles.size);
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    let near = u_tiles.inv_vp_matrix * vec4<f32>(ndc, 0.0, 1.0);
+    let far = u_tiles.inv_vp_matrix * vec4<f32>(ndc, 1.0, 1.0);
+    let origin = near.xyz / near.w;
+    return Ray(origin, normalize(far.xyz / far.w - origin));
+}
+
+// Side plane of the tile through the rays `a` and `b`, facing the point `inside`
+fn side_plane(a: Ray, b: Ray, inside: vec3<f32>) -> vec4<f32> {
+    var n = normalize(cross(a.dir, b.origin + b.dir - a.origin));
+    if dot(n, inside - a.origin) < 0.0 {
+        n = -n;
+    }
+    return vec4<f32>(n, -dot(n, a.origin));
+}
+
+@compute @workgroup_size(16, 16, 1)
+fn cs_main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(workgroup_id) tile: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+) {
+    if local == 0u {
+        atomicStore(&z_min, 0xffffffffu);
+        atomicStore(&z_max, 0u);
+    }
+    if local < MASK_WORDS {
+        atomicStore(&hits[local], 0u);
+    }
+    workgroupBarrier();
+
+    if all(id.xy < u_tiles.size) {
+        let z = textureLoad(linear_z_tex, vec2<i32>(id.xy), 0).r;
+        // 0 marks pixels nothing was drawn to
+        if z != 0u {
+            atomicMin(&z_min, z);
+            atomicMax(&z_max, z);
+        }
+    }
+    workgroupBarrier();
+
+    let z_far = atomicLoad(&z_max);
+    // Tiles with no geometry are not lit
+    if z_far != 0u {
+        // Widened by a step to cover the quantization of linear-Z
+        let near_distance = decode_linear_z(max(f32(atomicLoad(&z_min)) - 1.0, 0.0));
+        let far_distance = decode_linear_z(f32(z_far) + 1.0);
+
+        let lo = tile.xy * TILE_SIZE;
+        let hi = min(lo + vec2<u32>(TILE_SIZE), u_tiles.size);
+        var corners = array<Ray, 4>(
+            corner_ray(lo),
+            corner_ray(vec2<u32>(hi.x, lo.y)),
+            corner_ray(hi),
+            corner_ray(vec2<u32>(lo.x, hi.y))
+        );
+        let centre = corner_ray((lo + hi) / 2u);
+        let inside = centre.origin + centre.dir;
+        var planes: array<vec4<f32>, 4>;
+        for (var i = 0u; i < 4u; i = i + 1u) {
+            planes[i] = side_plane(corners[i], corners[(i + 1u) % 4u], inside);
+        }
+
+        for (var l = local; l < u_tiles.light_count; l = l + 256u) {
+            let light = lights[l];
+            // Linear-Z is the distance from the camera, so the depth range is a shell
+            let distance = length(light.position - u_tiles.camera_position);
+            if distance + light.radius < near_distance || distance - light.radius > far_distance {
+                continue;
+            }
+            var outside = false;
+            for (var i = 0u; i < 4u; i = i + 1u) {
+                if dot(planes[i].xyz, light.position) + planes[i].w < -light.radius {
+                    outside = true;
+                }
+            }
+            if !outside {
+                atomicOr(&hits[l / 32u], 1u << (l % 32u));
+            }
+        }
+    }
+    workgroupBarrier();
+
+    if local != 0u {
+        return;
+    }
+    let base = (tile.y * u_tiles.tiles_x + tile.x) * TILE_WORDS;
+    var count = 0u;
+    for (var w = 0u; w < MASK_WORDS; w = w + 1u) {
+        var bits = atomicLoad(&hits[w]);
+        while bits != 0u && count < MAX_LIGHTS_PER_TILE {
+            let bit = firstTrailingBit(bits);
+            tile_lights[base + 1u + count] = w * 32u + bit;
+            count = count + 1u;
+            bits = bits & (bits - 1u);
+        }
+    }
+    tile_lights[base] = count;
+}
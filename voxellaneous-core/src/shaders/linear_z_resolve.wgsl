@@ -0,0 +1,29 @@
+// Resolves a multisampled linear-Z target to one sample per pixel. Integer formats
+// cannot be resolved by the hardware, and an average of distances would invent depths
+// between the surfaces at an edge, so each pixel keeps its nearest covered sample.
+
+@vertex
+fn vs_main(@builtin(vertex_index) vi: u32) -> @builtin(position) vec4<f32> {
+    var corners = array<vec2<f32>,3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>( 3.0, -1.0),
+        vec2<f32>(-1.0,  3.0)
+    );
+    return vec4<f32>(corners[vi], 0.0, 1.0);
+}
+
+@group(0) @binding(0) var linear_z_tex: texture_multisampled_2d<u32>;
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) u32 {
+    let coord = vec2<i32>(position.xy);
+    // 0 marks background, which only wins when no sample was covered
+    var nearest = 0u;
+    for (var i = 0u; i < textureNumSamples(linear_z_tex); i = i + 1u) {
+        let z = textureLoad(linear_z_tex, coord, i32(i)).r;
+        if z != 0u && (nearest == 0u || z < nearest) {
+            nearest = z;
+        }
+    }
+    return nearest;
+}
@@ -0,0 +1,95 @@
+// Layout of `MeshVertex` in constants.rs (stride 28 bytes)
+struct VertexInput {
+    @location(0) position:      vec3<f32>, // world space, offset 0
+    @location(1) normal:        vec3<f32>, // world space, offset 12
+    @location(2) palette_index: u32,       // offset 24
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) @interpolate(flat) palette_index: u32,
+};
+
+struct PerFrameUniforms {
+    vp_matrix:  mat4x4<f32>,
+    cam_pos_ws: vec3<f32>,
+    voxel_rounding: f32,
+};
+@group(1) @binding(0) var<uniform> u_frame: PerFrameUniforms;
+
+struct StaticUniforms {
+    palette: array<vec4<u32>, 64>,
+};
+@group(0) @binding(0) var<uniform> u_static: StaticUniforms;
+
+struct ClipPlanesUniforms {
+    planes: array<vec4<f32>, 4>,
+    count:  u32,
+};
+@group(0) @binding(1) var<uniform> u_clip: ClipPlanesUniforms;
+
+struct GBuffer {
+    @location(0) albedo:    vec4<f32>,
+    @location(1) normal:    vec2<f32>, // Rg8Unorm, octahedral
+    @location(2) linear_z:  u32,
+    @location(3) material:  u32,       // Palette index; meshes have no occlusion
+}
+
+// Octahedral normal encoding in [0,1]^2; must match decode_normal in quad_lighting.wgsl.
+// (0, 0) is reserved for empty pixels.
+fn encode_normal(n: vec3<f32>) -> vec2<f32> {
+    var p = n.xy / (abs(n.x) + abs(n.y) + abs(n.z));
+    if n.z < 0.0 {
+        let sign_not_zero = select(vec2<f32>(-1.0), vec2<f32>(1.0), p >= vec2<f32>(0.0));
+        p = (vec2<f32>(1.0) - abs(p.yx)) * sign_not_zero;
+    }
+    return max(p * 0.5 + 0.5, vec2<f32>(1.0 / 255.0));
+};
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = u_frame.vp_matrix * vec4<f32>(in.position, 1.0);
+    out.world_pos = in.position;
+    out.normal = in.normal;
+    out.palette_index = in.palette_index;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> GBuffer {
+    return shade(in);
+}
+
+// AlbedoOnly G-buffer profile: a single color target
+@fragment
+fn fs_albedo(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in).albedo;
+}
+
+fn shade(in: VertexOutput) -> GBuffer {
+    if u_clip.count > 0u {
+        for (var p = 0u; p < u_clip.count; p = p + 1u) {
+            let plane = u_clip.planes[p];
+            if dot(in.world_pos, plane.xyz) + plane.w < 0.0 {
+                discard;
+            }
+        }
+    }
+
+    let idx = min(in.palette_index, 255u);
+    // Alpha is the edge coverage read by the lighting pass; meshes cover whole pixels
+    let albedo = vec4<f32>(unpack4x8unorm(u_static.palette[idx / 4u][idx % 4u]).rgb, 1.0);
+    let normal = normalize(in.normal);
+
+    // Encoding must match LINEAR_Z_MAX_DISTANCE / LINEAR_Z_MAX_VALUE in constants.rs
+    let linear_z = length(in.world_pos - u_frame.cam_pos_ws);
+    return GBuffer(
+        albedo,
+        encode_normal(normal),
+        u32(clamp(linear_z / 100.0, 0.0, 1.0) * 65535.0),
+        idx
+    );
+}
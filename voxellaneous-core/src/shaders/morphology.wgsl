@@ -0,0 +1,87 @@
+// Voxel erosion / dilation over 6-connected face neighbours.
+// Output is packed 4 voxels per u32 in rows padded to whole words, laid out for a
+// buffer-to-texture copy into an R8Uint 3D texture.
+
+struct MorphologyParams {
+    dims:        vec3<u32>,
+    row_words:   u32,
+    empty_index: u32,
+};
+
+@group(0) @binding(0) var src: texture_3d<u32>;
+@group(0) @binding(1) var<storage, read_write> dst: array<u32>;
+@group(0) @binding(2) var<uniform> u_params: MorphologyParams;
+
+// 0 = erode, 1 = dilate
+override operation: u32 = 0u;
+
+const NEIGHBOURS = array<vec3<i32>, 6>(
+    vec3<i32>( 1,  0,  0),
+    vec3<i32>(-1,  0,  0),
+    vec3<i32>( 0,  1,  0),
+    vec3<i32>( 0, -1,  0),
+    vec3<i32>( 0,  0,  1),
+    vec3<i32>( 0,  0, -1)
+);
+
+// Voxels outside the grid count as empty
+fn load_voxel(p: vec3<i32>) -> u32 {
+    if any(p < vec3<i32>(0)) || any(p >= vec3<i32>(u_params.dims)) {
+        return u_params.empty_index;
+    }
+    return textureLoad(src, p, 0).r;
+}
+
+fn erode(p: vec3<i32>) -> u32 {
+    let empty = u_params.empty_index;
+    let v = load_voxel(p);
+    if v == empty {
+        return empty;
+    }
+    for (var i = 0; i < 6; i = i + 1) {
+        if load_voxel(p + NEIGHBOURS[i]) == empty {
+            return empty;
+        }
+    }
+    return v;
+}
+
+// Empty voxels take the colour of their first non-empty neighbour
+fn dilate(p: vec3<i32>) -> u32 {
+    let empty = u_params.empty_index;
+    let v = load_voxel(p);
+    if v != empty {
+        return v;
+    }
+    for (var i = 0; i < 6; i = i + 1) {
+        let n = load_voxel(p + NEIGHBOURS[i]);
+        if n != empty {
+            return n;
+        }
+    }
+    return empty;
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= u_params.row_words || id.y >= u_params.dims.y || id.z >= u_params.dims.z {
+        return;
+    }
+
+    var word = 0u;
+    for (var i = 0u; i < 4u; i = i + 1u) {
+        let x = id.x * 4u + i;
+        if x >= u_params.dims.x {
+            break;
+        }
+        let p = vec3<i32>(vec3<u32>(x, id.y, id.z));
+        var v: u32;
+        if operation == 0u {
+            v = erode(p);
+        } else {
+            v = dilate(p);
+        }
+        word = word | ((v & 0xffu) << (8u * i));
+    }
+    dst[(id.z * u_params.dims.y + id.y) * u_params.row_words + id.x] = word;
+}
@@ -0,0 +1,89 @@
+// Tests each object's world AABB against the Hi-Z pyramid (hiz.wgsl) and writes its
+// indexed indirect draw: one instance when it may be visible, none when every pixel
+// it covers already holds a nearer surface.
+
+struct CullUniforms {
+    // View the pyramid was rendered with
+    vp_matrix:    mat4x4<f32>,
+    cam_pos_ws:   vec3<f32>,
+    object_count: u32,
+    // G-buffer size in pixels
+    size:         vec2<u32>,
+    level_count:  u32,
+    _padding:     u32,
+};
+
+// World AABB, and the index range of the object's proxy shape
+struct CullObject {
+    min:         vec3<f32>,
+    index_count: u32,
+    max:         vec3<f32>,
+    first_index: u32,
+};
+
+@group(0) @binding(0) var hiz: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> u_cull: CullUniforms;
+@group(0) @binding(2) var<storage, read> objects: array<CullObject>;
+// DrawIndexedIndirectArgs, 5 words per object
+@group(0) @binding(3) var<storage, read_write> draws: array<u32>;
+@group(0) @binding(4) var<storage, read_write> culled_count: atomic<u32>;
+
+fn is_occluded(object: CullObject) -> bool {
+    // Nearest point of the box; a camera inside it sees it
+    let nearest = clamp(u_cull.cam_pos_ws, object.min, object.max);
+    let distance = length(nearest - u_cull.cam_pos_ws);
+    if distance <= 0.0 {
+        return false;
+    }
+
+    var rect_min = vec2<f32>(1e30);
+    var rect_max = vec2<f32>(-1e30);
+    for (var c = 0u; c < 8u; c = c + 1u) {
+        let corner = select(object.min, object.max, vec3<bool>((c & 1u) != 0u, (c & 2u) != 0u, (c & 4u) != 0u));
+        let clip = u_cull.vp_matrix * vec4<f32>(corner, 1.0);
+        if clip.w <= 0.0 {
+            // Crosses the camera plane: no usable screen rectangle
+            return false;
+        }
+        let ndc = clip.xy / clip.w;
+        let pixel = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5) * vec2<f32>(u_cull.size);
+        rect_min = min(rect_min, pixel);
+        rect_max = max(rect_max, pixel);
+    }
+    let size = vec2<f32>(u_cull.size);
+    rect_min = clamp(rect_min, vec2<f32>(0.0), size - vec2<f32>(1.0));
+    rect_max = clamp(rect_max, vec2<f32>(0.0), size - vec2<f32>(1.0));
+
+    // Level whose texels (2^(level + 1) pixels) are at least as large as the rectangle,
+    // so it spans at most 2x2 of them
+    let extent = max(max(rect_max.x - rect_min.x, rect_max.y - rect_min.y), 1.0);
+    let level = min(u32(max(ceil(log2(extent)) - 1.0, 0.0)), u_cull.level_count - 1u);
+    let texel = f32(1u << (level + 1u));
+    let level_max = vec2<i32>(textureDimensions(hiz, level)) - vec2<i32>(1);
+    let t0 = min(vec2<i32>(rect_min / texel), level_max);
+    let t1 = min(vec2<i32>(rect_max / texel), level_max);
+
+    let farthest = max(
+        max(textureLoad(hiz, t0, level).r, textureLoad(hiz, vec2<i32>(t1.x, t0.y), level).r),
+        max(textureLoad(hiz, vec2<i32>(t0.x, t1.y), level).r, textureLoad(hiz, t1, level).r)
+    );
+    return distance > farthest;
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= u_cull.object_count {
+        return;
+    }
+    var instances = 1u;
+    if is_occluded(objects[i]) {
+        instances = 0u;
+        atomicAdd(&culled_count, 1u);
+    }
+    draws[i * 5u] = objects[i].index_count;
+    draws[i * 5u + 1u] = instances;
+    draws[i * 5u + 2u] = objects[i].first_index;
+    draws[i * 5u + 3u] = 0u;
+    draws[i * 5u + 4u] = 0u;
+}
@@ -0,0 +1,27 @@
+// Prepended to the source of every custom post effect (Renderer::add_post_effect).
+// An effect defines `@fragment fn fs_main(in: VSOut) -> @location(0) vec4<f32>`, and
+// may declare `@group(0) @binding(2) var<uniform>` of its own type to receive the
+// bytes of Renderer::set_custom_uniform.
+
+struct VSOut {
+    @builtin(position) position: vec4<f32>,
+    // Texture-space uv (y down) of input_tex
+    @location(0)       uv:       vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vi: u32) -> VSOut {
+    var corners = array<vec2<f32>,3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>( 3.0, -1.0),
+        vec2<f32>(-1.0,  3.0)
+    );
+    var out: VSOut;
+    out.position = vec4<f32>(corners[vi], 0.0, 1.0);
+    out.uv = vec2<f32>(corners[vi].x * 0.5 + 0.5, 0.5 - corners[vi].y * 0.5);
+    return out;
+}
+
+// The image so far: the present pass output, after FXAA and any earlier effects
+@group(0) @binding(0) var input_tex: texture_2d<f32>;
+@group(0) @binding(1) var input_samp: sampler;
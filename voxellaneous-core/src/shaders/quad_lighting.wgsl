@@ -4,10 +4,39 @@ struct VSOut {
 };
 
 struct LightingUniforms {
-    light_dir: vec3<f32>,
-    ambient:   f32,
+    light_dir:         vec3<f32>,
+    ambient:           f32,
+    inv_vp_matrix:     mat4x4<f32>,
+    specular_strength: f32,
+    shininess:         f32,
+    // 0 = stored G-buffer normal, 1 = geometric normal from the linear-Z
+    normal_source:     u32,
+    // Non-zero darkens the ambient term by the occlusion in the material texture
+    ambient_occlusion: u32,
+    // Camera axes for the matcap lookup
+    view_right:        vec3<f32>,
+    // 0 = directional, 1 = matcap, 2 = PBR
+    lighting_model:    u32,
+    view_up:           vec3<f32>,
+    // Edge antialiasing: 0 = off, 1 = blend toward the background, 2 = also toward
+    // farther geometry
+    edge_aa:           u32,
+    rim_color:         vec3<f32>,
+    rim_power:         f32,
+    // 0 = no rim light
+    rim_intensity:     f32,
+    camera_position:   vec3<f32>,
+    // Tint of the highlight region, blended by alpha; 0 alpha when none is marked
+    highlight_color:   vec4<f32>,
 };
 
+// Target UV to G-buffer UV for the present fit; see PresentUniforms in lib.rs
+struct PresentUniforms {
+    uv_scale:  vec2<f32>,
+    uv_offset: vec2<f32>,
+};
+@group(0) @binding(15) var<uniform> u_present: PresentUniforms;
+
 @vertex
 fn vs_main(@builtin(vertex_index) vi: u32) -> VSOut {
     var corners = array<vec2<f32>,3>(
@@ -17,7 +46,7 @@ fn vs_main(@builtin(vertex_index) vi: u32) -> VSOut {
     );
     var out: VSOut;
     out.Position = vec4<f32>(corners[vi], 0.0, 1.0);
-    out.uv       = corners[vi] * 0.5 + vec2<f32>(0.5);
+    out.uv       = (corners[vi] * 0.5 + vec2<f32>(0.5)) * u_present.uv_scale + u_present.uv_offset;
     return out;
 }
 
@@ -25,37 +54,564 @@ fn vs_main(@builtin(vertex_index) vi: u32) -> VSOut {
 @group(0) @binding(1) var normal_tex: texture_2d<f32>;
 @group(0) @binding(2) var u_samp: sampler;
 @group(0) @binding(3) var<uniform> u_lighting: LightingUniforms;
+@group(0) @binding(4) var matcap_tex: texture_2d<f32>;
+@group(0) @binding(5) var matcap_samp: sampler;
+@group(0) @binding(6) var linear_z_tex: texture_2d<u32>;
 
-@fragment
-fn fs_main(in: VSOut) -> @location(0) vec4<f32> {
-    let dims = textureDimensions(albedo_tex, 0);
-    let coord = vec2<i32>(
-        i32(in.uv.x * f32(dims.x)),
-        i32((1.0 - in.uv.y) * f32(dims.y))
+// Per palette index: roughness in the low byte, metallic in the next (unorm8)
+struct Materials {
+    packed: array<vec4<u32>, 64>,
+};
+@group(0) @binding(7) var<uniform> u_materials: Materials;
+
+struct Environment {
+    // Irradiance SH, see environment.rs
+    sh:      array<vec4<f32>, 9>,
+    // 0 = no environment: flat ambient and a black background
+    enabled: u32,
+    max_lod: f32,
+};
+@group(0) @binding(8) var environment_tex: texture_2d<f32>;
+@group(0) @binding(9) var environment_samp: sampler;
+@group(0) @binding(10) var<uniform> u_environment: Environment;
+
+struct Shadows {
+    matrices:   array<mat4x4<f32>, 4>,
+    // Far view depth of each cascade
+    splits:     vec4<f32>,
+    // 0 = no shadows
+    count:      u32,
+    // Normal offset of the lookup, in texels
+    normal_offset: f32,
+    // World-space size of a shadow-map texel per cascade
+    texel_size: vec4<f32>,
+    // Fraction of each cascade, at its far end, blended into the next
+    blend:      f32,
+    // Tint pixels by cascade index
+    debug:      u32,
+};
+@group(0) @binding(11) var shadow_tex: texture_depth_2d_array;
+@group(0) @binding(12) var shadow_samp: sampler_comparison;
+@group(0) @binding(13) var<uniform> u_shadows: Shadows;
+
+// light_probes.rs
+struct LightProbes {
+    positions: array<vec4<f32>, 8>,
+    // 9 per probe
+    sh_coeffs: array<vec4<f32>, 72>,
+    // 0 = no probes
+    count:     u32,
+};
+@group(0) @binding(14) var<uniform> u_probes: LightProbes;
+
+// Point lights with the per-tile lists of light_cull.wgsl, see light_culling.rs
+struct LightTiles {
+    inv_vp_matrix:   mat4x4<f32>,
+    camera_position: vec3<f32>,
+    // 0 = no point lights; the tile lists are not read
+    light_count:     u32,
+    size:            vec2<u32>,
+    tiles_x:         u32,
+    // Tint pixels by the length of their tile's list
+    debug:           u32,
+};
+struct PointLight {
+    position:  vec3<f32>,
+    radius:    f32,
+    color:     vec3<f32>,
+    intensity: f32,
+};
+@group(0) @binding(16) var<storage, read> point_lights: array<PointLight>;
+@group(0) @binding(17) var<storage, read> tile_lights: array<u32>;
+@group(0) @binding(18) var<uniform> u_tiles: LightTiles;
+// Non-zero inside the highlight region, see highlight.rs
+@group(0) @binding(19) var stencil_tex: texture_2d<u32>;
+// Palette index in bits 0-7 and baked occlusion (unorm8) in bits 8-15, see
+// GBufferTextures::material
+@group(0) @binding(20) var material_tex: texture_2d<u32>;
+
+const TILE_SIZE: u32 = 16u;
+const TILE_WORDS: u32 = 64u;
+const MAX_LIGHTS_PER_TILE: u32 = 63u;
+
+// Set when the target does not sRGB-encode on write, so the PBR model, which works
+// in linear space, encodes its result itself
+override encode_srgb: bool = false;
+
+const PI: f32 = 3.14159265;
+
+// Inverse of encode_normal in shader.wgsl (octahedral, [0,1]^2)
+fn decode_normal(e: vec2<f32>) -> vec3<f32> {
+    let f = e * 2.0 - 1.0;
+    var n = vec3<f32>(f, 1.0 - abs(f.x) - abs(f.y));
+    let t = max(-n.z, 0.0);
+    n.x += select(t, -t, n.x >= 0.0);
+    n.y += select(t, -t, n.y >= 0.0);
+    return normalize(n);
+}
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+// Equirectangular lookup, +y at the top row and -z at the centre column
+fn environment_radiance(dir: vec3<f32>, lod: f32) -> vec3<f32> {
+    let d = normalize(dir);
+    let uv = vec2<f32>(atan2(d.x, -d.z) / (2.0 * PI) + 0.5, acos(clamp(d.y, -1.0, 1.0)) / PI);
+    return textureSampleLevel(environment_tex, environment_samp, uv, lod).rgb;
+}
+
+// Mean cosine-weighted radiance around `n` of irradiance SH `sh`; must match sh_basis
+// in environment.rs
+fn evaluate_sh(sh: array<vec4<f32>, 9>, n: vec3<f32>) -> vec3<f32> {
+    let e = sh[0].rgb * 0.282095
+        + 0.488603 * (sh[1].rgb * n.y + sh[2].rgb * n.z + sh[3].rgb * n.x)
+        + 1.092548 * (sh[4].rgb * n.x * n.y + sh[5].rgb * n.y * n.z + sh[7].rgb * n.x * n.z)
+        + sh[6].rgb * (0.315392 * (3.0 * n.z * n.z - 1.0))
+        + sh[8].rgb * (0.546274 * (n.x * n.x - n.y * n.y));
+    return max(e, vec3<f32>(0.0));
+}
+
+// Ambient light reaching the surface of pixel `coord` with normal `n`, before the
+// `ambient` scale: from the light probe nearest to the surface, else the environment
+// map, else uniform white
+fn ambient_irradiance(coord: vec2<i32>, ndc: vec2<f32>, n: vec3<f32>) -> vec3<f32> {
+    if u_probes.count > 0u {
+        let pos = world_position(coord, ndc);
+        var nearest = 0u;
+        var nearest_d2 = 3.4e38;
+        for (var i = 0u; i < u_probes.count; i = i + 1u) {
+            let d = u_probes.positions[i].xyz - pos;
+            if dot(d, d) < nearest_d2 {
+                nearest = i;
+                nearest_d2 = dot(d, d);
+            }
+        }
+        var sh: array<vec4<f32>, 9>;
+        for (var k = 0u; k < 9u; k = k + 1u) {
+            sh[k] = u_probes.sh_coeffs[nearest * 9u + k];
+        }
+        return evaluate_sh(sh, n);
+    }
+    if u_environment.enabled != 0u {
+        return evaluate_sh(u_environment.sh, n);
+    }
+    return vec3<f32>(1.0);
+}
+
+// Lit-target color of pixels with no geometry: the environment seen along the pixel
+// ray, or black without one
+fn background(ndc: vec2<f32>) -> vec3<f32> {
+    if u_environment.enabled == 0u {
+        return vec3<f32>(0.0);
+    }
+    let color = environment_radiance(-view_direction(ndc), 0.0);
+    if encode_srgb {
+        return linear_to_srgb(min(color, vec3<f32>(1.0)));
+    }
+    return color;
+}
+
+// Unit vector from the surface toward the camera, from the unprojected pixel ray, so
+// no world position has to be rebuilt from the quantized linear-Z
+fn view_direction(ndc: vec2<f32>) -> vec3<f32> {
+    let near = u_lighting.inv_vp_matrix * vec4<f32>(ndc, 0.0, 1.0);
+    let far = u_lighting.inv_vp_matrix * vec4<f32>(ndc, 1.0, 1.0);
+    return normalize(near.xyz / near.w - far.xyz / far.w);
+}
+
+// Distance from the camera to the surface of pixel `coord`. Decoding must match
+// LINEAR_Z_MAX_DISTANCE / LINEAR_Z_MAX_VALUE in constants.rs
+fn pixel_distance(coord: vec2<i32>) -> f32 {
+    return f32(textureLoad(linear_z_tex, coord, 0).r) / 65535.0 * 100.0;
+}
+
+fn world_position(coord: vec2<i32>, ndc: vec2<f32>) -> vec3<f32> {
+    return u_lighting.camera_position - view_direction(ndc) * pixel_distance(coord);
+}
+
+// Light visibility at `pos` in cascade `i`, with the lookup pushed along the normal by
+// a texel and a half so surfaces do not shadow themselves
+fn cascade_visibility(i: u32, pos: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let offset_pos = pos + normal * (u_shadows.texel_size[i] * u_shadows.normal_offset);
+    let p = u_shadows.matrices[i] * vec4<f32>(offset_pos, 1.0);
+    let uv = vec2<f32>(p.x * 0.5 + 0.5, 0.5 - p.y * 0.5);
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || p.z > 1.0 {
+        return 1.0;
+    }
+    return textureSampleCompareLevel(shadow_tex, shadow_samp, uv, i, p.z);
+}
+
+struct ShadowSample {
+    visibility: f32,
+    // -1 past the last cascade
+    cascade:    i32,
+};
+
+// World-space step from pixel `coord` at `pos` to its neighbour along `axis`, taken
+// toward the neighbour nearer in distance so the step does not cross a silhouette;
+// zero when both are background or outside the image
+fn neighbour_step(coord: vec2<i32>, pos: vec3<f32>, axis: vec2<i32>) -> vec3<f32> {
+    let dims = textureDimensions(linear_z_tex, 0);
+    let distance = pixel_distance(coord);
+    var step = vec3<f32>(0.0);
+    var nearest = 3.4e38;
+    for (var side = -1; side <= 1; side = side + 2) {
+        let n = coord + axis * side;
+        if any(n < vec2<i32>(0)) || any(n >= vec2<i32>(dims)) || textureLoad(linear_z_tex, n, 0).r == 0u {
+            continue;
+        }
+        let gap = abs(pixel_distance(n) - distance);
+        if gap < nearest {
+            nearest = gap;
+            step = (world_position(n, pixel_ndc(n, dims)) - pos) * f32(side);
+        }
+    }
+    return step;
+}
+
+// Normal the lighting shades pixel `coord` with: the stored G-buffer normal, or the
+// surface rebuilt from the linear-Z of its neighbours, facing the camera (the stored
+// normal where there are too few neighbours)
+fn surface_normal(coord: vec2<i32>, ndc: vec2<f32>, encoded: vec2<f32>) -> vec3<f32> {
+    let stored = decode_normal(encoded);
+    if u_lighting.normal_source == 0u {
+        return stored;
+    }
+    let pos = world_position(coord, ndc);
+    let n = cross(neighbour_step(coord, pos, vec2<i32>(1, 0)), neighbour_step(coord, pos, vec2<i32>(0, 1)));
+    if dot(n, n) < 1e-12 {
+        return stored;
+    }
+    let geometric = normalize(n);
+    return select(-geometric, geometric, dot(geometric, view_direction(ndc)) >= 0.0);
+}
+
+// Share of the ambient light reaching pixel `coord`: 1 unless ambient occlusion is on
+fn ambient_visibility(coord: vec2<i32>) -> f32 {
+    if u_lighting.ambient_occlusion == 0u {
+        return 1.0;
+    }
+    return 1.0 - f32((textureLoad(material_tex, coord, 0).r >> 8u) & 0xffu) / 255.0;
+}
+
+// Directional light visibility of the G-buffer pixel at `coord`
+fn shadow_at(coord: vec2<i32>, ndc: vec2<f32>, normal: vec3<f32>) -> ShadowSample {
+    if u_shadows.count == 0u {
+        return ShadowSample(1.0, -1);
+    }
+    let distance = pixel_distance(coord);
+    let pos = world_position(coord, ndc);
+    let depth = distance * dot(view_direction(ndc), view_direction(vec2<f32>(0.0)));
+    for (var i = 0u; i < u_shadows.count; i = i + 1u) {
+        let end = u_shadows.splits[i];
+        if depth > end {
+            continue;
+        }
+        var visibility = cascade_visibility(i, pos, normal);
+        let start = select(0.0, u_shadows.splits[max(i, 1u) - 1u], i > 0u);
+        let blend_start = end - (end - start) * u_shadows.blend;
+        if i + 1u < u_shadows.count && depth > blend_start {
+            let next = cascade_visibility(i + 1u, pos, normal);
+            visibility = mix(visibility, next, (depth - blend_start) / max(end - blend_start, 1e-6));
+        }
+        return ShadowSample(visibility, i32(i));
+    }
+    return ShadowSample(1.0, -1);
+}
+
+// Offset of the light list of the tile holding pixel `coord` in tile_lights
+fn tile_base(coord: vec2<i32>) -> u32 {
+    let tile = vec2<u32>(coord) / TILE_SIZE;
+    return (tile.y * u_tiles.tiles_x + tile.x) * TILE_WORDS;
+}
+
+// Windowed inverse-square falloff, reaching 0 at `radius`
+fn point_attenuation(distance: f32, radius: f32) -> f32 {
+    let window = saturate(1.0 - pow(distance / radius, 4.0));
+    return window * window / (distance * distance + 1.0);
+}
+
+struct PointLightSample {
+    // Unit vector from the surface toward the light
+    l:        vec3<f32>,
+    radiance: vec3<f32>,
+};
+
+// Light `i` of the tile list at `base`, seen from `pos`; zero radiance out of range
+fn point_light_sample(base: u32, i: u32, pos: vec3<f32>) -> PointLightSample {
+    let light = point_lights[tile_lights[base + 1u + i]];
+    let to_light = light.position - pos;
+    let distance = length(to_light);
+    if distance >= light.radius {
+        return PointLightSample(vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(0.0));
+    }
+    let attenuation = point_attenuation(distance, light.radius);
+    return PointLightSample(to_light / max(distance, 1e-4), light.color * (light.intensity * attenuation));
+}
+
+struct PointLighting {
+    diffuse:  vec3<f32>,
+    specular: vec3<f32>,
+};
+
+// Lambert diffuse and Blinn-Phong highlight of the point lights reaching pixel `coord`
+fn point_lighting(coord: vec2<i32>, ndc: vec2<f32>, n: vec3<f32>) -> PointLighting {
+    var sum = PointLighting(vec3<f32>(0.0), vec3<f32>(0.0));
+    if u_tiles.light_count == 0u {
+        return sum;
+    }
+    let pos = world_position(coord, ndc);
+    let v = view_direction(ndc);
+    let base = tile_base(coord);
+    for (var i = 0u; i < tile_lights[base]; i = i + 1u) {
+        let light = point_light_sample(base, i, pos);
+        let n_dot_l = max(dot(n, light.l), 0.0);
+        sum.diffuse += light.radiance * n_dot_l;
+        if u_lighting.specular_strength > 0.0 && n_dot_l > 0.0 {
+            let h = normalize(light.l + v);
+            sum.specular += light.radiance
+                * (u_lighting.specular_strength * pow(max(dot(n, h), 0.0), u_lighting.shininess));
+        }
+    }
+    return sum;
+}
+
+// Blue (no lights) through green to red (a full list)
+fn tile_heat(coord: vec2<i32>) -> vec3<f32> {
+    let t = f32(tile_lights[tile_base(coord)]) / f32(MAX_LIGHTS_PER_TILE);
+    return vec3<f32>(saturate(t * 2.0 - 1.0), 1.0 - abs(t * 2.0 - 1.0), saturate(1.0 - t * 2.0));
+}
+
+fn cascade_tint(cascade: i32) -> vec3<f32> {
+    var tints = array<vec3<f32>, 4>(
+        vec3<f32>(1.0, 0.2, 0.2), vec3<f32>(0.2, 1.0, 0.2),
+        vec3<f32>(0.2, 0.4, 1.0), vec3<f32>(1.0, 1.0, 0.2)
     );
+    return tints[cascade];
+}
+
+// Cook-Torrance GGX reflectance times n.l for light from direction `l`, zero when the
+// light is behind the surface
+fn pbr_direct(n: vec3<f32>, v: vec3<f32>, l: vec3<f32>, f0: vec3<f32>, diffuse_color: vec3<f32>, a2: f32) -> vec3<f32> {
+    let n_dot_l = dot(n, l);
+    if n_dot_l <= 0.0 {
+        return vec3<f32>(0.0);
+    }
+    let n_dot_v = max(dot(n, v), 1e-4);
+    let h = normalize(l + v);
+    let n_dot_h = max(dot(n, h), 0.0);
+    let fresnel = f0 + (1.0 - f0) * pow(1.0 - max(dot(v, h), 0.0), 5.0);
+    let d_denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    let distribution = a2 / (PI * d_denom * d_denom);
+    // Height-correlated Smith visibility, G / (4 n.l n.v)
+    let visibility = 0.5 / (n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - a2) + a2)
+        + n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - a2) + a2));
+    let specular = distribution * visibility * fresnel;
+    let diffuse = (1.0 - fresnel) * diffuse_color / PI;
+    return (diffuse + specular) * n_dot_l;
+}
+
+// Cook-Torrance GGX for the directional light and the point lights of pixel `coord`,
+// plus a uniform environment of radiance `ambient`, or the environment map scaled by
+// `ambient` when one is set; diffuse ambient comes from `irradiance` (see
+// ambient_irradiance). The diffuse lobe only gets the energy the specular lobe does
+// not reflect, so a white dielectric under a white environment (ambient 1) shades to 1.
+fn shade_pbr(coord: vec2<i32>, ndc: vec2<f32>, albedo_srgb: vec3<f32>, n: vec3<f32>, material: vec2<f32>, shadow: f32, irradiance: vec3<f32>) -> vec3<f32> {
+    let v = view_direction(ndc);
+    let albedo = srgb_to_linear(albedo_srgb);
+    let roughness = max(material.x, 0.045);
+    let metallic = material.y;
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+    let diffuse_color = albedo * (1.0 - metallic);
+    let n_dot_v = max(dot(n, v), 1e-4);
+
+    // Environment: analytic fit of the pre-integrated split-sum specular (Karis 2014)
+    let r = roughness * vec4<f32>(-1.0, -0.0275, -0.572, 0.022) + vec4<f32>(1.0, 0.0425, 1.04, -0.04);
+    let a004 = min(r.x * r.x, exp2(-9.28 * n_dot_v)) * r.x + r.y;
+    let env_brdf = vec2<f32>(-1.04, 1.04) * a004 + r.zw;
+    let env_specular = f0 * env_brdf.x + env_brdf.y;
+    var reflected = vec3<f32>(1.0);
+    if u_environment.enabled != 0u {
+        reflected = environment_radiance(reflect(-v, n), roughness * u_environment.max_lod);
+    }
+    var color = u_lighting.ambient
+        * (env_specular * reflected + (1.0 - env_specular) * diffuse_color * irradiance);
+
+    // Radiance at which a white Lambertian surface facing the light gets the
+    // 1 - ambient the directional model gives it
+    let l = normalize(u_lighting.light_dir);
+    color += pbr_direct(n, v, l, f0, diffuse_color, a2) * PI * (1.0 - u_lighting.ambient) * shadow;
+
+    // Point light radiance is scaled like the directional model's diffuse
+    if u_tiles.light_count > 0u {
+        let pos = world_position(coord, ndc);
+        let base = tile_base(coord);
+        for (var i = 0u; i < tile_lights[base]; i = i + 1u) {
+            let light = point_light_sample(base, i, pos);
+            color += pbr_direct(n, v, light.l, f0, diffuse_color, a2) * PI * light.radiance;
+        }
+    }
+    return color;
+}
 
+// Lit color of the G-buffer pixel at `coord`, with alpha 0 for pixels with no geometry
+fn shade(coord: vec2<i32>, ndc: vec2<f32>) -> vec4<f32> {
+    let normal_encoded = textureLoad(normal_tex, coord, 0).rg;
+    if all(normal_encoded == vec2<f32>(0.0)) {
+        return vec4<f32>(0.0);
+    }
+    let normal = surface_normal(coord, ndc, normal_encoded);
+    let shadow = shadow_at(coord, ndc, normal);
+    var color = shade_model(coord, ndc, normal, shadow.visibility);
+    if u_shadows.debug != 0u && shadow.cascade >= 0 {
+        color = vec4<f32>(mix(color.rgb, cascade_tint(shadow.cascade), 0.4), 1.0);
+    }
+    if u_tiles.debug != 0u {
+        color = vec4<f32>(mix(color.rgb, tile_heat(coord), 0.5), 1.0);
+    }
+    // Skipped entirely when off, so the output stays bit-identical
+    if u_lighting.rim_intensity <= 0.0 {
+        return color;
+    }
+    let rim = pow(1.0 - saturate(dot(normal, view_direction(ndc))), u_lighting.rim_power);
+    return vec4<f32>(color.rgb + u_lighting.rim_color * (u_lighting.rim_intensity * rim), 1.0);
+}
+
+// `shade` for the active lighting model, without the rim light, for a pixel with
+// geometry shaded with `normal`. `shadow` scales the directional light.
+fn shade_model(coord: vec2<i32>, ndc: vec2<f32>, normal: vec3<f32>, shadow: f32) -> vec4<f32> {
     let albedo = textureLoad(albedo_tex, coord, 0);
-    let normal_encoded = textureLoad(normal_tex, coord, 0);
+    let visibility = ambient_visibility(coord);
 
-    // Skip pixels with no geometry (normal = 0)
-    if all(normal_encoded.rgb == vec3<f32>(0.0)) {
-        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    if u_lighting.lighting_model == 1u {
+        // View-space normal xy mapped onto the matcap sphere, +y up in the image
+        let n = vec2<f32>(dot(normal, u_lighting.view_right), dot(normal, u_lighting.view_up));
+        let matcap_uv = vec2<f32>(n.x, -n.y) * 0.49 + vec2<f32>(0.5);
+        let matcap = textureSampleLevel(matcap_tex, matcap_samp, matcap_uv, 0.0);
+        return vec4<f32>(albedo.rgb * matcap.rgb * visibility, 1.0);
     }
 
-    // Decode normal from [0,1] to [-1,1]
-    let normal = normalize(normal_encoded.rgb * 2.0 - 1.0);
+    let irradiance = ambient_irradiance(coord, ndc, normal) * visibility;
+
+    if u_lighting.lighting_model == 2u {
+        let idx = textureLoad(material_tex, coord, 0).r & 0xffu;
+        let material = unpack4x8unorm(u_materials.packed[idx / 4u][idx % 4u]).xy;
+        var color = max(shade_pbr(coord, ndc, albedo.rgb, normal, material, shadow, irradiance), vec3<f32>(0.0));
+        if encode_srgb {
+            color = linear_to_srgb(min(color, vec3<f32>(1.0)));
+        }
+        return vec4<f32>(color, 1.0);
+    }
 
     // Normalize light direction (should already be normalized, but just in case)
     let light_dir = normalize(u_lighting.light_dir);
 
-    // N dot L shading
-    let ndotl = max(dot(normal, light_dir), 0.0);
+    // N dot L shading, zero where the light is shadowed
+    let ndotl = max(dot(normal, light_dir), 0.0) * shadow;
 
-    // Combine ambient and diffuse
-    let lighting = u_lighting.ambient + (1.0 - u_lighting.ambient) * ndotl;
+    // Combine ambient and diffuse; probes or the environment map, when set, tint the
+    // ambient
+    let ambient = u_lighting.ambient * irradiance;
+    let points = point_lighting(coord, ndc, normal);
+    let lighting = ambient + (1.0 - u_lighting.ambient) * ndotl + points.diffuse;
+
+    // Blinn-Phong highlight
+    var specular = points.specular;
+    if u_lighting.specular_strength > 0.0 && ndotl > 0.0 {
+        let half_dir = normalize(light_dir + view_direction(ndc));
+        specular = vec3<f32>(u_lighting.specular_strength
+            * pow(max(dot(normal, half_dir), 0.0), u_lighting.shininess) * shadow);
+    }
+    // Environment reflection, blurrier toward low shininess
+    if u_lighting.specular_strength > 0.0 && u_environment.enabled != 0u {
+        let v = view_direction(ndc);
+        let lod = sqrt(2.0 / (u_lighting.shininess + 2.0)) * u_environment.max_lod;
+        specular += u_lighting.specular_strength * u_lighting.ambient
+            * environment_radiance(reflect(-v, normal), lod);
+    }
 
     // Apply lighting to albedo
-    let lit_color = albedo.rgb * lighting;
+    let lit_color = albedo.rgb * lighting + specular;
 
     return vec4<f32>(lit_color, 1.0);
 }
+
+fn pixel_ndc(coord: vec2<i32>, dims: vec2<u32>) -> vec2<f32> {
+    let uv = (vec2<f32>(coord) + vec2<f32>(0.5)) / vec2<f32>(dims);
+    return vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+}
+
+// `shade` result of `coord`, tinted inside the highlight region
+fn highlight(coord: vec2<i32>, color: vec4<f32>) -> vec4<f32> {
+    let tint = u_lighting.highlight_color;
+    if tint.a == 0.0 || color.a == 0.0 || textureLoad(stencil_tex, coord, 0).r == 0u {
+        return color;
+    }
+    return vec4<f32>(mix(color.rgb, tint.rgb, tint.a), color.a);
+}
+
+@fragment
+fn fs_main(in: VSOut) -> @location(0) vec4<f32> {
+    // Letterbox bars outside the image
+    if any(in.uv < vec2<f32>(0.0)) || any(in.uv >= vec2<f32>(1.0)) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    let dims = textureDimensions(albedo_tex, 0);
+    let coord = vec2<i32>(
+        i32(in.uv.x * f32(dims.x)),
+        i32((1.0 - in.uv.y) * f32(dims.y))
+    );
+
+    let ndc = in.uv * 2.0 - 1.0;
+    let color = highlight(coord, shade(coord, ndc));
+    if color.a == 0.0 {
+        return vec4<f32>(background(ndc), 1.0);
+    }
+
+    // Albedo alpha is the coverage of the pixel by its surface at silhouette edges
+    let coverage = textureLoad(albedo_tex, coord, 0).a;
+    if u_lighting.edge_aa == 0u || coverage >= 1.0 {
+        return vec4<f32>(color.rgb, 1.0);
+    }
+
+    // Blend with what lies behind the edge: the background, or at high quality the
+    // farthest edge neighbour when it is farther than this pixel
+    var behind = background(ndc);
+    if u_lighting.edge_aa == 2u {
+        var offsets = array<vec2<i32>, 4>(
+            vec2<i32>(-1, 0), vec2<i32>(1, 0), vec2<i32>(0, -1), vec2<i32>(0, 1)
+        );
+        var farthest = coord;
+        var farthest_z = textureLoad(linear_z_tex, coord, 0).r;
+        var background = false;
+        for (var i = 0; i < 4; i = i + 1) {
+            let n = coord + offsets[i];
+            if any(n < vec2<i32>(0)) || any(n >= vec2<i32>(dims)) {
+                continue;
+            }
+            let z = textureLoad(linear_z_tex, n, 0).r;
+            if z == 0u {
+                background = true;
+                break;
+            }
+            if z > farthest_z {
+                farthest = n;
+                farthest_z = z;
+            }
+        }
+        if !background && any(farthest != coord) {
+            behind = highlight(farthest, shade(farthest, pixel_ndc(farthest, dims))).rgb;
+        } else if !background {
+            // Nothing farther around: keep the pixel's own color
+            behind = color.rgb;
+        }
+    }
+    return vec4<f32>(mix(behind, color.rgb, coverage), 1.0);
+}
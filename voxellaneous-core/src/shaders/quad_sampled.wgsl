@@ -0,0 +1,38 @@
+struct VSOut {
+    @builtin(position) Position: vec4<f32>,
+    @location(0)         uv:       vec2<f32>,
+};
+
+// Target UV to G-buffer UV for the present fit; see PresentUniforms in lib.rs
+struct PresentUniforms {
+    uv_scale:  vec2<f32>,
+    uv_offset: vec2<f32>,
+};
+@group(0) @binding(2) var<uniform> u_present: PresentUniforms;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vi: u32) -> VSOut {
+    var corners = array<vec2<f32>,3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>( 3.0, -1.0),
+        vec2<f32>(-1.0,  3.0)
+    );
+    var out: VSOut;
+    out.Position = vec4<f32>(corners[vi], 0.0, 1.0);
+    out.uv       = (corners[vi] * 0.5 + vec2<f32>(0.5)) * u_present.uv_scale + u_present.uv_offset;
+    return out;
+}
+
+@group(0) @binding(0) var u_tex: texture_2d<f32>;
+@group(0) @binding(1) var u_samp: sampler;
+
+// quad_float.wgsl through the sampler, so the filter applies when the present fit
+// scales the image
+@fragment
+fn fs_main(in: VSOut) -> @location(0) vec4<f32> {
+    // Letterbox bars outside the image
+    if any(in.uv < vec2<f32>(0.0)) || any(in.uv >= vec2<f32>(1.0)) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    return textureSampleLevel(u_tex, u_samp, vec2<f32>(in.uv.x, 1.0 - in.uv.y), 0.0);
+}
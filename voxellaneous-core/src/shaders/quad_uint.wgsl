@@ -3,6 +3,13 @@ struct VSOut {
     @location(0)         uv:       vec2<f32>,
 };
 
+// Target UV to G-buffer UV for the present fit; see PresentUniforms in lib.rs
+struct PresentUniforms {
+    uv_scale:  vec2<f32>,
+    uv_offset: vec2<f32>,
+};
+@group(0) @binding(2) var<uniform> u_present: PresentUniforms;
+
 @vertex
 fn vs_main(@builtin(vertex_index) vi: u32) -> VSOut {
     var corners = array<vec2<f32>,3>(
@@ -12,7 +19,7 @@ fn vs_main(@builtin(vertex_index) vi: u32) -> VSOut {
     );
     var out: VSOut;
     out.Position = vec4<f32>(corners[vi], 0.0, 1.0);
-    out.uv       = corners[vi] * 0.5 + vec2<f32>(0.5);
+    out.uv       = (corners[vi] * 0.5 + vec2<f32>(0.5)) * u_present.uv_scale + u_present.uv_offset;
     return out;
 }
 
@@ -21,6 +28,10 @@ fn vs_main(@builtin(vertex_index) vi: u32) -> VSOut {
 
 @fragment
 fn fs_main(in: VSOut) -> @location(0) vec4<f32> {
+    // Letterbox bars outside the image
+    if any(in.uv < vec2<f32>(0.0)) || any(in.uv >= vec2<f32>(1.0)) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
     let dims = textureDimensions(u_tex, 0);
     let coord = vec2<i32>(
         i32(in.uv.x * f32(dims.x)),
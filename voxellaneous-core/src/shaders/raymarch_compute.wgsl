@@ -0,0 +1,443 @@
+// Full-screen compute raymarch of up to 8 volumes, writing the same G-buffer as the
+// raster path (shader.wgsl). Each invocation shades two horizontally adjacent pixels
+// so every output word belongs to one invocation; outputs are packed into rows padded
+// to 256 bytes for buffer-to-texture copies into the G-buffer targets.
+
+struct RaymarchParams {
+    inv_vp_matrix:      mat4x4<f32>,
+    cam_pos_ws:         vec3<f32>,
+    voxel_rounding:     f32,
+    size:               vec2<u32>,
+    object_count:       u32,
+    color_row_words:    u32,
+    normal_row_words:   u32,
+    linear_z_row_words: u32,
+    empty_index:        u32,
+};
+
+struct RaymarchObject {
+    model_matrix:     mat4x4<f32>,
+    inv_model_matrix: mat4x4<f32>,
+    dims:             vec3<u32>,
+    // Albedo multiplier, as PerDrawUniforms.tint in shader.wgsl
+    tint:             vec4<f32>,
+    // As PerDrawUniforms.voxel_size in shader.wgsl
+    voxel_size:       vec3<f32>,
+};
+
+struct StaticUniforms {
+    palette: array<vec4<u32>, 64>,
+};
+
+struct ClipPlanesUniforms {
+    planes: array<vec4<f32>, 4>,
+    count:  u32,
+};
+
+@group(0) @binding(0) var voxels_0: texture_3d<u32>;
+@group(0) @binding(1) var voxels_1: texture_3d<u32>;
+@group(0) @binding(2) var voxels_2: texture_3d<u32>;
+@group(0) @binding(3) var voxels_3: texture_3d<u32>;
+@group(0) @binding(4) var voxels_4: texture_3d<u32>;
+@group(0) @binding(5) var voxels_5: texture_3d<u32>;
+@group(0) @binding(6) var voxels_6: texture_3d<u32>;
+@group(0) @binding(7) var voxels_7: texture_3d<u32>;
+@group(0) @binding(8) var<storage, read> objects: array<RaymarchObject, 8>;
+@group(0) @binding(9) var<uniform> u_params: RaymarchParams;
+@group(0) @binding(10) var<uniform> u_static: StaticUniforms;
+@group(0) @binding(11) var<uniform> u_clip: ClipPlanesUniforms;
+@group(0) @binding(12) var<storage, read_write> out_albedo: array<u32>;
+@group(0) @binding(13) var<storage, read_write> out_normal: array<u32>;
+@group(0) @binding(14) var<storage, read_write> out_linear_z: array<u32>;
+// As palette_tex in shader.wgsl
+@group(0) @binding(15) var palette_tex: texture_2d<f32>;
+// In the layout of out_linear_z, as the material target shares its format
+@group(0) @binding(16) var<storage, read_write> out_material: array<u32>;
+
+// VoxelFilterMode: 0 = nearest, 1 = bilinear (across the hit face), 2 = trilinear
+override voxel_filter: u32 = 0u;
+// VoxelWrapMode: 0 = clamp (the outside is empty), 1 = repeat, 2 = mirror
+override voxel_wrap: u32 = 0u;
+// Bytes per linear-Z texel: 2 for R16Uint, 4 for R32Uint
+override linear_z_bytes: u32 = 2u;
+
+// Texture bindings cannot be indexed dynamically without binding arrays
+fn load_voxel(slot: u32, p: vec3<u32>) -> u32 {
+    switch slot {
+        case 0u: { return textureLoad(voxels_0, p, 0).r; }
+        case 1u: { return textureLoad(voxels_1, p, 0).r; }
+        case 2u: { return textureLoad(voxels_2, p, 0).r; }
+        case 3u: { return textureLoad(voxels_3, p, 0).r; }
+        case 4u: { return textureLoad(voxels_4, p, 0).r; }
+        case 5u: { return textureLoad(voxels_5, p, 0).r; }
+        case 6u: { return textureLoad(voxels_6, p, 0).r; }
+        default: { return textureLoad(voxels_7, p, 0).r; }
+    }
+}
+
+// Octahedral normal encoding in [0,1]^2; must match shader.wgsl.
+// (0, 0) is reserved for empty pixels.
+fn encode_normal(n: vec3<f32>) -> vec2<f32> {
+    var p = n.xy / (abs(n.x) + abs(n.y) + abs(n.z));
+    if n.z < 0.0 {
+        let sign_not_zero = select(vec2<f32>(-1.0), vec2<f32>(1.0), p >= vec2<f32>(0.0));
+        p = (vec2<f32>(1.0) - abs(p.yx)) * sign_not_zero;
+    }
+    return max(p * 0.5 + 0.5, vec2<f32>(1.0 / 255.0));
+}
+
+// Material texel as encode_material in shader.wgsl; this path has no occlusion
+fn encode_material(hit: Hit) -> u32 {
+    return min(hit.palette_index, 255u);
+}
+
+fn palette_color(idx: u32) -> vec4<f32> {
+    if idx >= 256u {
+        return textureLoad(palette_tex, vec2<u32>(idx % 256u, idx / 256u), 0);
+    }
+    return unpack4x8unorm(u_static.palette[idx / 4u][idx % 4u]);
+}
+
+// As in shader.wgsl
+fn wrap_voxel(v: vec3<i32>, dims: vec3<i32>) -> vec3<i32> {
+    if voxel_wrap == 1u {
+        return (v % dims + dims) % dims;
+    }
+    if voxel_wrap == 2u {
+        let period = 2 * dims;
+        let m = (v % period + period) % period;
+        return select(m, period - vec3<i32>(1) - m, m >= dims);
+    }
+    return v;
+}
+
+// As in shader.wgsl
+fn filtered_albedo(slot: u32, p_voxel: vec3<f32>, hit_voxel: vec3<u32>, axes: vec3<bool>) -> vec4<f32> {
+    let dims = vec3<i32>(objects[slot].dims);
+    let base_f = floor(p_voxel - vec3<f32>(0.5));
+    let base = select(vec3<i32>(hit_voxel), vec3<i32>(base_f), axes);
+    let frac = select(vec3<f32>(0.0), p_voxel - vec3<f32>(0.5) - base_f, axes);
+
+    var color = vec4<f32>(0.0);
+    var weight = 0.0;
+    for (var c = 0u; c < 8u; c = c + 1u) {
+        let corner = vec3<u32>(c & 1u, (c >> 1u) & 1u, (c >> 2u) & 1u);
+        let w3 = select(vec3<f32>(1.0) - frac, frac, corner == vec3<u32>(1u));
+        let w = w3.x * w3.y * w3.z;
+        let v = wrap_voxel(base + vec3<i32>(corner), dims);
+        if w <= 0.0 || any(v < vec3<i32>(0)) || any(v >= dims) {
+            continue;
+        }
+        let idx = load_voxel(slot, vec3<u32>(v));
+        if idx == u_params.empty_index {
+            continue;
+        }
+        color += w * palette_color(idx);
+        weight += w;
+    }
+    if weight <= 0.0 {
+        return palette_color(load_voxel(slot, hit_voxel));
+    }
+    return color / weight;
+}
+
+fn sd_round_voxel(p: vec3<f32>, half_size: vec3<f32>, r: f32) -> f32 {
+    let q = abs(p) - (half_size - vec3<f32>(r));
+    return length(max(q, vec3<f32>(0.0))) + min(max(q.x, max(q.y, q.z)), 0.0) - r;
+}
+
+struct RoundedHit {
+    hit:    bool,
+    s:      f32,
+    normal: vec3<f32>,
+};
+
+fn trace_rounded_voxel(origin: vec3<f32>, dir: vec3<f32>, voxel: vec3<i32>, s_min: f32, s_max: f32, r: f32, size: vec3<f32>) -> RoundedHit {
+    var result = RoundedHit(false, 0.0, vec3<f32>(0.0));
+    let center = vec3<f32>(voxel) + vec3<f32>(0.5);
+    let half_size = 0.5 * size;
+    let inv_len = 1.0 / length(dir * size);
+    var s = s_min;
+    for (var i = 0u; i < 16u; i = i + 1u) {
+        let p = (origin + s * dir - center) * size;
+        let d = sd_round_voxel(p, half_size, r);
+        if d < 1e-3 {
+            let q = abs(p) - (half_size - vec3<f32>(r));
+            // Back from the stretched voxel by the transpose of its inverse
+            var n = sign(p) * max(q, vec3<f32>(0.0)) * size;
+            if all(q <= vec3<f32>(0.0)) {
+                if q.x > q.y && q.x > q.z {
+                    n = vec3<f32>(sign(p.x), 0.0, 0.0);
+                } else if q.y > q.z {
+                    n = vec3<f32>(0.0, sign(p.y), 0.0);
+                } else {
+                    n = vec3<f32>(0.0, 0.0, sign(p.z));
+                }
+            }
+            result = RoundedHit(true, s, normalize(n));
+            break;
+        }
+        s += d * inv_len;
+        if s > s_max {
+            break;
+        }
+    }
+    return result;
+}
+
+struct Hit {
+    hit:      bool,
+    // World-space distance from the camera
+    distance: f32,
+    albedo:   vec4<f32>,
+    // Object-space, as written by the raster path
+    normal:   vec3<f32>,
+    palette_index: u32,
+};
+
+// Part of the object-space ray from `cam_os` along `dir_os` within [t0, t1] that the
+// clip planes keep, and the index of the plane that moved t0, or -1; as clip_segment
+// in shader.wgsl
+struct ClipSegment {
+    t0:    f32,
+    t1:    f32,
+    plane: i32,
+};
+
+fn clip_segment(model_matrix: mat4x4<f32>, cam_os: vec3<f32>, dir_os: vec3<f32>, t0: f32, t1: f32) -> ClipSegment {
+    var segment = ClipSegment(t0, t1, -1);
+    for (var p = 0u; p < u_clip.count; p = p + 1u) {
+        let plane = u_clip.planes[p] * model_matrix;
+        let start = dot(plane.xyz, cam_os) + plane.w;
+        let rate = dot(plane.xyz, dir_os);
+        if rate == 0.0 {
+            if start < 0.0 {
+                segment.t1 = -1.0;
+            }
+            continue;
+        }
+        let t = -start / rate;
+        if rate < 0.0 {
+            segment.t1 = min(segment.t1, t);
+        } else if t > segment.t0 {
+            segment.t0 = t;
+            segment.plane = i32(p);
+        }
+    }
+    return segment;
+}
+
+// The raster path's per-fragment DDA, run for the ray through one object.
+fn march_object(slot: u32, dir_ws: vec3<f32>) -> Hit {
+    let miss = Hit(false, 0.0, vec4<f32>(0.0), vec3<f32>(0.0), 0u);
+    let obj = objects[slot];
+    let cam_os = (obj.inv_model_matrix * vec4<f32>(u_params.cam_pos_ws, 1.0)).xyz;
+    let dir_os = normalize((obj.inv_model_matrix * vec4<f32>(dir_ws, 0.0)).xyz);
+
+    let dims = obj.dims;
+    let dims_f = vec3<f32>(dims);
+    let inv_dir = 1.0 / dir_os;
+
+    let tmin = (vec3<f32>(-0.5) - cam_os) * inv_dir;
+    let tmax = (vec3<f32>(0.5) - cam_os) * inv_dir;
+    let t_entry = max(max(min(tmin.x, tmax.x), min(tmin.y, tmax.y)), min(tmin.z, tmax.z));
+    let t_exit  = min(min(max(tmin.x, tmax.x), max(tmin.y, tmax.y)), max(tmin.z, tmax.z));
+    if t_exit < 0.0 || t_entry > t_exit {
+        return miss;
+    }
+
+    // Marches only what the clip planes keep, and wrapped goes on past the grid into
+    // its copies, as the raster path does
+    let t_end = select(t_exit, 3.4e38, voxel_wrap != 0u);
+    let clip = clip_segment(obj.model_matrix, cam_os, dir_os, max(t_entry, 0.0), t_end);
+    var t = clip.t0;
+    if t > clip.t1 {
+        return miss;
+    }
+    let t_start = t;
+    let ray_start = cam_os + t * dir_os + vec3<f32>(0.5);
+    let offset = dir_os * (1.0 / dims_f);
+    let ray_voxel = ray_start * dims_f + offset;
+    var voxel = vec3<i32>(floor(ray_voxel));
+    let step = vec3<i32>(select(vec3<f32>(-1.0), vec3<f32>(1.0), dir_os > vec3<f32>(0.0)));
+    let next_boundary = select(floor(ray_voxel), vec3<f32>(ceil(ray_voxel)), dir_os > vec3<f32>(0.0));
+    let inv_dir_voxel = inv_dir / dims_f;
+    var t_max = (next_boundary - ray_voxel) * inv_dir_voxel;
+    let t_delta = abs(inv_dir_voxel);
+    var t_cell = 0.0;
+
+    var hit = false;
+    var hit_idx = 0u;
+    var hit_voxel = vec3<u32>(0u);
+    var hit_t = 0.0;
+    var hit_normal = vec3<f32>(0.0);
+
+    let entry_t = vec3<f32>(min(tmin.x, tmax.x), min(tmin.y, tmax.y), min(tmin.z, tmax.z));
+    var last_axis = 0;
+    if entry_t.y > entry_t.x && entry_t.y > entry_t.z {
+        last_axis = 1;
+    } else if entry_t.z > entry_t.x {
+        last_axis = 2;
+    }
+
+    for (var i = 0u; i < 256u; i = i + 1u) {
+        let outside = any(voxel < vec3<i32>(0)) || any(voxel >= vec3<i32>(dims));
+        if outside && voxel_wrap == 0u {
+            break;
+        }
+
+        let coord = vec3<u32>(wrap_voxel(voxel, vec3<i32>(dims)));
+        let idx = load_voxel(slot, coord);
+
+        if idx != u_params.empty_index && u_params.voxel_rounding > 0.0 {
+            let cell_exit = min(t_max.x, min(t_max.y, t_max.z));
+            let rounded = trace_rounded_voxel(ray_voxel, dir_os * dims_f, voxel, t_cell, cell_exit, u_params.voxel_rounding, obj.voxel_size);
+            if rounded.hit {
+                hit = true;
+                hit_idx = idx;
+                hit_voxel = coord;
+                hit_t = t_start + rounded.s;
+                hit_normal = rounded.normal;
+                break;
+            }
+        } else if idx != u_params.empty_index {
+            hit = true;
+            hit_idx = idx;
+            hit_voxel = coord;
+            hit_t = t;
+            if last_axis == 0 {
+                hit_normal = vec3<f32>(-f32(step.x), 0.0, 0.0);
+            } else if last_axis == 1 {
+                hit_normal = vec3<f32>(0.0, -f32(step.y), 0.0);
+            } else {
+                hit_normal = vec3<f32>(0.0, 0.0, -f32(step.z));
+            }
+            break;
+        }
+
+        if t_max.x < t_max.y && t_max.x < t_max.z {
+            voxel.x += step.x;
+            t = t_start + t_max.x;
+            t_cell = t_max.x;
+            t_max.x += t_delta.x;
+            last_axis = 0;
+        } else if t_max.y < t_max.z {
+            voxel.y += step.y;
+            t = t_start + t_max.y;
+            t_cell = t_max.y;
+            t_max.y += t_delta.y;
+            last_axis = 1;
+        } else {
+            voxel.z += step.z;
+            t = t_start + t_max.z;
+            t_cell = t_max.z;
+            t_max.z += t_delta.z;
+            last_axis = 2;
+        }
+    }
+
+    if !hit || hit_t > clip.t1 {
+        return miss;
+    }
+
+    // A voxel filled where the ray starts on a clip plane is cut by it, and faces
+    // out of the kept half-space
+    if clip.plane >= 0 && hit_t <= t_start {
+        hit_normal = -normalize((u_clip.planes[clip.plane] * obj.model_matrix).xyz);
+    }
+
+    let hit_pos_os = cam_os + hit_t * dir_os;
+    let hit_pos_ws = (obj.model_matrix * vec4<f32>(hit_pos_os, 1.0)).xyz;
+
+    var albedo = palette_color(hit_idx);
+    if voxel_filter != 0u {
+        let p_voxel = (hit_pos_os + vec3<f32>(0.5)) * dims_f - hit_normal * 1e-3;
+        var axes = vec3<bool>(true);
+        if voxel_filter == 1u {
+            axes = abs(hit_normal) < vec3<f32>(0.5);
+        }
+        albedo = filtered_albedo(slot, p_voxel, hit_voxel, axes);
+    }
+    albedo = vec4<f32>(albedo.rgb * obj.tint.rgb, albedo.a);
+    let distance = length(hit_pos_ws - u_params.cam_pos_ws);
+    // World-space normal by the inverse transpose, as normal_to_world in shader.wgsl
+    let normal_ws = normalize((vec4<f32>(hit_normal, 0.0) * obj.inv_model_matrix).xyz);
+    return Hit(true, distance, albedo, normal_ws, hit_idx);
+}
+
+// Nearest hit over all objects along the camera ray through `pixel`.
+fn trace_pixel(pixel: vec2<u32>) -> Hit {
+    let ndc = vec2<f32>(
+        (f32(pixel.x) + 0.5) / f32(u_params.size.x) * 2.0 - 1.0,
+        1.0 - (f32(pixel.y) + 0.5) / f32(u_params.size.y) * 2.0
+    );
+    let near = u_params.inv_vp_matrix * vec4<f32>(ndc, 0.0, 1.0);
+    let far = u_params.inv_vp_matrix * vec4<f32>(ndc, 1.0, 1.0);
+    let dir_ws = normalize(far.xyz / far.w - near.xyz / near.w);
+
+    var best = Hit(false, 0.0, vec4<f32>(0.0), vec3<f32>(0.0), 0u);
+    for (var slot = 0u; slot < u_params.object_count; slot = slot + 1u) {
+        let hit = march_object(slot, dir_ws);
+        if hit.hit && (!best.hit || hit.distance < best.distance) {
+            best = hit;
+        }
+    }
+    return best;
+}
+
+// Encoding must match LINEAR_Z_MAX_DISTANCE / LINEAR_Z_MAX_VALUE in constants.rs
+fn encode_linear_z(hit: Hit) -> u32 {
+    if !hit.hit {
+        return 0u;
+    }
+    return u32(clamp(hit.distance / 100.0, 0.0, 1.0) * 65535.0);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let x0 = id.x * 2u;
+    if x0 >= u_params.size.x || id.y >= u_params.size.y {
+        return;
+    }
+
+    var normals = vec4<f32>(0.0);
+    var linear_z = array<u32, 2>(0u, 0u);
+    var material = array<u32, 2>(0u, 0u);
+    for (var i = 0u; i < 2u; i = i + 1u) {
+        let x = x0 + i;
+        if x >= u_params.size.x {
+            break;
+        }
+        let hit = trace_pixel(vec2<u32>(x, id.y));
+        let color_index = id.y * u_params.color_row_words + x;
+        if hit.hit {
+            // Full coverage: this path has no edge antialiasing
+            out_albedo[color_index] = pack4x8unorm(vec4<f32>(hit.albedo.rgb, 1.0));
+            let normal = encode_normal(hit.normal);
+            if i == 0u {
+                normals = vec4<f32>(normal, normals.zw);
+            } else {
+                normals = vec4<f32>(normals.xy, normal);
+            }
+            material[i] = encode_material(hit);
+        } else {
+            out_albedo[color_index] = 0u;
+        }
+        linear_z[i] = encode_linear_z(hit);
+    }
+    // Two Rg8Unorm texels per word
+    out_normal[id.y * u_params.normal_row_words + id.x] = pack4x8unorm(normals);
+
+    let row = id.y * u_params.linear_z_row_words;
+    if linear_z_bytes == 2u {
+        out_linear_z[row + id.x] = (linear_z[0] & 0xffffu) | (linear_z[1] << 16u);
+        out_material[row + id.x] = (material[0] & 0xffffu) | (material[1] << 16u);
+    } else {
+        out_linear_z[row + x0] = linear_z[0];
+        out_material[row + x0] = material[0];
+        if x0 + 1u < u_params.size.x {
+            out_linear_z[row + x0 + 1u] = linear_z[1];
+            out_material[row + x0 + 1u] = material[1];
+        }
+    }
+}
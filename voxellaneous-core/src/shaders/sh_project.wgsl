@@ -0,0 +1,74 @@
+// Projects a cubemap onto nine spherical harmonics for light_probes.rs: one
+// workgroup per face sums its texels weighted by solid angle, and the CPU adds the
+// six faces. The basis and band scaling match environment.rs.
+
+@group(0) @binding(0) var cubemap: texture_2d_array<f32>;
+// 9 rgb coefficients per face
+@group(0) @binding(1) var<storage, read_write> out_coeffs: array<vec4<f32>, 54>;
+
+const WORKGROUP_SIZE: u32 = 64u;
+
+var<workgroup> partial: array<array<vec3<f32>, 9>, 64>;
+
+// Direction through (u, v) in [-1, 1]^2 (v down) of cube face `face`, in the
+// +X, -X, +Y, -Y, +Z, -Z layer order of WebGPU cube textures
+fn face_direction(face: u32, u: f32, v: f32) -> vec3<f32> {
+    switch face {
+        case 0u: { return vec3<f32>(1.0, -v, -u); }
+        case 1u: { return vec3<f32>(-1.0, -v, u); }
+        case 2u: { return vec3<f32>(u, 1.0, v); }
+        case 3u: { return vec3<f32>(u, -1.0, -v); }
+        case 4u: { return vec3<f32>(u, -v, 1.0); }
+        default: { return vec3<f32>(-u, -v, -1.0); }
+    }
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn cs_main(
+    @builtin(local_invocation_index) lid: u32,
+    @builtin(workgroup_id) wid: vec3<u32>,
+) {
+    let face = wid.x;
+    let size = textureDimensions(cubemap, 0).x;
+    var sum: array<vec3<f32>, 9>;
+    for (var k = 0u; k < 9u; k = k + 1u) {
+        sum[k] = vec3<f32>(0.0);
+    }
+    for (var i = lid; i < size * size; i = i + WORKGROUP_SIZE) {
+        let texel = vec2<u32>(i % size, i / size);
+        let uv = (vec2<f32>(texel) + vec2<f32>(0.5)) / f32(size) * 2.0 - 1.0;
+        let r2 = 1.0 + dot(uv, uv);
+        // Solid angle of the texel: its area on the unit-distance face over r^3
+        let weight = 4.0 / (f32(size * size) * r2 * sqrt(r2));
+        let d = normalize(face_direction(face, uv.x, uv.y));
+        let color = textureLoad(cubemap, texel, face, 0).rgb * weight;
+        sum[0] += color * 0.282095;
+        sum[1] += color * (0.488603 * d.y);
+        sum[2] += color * (0.488603 * d.z);
+        sum[3] += color * (0.488603 * d.x);
+        sum[4] += color * (1.092548 * d.x * d.y);
+        sum[5] += color * (1.092548 * d.y * d.z);
+        sum[6] += color * (0.315392 * (3.0 * d.z * d.z - 1.0));
+        sum[7] += color * (1.092548 * d.x * d.z);
+        sum[8] += color * (0.546274 * (d.x * d.x - d.y * d.y));
+    }
+    partial[lid] = sum;
+    workgroupBarrier();
+
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride = stride / 2u) {
+        if lid < stride {
+            for (var k = 0u; k < 9u; k = k + 1u) {
+                partial[lid][k] += partial[lid + stride][k];
+            }
+        }
+        workgroupBarrier();
+    }
+
+    if lid == 0u {
+        // Clamped-cosine convolution over pi, per band
+        var band_scale = array<f32, 9>(1.0, 2.0 / 3.0, 2.0 / 3.0, 2.0 / 3.0, 0.25, 0.25, 0.25, 0.25, 0.25);
+        for (var k = 0u; k < 9u; k = k + 1u) {
+            out_coeffs[face * 9u + k] = vec4<f32>(partial[0][k] * band_scale[k], 0.0);
+        }
+    }
+}
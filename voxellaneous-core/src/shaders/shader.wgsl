@@ -10,7 +10,13 @@ struct VertexOutput {
 struct PerFrameUniforms {
     vp_matrix:  mat4x4<f32>,
     cam_pos_ws: vec3<f32>,
-    _padding:   f32,
+    voxel_rounding: f32,
+    // 0 = every pixel, else 1 + the (x + y) parity of the pixels to render
+    checkerboard: u32,
+    // Palette index of empty voxels
+    empty_index: u32,
+    // Angle in radians between the view rays of adjacent pixels; 0 disables edge AA
+    pixel_angle: f32,
 };
 @group(1) @binding(0) var<uniform> u_frame: PerFrameUniforms;
 
@@ -18,33 +24,303 @@ struct StaticUniforms {
     palette: array<vec4<u32>, 64>,
 };
 @group(0) @binding(0) var<uniform> u_static: StaticUniforms;
+// The whole palette, entry i at (i % 256, i / 256), for u16 voxels past the 256
+// entries of u_static; 1x1 when the palette has no more
+@group(0) @binding(2) var palette_tex: texture_2d<f32>;
+
+struct ClipPlanesUniforms {
+    planes: array<vec4<f32>, 4>,
+    count:  u32,
+    // Non-zero caps cut voxels with `cap_color` instead of their own albedo
+    cap:       u32,
+    cap_color: vec4<f32>,
+};
+@group(0) @binding(1) var<uniform> u_clip: ClipPlanesUniforms;
 
 struct PerDrawUniforms {
     model_matrix:     mat4x4<f32>,
     inv_model_matrix: mat4x4<f32>,
+    // Object-space box around the filled voxels, inside [-0.5,0.5]^3
+    proxy_min:        vec3<f32>,
+    proxy_max:        vec3<f32>,
+    // Albedo multiplier, white by default; see Renderer::set_object_tint
+    tint:             vec4<f32>,
+    // VoxelObject::voxel_proportions, for rounded voxels
+    voxel_size:       vec3<f32>,
 };
 @group(2) @binding(1) var<uniform> u_draw: PerDrawUniforms;
 
-@group(2) @binding(0) var voxel_texture: texture_3d<u32>;
+// VoxelTexture and the texel_* functions come from voxel_palette.wgsl or
+// voxel_rgba.wgsl, appended by ColorMode::compose_shader
+@group(2) @binding(0) var voxel_texture: VoxelTexture;
+// Baked AO per voxel (1 = unoccluded), or a single unoccluded texel
+@group(2) @binding(2) var ao_texture: texture_3d<f32>;
+
+// VoxelFilterMode: 0 = nearest, 1 = bilinear (across the hit face), 2 = trilinear
+override voxel_filter: u32 = 0u;
+// VoxelWrapMode: 0 = clamp (the outside is empty), 1 = repeat, 2 = mirror
+override voxel_wrap: u32 = 0u;
 
-// G‑buffer outputs: albedo, normal, linear depth
+// G‑buffer outputs: albedo, normal, linear depth, material
 struct GBuffer {
     @location(0) albedo:    vec4<f32>, // Rgba8Unorm
-    @location(1) normal:    vec4<f32>, // Rgba8Unorm encoded
-    @location(2) linear_z:  u32,       // R16Uint
+    @location(1) normal:    vec2<f32>, // Rg8Unorm, octahedral
+    @location(2) linear_z:  u32,       // R16Uint (R32Uint fallback)
+    @location(3) material:  u32,       // As linear_z: palette index, occlusion << 8
 };
 
+// Material texel: the palette index, for the material table of the lighting pass,
+// and the ambient occlusion as unorm8 above it
+fn encode_material(idx: u32, occlusion: f32) -> u32 {
+    return min(idx, 255u) | (u32(round(saturate(occlusion) * 255.0)) << 8u);
+}
+
+// Object-space normal to world space by the inverse transpose of the model matrix,
+// which keeps it perpendicular to the faces under non-uniform scale (v * M is
+// transpose(M) * v)
+fn normal_to_world(n_os: vec3<f32>) -> vec3<f32> {
+    return normalize((vec4<f32>(n_os, 0.0) * u_draw.inv_model_matrix).xyz);
+}
+
+// Octahedral normal encoding in [0,1]^2; must match decode_normal in quad_lighting.wgsl.
+// (0, 0) is reserved for empty pixels.
+fn encode_normal(n: vec3<f32>) -> vec2<f32> {
+    var p = n.xy / (abs(n.x) + abs(n.y) + abs(n.z));
+    if n.z < 0.0 {
+        let sign_not_zero = select(vec2<f32>(-1.0), vec2<f32>(1.0), p >= vec2<f32>(0.0));
+        p = (vec2<f32>(1.0) - abs(p.yx)) * sign_not_zero;
+    }
+    return max(p * 0.5 + 0.5, vec2<f32>(1.0 / 255.0));
+}
+
+fn palette_color(idx: u32) -> vec4<f32> {
+    if idx >= 256u {
+        return textureLoad(palette_tex, vec2<u32>(idx % 256u, idx / 256u), 0);
+    }
+    return unpack4x8unorm(u_static.palette[idx / 4u][idx % 4u]);
+}
+
+// Colour of voxel `coord`: the texel itself for rgba objects, else its palette entry
+fn voxel_albedo(coord: vec3<u32>) -> vec4<f32> {
+    if RGBA_VOXELS {
+        return texel_rgba(voxel_texture, coord);
+    }
+    return palette_color(texel_index(voxel_texture, coord));
+}
+
+// Voxel `v` of a `dims` grid wrapped by voxel_wrap; left outside the grid under clamp
+fn wrap_voxel(v: vec3<i32>, dims: vec3<i32>) -> vec3<i32> {
+    if voxel_wrap == 1u {
+        return (v % dims + dims) % dims;
+    }
+    if voxel_wrap == 2u {
+        let period = 2 * dims;
+        let m = (v % period + period) % period;
+        return select(m, period - vec3<i32>(1) - m, m >= dims);
+    }
+    return v;
+}
+
+// Blends the colors of occupied voxels around `p_voxel` (voxel space) with
+// trilinear weights. Axes not selected by `axes` stay on `hit_voxel`'s layer.
+fn filtered_albedo(p_voxel: vec3<f32>, hit_voxel: vec3<u32>, axes: vec3<bool>) -> vec4<f32> {
+    let dims = vec3<i32>(textureDimensions(voxel_texture, 0));
+    let base_f = floor(p_voxel - vec3<f32>(0.5));
+    let base = select(vec3<i32>(hit_voxel), vec3<i32>(base_f), axes);
+    let frac = select(vec3<f32>(0.0), p_voxel - vec3<f32>(0.5) - base_f, axes);
+
+    var color = vec4<f32>(0.0);
+    var weight = 0.0;
+    for (var c = 0u; c < 8u; c = c + 1u) {
+        let corner = vec3<u32>(c & 1u, (c >> 1u) & 1u, (c >> 2u) & 1u);
+        let w3 = select(vec3<f32>(1.0) - frac, frac, corner == vec3<u32>(1u));
+        let w = w3.x * w3.y * w3.z;
+        let v = wrap_voxel(base + vec3<i32>(corner), dims);
+        if w <= 0.0 || any(v < vec3<i32>(0)) || any(v >= dims) {
+            continue;
+        }
+        if !texel_filled(voxel_texture, vec3<u32>(v), u_frame.empty_index) {
+            continue;
+        }
+        color += w * voxel_albedo(vec3<u32>(v));
+        weight += w;
+    }
+    if weight <= 0.0 {
+        return voxel_albedo(hit_voxel);
+    }
+    return color / weight;
+}
+
+fn voxel_filled(voxel: vec3<i32>) -> bool {
+    let dims = vec3<i32>(textureDimensions(voxel_texture, 0));
+    let v = wrap_voxel(voxel, dims);
+    if any(v < vec3<i32>(0)) || any(v >= dims) {
+        return false;
+    }
+    return texel_filled(voxel_texture, vec3<u32>(v), u_frame.empty_index);
+}
+
+// Fraction of the pixel covered by the hit face, from the screen-space distance of the
+// hit point to the nearest silhouette edge of the face: an edge with no filled voxel
+// across it, neither in the face plane nor in front of it. The coverage ramps over one
+// pixel inside the silhouette, so edges move smoothly instead of stepping.
+// `hit_cell` is the hit voxel before wrapping, so it contains the hit point.
+fn edge_coverage(hit_pos_os: vec3<f32>, hit_cell: vec3<i32>, normal: vec3<f32>, view_ws: vec3<f32>, distance: f32) -> f32 {
+    let dims_f = vec3<f32>(textureDimensions(voxel_texture, 0));
+    // Hit point inside the hit voxel, in [0,1]^3
+    let local = (hit_pos_os + vec3<f32>(0.5)) * dims_f - vec3<f32>(hit_cell);
+    let footprint = distance * u_frame.pixel_angle;
+    var offsets = array<vec3<i32>, 6>(
+        vec3<i32>(-1, 0, 0), vec3<i32>(1, 0, 0),
+        vec3<i32>(0, -1, 0), vec3<i32>(0, 1, 0),
+        vec3<i32>(0, 0, -1), vec3<i32>(0, 0, 1)
+    );
+    var coverage = 1.0;
+    for (var i = 0; i < 6; i = i + 1) {
+        let e = vec3<f32>(offsets[i]);
+        if abs(dot(e, normal)) > 0.5 {
+            continue;
+        }
+        let across = hit_cell + offsets[i];
+        if voxel_filled(across) || voxel_filled(across + vec3<i32>(normal)) {
+            continue;
+        }
+        // Distance to the face edge on the side of `e`, in voxels
+        let along = dot(local, e);
+        let voxel_distance = select(-along, 1.0 - along, dot(e, vec3<f32>(1.0)) > 0.0);
+        // One voxel step along the edge normal, in world space
+        let step_ws = (u_draw.model_matrix * vec4<f32>(e / dims_f, 0.0)).xyz;
+        let step_len = length(step_ws);
+        // In-plane distances shrink on screen as the face turns away from the view
+        let facing = dot(step_ws / step_len, view_ws);
+        let projected = voxel_distance * step_len * sqrt(max(1.0 - facing * facing, 0.0));
+        coverage = min(coverage, clamp(projected / footprint + 0.5, 0.0, 1.0));
+    }
+    return coverage;
+}
+
+// Signed distance to a voxel-sized box (`half_size`, 0.5 for cubic voxels) with corners
+// rounded by `r`.
+fn sd_round_voxel(p: vec3<f32>, half_size: vec3<f32>, r: f32) -> f32 {
+    let q = abs(p) - (half_size - vec3<f32>(r));
+    return length(max(q, vec3<f32>(0.0))) + min(max(q.x, max(q.y, q.z)), 0.0) - r;
+}
+
+struct RoundedHit {
+    hit:    bool,
+    s:      f32,
+    normal: vec3<f32>,
+};
+
+// Sphere-traces the rounded voxel `voxel` along `origin + s * dir` (voxel space) for s in [s_min, s_max].
+// The distances are measured with the voxel stretched to its proportions `size`, so the
+// rounding stays circular on anisotropic voxels.
+fn trace_rounded_voxel(origin: vec3<f32>, dir: vec3<f32>, voxel: vec3<i32>, s_min: f32, s_max: f32, r: f32, size: vec3<f32>) -> RoundedHit {
+    var result = RoundedHit(false, 0.0, vec3<f32>(0.0));
+    let center = vec3<f32>(voxel) + vec3<f32>(0.5);
+    let half_size = 0.5 * size;
+    let inv_len = 1.0 / length(dir * size);
+    var s = s_min;
+    for (var i = 0u; i < 16u; i = i + 1u) {
+        let p = (origin + s * dir - center) * size;
+        let d = sd_round_voxel(p, half_size, r);
+        if d < 1e-3 {
+            let q = abs(p) - (half_size - vec3<f32>(r));
+            // Back from the stretched voxel by the transpose of its inverse
+            var n = sign(p) * max(q, vec3<f32>(0.0)) * size;
+            if all(q <= vec3<f32>(0.0)) {
+                // Inside the flat core: pick the dominant axis
+                if q.x > q.y && q.x > q.z {
+                    n = vec3<f32>(sign(p.x), 0.0, 0.0);
+                } else if q.y > q.z {
+                    n = vec3<f32>(0.0, sign(p.y), 0.0);
+                } else {
+                    n = vec3<f32>(0.0, 0.0, sign(p.z));
+                }
+            }
+            result = RoundedHit(true, s, normalize(n));
+            break;
+        }
+        s += d * inv_len;
+        if s > s_max {
+            break;
+        }
+    }
+    return result;
+}
+
+// Ray parameter at which the object-space ray from `cam_os` along `dir_os` crosses the
+// near plane (clip-space z = 0), or 0 when it does not ahead of the camera
+fn near_plane_t(cam_os: vec3<f32>, dir_os: vec3<f32>) -> f32 {
+    let mvp = u_frame.vp_matrix * u_draw.model_matrix;
+    let z0 = (mvp * vec4<f32>(cam_os, 1.0)).z;
+    let dz = (mvp * vec4<f32>(dir_os, 0.0)).z;
+    if dz <= 0.0 {
+        return 0.0;
+    }
+    return max(-z0 / dz, 0.0);
+}
+
+// Part of the object-space ray from `cam_os` along `dir_os` within [t0, t1] that the
+// clip planes keep, and the index of the plane that moved t0, or -1
+struct ClipSegment {
+    t0:    f32,
+    t1:    f32,
+    plane: i32,
+};
+
+fn clip_segment(cam_os: vec3<f32>, dir_os: vec3<f32>, t0: f32, t1: f32) -> ClipSegment {
+    var segment = ClipSegment(t0, t1, -1);
+    for (var p = 0u; p < u_clip.count; p = p + 1u) {
+        // dot(plane, M * x) is dot(plane * M, x), the plane in object space
+        let plane = u_clip.planes[p] * u_draw.model_matrix;
+        let start = dot(plane.xyz, cam_os) + plane.w;
+        let rate = dot(plane.xyz, dir_os);
+        if rate == 0.0 {
+            if start < 0.0 {
+                segment.t1 = -1.0;
+            }
+            continue;
+        }
+        let t = -start / rate;
+        if rate < 0.0 {
+            segment.t1 = min(segment.t1, t);
+        } else if t > segment.t0 {
+            segment.t0 = t;
+            segment.plane = i32(p);
+        }
+    }
+    return segment;
+}
+
 @vertex
 fn vs_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
-    let ws4 = u_draw.model_matrix * vec4<f32>(in.position, 1.0);
+    // Shrink the unit cube to the proxy box
+    let obj_pos = mix(u_draw.proxy_min, u_draw.proxy_max, in.position + vec3<f32>(0.5));
+    let ws4 = u_draw.model_matrix * vec4<f32>(obj_pos, 1.0);
     out.position = u_frame.vp_matrix * ws4;
-    out.obj_pos  = in.position;
+    out.obj_pos  = obj_pos;
     return out;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> GBuffer {
+    return raymarch(in);
+}
+
+// AlbedoOnly G-buffer profile: a single color target
+@fragment
+fn fs_albedo(in: VertexOutput) -> @location(0) vec4<f32> {
+    return raymarch(in).albedo;
+}
+
+fn raymarch(in: VertexOutput) -> GBuffer {
+    let parity = (u32(in.position.x) + u32(in.position.y)) % 2u;
+    if u_frame.checkerboard != 0u && parity != u_frame.checkerboard - 1u {
+        discard;
+    }
+
     let cam_os = (u_draw.inv_model_matrix * vec4<f32>(u_frame.cam_pos_ws, 1.0)).xyz;
     let dir_os = normalize(in.obj_pos - cam_os);
 
@@ -52,8 +328,10 @@ fn fs_main(in: VertexOutput) -> GBuffer {
     let dims_f = vec3<f32>(dims);
     let inv_dir = 1.0 / dir_os;
 
-    let bounds_min = vec3<f32>(-0.5);
-    let bounds_max = vec3<f32>(0.5);
+    // Same box as the proxy; it is voxel-aligned, so the walk below only skips
+    // empty voxels and hits exactly what a walk from the unit cube would
+    let bounds_min = u_draw.proxy_min;
+    let bounds_max = u_draw.proxy_max;
     let tmin = (bounds_min - cam_os) * inv_dir;
     let tmax = (bounds_max - cam_os) * inv_dir;
 
@@ -64,7 +342,17 @@ fn fs_main(in: VertexOutput) -> GBuffer {
         discard;
     }
 
-    var t = max(t_entry, 0.0);
+    // The ray starts no nearer than the near plane, which clips marched voxels as it
+    // would rasterized geometry when the camera is inside the proxy, and marches only
+    // what the clip planes keep, so it reaches the voxels behind the clipped ones.
+    // Wrapped, the ray goes on past the box into the grid's copies (see wrapped below)
+    let t_end = select(t_exit, 3.4e38, voxel_wrap != 0u);
+    let clip = clip_segment(cam_os, dir_os, max(t_entry, near_plane_t(cam_os, dir_os)), t_end);
+    var t = clip.t0;
+    if t > clip.t1 {
+        discard;
+    }
+    let t_start = t;
     let ray_start = cam_os + t * dir_os + vec3<f32>(0.5);
     let offset = dir_os * (1.0 / dims_f);
     let ray_voxel = ray_start * dims_f + offset;
@@ -74,9 +362,13 @@ fn fs_main(in: VertexOutput) -> GBuffer {
     let inv_dir_voxel = inv_dir / dims_f;
     var t_max = (next_boundary - ray_voxel) * inv_dir_voxel;
     let t_delta = abs(inv_dir_voxel);
+    // Ray parameter (from ray_voxel) at which the current voxel was entered
+    var t_cell = 0.0;
 
+    var hit = false;
     var hit_idx = 0u;
     var hit_voxel = vec3<u32>(0u);
+    var hit_cell = vec3<i32>(0);
     var hit_t = 0.0;
     var hit_normal = vec3<f32>(0.0);
 
@@ -89,18 +381,40 @@ fn fs_main(in: VertexOutput) -> GBuffer {
         last_axis = 2;
     }
 
+    // The walk ends where it leaves the proxy box rather than the grid, as a slice
+    // view (see Renderer::set_slice_view) narrows the box to one layer of filled voxels.
+    // Wrapped, the box spans the whole grid and the walk crosses its faces into the
+    // copies on every axis but a slice's, until MAX_STEPS
+    let proxy_lo = vec3<i32>(round((bounds_min + vec3<f32>(0.5)) * dims_f));
+    let proxy_hi = vec3<i32>(round((bounds_max + vec3<f32>(0.5)) * dims_f));
+    let dims_i = vec3<i32>(dims);
+    let wrapped = vec3<bool>(voxel_wrap != 0u) & (proxy_lo == vec3<i32>(0)) & (proxy_hi == dims_i);
     let MAX_STEPS = 256u;
     for (var i = 0u; i < MAX_STEPS; i = i + 1u) {
-        if any(voxel < vec3<i32>(0)) || any(voxel >= vec3<i32>(dims)) {
+        if any(((voxel < proxy_lo) | (voxel >= proxy_hi)) & !wrapped) {
             break;
         }
 
-        let coord = vec3<u32>(voxel);
-        let idx = textureLoad(voxel_texture, coord, 0).r;
+        let coord = vec3<u32>(wrap_voxel(voxel, dims_i));
+        let filled = texel_filled(voxel_texture, coord, u_frame.empty_index);
 
-        if idx != 0u {
-            hit_idx = idx;
+        if filled && u_frame.voxel_rounding > 0.0 {
+            let cell_exit = min(t_max.x, min(t_max.y, t_max.z));
+            let rounded = trace_rounded_voxel(ray_voxel, dir_os * dims_f, voxel, t_cell, cell_exit, u_frame.voxel_rounding, u_draw.voxel_size);
+            if rounded.hit {
+                hit = true;
+                hit_idx = texel_index(voxel_texture, coord);
+                hit_voxel = coord;
+                hit_cell = voxel;
+                hit_t = t_start + rounded.s;
+                hit_normal = rounded.normal;
+                break;
+            }
+        } else if filled {
+            hit = true;
+            hit_idx = texel_index(voxel_texture, coord);
             hit_voxel = coord;
+            hit_cell = voxel;
             hit_t = t;
             
             if last_axis == 0 {
@@ -116,36 +430,71 @@ fn fs_main(in: VertexOutput) -> GBuffer {
 
         if t_max.x < t_max.y && t_max.x < t_max.z {
             voxel.x += step.x;
-            t += t_max.x;
+            t = t_start + t_max.x;
+            t_cell = t_max.x;
             t_max.x += t_delta.x;
             last_axis = 0;
         } else if t_max.y < t_max.z {
             voxel.y += step.y;
-            t += t_max.y;
+            t = t_start + t_max.y;
+            t_cell = t_max.y;
             t_max.y += t_delta.y;
             last_axis = 1;
         } else {
             voxel.z += step.z;
-            t += t_max.z;
+            t = t_start + t_max.z;
+            t_cell = t_max.z;
             t_max.z += t_delta.z;
             last_axis = 2;
         }
     }
 
-    if hit_idx == 0u {
+    if !hit || hit_t > clip.t1 {
         discard;
     }
 
+    // A voxel filled where the ray starts on a clip plane is cut by it, and faces
+    // out of the kept half-space
+    let cut = clip.plane >= 0 && hit_t <= t_start;
+    if cut {
+        hit_normal = -normalize((u_clip.planes[clip.plane] * u_draw.model_matrix).xyz);
+    }
+
     let hit_pos_os = cam_os + hit_t * dir_os;
     let hit_pos_ws = (u_draw.model_matrix * vec4<f32>(hit_pos_os, 1.0)).xyz;
 
-    let packed = u_static.palette[hit_idx / 4u][hit_idx % 4u];
-    let albedo = unpack4x8unorm(packed);
+    var albedo = voxel_albedo(hit_voxel);
+    if voxel_filter != 0u {
+        // Sample slightly inside the hit voxel so the face layer is the hit one
+        let p_voxel = (hit_pos_os + vec3<f32>(0.5)) * dims_f - hit_normal * 1e-3;
+        var axes = vec3<bool>(true);
+        if voxel_filter == 1u {
+            axes = abs(hit_normal) < vec3<f32>(0.5);
+        }
+        albedo = filtered_albedo(p_voxel, hit_voxel, axes);
+    }
+    albedo = vec4<f32>(albedo.rgb * u_draw.tint.rgb, albedo.a);
+    if cut && u_clip.cap != 0u {
+        albedo = u_clip.cap_color;
+    }
 
+    // Encoding must match LINEAR_Z_MAX_DISTANCE / LINEAR_Z_MAX_VALUE in constants.rs
     let linear_z = length(hit_pos_ws - u_frame.cam_pos_ws);
+
+    // Albedo alpha carries the edge coverage for the lighting pass; rounded voxels
+    // have no sharp face edges
+    albedo.a = 1.0;
+    if u_frame.pixel_angle > 0.0 && u_frame.voxel_rounding <= 0.0 && !cut {
+        let view_ws = (hit_pos_ws - u_frame.cam_pos_ws) / linear_z;
+        albedo.a = edge_coverage(hit_pos_os, hit_cell, hit_normal, view_ws, linear_z);
+    }
+    // Stored as occlusion, so objects without AO keep the 0 other writers leave
+    let ao_coord = min(hit_voxel, textureDimensions(ao_texture) - vec3<u32>(1u));
+    let occlusion = 1.0 - textureLoad(ao_texture, ao_coord, 0).r;
     return GBuffer(
         albedo,
-        vec4<f32>(hit_normal * 0.5 + 0.5, 1.0),
-        u32(clamp(linear_z / 100.0, 0.0, 1.0) * 65535.0)
+        encode_normal(normal_to_world(hit_normal)),
+        u32(clamp(linear_z / 100.0, 0.0, 1.0) * 65535.0),
+        encode_material(hit_idx, occlusion)
     );
 }
\ No newline at end of file
@@ -0,0 +1,152 @@
+// Shadow caster depth for one cascade of shadows.rs. Voxel objects are marched along
+// the light direction from their proxy box so they cast the shadow of their filled
+// voxels, not of the box; meshes write their rasterized depth.
+
+struct CascadeUniforms {
+    vp_matrix:     mat4x4<f32>,
+    // Direction the light travels, world space
+    light_forward: vec3<f32>,
+    empty_index:   u32,
+    // Depth bias of the caster pipelines, see voxel_depth_bias
+    constant_bias: f32,
+    slope_bias:    f32,
+    bias_clamp:    f32,
+    // Shadow-map texel size in NDC
+    ndc_per_texel: f32,
+};
+@group(0) @binding(0) var<uniform> u_cascade: CascadeUniforms;
+
+struct ClipPlanesUniforms {
+    planes: array<vec4<f32>, 4>,
+    count:  u32,
+};
+@group(0) @binding(1) var<uniform> u_clip: ClipPlanesUniforms;
+
+struct PerDrawUniforms {
+    model_matrix:     mat4x4<f32>,
+    inv_model_matrix: mat4x4<f32>,
+    proxy_min:        vec3<f32>,
+    proxy_max:        vec3<f32>,
+};
+// VoxelTexture and texel_filled come from the file appended by ColorMode::compose_shader
+@group(1) @binding(0) var voxel_texture: VoxelTexture;
+@group(1) @binding(1) var<uniform> u_draw: PerDrawUniforms;
+
+struct VoxelOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0)       obj_pos:  vec3<f32>,
+};
+
+@vertex
+fn vs_voxel(@location(0) position: vec3<f32>) -> VoxelOut {
+    // Same proxy box as vs_main in shader.wgsl
+    let obj_pos = mix(u_draw.proxy_min, u_draw.proxy_max, position + vec3<f32>(0.5));
+    var out: VoxelOut;
+    out.position = u_cascade.vp_matrix * u_draw.model_matrix * vec4<f32>(obj_pos, 1.0);
+    out.obj_pos = obj_pos;
+    return out;
+}
+
+@fragment
+fn fs_voxel(in: VoxelOut) -> @builtin(frag_depth) f32 {
+    let dir_os = normalize((u_draw.inv_model_matrix * vec4<f32>(u_cascade.light_forward, 0.0)).xyz);
+    // Start outside the unit cube, so the walk below sees the whole object
+    let origin = in.obj_pos - dir_os * 2.0;
+    let inv_dir = 1.0 / dir_os;
+    let tmin = (u_draw.proxy_min - origin) * inv_dir;
+    let tmax = (u_draw.proxy_max - origin) * inv_dir;
+    let t_entry = max(max(min(tmin.x, tmax.x), min(tmin.y, tmax.y)), min(tmin.z, tmax.z));
+    let t_exit = min(min(max(tmin.x, tmax.x), max(tmin.y, tmax.y)), max(tmin.z, tmax.z));
+    if t_entry > t_exit {
+        discard;
+    }
+    // Axis of the face the walk last crossed, starting with the proxy box face
+    let near = min(tmin, tmax);
+    var axis = select(select(2u, 1u, near.y >= near.z), 0u, near.x >= near.y && near.x >= near.z);
+
+    // Voxel walk as in raymarch() of shader.wgsl
+    let dims = vec3<i32>(textureDimensions(voxel_texture, 0));
+    let dims_f = vec3<f32>(dims);
+    let t_start = t_entry;
+    let ray_voxel = (origin + t_start * dir_os + vec3<f32>(0.5)) * dims_f + dir_os / dims_f;
+    var voxel = vec3<i32>(floor(ray_voxel));
+    let step = vec3<i32>(select(vec3<f32>(-1.0), vec3<f32>(1.0), dir_os > vec3<f32>(0.0)));
+    let next_boundary = select(floor(ray_voxel), ceil(ray_voxel), dir_os > vec3<f32>(0.0));
+    let inv_dir_voxel = inv_dir / dims_f;
+    var t_max = (next_boundary - ray_voxel) * inv_dir_voxel;
+    let t_delta = abs(inv_dir_voxel);
+    var t = t_start;
+    var hit = false;
+    for (var i = 0u; i < 256u; i = i + 1u) {
+        if any(voxel < vec3<i32>(0)) || any(voxel >= dims) {
+            break;
+        }
+        if texel_filled(voxel_texture, vec3<u32>(voxel), u_cascade.empty_index) {
+            hit = true;
+            break;
+        }
+        if t_max.x < t_max.y && t_max.x < t_max.z {
+            voxel.x += step.x;
+            axis = 0u;
+            t = t_start + t_max.x;
+            t_max.x += t_delta.x;
+        } else if t_max.y < t_max.z {
+            voxel.y += step.y;
+            axis = 1u;
+            t = t_start + t_max.y;
+            t_max.y += t_delta.y;
+        } else {
+            voxel.z += step.z;
+            axis = 2u;
+            t = t_start + t_max.z;
+            t_max.z += t_delta.z;
+        }
+    }
+    if !hit {
+        discard;
+    }
+
+    let hit_ws = u_draw.model_matrix * vec4<f32>(origin + t * dir_os, 1.0);
+    // Clipped-away voxels cast no shadow
+    for (var p = 0u; p < u_clip.count; p = p + 1u) {
+        let plane = u_clip.planes[p];
+        if dot(hit_ws.xyz, plane.xyz) + plane.w < 0.0 {
+            discard;
+        }
+    }
+    let clip = u_cascade.vp_matrix * hit_ws;
+    let depth = clip.z / clip.w;
+    return clamp(depth + voxel_depth_bias(depth, axis), 0.0, 1.0);
+}
+
+fn unit_axis(axis: u32) -> vec3<f32> {
+    var v = vec3<f32>(0.0);
+    v[axis] = 1.0;
+    return v;
+}
+
+// The pipeline's depth bias does not apply to a written frag_depth, so voxels add the
+// same bias here: the constant in units of Depth32Float precision at `depth`, plus the
+// slope scale times the largest depth change across a texel of the face hit, whose
+// normal is object axis `axis`
+fn voxel_depth_bias(depth: f32, axis: u32) -> f32 {
+    let m = u_cascade.vp_matrix * u_draw.model_matrix;
+    let a = (m * vec4<f32>(unit_axis((axis + 1u) % 3u), 0.0)).xyz;
+    let b = (m * vec4<f32>(unit_axis((axis + 2u) % 3u), 0.0)).xyz;
+    // Depth gradient over NDC x and y on the plane spanned by a and b; an edge-on
+    // face gets the clamp
+    let det = a.x * b.y - a.y * b.x;
+    if abs(det) < 1e-12 {
+        return u_cascade.bias_clamp;
+    }
+    let dz = vec2<f32>(a.z * b.y - b.z * a.y, b.z * a.x - a.z * b.x) / det;
+    let slope = max(abs(dz.x), abs(dz.y)) * u_cascade.ndc_per_texel;
+    let unit = exp2(floor(log2(max(depth, 1e-30))) - 23.0);
+    return min(u_cascade.constant_bias * unit + u_cascade.slope_bias * slope, u_cascade.bias_clamp);
+}
+
+// Mesh vertices are already in world space
+@vertex
+fn vs_mesh(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return u_cascade.vp_matrix * vec4<f32>(position, 1.0);
+}
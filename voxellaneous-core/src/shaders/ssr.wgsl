@@ -0,0 +1,222 @@
+// Screen-space reflections over the lit image, see ssr.rs
+
+struct VSOut {
+    @builtin(position) Position: vec4<f32>,
+    @location(0)       uv:       vec2<f32>,
+};
+
+struct SsrUniforms {
+    vp_matrix:       mat4x4<f32>,
+    inv_vp_matrix:   mat4x4<f32>,
+    camera_position: vec3<f32>,
+    max_steps:       u32,
+};
+
+// Target UV to G-buffer UV for the present fit; see PresentUniforms in lib.rs
+struct PresentUniforms {
+    uv_scale:  vec2<f32>,
+    uv_offset: vec2<f32>,
+};
+
+// Per palette index: roughness in the low byte, metallic in the next (unorm8)
+struct Materials {
+    packed: array<vec4<u32>, 64>,
+};
+
+@group(0) @binding(0) var lit_tex: texture_2d<f32>;
+@group(0) @binding(1) var albedo_tex: texture_2d<f32>;
+@group(0) @binding(2) var normal_tex: texture_2d<f32>;
+@group(0) @binding(3) var linear_z_tex: texture_2d<u32>;
+@group(0) @binding(4) var<uniform> u_materials: Materials;
+@group(0) @binding(5) var<uniform> u_present: PresentUniforms;
+@group(0) @binding(6) var<uniform> u_ssr: SsrUniforms;
+// Palette index in bits 0-7, see GBufferTextures::material
+@group(0) @binding(7) var material_tex: texture_2d<u32>;
+
+// Fraction of the screen, from each edge, over which reflections fade out
+const EDGE_FADE: f32 = 0.1;
+// Farthest a reflected ray is marched; LINEAR_Z_MAX_DISTANCE in constants.rs
+const MAX_DISTANCE: f32 = 100.0;
+// Bisection steps refining a hit between the last two samples
+const REFINE_STEPS: u32 = 4u;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vi: u32) -> VSOut {
+    var corners = array<vec2<f32>,3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>( 3.0, -1.0),
+        vec2<f32>(-1.0,  3.0)
+    );
+    var out: VSOut;
+    out.Position = vec4<f32>(corners[vi], 0.0, 1.0);
+    // Target uv, +y up as in quad_lighting.wgsl
+    out.uv = corners[vi] * 0.5 + vec2<f32>(0.5);
+    return out;
+}
+
+// Inverse of encode_normal in shader.wgsl (octahedral, [0,1]^2)
+fn decode_normal(e: vec2<f32>) -> vec3<f32> {
+    let f = e * 2.0 - 1.0;
+    var n = vec3<f32>(f, 1.0 - abs(f.x) - abs(f.y));
+    let t = max(-n.z, 0.0);
+    n.x += select(t, -t, n.x >= 0.0);
+    n.y += select(t, -t, n.y >= 0.0);
+    return normalize(n);
+}
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+// Distance from the camera to the surface at G-buffer texel `coord`, 0 for no
+// geometry. Decoding must match LINEAR_Z_MAX_DISTANCE / LINEAR_Z_MAX_VALUE.
+fn pixel_distance(coord: vec2<i32>) -> f32 {
+    return f32(textureLoad(linear_z_tex, coord, 0).r) / 65535.0 * MAX_DISTANCE;
+}
+
+// G-buffer texel of camera NDC `ndc`
+fn gbuffer_coord(ndc: vec2<f32>) -> vec2<i32> {
+    let dims = vec2<f32>(textureDimensions(linear_z_tex, 0));
+    let uv = ndc * 0.5 + vec2<f32>(0.5);
+    return vec2<i32>(vec2<f32>(uv.x, 1.0 - uv.y) * dims);
+}
+
+// Unit vector from the camera through the pixel at `ndc`
+fn pixel_ray(ndc: vec2<f32>) -> vec3<f32> {
+    let near = u_ssr.inv_vp_matrix * vec4<f32>(ndc, 0.0, 1.0);
+    let far = u_ssr.inv_vp_matrix * vec4<f32>(ndc, 1.0, 1.0);
+    return normalize(far.xyz / far.w - near.xyz / near.w);
+}
+
+// Lit color at camera NDC `ndc`, or alpha 0 outside the lit image (letterbox bars)
+fn lit_color(ndc: vec2<f32>) -> vec4<f32> {
+    let uv = (ndc * 0.5 + vec2<f32>(0.5) - u_present.uv_offset) / u_present.uv_scale;
+    if any(uv < vec2<f32>(0.0)) || any(uv >= vec2<f32>(1.0)) {
+        return vec4<f32>(0.0);
+    }
+    let dims = vec2<f32>(textureDimensions(lit_tex, 0));
+    let coord = vec2<i32>(vec2<f32>(uv.x, 1.0 - uv.y) * dims);
+    return vec4<f32>(textureLoad(lit_tex, coord, 0).rgb, 1.0);
+}
+
+struct Hit {
+    ndc:   vec2<f32>,
+    found: bool,
+};
+
+// Marches the ray from `origin` along `dir` in screen space, in steps of equal screen
+// length between its projection's start and the edge of the screen or the near plane,
+// and stops at the first sample behind the linear-Z surface by less than a step
+fn trace(origin: vec3<f32>, dir: vec3<f32>) -> Hit {
+    var miss: Hit;
+    miss.found = false;
+    let c0 = u_ssr.vp_matrix * vec4<f32>(origin, 1.0);
+    let dc = u_ssr.vp_matrix * vec4<f32>(dir, 0.0);
+    // Keep the end in front of the camera: clip w is linear along the ray
+    var ray_length = MAX_DISTANCE;
+    if dc.w < 0.0 {
+        ray_length = min(ray_length, (c0.w * 0.99) / -dc.w);
+    }
+    let c1 = c0 + dc * ray_length;
+    let s0 = c0.xy / c0.w;
+    let s1 = c1.xy / c1.w;
+    // Cut the screen segment at the screen edge; NDC is linear in the screen parameter
+    let delta = s1 - s0;
+    var u_end = 1.0;
+    for (var axis = 0; axis < 2; axis = axis + 1) {
+        if delta[axis] > 0.0 {
+            u_end = min(u_end, (1.0 - s0[axis]) / delta[axis]);
+        } else if delta[axis] < 0.0 {
+            u_end = min(u_end, (-1.0 - s0[axis]) / delta[axis]);
+        }
+    }
+    if u_end <= 0.0 {
+        return miss;
+    }
+    // 1 / w and t / w are linear in screen space, t being the distance along the ray
+    let k0 = 1.0 / c0.w;
+    let k1 = 1.0 / c1.w;
+    let steps = max(u_ssr.max_steps, 1u);
+    var prev_u = 0.0;
+    for (var i = 1u; i <= steps; i = i + 1u) {
+        let u = u_end * f32(i) / f32(steps);
+        let t = (ray_length * k1 * u) / mix(k0, k1, u);
+        let ndc = mix(s0, s1, u);
+        let scene = pixel_distance(gbuffer_coord(ndc));
+        let ray = distance(origin + dir * t, u_ssr.camera_position);
+        if scene > 0.0 && ray >= scene {
+            let prev_t = (ray_length * k1 * prev_u) / mix(k0, k1, prev_u);
+            // Too far behind to be the same surface: the ray passed under an edge
+            if ray - scene > max(t - prev_t, 0.01 * scene) {
+                return miss;
+            }
+            // Bisect between the last sample in front and this one
+            var lo = prev_u;
+            var hi = u;
+            for (var j = 0u; j < REFINE_STEPS; j = j + 1u) {
+                let mid = 0.5 * (lo + hi);
+                let mid_t = (ray_length * k1 * mid) / mix(k0, k1, mid);
+                let mid_scene = pixel_distance(gbuffer_coord(mix(s0, s1, mid)));
+                let mid_ray = distance(origin + dir * mid_t, u_ssr.camera_position);
+                if mid_scene > 0.0 && mid_ray >= mid_scene {
+                    hi = mid;
+                } else {
+                    lo = mid;
+                }
+            }
+            var hit: Hit;
+            hit.ndc = mix(s0, s1, hi);
+            hit.found = true;
+            return hit;
+        }
+        prev_u = u;
+    }
+    return miss;
+}
+
+@fragment
+fn fs_main(in: VSOut) -> @location(0) vec4<f32> {
+    let dims = vec2<f32>(textureDimensions(lit_tex, 0));
+    let lit = textureLoad(lit_tex, vec2<i32>(vec2<f32>(in.uv.x, 1.0 - in.uv.y) * dims), 0);
+    let guv = in.uv * u_present.uv_scale + u_present.uv_offset;
+    if any(guv < vec2<f32>(0.0)) || any(guv >= vec2<f32>(1.0)) {
+        return lit;
+    }
+    let ndc = guv * 2.0 - 1.0;
+    let coord = gbuffer_coord(ndc);
+    let normal_encoded = textureLoad(normal_tex, coord, 0);
+    let distance_to_camera = pixel_distance(coord);
+    if all(normal_encoded.rg == vec2<f32>(0.0)) || distance_to_camera == 0.0 {
+        return lit;
+    }
+    let idx = textureLoad(material_tex, coord, 0).r & 0xffu;
+    let material = unpack4x8unorm(u_materials.packed[idx / 4u][idx % 4u]).xy;
+    let gloss = (1.0 - material.x) * (1.0 - material.x);
+    if gloss <= 0.0 {
+        return lit;
+    }
+
+    let normal = decode_normal(normal_encoded.rg);
+    let ray = pixel_ray(ndc);
+    let position = u_ssr.camera_position + ray * distance_to_camera;
+    let dir = reflect(ray, normal);
+    // Start a little off the surface so the ray does not hit its own texel
+    let hit = trace(position + normal * (0.01 * distance_to_camera), dir);
+    if !hit.found {
+        return lit;
+    }
+    let reflected = lit_color(hit.ndc);
+    if reflected.a == 0.0 {
+        return lit;
+    }
+
+    // Schlick Fresnel, as shade_pbr's f0
+    let albedo = srgb_to_linear(textureLoad(albedo_tex, coord, 0).rgb);
+    let f0 = mix(vec3<f32>(0.04), albedo, material.y);
+    let n_dot_v = saturate(dot(normal, -ray));
+    let fresnel = f0 + (vec3<f32>(1.0) - f0) * pow(1.0 - n_dot_v, 5.0);
+    // Fade toward the screen edges, where the reflected surface may be cut off
+    let edge = saturate((1.0 - max(abs(hit.ndc.x), abs(hit.ndc.y))) / EDGE_FADE);
+    let weight = fresnel * (gloss * edge);
+    return vec4<f32>(mix(lit.rgb, reflected.rgb, weight), lit.a);
+}
@@ -0,0 +1,320 @@
+// Compute raymarch of one object through its sparse voxel octree (svo.rs), run after
+// raymarch_compute.wgsl has written the frame. Each ray descends from the root to
+// the cell it is in and jumps past empty cells whole, then the hit is depth-tested
+// against the packed outputs, which use the layout of raymarch_compute.wgsl.
+
+struct RaymarchParams {
+    inv_vp_matrix:      mat4x4<f32>,
+    cam_pos_ws:         vec3<f32>,
+    voxel_rounding:     f32,
+    size:               vec2<u32>,
+    object_count:       u32,
+    color_row_words:    u32,
+    normal_row_words:   u32,
+    linear_z_row_words: u32,
+    empty_index:        u32,
+};
+
+struct SvoObject {
+    model_matrix:     mat4x4<f32>,
+    inv_model_matrix: mat4x4<f32>,
+    dims:             vec3<u32>,
+    root_size:        u32,
+    // Albedo multiplier, as PerDrawUniforms.tint in shader.wgsl
+    tint:             vec4<f32>,
+};
+
+struct StaticUniforms {
+    palette: array<vec4<u32>, 64>,
+};
+
+struct ClipPlanesUniforms {
+    planes: array<vec4<f32>, 4>,
+    count:  u32,
+};
+
+@group(0) @binding(0) var<storage, read> nodes: array<u32>;
+@group(0) @binding(1) var<uniform> u_object: SvoObject;
+@group(0) @binding(2) var<uniform> u_params: RaymarchParams;
+@group(0) @binding(3) var<uniform> u_static: StaticUniforms;
+@group(0) @binding(4) var<uniform> u_clip: ClipPlanesUniforms;
+@group(0) @binding(5) var<storage, read_write> out_albedo: array<u32>;
+@group(0) @binding(6) var<storage, read_write> out_normal: array<u32>;
+@group(0) @binding(7) var<storage, read_write> out_linear_z: array<u32>;
+// In the layout of out_linear_z, as the material target shares its format
+@group(0) @binding(8) var<storage, read_write> out_material: array<u32>;
+
+// Bytes per linear-Z texel: 2 for R16Uint, 4 for R32Uint
+override linear_z_bytes: u32 = 2u;
+
+const LEAF: u32 = 0x80000000u;
+// Deeper than any octree under the 2048 voxel texture size limit
+const MAX_DEPTH: u32 = 16u;
+
+struct Cell {
+    filled: bool,
+    index:  u32,
+    origin: vec3<u32>,
+    size:   u32,
+};
+
+// The leaf or empty cell holding voxel `q`.
+fn find_cell(q: vec3<u32>) -> Cell {
+    var node = 0u;
+    var origin = vec3<u32>(0u);
+    var size = u_object.root_size;
+    for (var depth = 0u; depth < MAX_DEPTH; depth = depth + 1u) {
+        let value = nodes[node];
+        if (value & LEAF) != 0u {
+            return Cell(true, value & 0xffu, origin, size);
+        }
+        size = max(size / 2u, 1u);
+        let upper = q >= origin + vec3<u32>(size);
+        let octant = select(0u, 1u, upper.x) | select(0u, 2u, upper.y) | select(0u, 4u, upper.z);
+        origin += select(vec3<u32>(0u), vec3<u32>(size), upper);
+        let mask = value & 0xffu;
+        if (mask & (1u << octant)) == 0u {
+            break;
+        }
+        node = (value >> 8u) + countOneBits(mask & ((1u << octant) - 1u));
+    }
+    return Cell(false, 0u, origin, size);
+}
+
+// Octahedral normal encoding in [0,1]^2; must match shader.wgsl.
+// (0, 0) is reserved for empty pixels.
+fn encode_normal(n: vec3<f32>) -> vec2<f32> {
+    var p = n.xy / (abs(n.x) + abs(n.y) + abs(n.z));
+    if n.z < 0.0 {
+        let sign_not_zero = select(vec2<f32>(-1.0), vec2<f32>(1.0), p >= vec2<f32>(0.0));
+        p = (vec2<f32>(1.0) - abs(p.yx)) * sign_not_zero;
+    }
+    return max(p * 0.5 + 0.5, vec2<f32>(1.0 / 255.0));
+}
+
+// Material texel as encode_material in shader.wgsl; this path has no occlusion
+fn encode_material(hit: Hit) -> u32 {
+    return min(hit.palette_index, 255u);
+}
+
+fn palette_color(idx: u32) -> vec4<f32> {
+    return unpack4x8unorm(u_static.palette[idx / 4u][idx % 4u]);
+}
+
+struct Hit {
+    hit:      bool,
+    // World-space distance from the camera
+    distance: f32,
+    albedo:   vec4<f32>,
+    // Object-space, as written by the raster path
+    normal:   vec3<f32>,
+    palette_index: u32,
+};
+
+fn axis_normal(axis: u32, dir: vec3<f32>) -> vec3<f32> {
+    var n = vec3<f32>(0.0);
+    n[axis] = -sign(dir[axis]);
+    return n;
+}
+
+// Part of the object-space ray from `cam_os` along `dir_os` within [t0, t1] that the
+// clip planes keep, and the index of the plane that moved t0, or -1; as clip_segment
+// in shader.wgsl
+struct ClipSegment {
+    t0:    f32,
+    t1:    f32,
+    plane: i32,
+};
+
+fn clip_segment(model_matrix: mat4x4<f32>, cam_os: vec3<f32>, dir_os: vec3<f32>, t0: f32, t1: f32) -> ClipSegment {
+    var segment = ClipSegment(t0, t1, -1);
+    for (var p = 0u; p < u_clip.count; p = p + 1u) {
+        let plane = u_clip.planes[p] * model_matrix;
+        let start = dot(plane.xyz, cam_os) + plane.w;
+        let rate = dot(plane.xyz, dir_os);
+        if rate == 0.0 {
+            if start < 0.0 {
+                segment.t1 = -1.0;
+            }
+            continue;
+        }
+        let t = -start / rate;
+        if rate < 0.0 {
+            segment.t1 = min(segment.t1, t);
+        } else if t > segment.t0 {
+            segment.t0 = t;
+            segment.plane = i32(p);
+        }
+    }
+    return segment;
+}
+
+fn march_octree(dir_ws: vec3<f32>) -> Hit {
+    let miss = Hit(false, 0.0, vec4<f32>(0.0), vec3<f32>(0.0), 0u);
+    let cam_os = (u_object.inv_model_matrix * vec4<f32>(u_params.cam_pos_ws, 1.0)).xyz;
+    let dir_os = normalize((u_object.inv_model_matrix * vec4<f32>(dir_ws, 0.0)).xyz);
+    let inv_dir = 1.0 / dir_os;
+
+    let tmin = (vec3<f32>(-0.5) - cam_os) * inv_dir;
+    let tmax = (vec3<f32>(0.5) - cam_os) * inv_dir;
+    let entry_t = min(tmin, tmax);
+    let t_entry = max(max(entry_t.x, entry_t.y), entry_t.z);
+    let t_exit = min(min(max(tmin.x, tmax.x), max(tmin.y, tmax.y)), max(tmin.z, tmax.z));
+    if t_exit < 0.0 || t_entry > t_exit {
+        return miss;
+    }
+
+    // Voxel space: the grid spans [0, dims], and t stays the object-space distance
+    let dims = u_object.dims;
+    let dims_f = vec3<f32>(dims);
+    let origin_v = (cam_os + vec3<f32>(0.5)) * dims_f;
+    let dir_v = dir_os * dims_f;
+    let inv_dir_v = inv_dir / dims_f;
+
+    var axis = 0u;
+    if entry_t.y > entry_t.x && entry_t.y > entry_t.z {
+        axis = 1u;
+    } else if entry_t.z > entry_t.x {
+        axis = 2u;
+    }
+    // Marches only what the clip planes keep, as the raster path does
+    let clip = clip_segment(u_object.model_matrix, cam_os, dir_os, max(t_entry, 0.0), t_exit);
+    var t = clip.t0;
+    let t_start = t;
+    // Whether the ray is on the boundary of `axis`, e.g. after entering the box
+    var on_face = t_entry > 0.0 && clip.plane < 0;
+
+    var hit = false;
+    var hit_idx = 0u;
+    for (var i = 0u; i < 256u; i = i + 1u) {
+        if t > clip.t1 {
+            break;
+        }
+        let p = origin_v + t * dir_v;
+        var q = clamp(vec3<i32>(floor(p)), vec3<i32>(0), vec3<i32>(dims) - vec3<i32>(1));
+        if on_face {
+            // Take the cell the ray enters, not the one it leaves
+            let boundary = i32(round(p[axis]));
+            q[axis] = clamp(select(boundary - 1, boundary, dir_v[axis] > 0.0), 0, i32(dims[axis]) - 1);
+        }
+        let cell = find_cell(vec3<u32>(q));
+        if cell.filled {
+            hit = true;
+            hit_idx = cell.index;
+            break;
+        }
+
+        let lo = vec3<f32>(cell.origin);
+        let bound = select(lo, lo + vec3<f32>(f32(cell.size)), dir_v > vec3<f32>(0.0));
+        let t_cell = (bound - origin_v) * inv_dir_v;
+        if t_cell.x < t_cell.y && t_cell.x < t_cell.z {
+            axis = 0u;
+        } else if t_cell.y < t_cell.z {
+            axis = 1u;
+        } else {
+            axis = 2u;
+        }
+        t = max(t_cell[axis], t);
+        on_face = true;
+    }
+
+    if !hit {
+        return miss;
+    }
+
+    let hit_pos_os = cam_os + t * dir_os;
+    let hit_pos_ws = (u_object.model_matrix * vec4<f32>(hit_pos_os, 1.0)).xyz;
+    let distance = length(hit_pos_ws - u_params.cam_pos_ws);
+    let albedo = palette_color(hit_idx) * vec4<f32>(u_object.tint.rgb, 1.0);
+    // A cell filled where the ray starts on a clip plane is cut by it, and faces out
+    // of the kept half-space
+    var normal_os = vec4<f32>(axis_normal(axis, dir_os), 0.0);
+    if clip.plane >= 0 && t <= t_start {
+        normal_os = vec4<f32>(-normalize((u_clip.planes[clip.plane] * u_object.model_matrix).xyz), 0.0);
+    }
+    // World-space normal by the inverse transpose, as normal_to_world in shader.wgsl
+    let normal_ws = normalize((normal_os * u_object.inv_model_matrix).xyz);
+    return Hit(true, distance, albedo, normal_ws, hit_idx);
+}
+
+fn pixel_direction(pixel: vec2<u32>) -> vec3<f32> {
+    let ndc = vec2<f32>(
+        (f32(pixel.x) + 0.5) / f32(u_params.size.x) * 2.0 - 1.0,
+        1.0 - (f32(pixel.y) + 0.5) / f32(u_params.size.y) * 2.0
+    );
+    let near = u_params.inv_vp_matrix * vec4<f32>(ndc, 0.0, 1.0);
+    let far = u_params.inv_vp_matrix * vec4<f32>(ndc, 1.0, 1.0);
+    return normalize(far.xyz / far.w - near.xyz / near.w);
+}
+
+// Encoding must match LINEAR_Z_MAX_DISTANCE / LINEAR_Z_MAX_VALUE in constants.rs
+fn encode_linear_z(distance: f32) -> u32 {
+    return u32(clamp(distance / 100.0, 0.0, 1.0) * 65535.0);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let x0 = id.x * 2u;
+    if x0 >= u_params.size.x || id.y >= u_params.size.y {
+        return;
+    }
+
+    let normal_index = id.y * u_params.normal_row_words + id.x;
+    var normals = unpack4x8unorm(out_normal[normal_index]);
+    let row = id.y * u_params.linear_z_row_words;
+    var linear_z = array<u32, 2>(0u, 0u);
+    var material = array<u32, 2>(0u, 0u);
+    if linear_z_bytes == 2u {
+        let word = out_linear_z[row + id.x];
+        linear_z = array<u32, 2>(word & 0xffffu, word >> 16u);
+        let material_word = out_material[row + id.x];
+        material = array<u32, 2>(material_word & 0xffffu, material_word >> 16u);
+    } else {
+        linear_z[0] = out_linear_z[row + x0];
+        material[0] = out_material[row + x0];
+        if x0 + 1u < u_params.size.x {
+            linear_z[1] = out_linear_z[row + x0 + 1u];
+            material[1] = out_material[row + x0 + 1u];
+        }
+    }
+
+    var changed = false;
+    for (var i = 0u; i < 2u; i = i + 1u) {
+        let x = x0 + i;
+        if x >= u_params.size.x {
+            break;
+        }
+        let hit = march_octree(pixel_direction(vec2<u32>(x, id.y)));
+        let z = encode_linear_z(hit.distance);
+        // 0 marks pixels nothing was drawn to
+        if !hit.hit || (linear_z[i] != 0u && z >= linear_z[i]) {
+            continue;
+        }
+        changed = true;
+        out_albedo[id.y * u_params.color_row_words + x] = pack4x8unorm(vec4<f32>(hit.albedo.rgb, 1.0));
+        let normal = encode_normal(hit.normal);
+        if i == 0u {
+            normals = vec4<f32>(normal, normals.zw);
+        } else {
+            normals = vec4<f32>(normals.xy, normal);
+        }
+        linear_z[i] = z;
+        material[i] = encode_material(hit);
+    }
+    if !changed {
+        return;
+    }
+    // Two Rg8Unorm texels per word
+    out_normal[normal_index] = pack4x8unorm(normals);
+    if linear_z_bytes == 2u {
+        out_linear_z[row + id.x] = (linear_z[0] & 0xffffu) | (linear_z[1] << 16u);
+        out_material[row + id.x] = (material[0] & 0xffffu) | (material[1] << 16u);
+    } else {
+        out_linear_z[row + x0] = linear_z[0];
+        out_material[row + x0] = material[0];
+        if x0 + 1u < u_params.size.x {
+            out_linear_z[row + x0 + 1u] = linear_z[1];
+            out_material[row + x0 + 1u] = material[1];
+        }
+    }
+}
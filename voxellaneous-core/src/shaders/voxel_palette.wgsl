@@ -0,0 +1,19 @@
+// Voxel access for palette objects, appended to the voxel-marching shaders by
+// ColorMode::compose_shader: each texel is a palette index.
+
+alias VoxelTexture = texture_3d<u32>;
+
+const RGBA_VOXELS: bool = false;
+
+fn texel_filled(tex: VoxelTexture, coord: vec3<u32>, empty_index: u32) -> bool {
+    return textureLoad(tex, coord, 0).r != empty_index;
+}
+
+fn texel_index(tex: VoxelTexture, coord: vec3<u32>) -> u32 {
+    return textureLoad(tex, coord, 0).r;
+}
+
+// Palette texels have no colour of their own; see texel_index
+fn texel_rgba(tex: VoxelTexture, coord: vec3<u32>) -> vec4<f32> {
+    return vec4<f32>(0.0);
+}
@@ -0,0 +1,20 @@
+// Voxel access for rgba objects, appended to the voxel-marching shaders by
+// ColorMode::compose_shader: each texel is the voxel's colour, alpha 0 for empty.
+
+alias VoxelTexture = texture_3d<f32>;
+
+const RGBA_VOXELS: bool = true;
+
+// The empty index does not apply to rgba voxels
+fn texel_filled(tex: VoxelTexture, coord: vec3<u32>, empty_index: u32) -> bool {
+    return textureLoad(tex, coord, 0).a > 0.0;
+}
+
+// Rgba voxels take the PBR material of palette entry 0
+fn texel_index(tex: VoxelTexture, coord: vec3<u32>) -> u32 {
+    return 0u;
+}
+
+fn texel_rgba(tex: VoxelTexture, coord: vec3<u32>) -> vec4<f32> {
+    return textureLoad(tex, coord, 0);
+}
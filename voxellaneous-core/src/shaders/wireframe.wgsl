@@ -4,12 +4,13 @@ struct VertexInput {
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
+    @location(0) world_pos: vec3<f32>,
 };
 
 struct PerFrameUniforms {
     vp_matrix:  mat4x4<f32>,
     cam_pos_ws: vec3<f32>,
-    _padding:   f32,
+    voxel_rounding: f32,
 };
 @group(0) @binding(0) var<uniform> u_frame: PerFrameUniforms;
 
@@ -19,15 +20,28 @@ struct PerDrawUniforms {
 };
 @group(1) @binding(0) var<uniform> u_draw: PerDrawUniforms;
 
+struct ClipPlanesUniforms {
+    planes: array<vec4<f32>, 4>,
+    count:  u32,
+};
+@group(1) @binding(1) var<uniform> u_clip: ClipPlanesUniforms;
+
 @vertex
 fn vs_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
     let ws4 = u_draw.model_matrix * vec4<f32>(in.position, 1.0);
     out.position = u_frame.vp_matrix * ws4;
+    out.world_pos = ws4.xyz;
     return out;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    for (var p = 0u; p < u_clip.count; p = p + 1u) {
+        let plane = u_clip.planes[p];
+        if dot(in.world_pos, plane.xyz) + plane.w < 0.0 {
+            discard;
+        }
+    }
     return vec4<f32>(1.0, 1.0, 0.0, 1.0); // Yellow wireframe
 }
@@ -0,0 +1,667 @@
+//! Cascaded shadow maps from the directional light for the lit present target.
+//!
+//! The camera view range holding the scene is split into up to `MAX_CASCADES` slices
+//! by view depth. Each slice gets an orthographic light projection fit to its bounding
+//! sphere, snapped to whole texels so the shadows do not shimmer as the camera moves,
+//! and its casters are rendered into one layer of a `Depth32Float` array by
+//! shadow_cascade.wgsl. The lighting pass picks the cascade per pixel by view depth.
+
+use crate::bounds::Aabb;
+use crate::constants::{MeshVertex, Vertex};
+use crate::math::{self, Mat4};
+use crate::scene::ColorMode;
+
+pub const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+pub const MAX_CASCADES: u32 = 4;
+/// Alignment of the per-cascade records, bound with dynamic offsets.
+const CASCADE_STRIDE: u64 = 256;
+/// Defaults of `CascadedShadows::set_bias`: the constant in units of the depth
+/// precision, the slope scale per texel of depth change, the normal offset in texels.
+pub const DEFAULT_SHADOW_BIAS: (f32, f32, f32) = (1000.0, 1.5, 1.5);
+const MAX_CONSTANT_BIAS: f32 = 1.0e6;
+const MAX_SLOPE_BIAS: f32 = 16.0;
+const MAX_NORMAL_OFFSET: f32 = 8.0;
+/// Largest depth bias, in depth units, which steep faces would otherwise exceed.
+const DEPTH_BIAS_CLAMP: f32 = 0.01;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CascadeUniforms {
+    vp_matrix: Mat4,
+    light_forward: [f32; 3],
+    empty_index: u32,
+    /// The pipeline depth bias, for voxels writing their own depth
+    constant_bias: f32,
+    slope_bias: f32,
+    bias_clamp: f32,
+    ndc_per_texel: f32,
+}
+
+/// Cascade lookup for quad_lighting.wgsl.
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowUniforms {
+    matrices: [Mat4; MAX_CASCADES as usize],
+    /// Far view depth of each cascade
+    splits: [f32; 4],
+    /// 0 = no shadows
+    count: u32,
+    /// Normal offset of the lookup, in texels
+    normal_offset: f32,
+    _padding0: [u32; 2],
+    /// World-space size of a shadow-map texel per cascade, for the normal offset
+    texel_size: [f32; 4],
+    blend: f32,
+    debug: u32,
+    _padding: [u32; 2],
+}
+
+/// One cascade to render: its light view-projection, and the layer it writes.
+pub struct Cascade<'a> {
+    pub vp_matrix: Mat4,
+    pub view: &'a wgpu::TextureView,
+    /// Dynamic offset of its record in `bind_group`
+    pub offset: u32,
+}
+
+pub struct CascadedShadows {
+    count: u32,
+    split_lambda: f32,
+    resolution: u32,
+    /// Fraction of each cascade, at its far end, blended into the next one.
+    pub blend: f32,
+    /// Tints lit pixels by the index of their cascade.
+    pub debug: bool,
+    pub view: wgpu::TextureView,
+    layer_views: Vec<wgpu::TextureView>,
+    pub sampler: wgpu::Sampler,
+    /// `ShadowUniforms` of the last `prepare`, bound by the lighting pass.
+    pub uniform_buffer: wgpu::Buffer,
+    /// `ShadowUniforms` with no cascades, for lighting passes of other cameras.
+    pub off_uniform_buffer: wgpu::Buffer,
+    cascade_buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    pub voxel_pipeline: wgpu::RenderPipeline,
+    /// `voxel_pipeline` for `ColorMode::Rgba` objects
+    pub rgba_voxel_pipeline: wgpu::RenderPipeline,
+    pub mesh_pipeline: wgpu::RenderPipeline,
+    caster_source: CasterPipelineSource,
+    depth_bias: wgpu::DepthBiasState,
+    normal_offset: f32,
+    matrices: Vec<Mat4>,
+}
+
+/// What the caster pipelines are rebuilt from when the depth bias changes.
+struct CasterPipelineSource {
+    shader: wgpu::ShaderModule,
+    rgba_shader: wgpu::ShaderModule,
+    voxel_layout: wgpu::PipelineLayout,
+    rgba_voxel_layout: wgpu::PipelineLayout,
+    mesh_layout: wgpu::PipelineLayout,
+}
+
+impl CascadedShadows {
+    /// Shadows start disabled (no cascades). `per_draw_bind_group_layouts` are those
+    /// of palette and of rgba objects.
+    pub fn new(
+        device: &wgpu::Device,
+        per_draw_bind_group_layouts: [&wgpu::BindGroupLayout; 2],
+        clip_planes_buffer: &wgpu::Buffer,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let uniform_buffer = |label| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: std::mem::size_of::<ShadowUniforms>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        let cascade_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Cascade Uniform Buffer"),
+            size: CASCADE_STRIDE * MAX_CASCADES as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shadow Cascade Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<CascadeUniforms>() as u64,
+                        ),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shadow Cascade Bind Group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &cascade_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<CascadeUniforms>() as u64),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: clip_planes_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let [shader, rgba_shader] = [ColorMode::Palette, ColorMode::Rgba].map(|color_mode| {
+            let source = color_mode.compose_shader(include_str!("shaders/shadow_cascade.wgsl"));
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Shadow Cascade Shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            })
+        });
+        let [voxel_layout, rgba_voxel_layout] = per_draw_bind_group_layouts.map(|per_draw| {
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Shadow Voxel Pipeline Layout"),
+                bind_group_layouts: &[&layout, per_draw],
+                push_constant_ranges: &[],
+            })
+        });
+        let mesh_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Mesh Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            // Bilinear comparison: 2x2 PCF in hardware
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+        let (view, layer_views) = create_shadow_map(device, 1, 1);
+        let caster_source = CasterPipelineSource {
+            shader,
+            rgba_shader,
+            voxel_layout,
+            rgba_voxel_layout,
+            mesh_layout,
+        };
+        let (constant, slope_scale, normal_offset) = DEFAULT_SHADOW_BIAS;
+        let depth_bias = wgpu::DepthBiasState {
+            constant: constant as i32,
+            slope_scale,
+            clamp: DEPTH_BIAS_CLAMP,
+        };
+        let (voxel_pipeline, rgba_voxel_pipeline, mesh_pipeline) =
+            caster_source.create(device, depth_bias, cache);
+        CascadedShadows {
+            count: 0,
+            split_lambda: 0.5,
+            resolution: 1,
+            blend: 0.1,
+            debug: false,
+            view,
+            layer_views,
+            sampler,
+            uniform_buffer: uniform_buffer("Shadow Uniform Buffer"),
+            off_uniform_buffer: uniform_buffer("Shadow Off Uniform Buffer"),
+            cascade_buffer,
+            bind_group,
+            voxel_pipeline,
+            rgba_voxel_pipeline,
+            mesh_pipeline,
+            caster_source,
+            depth_bias,
+            normal_offset,
+            matrices: Vec::new(),
+        }
+    }
+
+    /// Sets the caster depth bias, `constant` in units of the shadow map's depth
+    /// precision plus `slope_scaled` times the depth change across a texel of the
+    /// caster, and moves each lookup `normal_offset` texels off the surface along its
+    /// normal. Values are clamped to [0, 1e6], [0, 16] and [0, 8]; NaN is an error.
+    pub fn set_bias(
+        &mut self,
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        constant: f32,
+        slope_scaled: f32,
+        normal_offset: f32,
+    ) -> Result<(), String> {
+        if constant.is_nan() || slope_scaled.is_nan() || normal_offset.is_nan() {
+            return Err("shadow bias must not be NaN".to_string());
+        }
+        let depth_bias = wgpu::DepthBiasState {
+            constant: constant.clamp(0.0, MAX_CONSTANT_BIAS).round() as i32,
+            slope_scale: slope_scaled.clamp(0.0, MAX_SLOPE_BIAS),
+            clamp: DEPTH_BIAS_CLAMP,
+        };
+        if depth_bias != self.depth_bias {
+            (
+                self.voxel_pipeline,
+                self.rgba_voxel_pipeline,
+                self.mesh_pipeline,
+            ) = self.caster_source.create(device, depth_bias, cache);
+            self.depth_bias = depth_bias;
+        }
+        self.normal_offset = normal_offset.clamp(0.0, MAX_NORMAL_OFFSET);
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.count > 0
+    }
+
+    pub fn voxel_pipeline_for(&self, color_mode: ColorMode) -> &wgpu::RenderPipeline {
+        match color_mode {
+            ColorMode::Palette => &self.voxel_pipeline,
+            ColorMode::Rgba => &self.rgba_voxel_pipeline,
+        }
+    }
+
+    /// Sets the number of cascades (0 disables shadows), the split scheme (0 =
+    /// uniform slices, 1 = logarithmic) and the edge of each square cascade layer.
+    pub fn configure(
+        &mut self,
+        device: &wgpu::Device,
+        count: u32,
+        split_lambda: f32,
+        resolution: u32,
+    ) -> Result<(), String> {
+        if count > MAX_CASCADES {
+            return Err(format!(
+                "at most {} shadow cascades are supported, got {}",
+                MAX_CASCADES, count
+            ));
+        }
+        if !(0.0..=1.0).contains(&split_lambda) {
+            return Err(format!(
+                "split_lambda must be in [0, 1], got {}",
+                split_lambda
+            ));
+        }
+        let max = device.limits().max_texture_dimension_2d;
+        if count > 0 && !(1..=max).contains(&resolution) {
+            return Err(format!(
+                "shadow map resolution must be between 1 and {}, got {}",
+                max, resolution
+            ));
+        }
+        let (size, layers) = if count == 0 {
+            (1, 1)
+        } else {
+            (resolution, count)
+        };
+        (self.view, self.layer_views) = create_shadow_map(device, size, layers);
+        self.count = count;
+        self.split_lambda = split_lambda;
+        self.resolution = size;
+        Ok(())
+    }
+
+    /// Fits the cascades to the camera and the scene and writes their uniforms. There
+    /// are no cascades to render when nothing in `scene_bounds` is in view or shadows
+    /// are off, and the lighting pass then skips the lookup.
+    pub fn prepare(
+        &mut self,
+        queue: &wgpu::Queue,
+        vp_matrix: &Mat4,
+        camera_position: [f32; 3],
+        light_dir: [f32; 3],
+        scene_bounds: Option<&Aabb>,
+        empty_index: u32,
+    ) {
+        let mut uniforms = ShadowUniforms::default();
+        self.matrices.clear();
+        if let (true, Some(bounds)) = (self.is_enabled(), scene_bounds) {
+            let light_forward = light_dir.map(|c| -c);
+            if let Some(cascades) = self.fit(vp_matrix, camera_position, light_forward, bounds) {
+                for (i, (matrix, split, texel_size)) in cascades.into_iter().enumerate() {
+                    uniforms.matrices[i] = matrix;
+                    uniforms.splits[i] = split;
+                    uniforms.texel_size[i] = texel_size;
+                    let record = CascadeUniforms {
+                        vp_matrix: matrix,
+                        light_forward,
+                        empty_index,
+                        constant_bias: self.depth_bias.constant as f32,
+                        slope_bias: self.depth_bias.slope_scale,
+                        bias_clamp: self.depth_bias.clamp,
+                        ndc_per_texel: 2.0 / self.resolution as f32,
+                    };
+                    queue.write_buffer(
+                        &self.cascade_buffer,
+                        i as u64 * CASCADE_STRIDE,
+                        bytemuck::bytes_of(&record),
+                    );
+                    self.matrices.push(matrix);
+                }
+                uniforms.count = self.matrices.len() as u32;
+            }
+        }
+        uniforms.normal_offset = self.normal_offset;
+        uniforms.blend = self.blend;
+        uniforms.debug = self.debug as u32;
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+        queue.write_buffer(
+            &self.off_uniform_buffer,
+            0,
+            bytemuck::bytes_of(&ShadowUniforms::default()),
+        );
+    }
+
+    /// The cascades fit by the last `prepare`.
+    pub fn cascades(&self) -> Vec<Cascade<'_>> {
+        self.matrices
+            .iter()
+            .zip(&self.layer_views)
+            .enumerate()
+            .map(|(i, (&vp_matrix, view))| Cascade {
+                vp_matrix,
+                view,
+                offset: (i as u64 * CASCADE_STRIDE) as u32,
+            })
+            .collect()
+    }
+
+    /// Light view-projection, far split depth and texel size of each cascade, or
+    /// `None` when the scene is entirely behind the camera or the camera is invalid.
+    fn fit(
+        &self,
+        vp_matrix: &Mat4,
+        camera: [f32; 3],
+        light_forward: [f32; 3],
+        bounds: &Aabb,
+    ) -> Option<Vec<(Mat4, f32, f32)>> {
+        let inv_vp = math::invert(vp_matrix)?;
+        let ndc_corners = [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0], [1.0, 1.0]];
+        let far_corners = ndc_corners.map(|[x, y]| math::project_point(&inv_vp, [x, y, 1.0]));
+        let near_corners = ndc_corners.map(|[x, y]| math::project_point(&inv_vp, [x, y, 0.0]));
+        let far_center = math::project_point(&inv_vp, [0.0, 0.0, 1.0]);
+        let forward = math::normalize(sub(far_center, camera))?;
+        let light_forward = math::normalize(light_forward)?;
+
+        // The split range ends where the scene or the view volume does
+        let near = dot(sub(near_corners[0], camera), forward).max(1e-3);
+        let view_far = dot(sub(far_corners[0], camera), forward);
+        let scene_far = box_corners(bounds)
+            .iter()
+            .map(|&p| dot(sub(p, camera), forward))
+            .fold(f32::NEG_INFINITY, f32::max);
+        let far = if view_far.is_finite() && view_far > near {
+            view_far.min(scene_far)
+        } else {
+            scene_far
+        };
+        if !far.is_finite() || far <= near {
+            return None;
+        }
+
+        // Practical split scheme: a blend of logarithmic and uniform slices
+        let splits: Vec<f32> = (1..=self.count)
+            .map(|i| {
+                let f = i as f32 / self.count as f32;
+                let log = near * (far / near).powf(f);
+                let uniform = near + (far - near) * f;
+                self.split_lambda * log + (1.0 - self.split_lambda) * uniform
+            })
+            .collect();
+
+        let up = if light_forward[1].abs() > 0.99 {
+            [1.0, 0.0, 0.0]
+        } else {
+            [0.0, 1.0, 0.0]
+        };
+        let right = math::normalize(cross(light_forward, up))?;
+        let up = cross(right, light_forward);
+        let scene_depths = box_corners(bounds).map(|p| dot(p, light_forward));
+
+        let mut start = near;
+        let mut cascades = Vec::with_capacity(splits.len());
+        for &end in &splits {
+            // Slice corners along the four frustum edges, at view depths start and end
+            let mut corners = Vec::with_capacity(8);
+            for &far_corner in &far_corners {
+                let ray = sub(far_corner, camera);
+                let ray_depth = dot(ray, forward);
+                for depth in [start, end] {
+                    corners.push(add(camera, scale(ray, depth / ray_depth)));
+                }
+            }
+            let center = scale(corners.iter().fold([0.0; 3], |sum, &p| add(sum, p)), 0.125);
+            let radius = corners
+                .iter()
+                .map(|&p| length(sub(p, center)))
+                .fold(0.0, f32::max);
+            // Quantized so the texel size only changes with the split distances
+            let radius = (radius * 16.0).ceil() / 16.0;
+            let texel_size = 2.0 * radius / self.resolution as f32;
+
+            let snap = |v: f32| (v / texel_size).round() * texel_size;
+            let (cx, cy) = (snap(dot(center, right)), snap(dot(center, up)));
+            // Casters outside the slice but toward the light still shadow it
+            let center_depth = dot(center, light_forward);
+            let depth_min = scene_depths
+                .iter()
+                .copied()
+                .fold(center_depth - radius, f32::min);
+            let depth_max = scene_depths
+                .iter()
+                .copied()
+                .fold(center_depth + radius, f32::max);
+            let depth_range = (depth_max - depth_min).max(1e-3);
+
+            let mut matrix = [0.0; 16];
+            for axis in 0..3 {
+                matrix[axis * 4] = right[axis] / radius;
+                matrix[axis * 4 + 1] = up[axis] / radius;
+                matrix[axis * 4 + 2] = light_forward[axis] / depth_range;
+            }
+            matrix[12] = -cx / radius;
+            matrix[13] = -cy / radius;
+            matrix[14] = -depth_min / depth_range;
+            matrix[15] = 1.0;
+            cascades.push((matrix, end, texel_size));
+            start = end;
+        }
+        Some(cascades)
+    }
+}
+
+impl CasterPipelineSource {
+    /// The palette voxel, rgba voxel and mesh caster pipelines with `bias`.
+    fn create(
+        &self,
+        device: &wgpu::Device,
+        bias: wgpu::DepthBiasState,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> (
+        wgpu::RenderPipeline,
+        wgpu::RenderPipeline,
+        wgpu::RenderPipeline,
+    ) {
+        let create_pipeline = |label: &str,
+                               shader: &wgpu::ShaderModule,
+                               layout: &wgpu::PipelineLayout,
+                               vertex_entry: &str,
+                               fragment_entry: Option<&str>,
+                               buffer: wgpu::VertexBufferLayout| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some(vertex_entry),
+                    buffers: &[buffer],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: fragment_entry.map(|entry_point| wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some(entry_point),
+                    targets: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: SHADOW_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias,
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache,
+            })
+        };
+
+        let voxel_pipeline = |shader, layout| {
+            create_pipeline(
+                "Shadow Voxel Pipeline",
+                shader,
+                layout,
+                "vs_voxel",
+                Some("fs_voxel"),
+                wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+                },
+            )
+        };
+        // Only the position of each mesh vertex is read
+        let mesh_pipeline = create_pipeline(
+            "Shadow Mesh Pipeline",
+            &self.shader,
+            &self.mesh_layout,
+            "vs_mesh",
+            None,
+            wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<MeshVertex>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+            },
+        );
+        (
+            voxel_pipeline(&self.shader, &self.voxel_layout),
+            voxel_pipeline(&self.rgba_shader, &self.rgba_voxel_layout),
+            mesh_pipeline,
+        )
+    }
+}
+
+impl Default for ShadowUniforms {
+    fn default() -> Self {
+        ShadowUniforms {
+            matrices: [math::IDENTITY; MAX_CASCADES as usize],
+            splits: [0.0; 4],
+            count: 0,
+            normal_offset: 0.0,
+            _padding0: [0; 2],
+            texel_size: [0.0; 4],
+            blend: 0.0,
+            debug: 0,
+            _padding: [0; 2],
+        }
+    }
+}
+
+/// The `layers`-layer shadow map array, and a view of each layer to render into.
+fn create_shadow_map(
+    device: &wgpu::Device,
+    size: u32,
+    layers: u32,
+) -> (wgpu::TextureView, Vec<wgpu::TextureView>) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Shadow Map"),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: layers,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: SHADOW_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("Shadow Map View"),
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        ..Default::default()
+    });
+    let layer_views = (0..layers)
+        .map(|layer| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Shadow Cascade View"),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: layer,
+                array_layer_count: Some(1),
+                ..Default::default()
+            })
+        })
+        .collect();
+    (view, layer_views)
+}
+
+fn box_corners(bounds: &Aabb) -> [[f32; 3]; 8] {
+    std::array::from_fn(|corner| {
+        std::array::from_fn(|axis| {
+            if corner >> axis & 1 == 0 {
+                bounds.min[axis]
+            } else {
+                bounds.max[axis]
+            }
+        })
+    })
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    std::array::from_fn(|i| a[i] + b[i])
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    std::array::from_fn(|i| a[i] - b[i])
+}
+
+fn scale(v: [f32; 3], s: f32) -> [f32; 3] {
+    v.map(|c| c * s)
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn length(v: [f32; 3]) -> f32 {
+    dot(v, v).sqrt()
+}
@@ -0,0 +1,220 @@
+//! Screen-space reflections over the lit image, for shiny floors and walls.
+//!
+//! Each lit pixel reflects its view ray about the G-buffer normal and marches the
+//! reflected ray across the screen (shaders/ssr.wgsl), comparing the ray's distance
+//! from the camera with the linear-Z at each step. The color of the first surface it
+//! passes behind is blended in by Schlick Fresnel and the palette entry's smoothness,
+//! faded out toward the screen edges, where what the ray reflects may be off screen.
+//! Reflections exist only of what is on screen: rays that leave it or pass behind
+//! geometry keep the lit color.
+
+/// Upper bound of the `max_steps` of `Renderer::set_ssr`.
+pub const MAX_SSR_STEPS: u32 = 256;
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SsrUniforms {
+    vp_matrix: [f32; 16],
+    inv_vp_matrix: [f32; 16],
+    camera_position: [f32; 3],
+    max_steps: u32,
+}
+
+/// The G-buffer and lighting inputs of an `SsrPass::encode`.
+pub struct SsrInputs<'a> {
+    pub lit: &'a wgpu::TextureView,
+    pub albedo: &'a wgpu::TextureView,
+    pub normal: &'a wgpu::TextureView,
+    pub linear_z: &'a wgpu::TextureView,
+    pub material: &'a wgpu::TextureView,
+    pub materials: &'a wgpu::Buffer,
+    /// `PresentUniforms` of the present pass that wrote `lit`
+    pub present: &'a wgpu::Buffer,
+}
+
+pub struct SsrPass {
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    pub enabled: bool,
+    /// Steps each reflected ray is marched in, 1 to `MAX_SSR_STEPS`
+    pub max_steps: u32,
+}
+
+impl SsrPass {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("SSR Uniform Buffer"),
+            size: std::mem::size_of::<SsrUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let texture = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let uniform = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        // Every texture is loaded texel by texel
+        let float = wgpu::TextureSampleType::Float { filterable: false };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("SSR Bind Group Layout"),
+            entries: &[
+                texture(0, float),
+                texture(1, float),
+                texture(2, float),
+                texture(3, wgpu::TextureSampleType::Uint),
+                uniform(4),
+                uniform(5),
+                uniform(6),
+                texture(7, wgpu::TextureSampleType::Uint),
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("SSR Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/ssr.wgsl").into()),
+        });
+
+        let pipeline = {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("SSR Pipeline Layout"),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("SSR Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: Default::default(),
+                depth_stencil: None,
+                multisample: Default::default(),
+                multiview: None,
+                cache,
+            })
+        };
+
+        SsrPass {
+            layout,
+            pipeline,
+            uniform_buffer,
+            enabled: false,
+            max_steps: 32,
+        }
+    }
+
+    /// Writes the uniforms for the camera of the frame.
+    pub fn prepare(
+        &self,
+        queue: &wgpu::Queue,
+        vp_matrix: [f32; 16],
+        inv_vp_matrix: [f32; 16],
+        camera_position: [f32; 3],
+    ) {
+        let uniforms = SsrUniforms {
+            vp_matrix,
+            inv_vp_matrix,
+            camera_position,
+            max_steps: self.max_steps,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Records the pass, writing `inputs.lit` with the reflections added to `output`.
+    pub fn encode(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        inputs: &SsrInputs,
+        output: &wgpu::TextureView,
+    ) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("SSR BG"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(inputs.lit),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(inputs.albedo),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(inputs.normal),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(inputs.linear_z),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: inputs.materials.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: inputs.present.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::TextureView(inputs.material),
+                },
+            ],
+        });
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("SSR Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            ..Default::default()
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,143 @@
+//! Sparse voxel octree over an object's voxel grid, so the compute raymarch skips
+//! empty space in O(log n) lookups instead of stepping through every cell.
+//!
+//! Nodes are flat `u32`s with the root at index 0. A leaf has `LEAF` set and the
+//! palette index that fills its whole cell in the low 8 bits. An interior node holds
+//! the mask of its non-empty octants in the low 8 bits and the index of its first
+//! child above them; the children of the set octants are stored contiguously in
+//! octant order (bit 0 = +x, bit 1 = +y, bit 2 = +z). Must match svo_raymarch.wgsl.
+
+use wgpu::util::DeviceExt;
+
+const LEAF: u32 = 1 << 31;
+/// Child indices get the 23 bits between the mask and `LEAF`.
+const MAX_NODES: usize = 1 << 23;
+
+/// Octree whose root cell has the edge `root_size(dims)` of its grid.
+pub struct SparseOctree {
+    pub nodes: Vec<u32>,
+}
+
+enum Node {
+    Empty,
+    Leaf(u8),
+    Interior(Box<[Node; 8]>),
+}
+
+/// Edge of the root cell for a grid of `dims`: the power of two covering the longest
+/// axis. Cells past the grid are empty.
+pub fn root_size(dims: [u32; 3]) -> u32 {
+    dims.into_iter()
+        .max()
+        .unwrap_or(1)
+        .max(1)
+        .next_power_of_two()
+}
+
+impl SparseOctree {
+    /// Builds the octree of the `dims` grid `voxels` (x fastest, then y, then z).
+    /// Cells whose voxels all share one palette index collapse into a single leaf.
+    pub fn build(dims: [u32; 3], voxels: &[u8], empty_index: u8) -> Result<Self, String> {
+        let voxel_count = dims.iter().map(|&dim| dim as usize).product::<usize>();
+        if voxels.len() != voxel_count {
+            return Err(format!(
+                "expected {} voxels for dims {:?}, got {}",
+                voxel_count,
+                dims,
+                voxels.len()
+            ));
+        }
+        let grid = Grid {
+            dims,
+            voxels,
+            empty_index,
+        };
+        let root = grid.build([0; 3], root_size(dims));
+
+        let mut nodes = vec![0];
+        let mut pending = vec![(0, root)];
+        while let Some((index, node)) = pending.pop() {
+            nodes[index] = match node {
+                Node::Empty => 0,
+                Node::Leaf(value) => LEAF | value as u32,
+                Node::Interior(children) => {
+                    let first = nodes.len();
+                    let mut mask = 0;
+                    for (octant, child) in children.into_iter().enumerate() {
+                        if let Node::Empty = child {
+                            continue;
+                        }
+                        mask |= 1 << octant;
+                        pending.push((nodes.len(), child));
+                        nodes.push(0);
+                    }
+                    if nodes.len() > MAX_NODES {
+                        return Err(format!("the octree needs more than {} nodes", MAX_NODES));
+                    }
+                    (first as u32) << 8 | mask
+                }
+            };
+        }
+        Ok(SparseOctree { nodes })
+    }
+
+    /// Uploads the nodes into a storage buffer for svo_raymarch.wgsl.
+    pub fn upload(&self, device: &wgpu::Device) -> wgpu::Buffer {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sparse Voxel Octree"),
+            contents: bytemuck::cast_slice(&self.nodes),
+            usage: wgpu::BufferUsages::STORAGE,
+        })
+    }
+}
+
+struct Grid<'a> {
+    dims: [u32; 3],
+    voxels: &'a [u8],
+    empty_index: u8,
+}
+
+impl Grid<'_> {
+    fn voxel(&self, [x, y, z]: [u32; 3]) -> Option<u8> {
+        let [nx, ny, nz] = self.dims;
+        if x >= nx || y >= ny || z >= nz {
+            return None;
+        }
+        let value = self.voxels[(x + nx * (y + ny * z)) as usize];
+        (value != self.empty_index).then_some(value)
+    }
+
+    /// The cell of edge `size` at `origin`.
+    fn build(&self, origin: [u32; 3], size: u32) -> Node {
+        if origin
+            .iter()
+            .zip(self.dims)
+            .any(|(&start, dim)| start >= dim)
+        {
+            return Node::Empty;
+        }
+        if size == 1 {
+            return self.voxel(origin).map_or(Node::Empty, Node::Leaf);
+        }
+        let half = size / 2;
+        let children: [Node; 8] = std::array::from_fn(|octant| {
+            let offset = [octant & 1, octant >> 1 & 1, octant >> 2 & 1].map(|bit| bit as u32);
+            self.build(
+                [0, 1, 2].map(|axis| origin[axis] + offset[axis] * half),
+                half,
+            )
+        });
+        match &children {
+            children if children.iter().all(|child| matches!(child, Node::Empty)) => Node::Empty,
+            // A leaf must cover its whole cell, including any part past the grid
+            [Node::Leaf(value), rest @ ..]
+                if rest
+                    .iter()
+                    .all(|child| matches!(child, Node::Leaf(v) if v == value)) =>
+            {
+                Node::Leaf(*value)
+            }
+            _ => Node::Interior(Box::new(children)),
+        }
+    }
+}
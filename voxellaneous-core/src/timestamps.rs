@@ -0,0 +1,155 @@
+//! GPU timestamps around the passes of `Renderer::render`, with `TIMESTAMP_QUERY`.
+//!
+//! The G-buffer and present render passes write their own start and end timestamps.
+//! The compute G-buffer path and the post-processing chain, which span several
+//! passes, are bracketed by empty compute passes that only write a timestamp. Each
+//! frame resolves the queries into a staging buffer that is read back asynchronously;
+//! frames rendered while a readback is still in flight are not measured.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use crate::readback;
+
+pub const GBUFFER: u32 = 0;
+pub const PRESENT: u32 = 2;
+pub const POST: u32 = 4;
+/// A start and an end for each of the G-buffer, present and post-processing passes.
+const QUERY_COUNT: u32 = 6;
+const RESOLVE_BYTES: u64 = QUERY_COUNT as u64 * 8;
+
+/// GPU clock times in nanoseconds of the latest measured frame, as `BigInt` in JS.
+/// Only differences between them are meaningful.
+#[derive(Serialize, Copy, Clone, Default, Debug)]
+pub struct GpuTimestamps {
+    pub gbuffer_start_ns: u64,
+    pub gbuffer_end_ns: u64,
+    pub present_start_ns: u64,
+    pub present_end_ns: u64,
+    /// `None` when the frame had no post-processing pass
+    pub post_start_ns: Option<u64>,
+    pub post_end_ns: Option<u64>,
+}
+
+pub struct PassTimestamps {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    staging_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    pub enabled: bool,
+    /// Set while `staging_buffer` is being mapped and read
+    reading: Rc<Cell<bool>>,
+    latest: Rc<Cell<Option<GpuTimestamps>>>,
+}
+
+impl PassTimestamps {
+    /// Needs a device with `Features::TIMESTAMP_QUERY`.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Pass Timestamp Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: QUERY_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pass Timestamp Resolve Buffer"),
+            size: RESOLVE_BYTES,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pass Timestamp Staging Buffer"),
+            size: RESOLVE_BYTES,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        PassTimestamps {
+            query_set,
+            resolve_buffer,
+            staging_buffer,
+            period: queue.get_timestamp_period(),
+            enabled: true,
+            reading: Rc::default(),
+            latest: Rc::default(),
+        }
+    }
+
+    /// Whether this frame is measured: enabled, and no readback in flight.
+    pub fn active(&self) -> bool {
+        self.enabled && !self.reading.get()
+    }
+
+    /// Start and end writes for a render pass, at queries `first` and `first + 1`.
+    pub fn render_pass_writes(&self, first: u32) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        self.active().then_some(wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(first),
+            end_of_pass_write_index: Some(first + 1),
+        })
+    }
+
+    /// Writes query `index` at this point of `encoder`, with an empty compute pass.
+    pub fn mark(&self, encoder: &mut wgpu::CommandEncoder, index: u32) {
+        if !self.active() {
+            return;
+        }
+        encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Timestamp Marker"),
+            timestamp_writes: Some(wgpu::ComputePassTimestampWrites {
+                query_set: &self.query_set,
+                beginning_of_pass_write_index: Some(index),
+                end_of_pass_write_index: None,
+            }),
+        });
+    }
+
+    /// Copies the frame's queries to the staging buffer; returns whether it did, in
+    /// which case `read_back` must follow the submit.
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) -> bool {
+        if !self.active() {
+            return false;
+        }
+        encoder.resolve_query_set(&self.query_set, 0..QUERY_COUNT, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.staging_buffer,
+            0,
+            RESOLVE_BYTES,
+        );
+        true
+    }
+
+    /// Reads the queries resolved by the last submit into `latest`. `post` tells
+    /// whether the frame wrote the post-processing queries.
+    pub fn read_back(&self, post: bool) {
+        self.reading.set(true);
+        let reading = self.reading.clone();
+        let latest = self.latest.clone();
+        let buffer = self.staging_buffer.clone();
+        let period = f64::from(self.period);
+        wasm_bindgen_futures::spawn_local(async move {
+            if readback::map_for_read(&buffer).await.is_ok() {
+                let ticks: Vec<u64> =
+                    bytemuck::cast_slice(&buffer.slice(..).get_mapped_range()).to_vec();
+                buffer.unmap();
+                let ns = |i: u32| (ticks[i as usize] as f64 * period) as u64;
+                latest.set(Some(GpuTimestamps {
+                    gbuffer_start_ns: ns(GBUFFER),
+                    gbuffer_end_ns: ns(GBUFFER + 1),
+                    present_start_ns: ns(PRESENT),
+                    present_end_ns: ns(PRESENT + 1),
+                    post_start_ns: post.then(|| ns(POST)),
+                    post_end_ns: post.then(|| ns(POST + 1)),
+                }));
+            }
+            reading.set(false);
+        });
+    }
+
+    pub fn latest(&self) -> Option<GpuTimestamps> {
+        self.latest.get()
+    }
+}
@@ -0,0 +1,55 @@
+//! Deferred uniform writes between `Renderer::begin_update_batch` and
+//! `end_update_batch`.
+//!
+//! While a batch is open, the per-object and material setters hold their writes here
+//! instead of calling `Queue::write_buffer`. A write to a region (buffer, offset and
+//! length) already held replaces it, so animating hundreds of objects per frame costs
+//! one write per object however many setters touch it. Writes to buffers the render
+//! loop also writes, such as the palette, are never held.
+
+use std::collections::HashMap;
+
+/// Buffer, offset and length of a write.
+type Region = (wgpu::Buffer, u64, usize);
+
+#[derive(Default)]
+pub struct UpdateBatch {
+    /// `Some` while a batch is open: the latest bytes of each region written, after
+    /// the sequence number of that write
+    pending: Option<HashMap<Region, (u64, Vec<u8>)>>,
+    sequence: u64,
+}
+
+impl UpdateBatch {
+    /// Starts holding writes; no effect while a batch is already open.
+    pub fn begin(&mut self) {
+        self.pending.get_or_insert_with(HashMap::new);
+    }
+
+    /// Writes `data` to `buffer` at `offset`, or holds it until `flush` in a batch.
+    pub fn write(&mut self, queue: &wgpu::Queue, buffer: &wgpu::Buffer, offset: u64, data: &[u8]) {
+        match &mut self.pending {
+            Some(pending) => {
+                self.sequence += 1;
+                let region = (buffer.clone(), offset, data.len());
+                pending.insert(region, (self.sequence, data.to_vec()));
+            }
+            None => queue.write_buffer(buffer, offset, data),
+        }
+    }
+
+    /// Closes the batch, issuing the held writes in the order their regions were last
+    /// written, so overlapping regions end up as they would have unbatched. Returns
+    /// how many writes were issued.
+    pub fn flush(&mut self, queue: &wgpu::Queue) -> usize {
+        let Some(pending) = self.pending.take() else {
+            return 0;
+        };
+        let mut writes: Vec<_> = pending.into_iter().collect();
+        writes.sort_unstable_by_key(|(_, (sequence, _))| *sequence);
+        for ((buffer, offset, _), (_, data)) in &writes {
+            queue.write_buffer(buffer, *offset, data);
+        }
+        writes.len()
+    }
+}
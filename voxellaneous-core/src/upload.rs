@@ -0,0 +1,137 @@
+//! Batched scene upload for `Renderer::upload_scene_async`.
+
+use std::{cell::Cell, rc::Rc};
+
+use serde::Serialize;
+use wasm_bindgen::{prelude::*, JsCast};
+
+use crate::error::RendererError;
+use crate::primitives::RGBA;
+use crate::scene::{Scene, VoxelObject};
+use crate::Renderer;
+
+/// Voxel bytes uploaded between two yields to the event loop; a larger object
+/// still goes in a batch of its own.
+const BATCH_BYTES: usize = 4 << 20;
+
+/// Held by the renderer while an async upload runs. Dropping it (a new upload, a
+/// scene swap or the renderer being freed) cancels the upload.
+pub struct UploadToken(Rc<Cell<bool>>);
+
+impl Drop for UploadToken {
+    fn drop(&mut self) {
+        self.0.set(true);
+    }
+}
+
+/// Argument of the `on_progress` callback.
+#[derive(Serialize)]
+struct UploadProgress {
+    objects_done: u32,
+    objects_total: u32,
+    bytes_done: u64,
+}
+
+pub struct PendingUpload {
+    objects: js_sys::Array,
+    cancelled: Rc<Cell<bool>>,
+}
+
+/// Replaces the active scene with `scene`'s palette and no objects, returning the
+/// objects still to upload and the token that must be stored in the renderer.
+pub fn begin(
+    renderer: &mut Renderer,
+    scene: &JsValue,
+) -> Result<(PendingUpload, UploadToken), RendererError> {
+    let palette: Vec<RGBA> =
+        serde_wasm_bindgen::from_value(js_sys::Reflect::get(scene, &"palette".into())?)?;
+    let objects: js_sys::Array = js_sys::Reflect::get(scene, &"objects".into())?
+        .dyn_into()
+        .map_err(|_| RendererError::invalid("objects", "scene.objects must be an array"))?;
+    renderer.upload_parsed_scene(Scene {
+        palette,
+        objects: Vec::new(),
+    })?;
+
+    let cancelled = Rc::new(Cell::new(false));
+    Ok((
+        PendingUpload {
+            objects,
+            cancelled: cancelled.clone(),
+        },
+        UploadToken(cancelled),
+    ))
+}
+
+/// Deserializes and uploads the pending objects batch by batch, yielding to the
+/// event loop in between so the host can keep rendering.
+///
+/// # Safety
+///
+/// `renderer` must stay valid for as long as the upload's `UploadToken` is alive.
+/// The renderer owns the token, so this holds as long as it is stored in it.
+pub async unsafe fn run(
+    renderer: *mut Renderer,
+    pending: PendingUpload,
+    on_progress: Option<js_sys::Function>,
+    signal: Option<web_sys::AbortSignal>,
+) -> Result<(), RendererError> {
+    let objects_total = pending.objects.length();
+    let mut objects_done = 0;
+    let mut bytes_done = 0;
+
+    while objects_done < objects_total {
+        if pending.cancelled.get() {
+            return Err(RendererError::Destroyed("upload superseded".into()));
+        }
+        if signal.as_ref().is_some_and(|signal| signal.aborted()) {
+            return Err(RendererError::Aborted("upload aborted".into()));
+        }
+
+        let mut batch = Vec::new();
+        let mut batch_bytes = 0;
+        while objects_done < objects_total && (batch.is_empty() || batch_bytes < BATCH_BYTES) {
+            let obj: VoxelObject =
+                serde_wasm_bindgen::from_value(pending.objects.get(objects_done)).map_err(
+                    |error| RendererError::invalid_object(objects_done, None, error.to_string()),
+                )?;
+            batch_bytes += obj.voxels.len();
+            batch.push(obj);
+            objects_done += 1;
+        }
+        bytes_done += batch_bytes as u64;
+
+        // SAFETY: not cancelled, so the renderer is alive and this upload is still
+        // current; no other borrow of it is active between awaits.
+        unsafe { (*renderer).append_objects(batch) }?;
+
+        if let Some(on_progress) = &on_progress {
+            let progress = UploadProgress {
+                objects_done,
+                objects_total,
+                bytes_done,
+            };
+            serde_wasm_bindgen::to_value(&progress)
+                .map_err(JsValue::from)
+                .and_then(|progress| on_progress.call1(&JsValue::UNDEFINED, &progress))?;
+        }
+
+        yield_to_event_loop().await;
+    }
+    Ok(())
+}
+
+/// Resolves on a later task (`setTimeout(0)`), unlike a resolved promise, which
+/// would run before the browser gets to render or handle input.
+async fn yield_to_event_loop() {
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+        let scheduled = web_sys::window()
+            .map(|window| window.set_timeout_with_callback(&resolve).is_ok())
+            .unwrap_or(false);
+        if !scheduled {
+            let _ = resolve.call0(&JsValue::UNDEFINED);
+        }
+    });
+    // Resolves only, never rejects
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
@@ -1,12 +1,40 @@
-use wasm_bindgen::JsValue;
+pub mod log;
 
 use crate::primitives::RGBA;
 
-// e should accept any type which has to_string method
-pub fn map_wgpu_err(e: impl std::fmt::Display) -> JsValue {
-    JsValue::from_str(&e.to_string())
-}
-
 pub fn pack_rgba(rgba: &RGBA) -> u32 {
     ((rgba.3 as u32) << 24) | ((rgba.2 as u32) << 16) | ((rgba.1 as u32) << 8) | (rgba.0 as u32)
 }
+
+/// Builds a column-major model matrix from translation, rotation quaternion
+/// `[x, y, z, w]` and scale, applied as scale, then rotation, then translation.
+/// The quaternion is normalized first; a zero quaternion means no rotation.
+pub fn compose_trs(translation: [f32; 3], rotation: [f32; 4], scale: [f32; 3]) -> [f32; 16] {
+    let len = rotation.iter().map(|c| c * c).sum::<f32>().sqrt();
+    let [x, y, z, w] = if len > 0.0 && len.is_finite() {
+        rotation.map(|c| c / len)
+    } else {
+        [0.0, 0.0, 0.0, 1.0]
+    };
+    let [sx, sy, sz] = scale;
+    let [tx, ty, tz] = translation;
+
+    [
+        (1.0 - 2.0 * (y * y + z * z)) * sx,
+        2.0 * (x * y + z * w) * sx,
+        2.0 * (x * z - y * w) * sx,
+        0.0,
+        2.0 * (x * y - z * w) * sy,
+        (1.0 - 2.0 * (x * x + z * z)) * sy,
+        2.0 * (y * z + x * w) * sy,
+        0.0,
+        2.0 * (x * z + y * w) * sz,
+        2.0 * (y * z - x * w) * sz,
+        (1.0 - 2.0 * (x * x + y * y)) * sz,
+        0.0,
+        tx,
+        ty,
+        tz,
+        1.0,
+    ]
+}
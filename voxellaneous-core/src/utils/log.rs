@@ -0,0 +1,126 @@
+//! Leveled logging and the panic hook, the one place the crate writes to the console.
+//!
+//! The browser console is the sink on wasm; other targets write to stderr, and would
+//! forward to `log` or `tracing` here. A message below the level is never formatted:
+//! the macros only load the level before skipping it.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+#[cfg(target_arch = "wasm32")]
+use std::sync::Once;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+}
+
+impl Level {
+    /// Level named `name`: "off", "error", "warn", "info" or "debug".
+    pub fn parse(name: &str) -> Option<Level> {
+        match name {
+            "off" => Some(Level::Off),
+            "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+}
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Warn as u8);
+
+/// Sets the most verbose level written, for every renderer of the page.
+pub fn set_level(level: Level) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Writes `message` at `level`; use the macros, which skip it when disabled.
+pub fn write(level: Level, message: &str) {
+    let message = format!("[voxellaneous] {}", message);
+    #[cfg(target_arch = "wasm32")]
+    {
+        let message = wasm_bindgen::JsValue::from(message);
+        match level {
+            Level::Off => {}
+            Level::Error => web_sys::console::error_1(&message),
+            Level::Warn => web_sys::console::warn_1(&message),
+            Level::Info => web_sys::console::info_1(&message),
+            Level::Debug => web_sys::console::debug_1(&message),
+        }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if level != Level::Off {
+        eprintln!("{:?} {}", level, message);
+    }
+}
+
+/// Reports panics as console errors with their message, location and the JS stack,
+/// instead of wasm's bare "unreachable executed". Installed once, by the first
+/// renderer created.
+#[cfg(target_arch = "wasm32")]
+pub fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        std::panic::set_hook(Box::new(|info| {
+            // The stack of a fresh Error shows the JS and wasm frames of the panic
+            let stack = js_sys::Reflect::get(&js_sys::Error::new(""), &"stack".into())
+                .ok()
+                .and_then(|stack| stack.as_string())
+                .unwrap_or_default();
+            web_sys::console::error_1(&format!("[voxellaneous] {}\n{}", info, stack).into());
+        }));
+    });
+}
+
+/// Other targets keep the default hook, which already prints the location.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn install_panic_hook() {}
+
+macro_rules! log_at {
+    ($level:expr, $($arg:tt)*) => {
+        if $crate::utils::log::enabled($level) {
+            $crate::utils::log::write($level, &format!($($arg)*));
+        }
+    };
+}
+
+macro_rules! log_error {
+    ($($arg:tt)*) => { $crate::utils::log::log_at!($crate::utils::log::Level::Error, $($arg)*) };
+}
+
+macro_rules! log_warn {
+    ($($arg:tt)*) => { $crate::utils::log::log_at!($crate::utils::log::Level::Warn, $($arg)*) };
+}
+
+macro_rules! log_info {
+    ($($arg:tt)*) => { $crate::utils::log::log_at!($crate::utils::log::Level::Info, $($arg)*) };
+}
+
+macro_rules! log_debug {
+    ($($arg:tt)*) => { $crate::utils::log::log_at!($crate::utils::log::Level::Debug, $($arg)*) };
+}
+
+/// `warn!` at most once per call site, for messages a frame loop would repeat.
+macro_rules! warn_once {
+    ($($arg:tt)*) => {{
+        static WARNED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+        if $crate::utils::log::enabled($crate::utils::log::Level::Warn)
+            && !WARNED.swap(true, std::sync::atomic::Ordering::Relaxed)
+        {
+            $crate::utils::log::write($crate::utils::log::Level::Warn, &format!($($arg)*));
+        }
+    }};
+}
+
+// Renamed on export, as `warn` alone is ambiguous with the built-in attribute
+pub(crate) use {
+    log_at, log_debug as debug, log_error as error, log_info as info, log_warn as warn, warn_once,
+};
@@ -0,0 +1,100 @@
+use serde::Deserialize;
+
+use crate::constants::GBUFFER_NORMAL_FORMAT;
+use crate::{create_depth_texture, create_render_texture_view, create_stencil_view};
+
+/// One eye of a WebXR frame, as passed to `Renderer::render_xr`.
+#[derive(Deserialize)]
+pub struct XrView {
+    pub vp_matrix: [f32; 16],
+    pub camera_position: [f32; 3],
+    /// `[x, y, width, height]` in pixels of the presented texture.
+    pub viewport: [u32; 4],
+}
+
+/// G-buffer targets and per-frame uniforms for one eye, sized to its viewport.
+pub struct XrEye {
+    pub width: u32,
+    pub height: u32,
+    pub gbuffer_albedo: wgpu::TextureView,
+    pub gbuffer_normal: wgpu::TextureView,
+    pub gbuffer_linear_z: wgpu::TextureView,
+    pub gbuffer_material: wgpu::TextureView,
+    pub depth_texture_view: wgpu::TextureView,
+    pub stencil_view: wgpu::TextureView,
+    pub per_frame_uniform_buffer: wgpu::Buffer,
+    pub per_frame_bind_group: wgpu::BindGroup,
+    /// The lighting pass unprojects with the eye's own view-projection.
+    pub lighting_uniform_buffer: wgpu::Buffer,
+}
+
+impl XrEye {
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        linear_z_format: wgpu::TextureFormat,
+        per_frame_bind_group_layout: &wgpu::BindGroupLayout,
+        per_frame_uniform_size: u64,
+        lighting_uniform_size: u64,
+    ) -> Self {
+        let per_frame_uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("XR Per Frame Uniform Buffer"),
+            size: per_frame_uniform_size,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let per_frame_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("XR Per Frame Bind Group"),
+            layout: per_frame_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: per_frame_uniform_buffer.as_entire_binding(),
+            }],
+        });
+        let lighting_uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("XR Lighting Uniform Buffer"),
+            size: lighting_uniform_size,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let depth_texture = create_depth_texture(device, width, height);
+        XrEye {
+            width,
+            height,
+            gbuffer_albedo: create_render_texture_view(
+                device,
+                width,
+                height,
+                wgpu::TextureFormat::Rgba8Unorm,
+                "XR GBuffer Albedo",
+            ),
+            gbuffer_normal: create_render_texture_view(
+                device,
+                width,
+                height,
+                GBUFFER_NORMAL_FORMAT,
+                "XR GBuffer Normal",
+            ),
+            gbuffer_linear_z: create_render_texture_view(
+                device,
+                width,
+                height,
+                linear_z_format,
+                "XR GBuffer LinearZ",
+            ),
+            gbuffer_material: create_render_texture_view(
+                device,
+                width,
+                height,
+                linear_z_format,
+                "XR GBuffer Material",
+            ),
+            depth_texture_view: depth_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            stencil_view: create_stencil_view(&depth_texture),
+            per_frame_uniform_buffer,
+            per_frame_bind_group,
+            lighting_uniform_buffer,
+        }
+    }
+}
@@ -0,0 +1,896 @@
+//! Zstandard decompression (RFC 8878) for compressed binary scenes, behind the `zstd`
+//! feature.
+//!
+//! A small decoder in the style of the format's educational decoder: frames of raw,
+//! RLE and compressed blocks, Huffman-coded literals and FSE-coded sequences, with
+//! the content checksum verified. Dictionaries are not supported. Errors name the
+//! byte offset into the zstd stream at which decoding failed.
+
+const FRAME_MAGIC: u32 = 0xfd2f_b528;
+/// Skippable frames carry data for other tools and are passed over.
+const SKIPPABLE_MAGIC: u32 = 0x184d_2a50;
+const SKIPPABLE_MAGIC_MASK: u32 = 0xffff_fff0;
+
+/// Largest block, and largest literals section of a block, the format allows.
+const MAX_BLOCK_SIZE: usize = 128 << 10;
+const MAX_HUFFMAN_BITS: u32 = 11;
+const MAX_WEIGHT_LOG: u32 = 6;
+
+/// Sequence codes, in the order of their tables in a block.
+const LITERALS_LENGTH: usize = 0;
+const OFFSET: usize = 1;
+const MATCH_LENGTH: usize = 2;
+/// Largest accuracy log and symbol of each sequence code's FSE table.
+const MAX_LOG: [u32; 3] = [9, 8, 9];
+const MAX_SYMBOL: [usize; 3] = [35, 31, 52];
+/// Accuracy logs and distributions of the predefined tables.
+const DEFAULT_LOG: [u32; 3] = [6, 5, 6];
+const DEFAULT_DISTRIBUTION: [&[i16]; 3] = [
+    &[
+        4, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3, 2, 1, 1, 1,
+        1, 1, -1, -1, -1, -1,
+    ],
+    &[
+        1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1,
+    ],
+    &[
+        1, 4, 3, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1, -1, -1,
+    ],
+];
+
+/// Base values and extra bits of literals length codes 0..=35.
+const LITERALS_LENGTH_BASE: [u32; 36] = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 18, 20, 22, 24, 28, 32, 40, 48, 64,
+    128, 256, 512, 1024, 2048, 4096, 8192, 16384, 32768, 65536,
+];
+const LITERALS_LENGTH_EXTRA: [u8; 36] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 3, 3, 4, 6, 7, 8, 9, 10, 11,
+    12, 13, 14, 15, 16,
+];
+/// Base values and extra bits of match length codes 0..=52.
+const MATCH_LENGTH_BASE: [u32; 53] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27,
+    28, 29, 30, 31, 32, 33, 34, 35, 37, 39, 41, 43, 47, 51, 59, 67, 83, 99, 131, 259, 515, 1027,
+    2051, 4099, 8195, 16387, 32771, 65539,
+];
+const MATCH_LENGTH_EXTRA: [u8; 53] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    1, 1, 1, 1, 2, 2, 3, 3, 4, 4, 5, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+];
+
+/// Decompresses a zstd stream of one or more frames (skippable frames included),
+/// checking content sizes and checksums. Fails once the output would pass `limit`
+/// bytes.
+pub fn decompress(bytes: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let mut input = Input {
+        bytes,
+        pos: 0,
+        base: 0,
+    };
+    let mut out = Vec::new();
+    while input.pos < bytes.len() || input.pos == 0 {
+        let magic_at = input.pos;
+        let magic = input.u32()?;
+        if magic & SKIPPABLE_MAGIC_MASK == SKIPPABLE_MAGIC {
+            let len = input.u32()?;
+            input.take(len as usize)?;
+        } else if magic == FRAME_MAGIC {
+            decode_frame(&mut input, &mut out, limit)?;
+        } else if magic_at == 0 {
+            return Err(error_at(0, "not a zstd stream"));
+        } else {
+            return Err(error_at(magic_at, "trailing bytes after the zstd stream"));
+        }
+    }
+    Ok(out)
+}
+
+fn decode_frame(input: &mut Input, out: &mut Vec<u8>, limit: usize) -> Result<(), String> {
+    let descriptor_at = input.pos;
+    let descriptor = input.byte()?;
+    if descriptor & 0x08 != 0 {
+        return Err(error_at(descriptor_at, "reserved frame header bit is set"));
+    }
+    let single_segment = descriptor & 0x20 != 0;
+    let has_checksum = descriptor & 0x04 != 0;
+    if !single_segment {
+        // Window descriptor: the whole output is kept, so any window fits
+        input.byte()?;
+    }
+    let dictionary_at = input.pos;
+    let dictionary = input.take([0, 1, 2, 4][(descriptor & 0x03) as usize])?;
+    if dictionary.iter().any(|&byte| byte != 0) {
+        return Err(error_at(dictionary_at, "dictionaries are not supported"));
+    }
+    let size_len = match descriptor >> 6 {
+        0 => single_segment as usize,
+        1 => 2,
+        2 => 4,
+        _ => 8,
+    };
+    let size_at = input.pos;
+    let content_size = (size_len > 0).then(|| {
+        let bytes = input.take(size_len)?;
+        let size = bytes
+            .iter()
+            .rev()
+            .fold(0u64, |size, &byte| size << 8 | byte as u64);
+        Ok::<_, String>(if size_len == 2 { size + 256 } else { size })
+    });
+    let content_size = content_size.transpose()?;
+    if let Some(size) = content_size {
+        if size > (limit - out.len()) as u64 {
+            return Err(error_at(size_at, &past_limit(limit)));
+        }
+        out.reserve(size as usize);
+    }
+
+    let start = out.len();
+    let mut state = FrameState {
+        huffman: None,
+        tables: [None, None, None],
+        offsets: [1, 4, 8],
+    };
+    loop {
+        let header_at = input.pos;
+        let header = input.take(3)?;
+        let header = header[0] as usize | (header[1] as usize) << 8 | (header[2] as usize) << 16;
+        let last = header & 1 != 0;
+        let size = header >> 3;
+        if size > MAX_BLOCK_SIZE {
+            return Err(error_at(header_at, "block is larger than 128 KiB"));
+        }
+        match (header >> 1) & 0x03 {
+            0 => {
+                let bytes = input.take(size)?;
+                reserve(out, size, limit, header_at)?;
+                out.extend_from_slice(bytes);
+            }
+            1 => {
+                let byte = input.byte()?;
+                reserve(out, size, limit, header_at)?;
+                out.resize(out.len() + size, byte);
+            }
+            2 => {
+                let block = Input {
+                    bytes: input.take(size)?,
+                    pos: 0,
+                    base: header_at + 3,
+                };
+                state.decode_block(block, out, start, limit)?;
+            }
+            _ => return Err(error_at(header_at, "reserved block type")),
+        }
+        if last {
+            break;
+        }
+    }
+
+    if content_size.is_some_and(|size| size != (out.len() - start) as u64) {
+        return Err(error_at(size_at, "content size mismatch"));
+    }
+    if has_checksum {
+        let checksum_at = input.pos;
+        let checksum = input.u32()?;
+        if checksum != xxh64(&out[start..]) as u32 {
+            return Err(error_at(checksum_at, "content checksum mismatch"));
+        }
+    }
+    Ok(())
+}
+
+/// What the blocks of a frame pass on to the blocks after them.
+struct FrameState {
+    /// Literals code of the last block with one, for treeless literals
+    huffman: Option<Huffman>,
+    /// Sequence code tables of the last block, for the repeat mode
+    tables: [Option<Fse>; 3],
+    /// Repeat offsets, most recent first
+    offsets: [u64; 3],
+}
+
+struct Sequence {
+    literals: usize,
+    offset: u64,
+    matched: usize,
+}
+
+impl FrameState {
+    /// Decodes a compressed block, appending it to `out`; `start` is the start of the
+    /// frame in `out`.
+    fn decode_block(
+        &mut self,
+        mut block: Input,
+        out: &mut Vec<u8>,
+        start: usize,
+        limit: usize,
+    ) -> Result<(), String> {
+        let at = block.base;
+        let literals = self.decode_literals(&mut block)?;
+        let sequences = self.decode_sequences(&mut block)?;
+
+        let mut next = 0;
+        for sequence in sequences {
+            let offset = self.offset(sequence.offset, sequence.literals);
+            let Some(literals) = literals.get(next..next + sequence.literals) else {
+                return Err(error_at(at, "sequence runs past the literals"));
+            };
+            reserve(out, sequence.literals + sequence.matched, limit, at)?;
+            out.extend_from_slice(literals);
+            next += sequence.literals;
+            if offset == 0 || offset > (out.len() - start) as u64 {
+                return Err(error_at(at, "offset reaches before the start of the frame"));
+            }
+            let from = out.len() - offset as usize;
+            if offset as usize >= sequence.matched {
+                out.extend_from_within(from..from + sequence.matched);
+            } else {
+                // Byte by byte, as the copy overlaps what it is writing
+                for k in 0..sequence.matched {
+                    out.push(out[from + k]);
+                }
+            }
+        }
+        reserve(out, literals.len() - next, limit, at)?;
+        out.extend_from_slice(&literals[next..]);
+        Ok(())
+    }
+
+    fn decode_literals(&mut self, block: &mut Input) -> Result<Vec<u8>, String> {
+        let at = block.offset();
+        let header = block.byte()?;
+        let size_format = (header >> 2) & 0x03;
+        match header & 0x03 {
+            kind @ (0 | 1) => {
+                let size = match size_format {
+                    0 | 2 => (header >> 3) as usize,
+                    1 => (header >> 4) as usize | (block.byte()? as usize) << 4,
+                    _ => {
+                        let rest = block.take(2)?;
+                        (header >> 4) as usize | (rest[0] as usize) << 4 | (rest[1] as usize) << 12
+                    }
+                };
+                if size > MAX_BLOCK_SIZE {
+                    return Err(error_at(at, "literals are larger than 128 KiB"));
+                }
+                if kind == 0 {
+                    Ok(block.take(size)?.to_vec())
+                } else {
+                    Ok(vec![block.byte()?; size])
+                }
+            }
+            kind => {
+                let (streams, header_len, bits) = match size_format {
+                    0 => (1, 3, 10),
+                    1 => (4, 3, 10),
+                    2 => (4, 4, 14),
+                    _ => (4, 5, 18),
+                };
+                let rest = block.take(header_len - 1)?;
+                let header = rest
+                    .iter()
+                    .rev()
+                    .fold(0u64, |value, &byte| value << 8 | byte as u64)
+                    << 8
+                    | header as u64;
+                let mask = (1 << bits) - 1;
+                let regenerated = (header >> 4 & mask) as usize;
+                let compressed = (header >> (4 + bits) & mask) as usize;
+                if regenerated > MAX_BLOCK_SIZE {
+                    return Err(error_at(at, "literals are larger than 128 KiB"));
+                }
+                let mut data = Input {
+                    base: block.offset(),
+                    bytes: block.take(compressed)?,
+                    pos: 0,
+                };
+                if kind == 2 {
+                    self.huffman = Some(Huffman::read(&mut data)?);
+                }
+                let Some(huffman) = &self.huffman else {
+                    return Err(error_at(
+                        data.base,
+                        "treeless literals without a previous code",
+                    ));
+                };
+                let literals = &data.bytes[data.pos..];
+                huffman.decode_streams(literals, data.offset(), streams, regenerated)
+            }
+        }
+    }
+
+    fn decode_sequences(&mut self, block: &mut Input) -> Result<Vec<Sequence>, String> {
+        let count = match block.byte()? as usize {
+            0 => return Ok(Vec::new()),
+            count @ 1..=127 => count,
+            count @ 128..=254 => (count - 128) << 8 | block.byte()? as usize,
+            _ => {
+                let rest = block.take(2)?;
+                (rest[0] as usize | (rest[1] as usize) << 8) + 0x7f00
+            }
+        };
+        let modes_at = block.offset();
+        let modes = block.byte()?;
+        if modes & 0x03 != 0 {
+            return Err(error_at(modes_at, "reserved sequence mode bits are set"));
+        }
+        for (code, shift) in [(LITERALS_LENGTH, 6), (OFFSET, 4), (MATCH_LENGTH, 2)] {
+            let table_at = block.offset();
+            let table = match (modes >> shift) & 0x03 {
+                0 => Fse::new(DEFAULT_DISTRIBUTION[code], DEFAULT_LOG[code])
+                    .expect("predefined distribution"),
+                1 => {
+                    let symbol = block.byte()?;
+                    if symbol as usize > MAX_SYMBOL[code] {
+                        return Err(error_at(table_at, "invalid sequence code"));
+                    }
+                    Fse::rle(symbol)
+                }
+                2 => {
+                    let mut bits = ForwardBits {
+                        bytes: &block.bytes[block.pos..],
+                        pos: 0,
+                    };
+                    let table = Fse::read(&mut bits, MAX_LOG[code], MAX_SYMBOL[code])
+                        .map_err(|message| error_at(table_at, message))?;
+                    block.take(bits.pos.div_ceil(8))?;
+                    table
+                }
+                _ => match self.tables[code].take() {
+                    Some(table) => table,
+                    None => return Err(error_at(table_at, "repeated table with no previous one")),
+                },
+            };
+            self.tables[code] = Some(table);
+        }
+        let [Some(literals_table), Some(offset_table), Some(match_table)] = &self.tables else {
+            unreachable!("all three tables were just set");
+        };
+
+        let stream_at = block.offset();
+        let mut bits = BackwardBits::new(&block.bytes[block.pos..], stream_at)?;
+        let mut literals_state = bits.read(literals_table.log) as usize;
+        let mut offset_state = bits.read(offset_table.log) as usize;
+        let mut match_state = bits.read(match_table.log) as usize;
+        let mut sequences = Vec::with_capacity(count);
+        for i in 0..count {
+            let offset_code = offset_table.symbols[offset_state] as u32;
+            let literals_code = literals_table.symbols[literals_state] as usize;
+            let match_code = match_table.symbols[match_state] as usize;
+            if offset_code > MAX_SYMBOL[OFFSET] as u32 {
+                return Err(error_at(stream_at, "invalid offset code"));
+            }
+            let offset = (1u64 << offset_code) + bits.read(offset_code);
+            let matched = MATCH_LENGTH_BASE[match_code]
+                + bits.read(MATCH_LENGTH_EXTRA[match_code] as u32) as u32;
+            let literals = LITERALS_LENGTH_BASE[literals_code]
+                + bits.read(LITERALS_LENGTH_EXTRA[literals_code] as u32) as u32;
+            sequences.push(Sequence {
+                literals: literals as usize,
+                offset,
+                matched: matched as usize,
+            });
+            if i + 1 < count {
+                literals_state = literals_table.update(literals_state, &mut bits);
+                match_state = match_table.update(match_state, &mut bits);
+                offset_state = offset_table.update(offset_state, &mut bits);
+            }
+        }
+        if bits.offset != 0 {
+            return Err(error_at(
+                stream_at,
+                "sequence stream is not consumed exactly",
+            ));
+        }
+        block.pos = block.bytes.len();
+        Ok(sequences)
+    }
+
+    /// Offset of a sequence from its offset value, updating the repeat offsets.
+    /// Values 1 to 3 repeat a recent offset, shifted by one when there are no literals.
+    fn offset(&mut self, value: u64, literals: usize) -> u64 {
+        if value > 3 {
+            self.offsets = [value - 3, self.offsets[0], self.offsets[1]];
+            return self.offsets[0];
+        }
+        let index = value as usize - 1 + (literals == 0) as usize;
+        if index == 0 {
+            return self.offsets[0];
+        }
+        let offset = match index {
+            1 | 2 => self.offsets[index],
+            _ => self.offsets[0].saturating_sub(1),
+        };
+        if index > 1 {
+            self.offsets[2] = self.offsets[1];
+        }
+        self.offsets[1] = self.offsets[0];
+        self.offsets[0] = offset;
+        offset
+    }
+}
+
+/// Huffman decoding table of the literals, indexed by the next `max_bits` bits.
+struct Huffman {
+    max_bits: u32,
+    symbols: Vec<u8>,
+    lengths: Vec<u8>,
+}
+
+impl Huffman {
+    /// Reads a Huffman tree description: symbol weights, either packed four bits each
+    /// or FSE-compressed.
+    fn read(input: &mut Input) -> Result<Huffman, String> {
+        let at = input.offset();
+        let header = input.byte()?;
+        let weights = if header >= 128 {
+            let count = header as usize - 127;
+            let packed = input.take(count.div_ceil(2))?;
+            (0..count)
+                .map(|i| packed[i / 2] >> (if i % 2 == 0 { 4 } else { 0 }) & 0x0f)
+                .collect()
+        } else {
+            let data = input.take(header as usize)?;
+            decode_weights(data, at + 1)?
+        };
+        Huffman::from_weights(weights).map_err(|message| error_at(at, message))
+    }
+
+    /// Table of the code whose symbols have `weights`, except the last symbol, whose
+    /// weight completes the code.
+    fn from_weights(mut weights: Vec<u8>) -> Result<Huffman, &'static str> {
+        if weights.len() > 255 {
+            return Err("too many Huffman weights");
+        }
+        if weights
+            .iter()
+            .any(|&weight| weight as u32 > MAX_HUFFMAN_BITS)
+        {
+            return Err("Huffman weight is too large");
+        }
+        let total: u32 = weights
+            .iter()
+            .filter(|&&weight| weight > 0)
+            .map(|&weight| 1 << (weight - 1))
+            .sum();
+        if total == 0 {
+            return Err("Huffman code has no symbols");
+        }
+        let max_bits = 32 - total.leading_zeros();
+        let left = (1 << max_bits) - total;
+        if max_bits > MAX_HUFFMAN_BITS || !left.is_power_of_two() {
+            return Err("Huffman weights do not form a code");
+        }
+        weights.push(left.trailing_zeros() as u8 + 1);
+
+        // Longest codes first, each symbol's entries in symbol order
+        let code_len = |weight: u8| max_bits + 1 - weight as u32;
+        let mut counts = [0usize; MAX_HUFFMAN_BITS as usize + 2];
+        for &weight in weights.iter().filter(|&&weight| weight > 0) {
+            counts[code_len(weight) as usize] += 1;
+        }
+        let mut next = [0usize; MAX_HUFFMAN_BITS as usize + 2];
+        let mut position = 0;
+        for len in (1..=max_bits as usize).rev() {
+            next[len] = position;
+            position += counts[len] << (max_bits as usize - len);
+        }
+        let size = 1 << max_bits;
+        let mut symbols = vec![0; size];
+        let mut lengths = vec![0; size];
+        for (symbol, &weight) in weights.iter().enumerate().filter(|(_, &weight)| weight > 0) {
+            let len = code_len(weight);
+            let start = next[len as usize];
+            let entries = 1 << (max_bits - len);
+            symbols[start..start + entries].fill(symbol as u8);
+            lengths[start..start + entries].fill(len as u8);
+            next[len as usize] += entries;
+        }
+        Ok(Huffman {
+            max_bits,
+            symbols,
+            lengths,
+        })
+    }
+
+    /// Decodes `regenerated` literals from one stream, or four behind a jump table.
+    fn decode_streams(
+        &self,
+        data: &[u8],
+        at: usize,
+        streams: usize,
+        regenerated: usize,
+    ) -> Result<Vec<u8>, String> {
+        let mut out = Vec::with_capacity(regenerated);
+        if streams == 1 {
+            self.decode_stream(data, at, regenerated, &mut out)?;
+            return Ok(out);
+        }
+        let Some(jump_table) = data.get(..6) else {
+            return Err(error_at(at, "literals end inside the jump table"));
+        };
+        let quarter = regenerated.div_ceil(4);
+        let Some(last) = regenerated.checked_sub(3 * quarter) else {
+            return Err(error_at(at, "too few literals for four streams"));
+        };
+        let mut start = 6;
+        for i in 0..4 {
+            let end = if i < 3 {
+                start + u16::from_le_bytes([jump_table[2 * i], jump_table[2 * i + 1]]) as usize
+            } else {
+                data.len()
+            };
+            let Some(stream) = data.get(start..end) else {
+                return Err(error_at(at, "jump table points past the literals"));
+            };
+            let count = if i < 3 { quarter } else { last };
+            self.decode_stream(stream, at + start, count, &mut out)?;
+            start = end;
+        }
+        Ok(out)
+    }
+
+    fn decode_stream(
+        &self,
+        stream: &[u8],
+        at: usize,
+        count: usize,
+        out: &mut Vec<u8>,
+    ) -> Result<(), String> {
+        let mut bits = BackwardBits::new(stream, at)?;
+        let mask = (1 << self.max_bits) - 1;
+        let mut state = bits.read(self.max_bits) as usize;
+        for _ in 0..count {
+            out.push(self.symbols[state]);
+            let len = self.lengths[state] as u32;
+            state = ((state << len) | bits.read(len) as usize) & mask;
+        }
+        if bits.offset != -(self.max_bits as i64) {
+            return Err(error_at(at, "Huffman stream is not consumed exactly"));
+        }
+        Ok(())
+    }
+}
+
+/// Huffman weights compressed with an FSE table and two interleaved states.
+fn decode_weights(data: &[u8], at: usize) -> Result<Vec<u8>, String> {
+    let mut header = ForwardBits {
+        bytes: data,
+        pos: 0,
+    };
+    let table =
+        Fse::read(&mut header, MAX_WEIGHT_LOG, 255).map_err(|message| error_at(at, message))?;
+    let stream_start = header.pos.div_ceil(8);
+    let Some(stream) = data.get(stream_start..) else {
+        return Err(error_at(at, "Huffman weights end inside their table"));
+    };
+    let mut bits = BackwardBits::new(stream, at + stream_start)?;
+    let mut states = [bits.read(table.log) as usize, bits.read(table.log) as usize];
+    let mut weights = Vec::new();
+    for i in (0..2).cycle() {
+        weights.push(table.symbols[states[i]]);
+        states[i] = table.update(states[i], &mut bits);
+        if bits.offset < 0 {
+            weights.push(table.symbols[states[1 - i]]);
+            return Ok(weights);
+        }
+        if weights.len() > 255 {
+            return Err(error_at(at, "too many Huffman weights"));
+        }
+    }
+    unreachable!("the cycle only ends by returning")
+}
+
+/// FSE decoding table: the symbol of each state, and how to reach the next state.
+struct Fse {
+    log: u32,
+    symbols: Vec<u8>,
+    bits: Vec<u8>,
+    base: Vec<u16>,
+}
+
+impl Fse {
+    /// Reads a table description of at most `max_log` accuracy and `max_symbol`
+    /// symbols.
+    fn read(bits: &mut ForwardBits, max_log: u32, max_symbol: usize) -> Result<Fse, &'static str> {
+        let log = bits.read(4)? as u32 + 5;
+        if log > max_log {
+            return Err("FSE accuracy log is too large");
+        }
+        let mut remaining = 1i32 << log;
+        let mut distribution = Vec::new();
+        while remaining > 0 {
+            if distribution.len() > max_symbol {
+                return Err("FSE table has too many symbols");
+            }
+            // Values past what remains take one bit fewer
+            let width = 32 - (remaining + 1).leading_zeros();
+            let mut value = bits.read(width)? as i32;
+            let lower_mask = (1 << (width - 1)) - 1;
+            let threshold = (1 << width) - 1 - (remaining + 1);
+            if value & lower_mask < threshold {
+                bits.pos -= 1;
+                value &= lower_mask;
+            } else if value > lower_mask {
+                value -= threshold;
+            }
+            let probability = value - 1;
+            remaining -= probability.abs();
+            distribution.push(probability as i16);
+            if probability == 0 {
+                loop {
+                    let repeat = bits.read(2)?;
+                    distribution.resize(distribution.len() + repeat as usize, 0);
+                    if repeat != 3 {
+                        break;
+                    }
+                }
+            }
+        }
+        if remaining != 0 || distribution.len() > max_symbol + 1 {
+            return Err("corrupt FSE table distribution");
+        }
+        Fse::new(&distribution, log)
+    }
+
+    /// Table of a normalized `distribution` summing to `1 << log`, in which -1 stands
+    /// for a probability below one.
+    fn new(distribution: &[i16], log: u32) -> Result<Fse, &'static str> {
+        let size = 1 << log;
+        let mut symbols = vec![0u8; size];
+        let mut next = vec![0u16; distribution.len()];
+        // Below-one symbols take one state each, from the end
+        let mut high = size;
+        for (symbol, &probability) in distribution.iter().enumerate() {
+            if probability == -1 {
+                high -= 1;
+                symbols[high] = symbol as u8;
+                next[symbol] = 1;
+            }
+        }
+        let step = (size >> 1) + (size >> 3) + 3;
+        let mut position = 0;
+        for (symbol, &probability) in distribution.iter().enumerate() {
+            if probability <= 0 {
+                continue;
+            }
+            next[symbol] = probability as u16;
+            for _ in 0..probability {
+                symbols[position] = symbol as u8;
+                position = (position + step) & (size - 1);
+                while position >= high {
+                    position = (position + step) & (size - 1);
+                }
+            }
+        }
+        if position != 0 {
+            return Err("corrupt FSE table distribution");
+        }
+
+        let mut bits = vec![0u8; size];
+        let mut base = vec![0u16; size];
+        for state in 0..size {
+            let symbol = symbols[state] as usize;
+            let n = next[symbol];
+            next[symbol] += 1;
+            bits[state] = (log - (15 - n.leading_zeros())) as u8;
+            base[state] = ((n as u32) << bits[state]) as u16 - size as u16;
+        }
+        Ok(Fse {
+            log,
+            symbols,
+            bits,
+            base,
+        })
+    }
+
+    /// Table of a single symbol, which reads no bits.
+    fn rle(symbol: u8) -> Fse {
+        Fse {
+            log: 0,
+            symbols: vec![symbol],
+            bits: vec![0],
+            base: vec![0],
+        }
+    }
+
+    fn update(&self, state: usize, bits: &mut BackwardBits) -> usize {
+        self.base[state] as usize + bits.read(self.bits[state] as u32) as usize
+    }
+}
+
+/// Whole bytes of a stream or block.
+struct Input<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    /// Offset of `bytes` in the stream, for errors
+    base: usize,
+}
+
+impl<'a> Input<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len());
+        let Some(end) = end else {
+            return Err(error_at(
+                self.base + self.bytes.len(),
+                "unexpected end of stream",
+            ));
+        };
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn offset(&self) -> usize {
+        self.base + self.pos
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+/// Reads FSE table descriptions: least significant bit first, from the start.
+struct ForwardBits<'a> {
+    bytes: &'a [u8],
+    /// Next bit to read
+    pos: usize,
+}
+
+impl ForwardBits<'_> {
+    fn read(&mut self, count: u32) -> Result<u64, &'static str> {
+        if self.pos + count as usize > self.bytes.len() * 8 {
+            return Err("FSE table runs past its data");
+        }
+        let value = bits_at(self.bytes, self.pos, count);
+        self.pos += count as usize;
+        Ok(value)
+    }
+}
+
+/// Reads the bit streams of literals and sequences, which are written forwards and
+/// read from the end: the last byte's highest set bit marks where they stop.
+struct BackwardBits<'a> {
+    bytes: &'a [u8],
+    /// Bits left before the next read; negative once reads passed the start, which
+    /// read as zeros
+    offset: i64,
+}
+
+impl<'a> BackwardBits<'a> {
+    fn new(bytes: &'a [u8], at: usize) -> Result<BackwardBits<'a>, String> {
+        match bytes.last() {
+            Some(&last) if last != 0 => Ok(BackwardBits {
+                bytes,
+                offset: bytes.len() as i64 * 8 - last.leading_zeros() as i64 - 1,
+            }),
+            _ => Err(error_at(at + bytes.len(), "bit stream has no end marker")),
+        }
+    }
+
+    /// The `count` (at most 56) bits before the last read.
+    fn read(&mut self, count: u32) -> u64 {
+        if count == 0 {
+            return 0;
+        }
+        self.offset -= count as i64;
+        if self.offset >= 0 {
+            bits_at(self.bytes, self.offset as usize, count)
+        } else {
+            let available = count as i64 + self.offset;
+            if available <= 0 {
+                return 0;
+            }
+            bits_at(self.bytes, 0, available as u32) << -self.offset
+        }
+    }
+}
+
+/// `count` (at most 56) bits of `bytes` from bit `pos`, least significant first.
+fn bits_at(bytes: &[u8], pos: usize, count: u32) -> u64 {
+    let word = bytes[pos / 8..]
+        .iter()
+        .take(8)
+        .enumerate()
+        .fold(0u64, |word, (i, &byte)| word | (byte as u64) << (8 * i));
+    (word >> (pos % 8)) & ((1 << count) - 1)
+}
+
+/// Errs unless `len` more bytes fit in `out` under `limit`.
+fn reserve(out: &[u8], len: usize, limit: usize, at: usize) -> Result<(), String> {
+    if len > limit - out.len() {
+        return Err(error_at(at, &past_limit(limit)));
+    }
+    Ok(())
+}
+
+fn past_limit(limit: usize) -> String {
+    format!("output past the {}-byte limit", limit)
+}
+
+fn error_at(offset: usize, message: &str) -> String {
+    format!("corrupt zstd stream at byte {}: {}", offset, message)
+}
+
+const PRIME_1: u64 = 0x9e37_79b1_85eb_ca87;
+const PRIME_2: u64 = 0xc2b2_ae3d_27d4_eb4f;
+const PRIME_3: u64 = 0x1656_67b1_9e37_79f9;
+const PRIME_4: u64 = 0x85eb_ca77_c2b2_ae63;
+const PRIME_5: u64 = 0x27d4_eb2f_1656_67c5;
+
+/// XXH64 with seed 0, whose low 32 bits are a frame's content checksum.
+fn xxh64(data: &[u8]) -> u64 {
+    let round = |acc: u64, lane: u64| {
+        acc.wrapping_add(lane.wrapping_mul(PRIME_2))
+            .rotate_left(31)
+            .wrapping_mul(PRIME_1)
+    };
+    let lane = |bytes: &[u8]| u64::from_le_bytes(bytes.try_into().expect("8 bytes"));
+
+    let stripes = data.chunks_exact(32);
+    let tail = stripes.remainder();
+    let mut hash = if data.len() >= 32 {
+        let mut acc = [
+            PRIME_1.wrapping_add(PRIME_2),
+            PRIME_2,
+            0,
+            0u64.wrapping_sub(PRIME_1),
+        ];
+        for stripe in stripes {
+            for (i, acc) in acc.iter_mut().enumerate() {
+                *acc = round(*acc, lane(&stripe[8 * i..8 * i + 8]));
+            }
+        }
+        let mut hash = acc[0]
+            .rotate_left(1)
+            .wrapping_add(acc[1].rotate_left(7))
+            .wrapping_add(acc[2].rotate_left(12))
+            .wrapping_add(acc[3].rotate_left(18));
+        for acc in acc {
+            hash = (hash ^ round(0, acc))
+                .wrapping_mul(PRIME_1)
+                .wrapping_add(PRIME_4);
+        }
+        hash
+    } else {
+        PRIME_5
+    };
+    hash = hash.wrapping_add(data.len() as u64);
+
+    let mut words = tail.chunks_exact(8);
+    for word in &mut words {
+        hash = (hash ^ round(0, lane(word)))
+            .rotate_left(27)
+            .wrapping_mul(PRIME_1)
+            .wrapping_add(PRIME_4);
+    }
+    let mut rest = words.remainder();
+    if rest.len() >= 4 {
+        let word = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as u64;
+        hash = (hash ^ word.wrapping_mul(PRIME_1))
+            .rotate_left(23)
+            .wrapping_mul(PRIME_2)
+            .wrapping_add(PRIME_3);
+        rest = &rest[4..];
+    }
+    for &byte in rest {
+        hash = (hash ^ (byte as u64).wrapping_mul(PRIME_5))
+            .rotate_left(11)
+            .wrapping_mul(PRIME_1);
+    }
+
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(PRIME_2);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(PRIME_3);
+    hash ^ (hash >> 32)
+}
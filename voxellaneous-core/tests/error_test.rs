@@ -0,0 +1,81 @@
+//! Checks the `{ code, message, details }` objects `RendererError` becomes in JS.
+//! Run with `wasm-pack test --chrome --headless`.
+
+use voxellaneous_core::RendererError;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn get(object: &JsValue, key: &str) -> JsValue {
+    js_sys::Reflect::get(object, &key.into()).expect("property")
+}
+
+#[wasm_bindgen_test]
+fn validation_error_names_its_object_and_field() {
+    let error: JsValue =
+        RendererError::invalid_object("wall", Some("dims"), "object 'wall' has an empty dimension")
+            .into();
+    assert_eq!(
+        get(&error, "code").as_string().as_deref(),
+        Some("validation")
+    );
+    assert_eq!(
+        get(&error, "message").as_string().as_deref(),
+        Some("object 'wall' has an empty dimension")
+    );
+    let details = get(&error, "details");
+    assert_eq!(
+        get(&details, "object_id").as_string().as_deref(),
+        Some("wall")
+    );
+    assert_eq!(get(&details, "field").as_string().as_deref(), Some("dims"));
+
+    let error: JsValue = RendererError::invalid("duration", "duration must be finite").into();
+    assert!(get(&get(&error, "details"), "object_id").is_null());
+}
+
+#[wasm_bindgen_test]
+fn limit_error_reports_the_limit() {
+    let error: JsValue = RendererError::limit("clip_planes", 9u32, 8u32, "too many").into();
+    assert_eq!(
+        get(&error, "code").as_string().as_deref(),
+        Some("limit_exceeded")
+    );
+    let details = get(&error, "details");
+    assert_eq!(
+        get(&details, "limit").as_string().as_deref(),
+        Some("clip_planes")
+    );
+    assert_eq!(get(&details, "requested").as_f64(), Some(9.0));
+    assert_eq!(get(&details, "max").as_f64(), Some(8.0));
+}
+
+#[wasm_bindgen_test]
+fn other_errors_have_null_details() {
+    for (error, code) in [
+        (RendererError::DeviceLost, "device_lost"),
+        (RendererError::Surface("lost".into()), "surface"),
+        (RendererError::Destroyed("superseded".into()), "destroyed"),
+        (RendererError::Aborted("aborted".into()), "aborted"),
+    ] {
+        let error: JsValue = error.into();
+        assert_eq!(get(&error, "code").as_string().as_deref(), Some(code));
+        assert!(get(&error, "message")
+            .as_string()
+            .is_some_and(|m| !m.is_empty()));
+        assert!(get(&error, "details").is_null());
+    }
+}
+
+#[wasm_bindgen_test]
+fn js_error_keeps_the_thrown_value() {
+    let thrown: JsValue = js_sys::Error::new("callback failed").into();
+    let error: JsValue = RendererError::from(thrown.clone()).into();
+    assert_eq!(get(&error, "code").as_string().as_deref(), Some("js"));
+    assert_eq!(
+        get(&error, "message").as_string().as_deref(),
+        Some("callback failed")
+    );
+    assert!(get(&get(&error, "details"), "cause") == thrown);
+}
@@ -0,0 +1,1634 @@
+//! Renders a minimal scene in a browser and checks the presented pixels, covering
+//! upload, the G-buffer pass, lighting and present. Run with
+//! `wasm-pack test --chrome --headless`.
+
+use voxellaneous_core::{
+    BoundingShape, EdgeAaQuality, LightingModel, NormalSource, RenderPath, Renderer, RendererError,
+    VoxelWrapMode,
+};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const SIZE: u32 = 64;
+
+/// A 2×2×2 cube of palette entry 1, opaque red, with the default unit transform.
+const RED_CUBE_SCENE: &str = r#"{
+    "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+    "objects": [{ "id": "cube", "dims": [2, 2, 2], "voxels": [1, 1, 1, 1, 1, 1, 1, 1] }]
+}"#;
+
+/// A white wall through z = 0.5, wider than the view of `view_projection(3.0)`.
+const WALL_SCENE: &str = r#"{
+    "palette": [[0, 0, 0, 0], [255, 255, 255, 255]],
+    "objects": [{ "id": "wall", "dims": [1, 1, 1], "voxels": [1], "scale": [8, 8, 1] }]
+}"#;
+
+/// Column-major view-projection of a camera at (0, 0, `distance`) looking down -z,
+/// with a 60° vertical field of view and WebGPU's [0, 1] depth range.
+fn view_projection(distance: f32) -> [f32; 16] {
+    let (near, far) = (0.1, 100.0);
+    let f = 1.0 / 30f32.to_radians().tan();
+    let z = far / (near - far);
+    #[rustfmt::skip]
+    let vp = [
+        f,   0.0, 0.0,                          0.0,
+        0.0, f,   0.0,                          0.0,
+        0.0, 0.0, z,                            -1.0,
+        0.0, 0.0, -distance * z + near * z,     distance,
+    ];
+    vp
+}
+
+/// Offscreen canvas element: never attached to the document.
+fn create_canvas() -> web_sys::HtmlCanvasElement {
+    let canvas: web_sys::HtmlCanvasElement = web_sys::window()
+        .and_then(|window| window.document())
+        .expect("document")
+        .create_element("canvas")
+        .expect("canvas element")
+        .dyn_into()
+        .expect("HtmlCanvasElement");
+    canvas.set_width(SIZE);
+    canvas.set_height(SIZE);
+    canvas
+}
+
+/// RGBA8 pixels of the last presented frame.
+async fn capture(renderer: &mut Renderer) -> Vec<u8> {
+    let pixels = JsFuture::from(renderer.capture_frame())
+        .await
+        .expect("capture");
+    let pixels = js_sys::Uint8Array::new(&pixels).to_vec();
+    assert_eq!(pixels.len(), (SIZE * SIZE * 4) as usize);
+    pixels
+}
+
+fn red(pixels: &[u8], x: u32, y: u32) -> u8 {
+    pixels[((y * SIZE + x) * 4) as usize]
+}
+
+#[wasm_bindgen_test]
+async fn renders_red_cube() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+
+    // Full ambient: the lit target shows the albedo unchanged
+    renderer
+        .render(
+            &view_projection(3.0),
+            &[0.0, 0.0, 3.0],
+            4,
+            &[0.0, 0.0, 1.0],
+            1.0,
+            false,
+        )
+        .expect("render");
+    let pixels = capture(&mut renderer).await;
+
+    for (x, y) in [(SIZE / 2, SIZE / 2), (SIZE / 2 - 4, SIZE / 2 + 4)] {
+        let i = ((y * SIZE + x) * 4) as usize;
+        let [r, g, b] = [pixels[i], pixels[i + 1], pixels[i + 2]];
+        assert!(
+            r >= 250 && g <= 5 && b <= 5,
+            "pixel ({}, {}) is ({}, {}, {}), expected red",
+            x,
+            y,
+            r,
+            g,
+            b
+        );
+    }
+}
+
+#[wasm_bindgen_test]
+async fn short_camera_arguments_fail_validation() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let view = view_projection(3.0);
+    for (vp_matrix, view_position, expected) in [
+        (&view[..15], &[0.0, 0.0, 3.0][..], "vp_matrix"),
+        (&view[..], &[0.0, 0.0][..], "view_position"),
+    ] {
+        let error = renderer
+            .render(vp_matrix, view_position, 4, &[0.0, 0.0, 1.0], 1.0, false)
+            .unwrap_err();
+        let field = match error {
+            RendererError::Validation { field, .. } => field,
+            error => panic!("unexpected error: {:?}", error),
+        };
+        assert_eq!(field, Some(expected));
+    }
+}
+
+#[wasm_bindgen_test]
+async fn xr_viewports_wrapping_past_the_canvas_fail_validation() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let views = js_sys::JSON::parse(&format!(
+        r#"[{{ "vp_matrix": {:?}, "camera_position": [0, 0, 3], "viewport": [{}, 0, 2, 1] }}]"#,
+        view_projection(3.0),
+        u32::MAX
+    ))
+    .expect("views JSON");
+    let field = match renderer.render_xr(views).unwrap_err() {
+        RendererError::Validation { field, .. } => field,
+        error => panic!("unexpected error: {:?}", error),
+    };
+    assert_eq!(field, Some("viewport"));
+}
+
+/// A camera change the frame cap skips is drawn by the next due frame, not dropped.
+#[wasm_bindgen_test]
+async fn frames_skipped_by_the_cap_are_drawn_later() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    let render = |renderer: &mut Renderer, distance: f32| {
+        renderer
+            .render_if_needed(
+                &view_projection(distance),
+                &[0.0, 0.0, distance],
+                4,
+                &[0.0, 0.0, 1.0],
+                1.0,
+                false,
+            )
+            .expect("render")
+    };
+    renderer.set_max_fps(Some(1.0));
+    assert!(render(&mut renderer, 3.0), "the first frame is skipped");
+    assert!(
+        !render(&mut renderer, 4.0),
+        "the cap lets a second frame through"
+    );
+    assert!(
+        !render(&mut renderer, 4.0),
+        "the cap lets a third frame through"
+    );
+    renderer.set_max_fps(None);
+    assert!(
+        render(&mut renderer, 4.0),
+        "the skipped camera is never drawn"
+    );
+    assert!(
+        !render(&mut renderer, 4.0),
+        "an unchanged frame is drawn again"
+    );
+}
+
+/// A camera change made while paused is drawn by the first frame after resuming.
+#[wasm_bindgen_test]
+async fn frames_skipped_while_paused_are_drawn_on_resume() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    let render = |renderer: &mut Renderer, distance: f32| {
+        renderer
+            .render_if_needed(
+                &view_projection(distance),
+                &[0.0, 0.0, distance],
+                4,
+                &[0.0, 0.0, 1.0],
+                1.0,
+                false,
+            )
+            .expect("render")
+    };
+    assert!(render(&mut renderer, 3.0), "the first frame is skipped");
+    renderer.pause();
+    assert!(!render(&mut renderer, 4.0), "a paused renderer draws");
+    renderer.resume();
+    assert!(
+        render(&mut renderer, 4.0),
+        "the camera moved while paused is not drawn"
+    );
+    let near = capture(&mut renderer).await;
+    assert!(red(&near, SIZE / 2, SIZE / 2) > 0, "the cube is not drawn");
+}
+
+/// Ids follow the scene's objects and then the objects added after it, whatever the
+/// draw order, and a split sdf object still gets one id.
+#[wasm_bindgen_test]
+async fn object_ids_do_not_follow_draw_order() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(
+        r#"{
+            "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+            "objects": [
+                { "id": "last", "dims": [1, 1, 1], "voxels": [1], "order": 1 },
+                { "id": "first", "dims": [1, 1, 1], "voxels": [1] }
+            ]
+        }"#,
+    )
+    .expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    renderer.set_max_volume_size(4);
+    let sphere = |[x, y, z]: [f32; 3]| (x * x + y * y + z * z).sqrt() - 0.9;
+    assert_eq!(
+        renderer
+            .create_voxel_from_sdf([8, 4, 4], &sphere, 1)
+            .expect("sdf"),
+        2
+    );
+    assert_eq!(
+        renderer
+            .create_voxel_from_sdf([2, 2, 2], &sphere, 1)
+            .expect("sdf"),
+        3
+    );
+
+    let ids: Vec<String> = js_sys::Array::from(&renderer.get_scene_info())
+        .iter()
+        .map(|info| {
+            js_sys::Reflect::get(&info, &"id".into())
+                .expect("id")
+                .as_string()
+                .expect("string id")
+        })
+        .collect();
+    assert_eq!(ids, ["last", "first", "sdf_1", "sdf_2"]);
+}
+
+/// Oversized sdf grids fail on the limit before a single voxel is sampled.
+#[wasm_bindgen_test]
+async fn oversized_sdf_objects_hit_the_limit() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    renderer.set_max_volume_size(16);
+    for (dims, expected) in [
+        ([17, 1, 1], "max_volume_size"),
+        ([u32::MAX; 3], "max_volume_size"),
+    ] {
+        let error = renderer
+            .create_voxel_from_sdf(dims, &|_| panic!("sampled"), 1)
+            .unwrap_err();
+        let limit = match error {
+            RendererError::LimitExceeded { limit, .. } => limit,
+            error => panic!("unexpected error: {:?}", error),
+        };
+        assert_eq!(limit, expected);
+    }
+    renderer.set_max_volume_size(0);
+    let error = renderer
+        .create_voxel_from_sdf([2048, 2048, 2048], &|_| panic!("sampled"), 1)
+        .unwrap_err();
+    assert_eq!(error.code(), "limit_exceeded");
+}
+
+/// A point light just in front of the wall at the corner shared by four 16x16 light
+/// tiles: each tile has to keep it, so the four pixels around the corner are lit
+/// alike, while a pixel beyond its radius stays dark.
+#[wasm_bindgen_test]
+async fn point_light_reaches_all_tiles_at_a_corner() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(WALL_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+
+    // Pixel corner (16, 16) is at NDC (-0.5, 0.5): 2.5 along the view ray to the wall
+    let offset = 0.5 * 30f32.to_radians().tan() * 2.5;
+    let lights = format!(
+        r#"[{{ "position": [{}, {}, 0.6], "radius": 0.3, "color": [1, 1, 1] }}]"#,
+        -offset, offset
+    );
+    renderer
+        .set_point_lights(js_sys::JSON::parse(&lights).expect("lights JSON"))
+        .expect("point lights");
+
+    // No ambient, and the directional light behind the wall
+    renderer
+        .render(
+            &view_projection(3.0),
+            &[0.0, 0.0, 3.0],
+            4,
+            &[0.0, 0.0, -1.0],
+            0.0,
+            false,
+        )
+        .expect("render");
+    let pixels = capture(&mut renderer).await;
+
+    let corner = [(15, 15), (16, 15), (15, 16), (16, 16)].map(|(x, y)| red(&pixels, x, y));
+    let (min, max) = (*corner.iter().min().unwrap(), *corner.iter().max().unwrap());
+    assert!(
+        min >= 128,
+        "pixels around the tile corner are {:?}, expected lit",
+        corner
+    );
+    assert!(
+        max - min <= 16,
+        "pixels around the tile corner are {:?}, expected alike",
+        corner
+    );
+    assert_eq!(
+        red(&pixels, 48, 48),
+        0,
+        "pixel outside the light radius is lit"
+    );
+}
+
+/// White furnace: under the uniform white environment alone, a white PBR surface
+/// reflects at most the light it receives, and a dielectric exactly that (diffuse
+/// takes what specular leaves), at any roughness. With ambient 0.5 that is 188 in
+/// sRGB.
+#[wasm_bindgen_test]
+async fn pbr_white_furnace_conserves_energy() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(WALL_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    renderer.set_lighting_model(LightingModel::Pbr);
+
+    for metallic in [0.0, 1.0] {
+        for roughness in [0.0, 0.5, 1.0] {
+            renderer
+                .set_material(1, roughness, metallic)
+                .expect("material");
+            // The directional light behind the wall
+            renderer
+                .render(
+                    &view_projection(3.0),
+                    &[0.0, 0.0, 3.0],
+                    4,
+                    &[0.0, 0.0, -1.0],
+                    0.5,
+                    false,
+                )
+                .expect("render");
+            let pixels = capture(&mut renderer).await;
+            let value = red(&pixels, SIZE / 2, SIZE / 2);
+            assert!(
+                value <= 190,
+                "roughness {} metallic {} shades to {}, above the 188 it receives",
+                roughness,
+                metallic,
+                value
+            );
+            if metallic == 0.0 {
+                assert!(
+                    value >= 186,
+                    "dielectric of roughness {} shades to {}, expected 188",
+                    roughness,
+                    value
+                );
+            }
+        }
+    }
+}
+
+/// A white slab rotated about y and then stretched along z (model = scale * rotation),
+/// so its faces' normals are not the model matrix applied to the object-space normals.
+/// Lit from the direction of the front face's true normal, that face is fully lit; the
+/// plain model matrix or an untransformed normal would shade it at 0.77 or 0.64.
+#[wasm_bindgen_test]
+async fn non_uniform_scale_lights_faces_by_their_normals() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    // tan²θ = 0.21 and a scale ratio of 2.6 put the true normal 50° off the view axis
+    let theta = 0.21f32.sqrt().atan();
+    let (c, s) = (theta.cos(), theta.sin());
+    let (sx, sy, sz) = (4.0, 8.0, 10.4);
+    let scene = format!(
+        r#"{{
+            "palette": [[0, 0, 0, 0], [255, 255, 255, 255]],
+            "objects": [{{ "id": "slab", "dims": [1, 1, 1], "voxels": [1],
+                "model_matrix": [{}, 0, {}, 0, 0, {}, 0, 0, {}, 0, {}, 0, 0, 0, 0, 1] }}]
+        }}"#,
+        sx * c,
+        -sz * s,
+        sy,
+        sx * s,
+        sz * c
+    );
+    renderer
+        .upload_scene(js_sys::JSON::parse(&scene).expect("scene JSON"))
+        .expect("upload");
+
+    // Inverse transpose of the model matrix applied to the front face's +z normal
+    let normal = [s / sx, 0.0, c / sz];
+    let len = (normal[0] * normal[0] + normal[2] * normal[2]).sqrt();
+    let light = [normal[0] / len, 0.0, normal[2] / len];
+    renderer
+        .render(
+            &view_projection(20.0),
+            &[0.0, 0.0, 20.0],
+            4,
+            &light,
+            0.0,
+            false,
+        )
+        .expect("render");
+    let pixels = capture(&mut renderer).await;
+
+    for (x, y) in [(SIZE / 2, SIZE / 2), (SIZE / 2 + 4, SIZE / 2 - 4)] {
+        let r = red(&pixels, x, y);
+        assert!(
+            r >= 245,
+            "pixel ({}, {}) has red {}, expected fully lit",
+            x,
+            y,
+            r
+        );
+    }
+}
+
+/// A palette cube and an rgba cube side by side: each draws its own color, through the
+/// pipeline of its color mode. The rgba grid's upper voxel has alpha 0 and is empty.
+#[wasm_bindgen_test]
+async fn draws_palette_and_rgba_objects_together() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = r#"{
+        "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+        "objects": [
+            { "id": "palette", "dims": [1, 1, 1], "voxels": [1], "translation": [-0.75, 0, 0] },
+            { "id": "rgba", "dims": [1, 2, 1], "color_mode": "rgba",
+              "voxels": [0, 255, 0, 255, 0, 0, 255, 0], "translation": [0.75, 0, 0] }
+        ]
+    }"#;
+    renderer
+        .upload_scene(js_sys::JSON::parse(scene).expect("scene JSON"))
+        .expect("upload");
+
+    renderer
+        .render(
+            &view_projection(3.0),
+            &[0.0, 0.0, 3.0],
+            4,
+            &[0.0, 0.0, 1.0],
+            1.0,
+            false,
+        )
+        .expect("render");
+    let pixels = capture(&mut renderer).await;
+
+    let rgb = |x: u32, y: u32| {
+        let i = ((y * SIZE + x) * 4) as usize;
+        [pixels[i], pixels[i + 1], pixels[i + 2]]
+    };
+    // The cubes are centred 14 pixels either side of the middle
+    let [r, g, b] = rgb(18, 30);
+    assert!(
+        r >= 250 && g <= 5 && b <= 5,
+        "palette cube is ({}, {}, {})",
+        r,
+        g,
+        b
+    );
+    // The lower voxel fills y in [-0.5, 0], below the middle of the image
+    let [r, g, b] = rgb(46, 36);
+    assert!(
+        r <= 5 && g >= 250 && b <= 5,
+        "rgba voxel is ({}, {}, {})",
+        r,
+        g,
+        b
+    );
+    let [r, g, b] = rgb(46, 28);
+    assert!(
+        r <= 5 && g <= 5 && b <= 5,
+        "empty rgba voxel is ({}, {}, {})",
+        r,
+        g,
+        b
+    );
+}
+
+#[wasm_bindgen_test]
+async fn renders_video_frames_at_their_timestamp() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+
+    let promise = renderer.render_video_frame(
+        &view_projection(3.0),
+        &[0.0, 0.0, 3.0],
+        4,
+        &[0.0, 0.0, 1.0],
+        1.0,
+        false,
+        40_000.0,
+    );
+    let frame: web_sys::VideoFrame = JsFuture::from(promise)
+        .await
+        .expect("video frame")
+        .dyn_into()
+        .expect("VideoFrame");
+    assert_eq!((frame.coded_width(), frame.coded_height()), (SIZE, SIZE));
+    assert_eq!(frame.timestamp(), 40_000.0);
+    frame.close();
+}
+
+/// Tints set inside an update batch reach the GPU once, as the latest of them.
+#[wasm_bindgen_test]
+async fn update_batch_keeps_the_latest_write() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+
+    renderer.begin_update_batch();
+    renderer
+        .set_object_tint(0, &[0.0, 0.0, 0.0, 1.0])
+        .expect("tint");
+    renderer
+        .set_object_tint(0, &[1.0, 1.0, 1.0, 1.0])
+        .expect("tint");
+    assert_eq!(renderer.end_update_batch(), 1);
+    assert_eq!(renderer.end_update_batch(), 0);
+
+    renderer
+        .render(
+            &view_projection(3.0),
+            &[0.0, 0.0, 3.0],
+            4,
+            &[0.0, 0.0, 1.0],
+            1.0,
+            false,
+        )
+        .expect("render");
+    let pixels = capture(&mut renderer).await;
+    assert!(
+        red(&pixels, SIZE / 2, SIZE / 2) >= 250,
+        "the black tint was drawn"
+    );
+}
+
+/// A child placed at the origin of its parent's space draws where the parent is; a
+/// cycle of parents fails the upload.
+#[wasm_bindgen_test]
+async fn children_follow_their_parent() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = r#"{
+        "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+        "objects": [
+            { "id": "child", "dims": [1, 1, 1], "voxels": [1], "parent": "base" },
+            { "id": "base", "dims": [1, 1, 1], "voxels": [0], "translation": [-0.75, 0, 0] }
+        ]
+    }"#;
+    renderer
+        .upload_scene(js_sys::JSON::parse(scene).expect("scene JSON"))
+        .expect("upload");
+    renderer
+        .render(
+            &view_projection(3.0),
+            &[0.0, 0.0, 3.0],
+            4,
+            &[0.0, 0.0, 1.0],
+            1.0,
+            false,
+        )
+        .expect("render");
+    let pixels = capture(&mut renderer).await;
+    assert!(
+        red(&pixels, 18, 30) >= 250,
+        "the child is not at its parent"
+    );
+    assert!(
+        red(&pixels, SIZE / 2, SIZE / 2) <= 5,
+        "the child is at the origin"
+    );
+
+    let cycle = r#"{
+        "palette": [[0, 0, 0, 0]],
+        "objects": [
+            { "id": "a", "dims": [1, 1, 1], "voxels": [0], "parent": "b" },
+            { "id": "b", "dims": [1, 1, 1], "voxels": [0], "parent": "a" }
+        ]
+    }"#;
+    let cycle = js_sys::JSON::parse(cycle).expect("scene JSON");
+    assert!(renderer.upload_scene(cycle).is_err());
+}
+
+/// A 16³ red sphere (as a JSON scene), scaled to fill most of the view.
+fn sphere_scene() -> String {
+    let voxels: Vec<String> = (0..16 * 16 * 16)
+        .map(|i: i32| {
+            let [x, y, z] = [i % 16, i / 16 % 16, i / 256].map(|c| c as f32 - 7.5);
+            let filled = x * x + y * y + z * z <= 7.5 * 7.5;
+            (filled as u8).to_string()
+        })
+        .collect();
+    format!(
+        r#"{{
+            "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+            "objects": [{{ "id": "sphere", "dims": [16, 16, 16], "scale": [1.5, 1.5, 1.5],
+                           "voxels": [{}] }}]
+        }}"#,
+        voxels.join(",")
+    )
+}
+
+/// Split down the middle on every axis, the sphere draws exactly as in one piece:
+/// no cracks at the cuts, and no boundary voxel drawn twice.
+#[wasm_bindgen_test]
+async fn split_volumes_draw_like_the_whole() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let draw = |renderer: &mut Renderer| {
+        let scene = js_sys::JSON::parse(&sphere_scene()).expect("scene JSON");
+        renderer.upload_scene(scene).expect("upload");
+        renderer
+            .render(
+                &view_projection(3.0),
+                &[0.0, 0.0, 3.0],
+                4,
+                &[0.0, 0.0, 1.0],
+                0.5,
+                false,
+            )
+            .expect("render");
+    };
+    draw(&mut renderer);
+    let whole = capture(&mut renderer).await;
+    renderer.set_max_volume_size(8);
+    draw(&mut renderer);
+    let split = capture(&mut renderer).await;
+
+    assert!(
+        red(&whole, SIZE / 2, SIZE / 2) > 0,
+        "the sphere is not drawn"
+    );
+    let differing = whole
+        .chunks_exact(4)
+        .zip(split.chunks_exact(4))
+        .filter(|(a, b)| a.iter().zip(b.iter()).any(|(&a, &b)| a.abs_diff(b) > 2))
+        .count();
+    assert_eq!(differing, 0, "{} pixels differ once split", differing);
+}
+
+/// Pixels of `a` and `b` that differ by more than 2 in any channel.
+fn differing_pixels(a: &[u8], b: &[u8]) -> usize {
+    a.chunks_exact(4)
+        .zip(b.chunks_exact(4))
+        .filter(|(a, b)| a.iter().zip(b.iter()).any(|(&a, &b)| a.abs_diff(b) > 2))
+        .count()
+}
+
+/// The uploaded scene shown by `target` on `path`.
+async fn draw_by(renderer: &mut Renderer, path: RenderPath, target: usize) -> Vec<u8> {
+    renderer.set_render_path(path);
+    renderer
+        .render(
+            &view_projection(3.0),
+            &[0.0, 0.0, 3.0],
+            target,
+            &[0.0, 0.0, 1.0],
+            0.5,
+            false,
+        )
+        .expect("render");
+    capture(renderer).await
+}
+
+/// The compute raymarch writes the G-buffer the raster path does, and a frame it
+/// cannot draw, here for an object scissor, falls back to raster.
+#[wasm_bindgen_test]
+async fn compute_gbuffer_matches_raster() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(&sphere_scene()).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    // Albedo, normal, linear depth and lit; silhouette pixels may round either way
+    for target in [0, 1, 2, 4] {
+        let raster = draw_by(&mut renderer, RenderPath::Raster, target).await;
+        let compute = draw_by(&mut renderer, RenderPath::Compute, target).await;
+        assert!(!blank(&raster), "target {} is blank", target);
+        let differing = differing_pixels(&raster, &compute);
+        assert!(
+            differing <= 40,
+            "{} pixels of target {} differ on the compute path",
+            differing,
+            target
+        );
+    }
+
+    renderer
+        .set_object_scissor(0, Some(vec![0, 0, SIZE / 2, SIZE]))
+        .expect("scissor");
+    let raster = draw_by(&mut renderer, RenderPath::Raster, 4).await;
+    let compute = draw_by(&mut renderer, RenderPath::Compute, 4).await;
+    assert_eq!(
+        red(&compute, SIZE / 2 + 4, SIZE / 2),
+        0,
+        "the scissor is ignored"
+    );
+    assert_eq!(differing_pixels(&raster, &compute), 0);
+}
+
+/// The sphere tinted, moved by a new pivot and, with `slice`, cut down to layer 11.
+async fn draw_edited_sphere(renderer: &mut Renderer, slice: bool) -> Vec<u8> {
+    let scene = js_sys::JSON::parse(&sphere_scene()).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    renderer
+        .set_object_tint(0, &[0.5, 1.0, 1.0, 1.0])
+        .expect("tint");
+    renderer
+        .set_object_pivot(0, &[4.0, 4.0, 8.0])
+        .expect("pivot");
+    renderer.set_slice_view(0, 0, 11, slice).expect("slice");
+    renderer
+        .render(
+            &view_projection(3.0),
+            &[0.0, 0.0, 3.0],
+            4,
+            &[0.0, 0.0, 1.0],
+            0.5,
+            false,
+        )
+        .expect("render");
+    capture(renderer).await
+}
+
+/// A split sphere stays one object: tinting it, moving its pivot and slicing it
+/// changes every piece alike, so it draws as the whole sphere edited the same way.
+#[wasm_bindgen_test]
+async fn split_volumes_edit_like_the_whole() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let whole = [
+        draw_edited_sphere(&mut renderer, false).await,
+        draw_edited_sphere(&mut renderer, true).await,
+    ];
+    renderer.set_max_volume_size(8);
+    let split = [
+        draw_edited_sphere(&mut renderer, false).await,
+        draw_edited_sphere(&mut renderer, true).await,
+    ];
+
+    let objects = js_sys::Array::from(&renderer.get_scene_info());
+    assert_eq!(objects.length(), 1, "the pieces are separate objects");
+    for (whole, split) in whole.iter().zip(&split) {
+        assert!(
+            whole.chunks_exact(4).any(|pixel| pixel[0] > 0),
+            "the sphere is not drawn"
+        );
+        let differing = whole
+            .chunks_exact(4)
+            .zip(split.chunks_exact(4))
+            .filter(|(a, b)| a.iter().zip(b.iter()).any(|(&a, &b)| a.abs_diff(b) > 2))
+            .count();
+        assert_eq!(differing, 0, "{} pixels differ once split", differing);
+    }
+}
+
+/// Through the icosphere the red cube keeps its centre but loses the corners of its
+/// front face, which lie outside the inscribed sphere.
+#[wasm_bindgen_test]
+async fn icosphere_bounds_skip_the_box_corners() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    let draw = |renderer: &mut Renderer| {
+        renderer
+            .render(
+                &view_projection(3.0),
+                &[0.0, 0.0, 3.0],
+                4,
+                &[0.0, 0.0, 1.0],
+                1.0,
+                false,
+            )
+            .expect("render");
+    };
+    // Inside the silhouette of the front face, near its corner
+    let corner = SIZE / 2 + 10;
+    draw(&mut renderer);
+    let cube = capture(&mut renderer).await;
+    renderer
+        .set_object_bounding_shape(0, BoundingShape::Icosphere)
+        .expect("bounding shape");
+    draw(&mut renderer);
+    let icosphere = capture(&mut renderer).await;
+
+    assert!(
+        red(&cube, corner, corner) >= 250,
+        "the cube corner is not drawn"
+    );
+    assert!(
+        red(&icosphere, SIZE / 2, SIZE / 2) >= 250,
+        "the centre is not drawn"
+    );
+    assert_eq!(red(&icosphere, corner, corner), 0, "the corner is drawn");
+}
+
+/// A debug box is drawn over the frame after the call only. The box is flat, its
+/// edges a line through the centre of pixel row `SIZE / 2` in front of the red cube.
+#[wasm_bindgen_test]
+async fn debug_boxes_last_one_frame() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    let green = |pixels: &[u8]| pixels[((SIZE / 2 * SIZE + SIZE / 2) * 4 + 1) as usize];
+    let draw = |renderer: &mut Renderer| {
+        renderer
+            .render(
+                &view_projection(3.0),
+                &[0.0, 0.0, 3.0],
+                4,
+                &[0.0, 0.0, 1.0],
+                1.0,
+                false,
+            )
+            .expect("render");
+    };
+    // Centre of the row, 2.4 in front of the camera
+    let y = -0.0216;
+    renderer
+        .draw_debug_box(&[-0.5, y, 0.6], &[0.5, y, 0.6], &[0.0, 1.0, 0.0, 1.0])
+        .expect("debug box");
+    draw(&mut renderer);
+    let with_box = capture(&mut renderer).await;
+    draw(&mut renderer);
+    let after = capture(&mut renderer).await;
+
+    assert!(green(&with_box) >= 250, "the box is not drawn");
+    assert!(green(&after) <= 5, "the box outlives its frame");
+    assert!(
+        renderer
+            .draw_debug_box(&[1.0, 0.0, 0.0], &[0.0, 0.0, 0.0], &[1.0; 4])
+            .is_err(),
+        "an inverted box is accepted"
+    );
+}
+
+/// The red cube scaled by 2 with a stale identity inverse: the renderer inverts the
+/// model matrix itself, so the cube is drawn at its scaled size, and a singular
+/// matrix is rejected.
+#[wasm_bindgen_test]
+async fn model_matrices_are_inverted_by_the_renderer() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = r#"{
+        "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+        "objects": [{ "id": "cube", "dims": [2, 2, 2], "voxels": [1, 1, 1, 1, 1, 1, 1, 1],
+                      "model_matrix": [2, 0, 0, 0, 0, 2, 0, 0, 0, 0, 2, 0, 0, 0, 0, 1],
+                      "inv_model_matrix": [1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1] }]
+    }"#;
+    renderer
+        .upload_scene(js_sys::JSON::parse(scene).expect("scene JSON"))
+        .expect("upload");
+    renderer
+        .render(
+            &view_projection(3.0),
+            &[0.0, 0.0, 3.0],
+            4,
+            &[0.0, 0.0, 1.0],
+            1.0,
+            false,
+        )
+        .expect("render");
+    let pixels = capture(&mut renderer).await;
+    // Outside the silhouette of the unscaled cube, inside that of the scaled one
+    for x in [SIZE / 2, SIZE / 2 + 18] {
+        assert!(
+            red(&pixels, x, SIZE / 2) >= 250,
+            "pixel ({}, {}) is not red",
+            x,
+            SIZE / 2
+        );
+    }
+
+    let singular = scene.replace("[2, 0, 0, 0, 0, 2", "[0, 0, 0, 0, 0, 2");
+    let singular = js_sys::JSON::parse(&singular).expect("scene JSON");
+    assert!(renderer.upload_scene(singular).is_err());
+}
+
+/// Scissored to the left half of the target, the red cube is cut down its middle;
+/// without a scissor it is whole again.
+#[wasm_bindgen_test]
+async fn object_scissor_cuts_the_draw() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    let draw = |renderer: &mut Renderer| {
+        renderer
+            .render(
+                &view_projection(3.0),
+                &[0.0, 0.0, 3.0],
+                4,
+                &[0.0, 0.0, 1.0],
+                1.0,
+                false,
+            )
+            .expect("render");
+    };
+    let (left, right) = (SIZE / 2 - 4, SIZE / 2 + 4);
+    renderer
+        .set_object_scissor(0, Some(vec![0, 0, SIZE / 2, SIZE]))
+        .expect("scissor");
+    draw(&mut renderer);
+    let cut = capture(&mut renderer).await;
+    renderer.set_object_scissor(0, None).expect("scissor");
+    draw(&mut renderer);
+    let whole = capture(&mut renderer).await;
+
+    assert!(
+        red(&cut, left, SIZE / 2) >= 250,
+        "the left half is not drawn"
+    );
+    assert_eq!(red(&cut, right, SIZE / 2), 0, "the right half is drawn");
+    assert!(
+        red(&whole, right, SIZE / 2) >= 250,
+        "the scissor outlives its reset"
+    );
+    assert!(renderer.set_object_scissor(0, Some(vec![0, 0, 4])).is_err());
+}
+
+/// Under full ambient the red cube with neighbour AO baked (each voxel sees 7 of its
+/// 26 neighbours filled) is darkened to 19/26 only once the lighting reads the AO.
+/// Lit head-on instead, the front face shades alike with geometric normals.
+#[wasm_bindgen_test]
+async fn lighting_inputs_choose_the_normal_and_ao() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    renderer.bake_ao_cpu(0, 1).expect("AO bake");
+    let draw = |renderer: &mut Renderer, ambient: f32| {
+        renderer
+            .render(
+                &view_projection(3.0),
+                &[0.0, 0.0, 3.0],
+                4,
+                &[0.0, 0.0, 1.0],
+                ambient,
+                false,
+            )
+            .expect("render");
+    };
+    draw(&mut renderer, 1.0);
+    let without_ao = capture(&mut renderer).await;
+    renderer.set_lighting_inputs(NormalSource::Stored, true);
+    draw(&mut renderer, 1.0);
+    let with_ao = capture(&mut renderer).await;
+    renderer.set_lighting_inputs(NormalSource::Geometric, false);
+    draw(&mut renderer, 0.0);
+    let geometric = capture(&mut renderer).await;
+
+    assert!(
+        red(&without_ao, SIZE / 2, SIZE / 2) >= 250,
+        "AO is read by default"
+    );
+    let occluded = red(&with_ao, SIZE / 2, SIZE / 2);
+    assert!(
+        (180..=192).contains(&occluded),
+        "occluded red is {}",
+        occluded
+    );
+    assert!(
+        red(&geometric, SIZE / 2, SIZE / 2) >= 245,
+        "the face is not lit head-on"
+    );
+}
+
+/// Object 0's baked AO, one byte per voxel.
+async fn read_ao(renderer: &Renderer) -> Vec<u8> {
+    let ao = JsFuture::from(renderer.read_ao(0))
+        .await
+        .expect("AO readback");
+    js_sys::Uint8Array::new(&ao).to_vec()
+}
+
+/// The GPU and CPU neighbour AO bakes agree to within 1 of 255, on the sphere and on
+/// noise whose dims are not multiples of the 4³ workgroup, up to the largest radius.
+#[wasm_bindgen_test]
+async fn gpu_and_cpu_ao_bakes_agree() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let noise: Vec<String> = (0..7 * 5 * 9u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 31).to_string())
+        .collect();
+    let noise = format!(
+        r#"{{
+            "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+            "objects": [{{ "id": "noise", "dims": [7, 5, 9], "voxels": [{}] }}]
+        }}"#,
+        noise.join(",")
+    );
+    for scene in [sphere_scene(), noise] {
+        let scene = js_sys::JSON::parse(&scene).expect("scene JSON");
+        renderer.upload_scene(scene).expect("upload");
+        for radius in [1, 3, 6] {
+            JsFuture::from(renderer.bake_ao(0, radius))
+                .await
+                .expect("GPU bake");
+            let gpu = read_ao(&renderer).await;
+            renderer.bake_ao_cpu(0, radius).expect("CPU bake");
+            let cpu = read_ao(&renderer).await;
+            assert_eq!(gpu.len(), cpu.len());
+            let worst = gpu.iter().zip(&cpu).map(|(g, c)| g.abs_diff(*c)).max();
+            assert!(
+                worst <= Some(1),
+                "radius {}: the bakes differ by {:?}",
+                radius,
+                worst
+            );
+        }
+    }
+}
+
+/// Whether every pixel is (nearly) black, the cleared background.
+fn blank(pixels: &[u8]) -> bool {
+    pixels
+        .chunks_exact(4)
+        .all(|pixel| pixel[..3].iter().all(|&c| c <= 5))
+}
+
+fn draw_head_on(renderer: &mut Renderer) {
+    renderer
+        .render(
+            &view_projection(3.0),
+            &[0.0, 0.0, 3.0],
+            4,
+            &[0.0, 0.0, 1.0],
+            1.0,
+            false,
+        )
+        .expect("render");
+}
+
+/// Two columns four voxels deep: the left one filled only at the back, the right one
+/// only at the front, so rays through the left column cross three empty voxels.
+const DEEP_VOXEL_SCENE: &str = r#"{
+    "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+    "objects": [{ "id": "deep", "dims": [2, 1, 4], "voxels": [1, 0, 0, 0, 0, 0, 0, 1] }]
+}"#;
+
+#[wasm_bindgen_test]
+async fn raymarch_reaches_voxels_behind_empty_ones() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(DEEP_VOXEL_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    draw_head_on(&mut renderer);
+    let pixels = capture(&mut renderer).await;
+    assert!(red(&pixels, 38, 32) > 200, "the front voxel is missing");
+    assert!(red(&pixels, 26, 32) > 200, "the back voxel is missing");
+}
+
+/// Total red of a single red voxel turned `degrees` about the view axis.
+async fn turned_voxel_red(renderer: &mut Renderer, degrees: f32) -> u32 {
+    let (sin, cos) = (degrees.to_radians() * 0.5).sin_cos();
+    let scene = format!(
+        r#"{{
+            "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+            "objects": [{{ "id": "voxel", "dims": [1, 1, 1], "voxels": [1],
+                           "rotation_quat": [0, 0, {}, {}] }}]
+        }}"#,
+        sin, cos
+    );
+    let scene = js_sys::JSON::parse(&scene).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    draw_head_on(renderer);
+    let pixels = capture(renderer).await;
+    pixels.chunks_exact(4).map(|pixel| pixel[0] as u32).sum()
+}
+
+/// A slowly turning voxel keeps the area its face covers, so with edge antialiasing
+/// its total brightness holds steady from frame to frame, where hard edges crawl as
+/// whole pixels enter and leave the silhouette.
+#[wasm_bindgen_test]
+async fn edge_antialiasing_steadies_a_turning_voxel() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let mut spreads = Vec::new();
+    for enabled in [false, true] {
+        renderer.set_edge_antialiasing(enabled, EdgeAaQuality::Low);
+        let mut totals = Vec::new();
+        for step in 0..12 {
+            totals.push(turned_voxel_red(&mut renderer, 3.0 + step as f32 * 1.5).await);
+        }
+        let (min, max) = (*totals.iter().min().unwrap(), *totals.iter().max().unwrap());
+        assert!(min > 0, "the voxel is not drawn");
+        spreads.push((max - min) as f32 / max as f32);
+    }
+    let [hard, smooth] = [spreads[0], spreads[1]];
+    assert!(
+        smooth < 0.01,
+        "the antialiased voxel's brightness varies by {}",
+        smooth
+    );
+    assert!(
+        smooth < hard,
+        "antialiasing does not steady the edges: {} against {} without",
+        smooth,
+        hard
+    );
+}
+
+/// A column of one red voxel and one empty one, so it tiles into stripes.
+const STRIPE_SCENE: &str = r#"{
+    "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+    "objects": [{ "id": "stripe", "dims": [2, 1, 1], "voxels": [1, 0] }]
+}"#;
+
+#[wasm_bindgen_test]
+async fn repeat_wrap_tiles_the_volume_behind_its_box() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(STRIPE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    draw_head_on(&mut renderer);
+    let clamped = capture(&mut renderer).await;
+    assert!(red(&clamped, 26, 32) > 200, "the filled voxel is missing");
+    // The empty half, where rays drifting right reach the next copy's filled voxel
+    assert_eq!(
+        red(&clamped, 38, 32),
+        0,
+        "the empty voxel draws under Clamp"
+    );
+
+    renderer.set_voxel_wrap(VoxelWrapMode::Repeat);
+    draw_head_on(&mut renderer);
+    let repeated = capture(&mut renderer).await;
+    assert!(red(&repeated, 26, 32) > 200, "the filled voxel is missing");
+    assert!(
+        red(&repeated, 38, 32) > 200,
+        "the next copy does not show through the empty voxel"
+    );
+}
+
+/// A cube of index 0 in front of the camera; palette entry 0 is opaque blue, so it
+/// shows once `set_empty_index` makes another index empty.
+const ZERO_CUBE_SCENE: &str = r#"{
+    "palette": [[0, 0, 255, 255], [255, 0, 0, 255]],
+    "objects": [{ "id": "cube", "dims": [2, 2, 2], "voxels": [0, 0, 0, 0, 0, 0, 0, 0] }]
+}"#;
+
+#[wasm_bindgen_test]
+async fn all_empty_volumes_draw_nothing() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(ZERO_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    draw_head_on(&mut renderer);
+    assert!(
+        blank(&capture(&mut renderer).await),
+        "the empty volume draws"
+    );
+}
+
+/// Two overlapping cubes of index 0 collide only once another index is the empty one.
+#[wasm_bindgen_test]
+async fn collisions_use_the_empty_index() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = r#"{
+        "palette": [[0, 0, 255, 255], [255, 0, 0, 255]],
+        "objects": [
+            { "id": "cube", "dims": [2, 2, 2], "voxels": [0, 0, 0, 0, 0, 0, 0, 0] },
+            { "id": "moved", "dims": [2, 2, 2], "voxels": [0, 0, 0, 0, 0, 0, 0, 0],
+              "translation": [0.5, 0, 0] }
+        ]
+    }"#;
+    let scene = js_sys::JSON::parse(scene).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    let pairs = |renderer: &mut Renderer| {
+        serde_wasm_bindgen::from_value::<Vec<(u32, u32)>>(renderer.find_collisions())
+            .expect("pairs")
+    };
+    assert_eq!(pairs(&mut renderer), []);
+    renderer.set_empty_index(1).expect("empty index");
+    assert_eq!(pairs(&mut renderer), [(0, 1)]);
+}
+
+/// With index 1 empty, the cube of index 0 draws, bakes AO as a solid cube (each
+/// voxel sees 7 of its 26 neighbours filled) and erodes away entirely.
+#[wasm_bindgen_test]
+async fn empty_index_decides_what_draws() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(ZERO_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    renderer.set_empty_index(1).expect("empty index");
+    draw_head_on(&mut renderer);
+    let pixels = capture(&mut renderer).await;
+    let centre = ((SIZE / 2 * SIZE + SIZE / 2) * 4) as usize;
+    assert!(
+        pixels[centre] <= 5 && pixels[centre + 2] >= 250,
+        "index 0 is still empty"
+    );
+
+    renderer.bake_ao_cpu(0, 1).expect("CPU bake");
+    assert_eq!(read_ao(&renderer).await, [186; 8]);
+    JsFuture::from(renderer.bake_ao(0, 1))
+        .await
+        .expect("GPU bake");
+    let ao = read_ao(&renderer).await;
+    assert!(
+        ao.iter().all(|&ao| ao.abs_diff(186) <= 1),
+        "GPU AO {:?}",
+        ao
+    );
+
+    JsFuture::from(renderer.erode_object(0))
+        .await
+        .expect("erode");
+    draw_head_on(&mut renderer);
+    assert!(
+        blank(&capture(&mut renderer).await),
+        "erosion kept the cube"
+    );
+}
+
+/// Unloading the active slot leaves an empty scene, and the scene uploaded next
+/// outside any slot draws with its own palette.
+#[wasm_bindgen_test]
+async fn unloading_the_active_slot_empties_the_scene() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.load_scene_slot("level", scene).expect("load");
+    renderer.activate_scene("level").expect("activate");
+    draw_head_on(&mut renderer);
+    let pixels = capture(&mut renderer).await;
+    assert!(
+        red(&pixels, SIZE / 2, SIZE / 2) >= 250,
+        "the slot is not drawn"
+    );
+
+    renderer.unload_scene_slot("level").expect("unload");
+    draw_head_on(&mut renderer);
+    assert!(
+        blank(&capture(&mut renderer).await),
+        "the unloaded slot is drawn"
+    );
+    assert!(renderer.activate_scene("level").is_err());
+
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    draw_head_on(&mut renderer);
+    let pixels = capture(&mut renderer).await;
+    assert!(
+        red(&pixels, SIZE / 2, SIZE / 2) >= 250,
+        "the scene uploaded after unloading is not drawn"
+    );
+}
+
+/// Flying into a solid red cube of side 4: from outside, with the front face just
+/// closer than the near plane, and from inside, the view stays red.
+#[wasm_bindgen_test]
+async fn camera_inside_a_volume_still_sees_it() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = RED_CUBE_SCENE.replace("] }]", "], \"scale\": [4, 4, 4] }]");
+    renderer
+        .upload_scene(js_sys::JSON::parse(&scene).expect("scene JSON"))
+        .expect("upload");
+    // The front face is at z = 2 and the near plane 0.1 in front of the camera
+    for distance in [3.0, 2.05, 1.0] {
+        let camera = [0.0, 0.0, distance];
+        renderer
+            .render(
+                &view_projection(distance),
+                &camera,
+                4,
+                &[0.0, 0.0, 1.0],
+                1.0,
+                false,
+            )
+            .expect("render");
+        let pixels = capture(&mut renderer).await;
+        for (x, y) in [(SIZE / 2, SIZE / 2), (4, 4)] {
+            assert!(
+                red(&pixels, x, y) >= 250,
+                "({}, {}) is not red at z = {}",
+                x,
+                y,
+                distance
+            );
+        }
+    }
+}
+
+/// At half the render scale the cube still covers the middle of the full-size frame,
+/// and dynamic resolution starts within its bounds.
+#[wasm_bindgen_test]
+async fn render_scale_stretches_over_the_canvas() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    renderer.set_render_scale(0.5).expect("render scale");
+    renderer
+        .render(
+            &view_projection(3.0),
+            &[0.0, 0.0, 3.0],
+            4,
+            &[0.0, 0.0, 1.0],
+            1.0,
+            false,
+        )
+        .expect("render");
+    let pixels = capture(&mut renderer).await;
+
+    assert!(
+        red(&pixels, SIZE / 2, SIZE / 2) >= 250,
+        "the cube is not drawn"
+    );
+    assert!(
+        red(&pixels, SIZE / 2 - 8, SIZE / 2 + 8) >= 250,
+        "the cube is not stretched"
+    );
+    assert_eq!(red(&pixels, 4, 4), 0, "the background is drawn over");
+    assert!(renderer.set_render_scale(0.0).is_err());
+    assert!(renderer.set_render_scale(1.5).is_err());
+    assert!(renderer.enable_dynamic_resolution(60.0, 0.5, 0.25).is_err());
+    renderer
+        .enable_dynamic_resolution(60.0, 0.6, 0.8)
+        .expect("dynamic resolution");
+    assert_eq!(renderer.get_current_render_scale(), 0.6);
+}
+
+/// Highlighting the left half of the cube in blue tints only the pixels inside it,
+/// until the region is cleared.
+#[wasm_bindgen_test]
+async fn highlight_region_tints_what_it_contains() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    let draw = |renderer: &mut Renderer| {
+        renderer
+            .render(
+                &view_projection(3.0),
+                &[0.0, 0.0, 3.0],
+                4,
+                &[0.0, 0.0, 1.0],
+                1.0,
+                false,
+            )
+            .expect("render");
+    };
+    let blue = |pixels: &[u8], x: u32, y: u32| pixels[((y * SIZE + x) * 4 + 2) as usize];
+    let (left, right) = (SIZE / 2 - 4, SIZE / 2 + 4);
+    renderer
+        .set_highlight_region(&[-1.0, -1.0, -1.0], &[0.0, 1.0, 1.0], &[0.0, 0.0, 1.0, 1.0])
+        .expect("highlight");
+    draw(&mut renderer);
+    let highlighted = capture(&mut renderer).await;
+    renderer.clear_highlight_region();
+    draw(&mut renderer);
+    let cleared = capture(&mut renderer).await;
+
+    assert!(
+        blue(&highlighted, left, SIZE / 2) >= 250,
+        "the region is not tinted"
+    );
+    assert_eq!(
+        red(&highlighted, left, SIZE / 2),
+        0,
+        "the tint is not opaque"
+    );
+    assert!(
+        red(&highlighted, right, SIZE / 2) >= 250,
+        "outside the region is tinted"
+    );
+    assert_eq!(blue(&highlighted, 4, 4), 0, "the background is tinted");
+    assert!(
+        red(&cleared, left, SIZE / 2) >= 250,
+        "the tint outlives its region"
+    );
+    assert!(renderer
+        .set_highlight_region(&[0.0; 3], &[-1.0; 3], &[0.0, 0.0, 1.0, 1.0])
+        .is_err());
+}
+
+/// Clipping the front half of the cube away shows its cut face, in its own colour or
+/// the cap's, on the raster path as on the compute path with or without an octree;
+/// clipping all of it shows the background.
+#[wasm_bindgen_test]
+async fn clip_planes_cut_the_cube_open() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    let draw = |renderer: &mut Renderer| {
+        renderer
+            .render(
+                &view_projection(3.0),
+                &[0.0, 0.0, 3.0],
+                4,
+                &[0.0, 0.0, 1.0],
+                1.0,
+                false,
+            )
+            .expect("render");
+    };
+    let green = |pixels: &[u8], x: u32, y: u32| pixels[((y * SIZE + x) * 4 + 1) as usize];
+    // Keeps z <= 0
+    renderer
+        .set_clip_planes(&[0.0, 0.0, -1.0, 0.0])
+        .expect("clip planes");
+    draw(&mut renderer);
+    let cut = capture(&mut renderer).await;
+    renderer
+        .set_clip_cap(Some(vec![0.0, 1.0, 0.0, 1.0]))
+        .expect("cap");
+    draw(&mut renderer);
+    let capped = capture(&mut renderer).await;
+    // Keeps z <= -1, behind the cube
+    renderer
+        .set_clip_planes(&[0.0, 0.0, -1.0, -1.0])
+        .expect("clip planes");
+    draw(&mut renderer);
+    let clipped = capture(&mut renderer).await;
+
+    assert!(
+        red(&cut, SIZE / 2, SIZE / 2) >= 250,
+        "the cut face is not drawn"
+    );
+    assert!(
+        green(&capped, SIZE / 2, SIZE / 2) >= 250,
+        "the cut face is not capped"
+    );
+    assert_eq!(
+        red(&capped, SIZE / 2, SIZE / 2),
+        0,
+        "the cap keeps the voxel colour"
+    );
+    assert_eq!(
+        red(&clipped, SIZE / 2, SIZE / 2),
+        0,
+        "the clipped cube is drawn"
+    );
+    assert!(renderer.set_clip_planes(&[0.0; 3]).is_err());
+    assert!(matches!(
+        renderer.set_clip_planes(&[0.0; 20]),
+        Err(RendererError::LimitExceeded { max: 4, .. })
+    ));
+    assert!(renderer
+        .set_clip_planes(&[0.0, 0.0, f32::NAN, 0.0])
+        .is_err());
+
+    renderer.set_clip_cap(None).expect("cap");
+    renderer
+        .set_clip_planes(&[0.0, 0.0, -1.0, 0.0])
+        .expect("clip planes");
+    renderer.set_render_path(RenderPath::Compute);
+    for path in ["compute", "octree"] {
+        if path == "octree" {
+            renderer.build_svo(0).expect("octree");
+        }
+        draw(&mut renderer);
+        let cut = capture(&mut renderer).await;
+        assert!(
+            red(&cut, SIZE / 2, SIZE / 2) >= 250,
+            "the {} path does not draw the cut face",
+            path
+        );
+    }
+}
+
+/// A gradient ramps between its stops, and uploading it recolours the cube.
+#[wasm_bindgen_test]
+async fn gradient_palettes_upload_without_the_scene() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    let stops =
+        js_sys::JSON::parse("[[2, [0, 0, 255, 255]], [0, [0, 255, 0, 255]]]").expect("stops JSON");
+    let palette = Renderer::create_gradient_palette(stops).expect("gradient");
+    assert_eq!(palette.len(), 256);
+    assert_eq!(palette[0], 0xff00_ff00);
+    assert_eq!(palette[1], 0xff80_8000);
+    assert_eq!(palette[255], 0xffff_0000);
+
+    renderer.upload_palette(&palette).expect("palette");
+    renderer
+        .render(
+            &view_projection(3.0),
+            &[0.0, 0.0, 3.0],
+            4,
+            &[0.0, 0.0, 1.0],
+            1.0,
+            false,
+        )
+        .expect("render");
+    let pixels = capture(&mut renderer).await;
+    assert_eq!(
+        red(&pixels, SIZE / 2, SIZE / 2),
+        0,
+        "the scene palette is still drawn"
+    );
+    assert!(renderer.upload_palette(&palette[..255]).is_err());
+    assert!(Renderer::create_gradient_palette(js_sys::Array::new().into()).is_err());
+}
+
+/// A wire cube draws yellow edges around an empty middle, until it is solid again.
+#[wasm_bindgen_test]
+async fn wire_objects_draw_only_their_edges() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    let draw = |renderer: &mut Renderer| {
+        renderer
+            .render(
+                &view_projection(3.0),
+                &[0.0, 0.0, 3.0],
+                4,
+                &[0.0, 0.0, 1.0],
+                1.0,
+                false,
+            )
+            .expect("render");
+    };
+    let green = |pixels: &[u8], x: u32, y: u32| pixels[((y * SIZE + x) * 4 + 1) as usize];
+    renderer.set_object_wireframe(0, true).expect("wireframe");
+    draw(&mut renderer);
+    let wire = capture(&mut renderer).await;
+    renderer.set_object_wireframe(0, false).expect("wireframe");
+    draw(&mut renderer);
+    let solid = capture(&mut renderer).await;
+
+    assert_eq!(red(&wire, SIZE / 2, SIZE / 2), 0, "the wire cube is filled");
+    assert!(
+        (0..SIZE).any(|x| red(&wire, x, SIZE / 2) >= 250 && green(&wire, x, SIZE / 2) >= 250),
+        "the wire cube has no edges"
+    );
+    assert!(
+        red(&solid, SIZE / 2, SIZE / 2) >= 250,
+        "the cube stays wire"
+    );
+    assert!(renderer.set_object_wireframe(1, true).is_err());
+}
+
+#[wasm_bindgen_test]
+async fn slice_view_draws_one_layer() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    let draw = |renderer: &mut Renderer| {
+        renderer
+            .render(
+                &view_projection(3.0),
+                &[0.0, 0.0, 3.0],
+                4,
+                &[0.0, 0.0, 1.0],
+                1.0,
+                false,
+            )
+            .expect("render");
+    };
+    // The right half of the cube, x in [0, 0.5]
+    renderer.set_slice_view(0, 0, 1, true).expect("slice");
+    draw(&mut renderer);
+    let slice = capture(&mut renderer).await;
+    renderer.set_slice_view(0, 0, 1, false).expect("slice");
+    draw(&mut renderer);
+    let whole = capture(&mut renderer).await;
+
+    let (left, right) = (SIZE / 2 - 4, SIZE / 2 + 4);
+    assert_eq!(red(&slice, left, SIZE / 2), 0, "the other layer is drawn");
+    assert!(
+        red(&slice, right, SIZE / 2) >= 250,
+        "the slice is not drawn"
+    );
+    assert!(
+        red(&whole, left, SIZE / 2) >= 250,
+        "the slice view stays on"
+    );
+    assert!(renderer.set_slice_view(0, 3, 0, true).is_err());
+    assert!(renderer.set_slice_view(0, 0, 2, true).is_err());
+    assert!(renderer.set_slice_view(1, 0, 0, true).is_err());
+}
+
+#[wasm_bindgen_test]
+async fn offscreen_canvases_render_like_canvases() {
+    let offscreen = web_sys::OffscreenCanvas::new(SIZE, SIZE).expect("OffscreenCanvas");
+    let mut renderer = Renderer::new_offscreen(offscreen).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    renderer
+        .render(
+            &view_projection(3.0),
+            &[0.0, 0.0, 3.0],
+            4,
+            &[0.0, 0.0, 1.0],
+            1.0,
+            false,
+        )
+        .expect("render");
+    let pixels = capture(&mut renderer).await;
+
+    assert!(
+        red(&pixels, SIZE / 2, SIZE / 2) >= 250,
+        "the cube is not drawn"
+    );
+    assert_eq!(red(&pixels, 0, 0), 0, "the background is not clear");
+}
+
+#[wasm_bindgen_test]
+async fn max_render_dimension_caps_the_gbuffer() {
+    let mut renderer = Renderer::new(create_canvas()).await.expect("renderer");
+    let scene = js_sys::JSON::parse(RED_CUBE_SCENE).expect("scene JSON");
+    renderer.upload_scene(scene).expect("upload");
+    renderer
+        .set_max_render_dimension(SIZE / 4)
+        .expect("render dimension");
+    renderer
+        .render(
+            &view_projection(3.0),
+            &[0.0, 0.0, 3.0],
+            4,
+            &[0.0, 0.0, 1.0],
+            1.0,
+            false,
+        )
+        .expect("render");
+    let pixels = capture(&mut renderer).await;
+    let depth = JsFuture::from(renderer.capture_depth())
+        .await
+        .expect("capture depth");
+
+    assert!(
+        red(&pixels, SIZE / 2, SIZE / 2) >= 250,
+        "the cube is not drawn"
+    );
+    assert_eq!(red(&pixels, 4, 4), 0, "the background is drawn over");
+    assert_eq!(js_sys::Float32Array::new(&depth).length(), SIZE * SIZE / 16);
+    assert!(renderer.set_max_render_dimension(u32::MAX).is_err());
+    renderer
+        .set_max_render_dimension(0)
+        .expect("render dimension");
+}
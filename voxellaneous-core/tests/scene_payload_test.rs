@@ -0,0 +1,474 @@
+//! Round trips the binary scene fixtures through `upload_scene_binary` and
+//! `export_scene`. `fixtures/scene.vxsc` is a 16×16×16 two-colour sphere and
+//! `fixtures/scene.vxsc.gz` the same bytes from `gzip -9 -n`, `fixtures/scene.vxsc.zst`
+//! from `zstd -19` (read with the `zstd` feature). Also covers the palette
+//! quantization of RGBA voxels, anisotropic voxel sizes, pivots and the skipping of
+//! unchanged uploads. Run with `wasm-pack test --chrome --headless`.
+
+use voxellaneous_core::Renderer;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const SCENE: &[u8] = include_bytes!("fixtures/scene.vxsc");
+const SCENE_GZIP: &[u8] = include_bytes!("fixtures/scene.vxsc.gz");
+const SCENE_ZSTD: &[u8] = include_bytes!("fixtures/scene.vxsc.zst");
+
+async fn create_renderer() -> Renderer {
+    let canvas: web_sys::HtmlCanvasElement = web_sys::window()
+        .and_then(|window| window.document())
+        .expect("document")
+        .create_element("canvas")
+        .expect("canvas element")
+        .dyn_into()
+        .expect("HtmlCanvasElement");
+    Renderer::new(canvas).await.expect("renderer")
+}
+
+#[wasm_bindgen_test]
+async fn uncompressed_scene_round_trips() {
+    let mut renderer = create_renderer().await;
+    renderer.upload_scene_binary(SCENE).expect("upload");
+    assert_eq!(renderer.export_scene(), SCENE);
+}
+
+/// Exporting a scene uploaded as JSON and importing the blob reproduces it: the same
+/// objects, transforms, palette and voxels, and the same blob when exported again.
+#[wasm_bindgen_test]
+async fn exported_scenes_import_identically() {
+    let scene = r#"{
+        "palette": [[0, 0, 0, 0], [255, 0, 0, 255], [0, 128, 255, 200]],
+        "objects": [
+            { "id": "cube", "dims": [2, 2, 2], "voxels": [1, 2, 1, 2, 0, 1, 2, 1],
+              "translation": [0.5, -1, 2], "rotation_quat": [0, 0.6, 0, 0.8],
+              "scale": [1, 2, 0.5] },
+            { "id": "bar", "dims": [3, 1, 1], "voxels": [2, 0, 1], "order": 1 }
+        ]
+    }"#;
+    let mut renderer = create_renderer().await;
+    renderer
+        .upload_scene(js_sys::JSON::parse(scene).expect("scene JSON"))
+        .expect("upload");
+    let exported = renderer.export_scene();
+    let info = js_sys::JSON::stringify(&renderer.get_scene_info()).expect("info");
+    let bounds = js_sys::JSON::stringify(&renderer.get_scene_bounds()).expect("bounds");
+
+    let mut imported = create_renderer().await;
+    imported.upload_scene_binary(&exported).expect("upload");
+    assert_eq!(
+        js_sys::JSON::stringify(&imported.get_scene_info()).expect("info"),
+        info
+    );
+    assert_eq!(
+        js_sys::JSON::stringify(&imported.get_scene_bounds()).expect("bounds"),
+        bounds
+    );
+    assert_eq!(imported.export_scene(), exported);
+}
+
+#[wasm_bindgen_test]
+async fn gzip_scene_round_trips() {
+    let mut renderer = create_renderer().await;
+    renderer.upload_scene_binary(SCENE_GZIP).expect("upload");
+    assert_eq!(renderer.export_scene(), SCENE);
+}
+
+#[wasm_bindgen_test]
+async fn corrupt_gzip_scene_names_the_offset() {
+    let mut renderer = create_renderer().await;
+    let truncated = &SCENE_GZIP[..SCENE_GZIP.len() / 2];
+    let error: JsValue = renderer.upload_scene_binary(truncated).unwrap_err().into();
+    let message = js_sys::Reflect::get(&error, &"message".into())
+        .expect("message")
+        .as_string()
+        .expect("message string");
+    assert!(message.contains("at byte"), "unexpected error: {}", message);
+
+    let mut flipped = SCENE_GZIP.to_vec();
+    let last = flipped.len() - 8;
+    flipped[last] ^= 0xff;
+    assert!(renderer.upload_scene_binary(&flipped).is_err());
+}
+
+#[cfg(feature = "zstd")]
+#[wasm_bindgen_test]
+async fn zstd_scene_round_trips() {
+    let mut renderer = create_renderer().await;
+    renderer.upload_scene_binary(SCENE_ZSTD).expect("upload");
+    assert_eq!(renderer.export_scene(), SCENE);
+}
+
+#[cfg(feature = "zstd")]
+#[wasm_bindgen_test]
+async fn corrupt_zstd_scene_names_the_offset() {
+    let mut renderer = create_renderer().await;
+    let truncated = &SCENE_ZSTD[..SCENE_ZSTD.len() / 2];
+    let error: JsValue = renderer.upload_scene_binary(truncated).unwrap_err().into();
+    let message = js_sys::Reflect::get(&error, &"message".into())
+        .expect("message")
+        .as_string()
+        .expect("message string");
+    assert!(message.contains("at byte"), "unexpected error: {}", message);
+
+    // The content checksum
+    let mut flipped = SCENE_ZSTD.to_vec();
+    let last = flipped.len() - 1;
+    flipped[last] ^= 0xff;
+    assert!(renderer.upload_scene_binary(&flipped).is_err());
+}
+
+#[cfg(not(feature = "zstd"))]
+#[wasm_bindgen_test]
+async fn zstd_scenes_need_the_feature() {
+    let mut renderer = create_renderer().await;
+    let error: JsValue = renderer.upload_scene_binary(SCENE_ZSTD).unwrap_err().into();
+    let message = js_sys::Reflect::get(&error, &"message".into())
+        .expect("message")
+        .as_string()
+        .expect("message string");
+    assert!(
+        message.contains("`zstd` feature"),
+        "unexpected error: {}",
+        message
+    );
+}
+
+/// A `voxel_size` stretches the object's box and is kept by the binary format, in
+/// version 4. The exported matrices already hold the stretch, so a re-upload does not
+/// apply it twice.
+#[wasm_bindgen_test]
+async fn anisotropic_voxels_stretch_and_round_trip() {
+    let mut renderer = create_renderer().await;
+    let scene = r#"{
+        "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+        "objects": [{ "id": "scan", "dims": [1, 1, 1], "voxels": [1], "voxel_size": [1, 1, 4] }]
+    }"#;
+    renderer
+        .upload_scene(js_sys::JSON::parse(scene).expect("scene JSON"))
+        .expect("upload");
+    let max_z = |renderer: &Renderer| {
+        let max = js_sys::Reflect::get(&renderer.get_scene_bounds(), &"max".into()).unwrap();
+        js_sys::Reflect::get_u32(&max, 2).unwrap().as_f64().unwrap()
+    };
+    assert_eq!(max_z(&renderer), 2.0);
+
+    let exported = renderer.export_scene();
+    assert_eq!(u32::from_le_bytes(exported[4..8].try_into().unwrap()), 4);
+    renderer.upload_scene_binary(&exported).expect("upload");
+    assert_eq!(max_z(&renderer), 2.0);
+    assert_eq!(renderer.export_scene(), exported);
+}
+
+/// Moving the pivot of the unit-transform cube to its corner shifts the cube to put
+/// the corner at the origin; the pivot is reported and kept by the binary format, and
+/// a scene's `pivot` is the same voxel coordinates.
+#[wasm_bindgen_test]
+async fn pivots_move_objects_and_round_trip() {
+    let mut renderer = create_renderer().await;
+    let scene = r#"{
+        "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+        "objects": [{ "id": "cube", "dims": [2, 2, 2], "voxels": [1, 1, 1, 1, 1, 1, 1, 1] }]
+    }"#;
+    renderer
+        .upload_scene(js_sys::JSON::parse(scene).expect("scene JSON"))
+        .expect("upload");
+    let component = |value: &JsValue, key: &str, i: u32| {
+        let array = js_sys::Reflect::get(value, &key.into()).unwrap();
+        js_sys::Reflect::get_u32(&array, i)
+            .unwrap()
+            .as_f64()
+            .unwrap()
+    };
+    let info = |renderer: &Renderer| js_sys::Reflect::get_u32(&renderer.get_scene_info(), 0);
+    assert_eq!(component(&info(&renderer).unwrap(), "pivot", 0), 1.0);
+
+    renderer
+        .set_object_pivot(0, &[0.0, 0.0, 0.0])
+        .expect("pivot");
+    assert_eq!(component(&info(&renderer).unwrap(), "pivot", 0), 0.0);
+    let bounds = renderer.get_scene_bounds();
+    assert_eq!(component(&bounds, "min", 0), 0.0);
+    assert_eq!(component(&bounds, "max", 2), 1.0);
+
+    let exported = renderer.export_scene();
+    assert_eq!(u32::from_le_bytes(exported[4..8].try_into().unwrap()), 5);
+    renderer.upload_scene_binary(&exported).expect("upload");
+    assert_eq!(renderer.export_scene(), exported);
+
+    let scene = scene.replace(r#""dims""#, r#""pivot": [0, 0, 0], "dims""#);
+    renderer
+        .upload_scene(js_sys::JSON::parse(&scene).expect("scene JSON"))
+        .expect("upload");
+    assert_eq!(component(&info(&renderer).unwrap(), "pivot", 0), 0.0);
+    let bounds = renderer.get_scene_bounds();
+    assert_eq!(component(&bounds, "min", 0), 0.0);
+    assert_eq!(component(&bounds, "max", 2), 1.0);
+}
+
+/// `Renderer::quantize_voxels` as `(indices, palette)`.
+fn quantize(rgba: &[u8], dims: [u32; 3], max_colors: u32) -> (Vec<u8>, Vec<[u8; 4]>) {
+    let quantized =
+        Renderer::quantize_voxels(rgba, dims[0], dims[1], dims[2], max_colors).expect("quantize");
+    serde_wasm_bindgen::from_value(quantized).expect("indices and palette")
+}
+
+#[wasm_bindgen_test]
+fn quantize_keeps_up_to_255_colours_exactly() {
+    // 200 distinct colours, with every seventh voxel empty
+    let rgba: Vec<u8> = (0..512u32)
+        .flat_map(|i| match i % 7 {
+            0 => [0, 0, 0, 0],
+            _ => {
+                let c = (i % 200) as u8;
+                [c, 255 - c, c / 2, 255]
+            }
+        })
+        .collect();
+    let (indices, palette) = quantize(&rgba, [8, 8, 8], 255);
+    assert_eq!(palette[0], [0, 0, 0, 0]);
+    for (texel, &index) in rgba.chunks_exact(4).zip(&indices) {
+        match texel[3] {
+            0 => assert_eq!(index, 0),
+            _ => assert_eq!(&palette[index as usize], texel),
+        }
+    }
+}
+
+#[wasm_bindgen_test]
+fn quantize_bounds_the_error_of_a_gradient() {
+    // 4096 colours, 17 apart on each axis
+    let rgba: Vec<u8> = (0..4096u32)
+        .map(|i| [i % 16, i / 16 % 16, i / 256].map(|c| (c * 17) as u8))
+        .flat_map(|[r, g, b]| [r, g, b, 255])
+        .collect();
+    let (indices, palette) = quantize(&rgba, [16, 16, 16], 255);
+    assert!(palette.len() <= 256);
+    let error = rgba
+        .chunks_exact(4)
+        .zip(&indices)
+        .flat_map(|(texel, &index)| {
+            let color = palette[index as usize];
+            (0..4).map(move |c| (color[c] as i32 - texel[c] as i32).abs())
+        })
+        .max()
+        .unwrap_or(0);
+    assert!(error <= 32, "largest channel error is {}", error);
+}
+
+#[wasm_bindgen_test]
+async fn quantized_rgba_objects_upload_into_the_palette() {
+    let mut renderer = create_renderer().await;
+    let scene = r#"{
+        "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+        "objects": [{ "id": "rgba", "dims": [2, 1, 1], "color_mode": "rgba", "quantize": true,
+                      "voxels": [0, 255, 0, 255, 0, 0, 0, 0] }]
+    }"#;
+    renderer
+        .upload_scene(js_sys::JSON::parse(scene).expect("scene JSON"))
+        .expect("upload");
+    let exported = renderer.export_scene();
+    // The green voxel is appended to the palette, after the scene's two entries
+    let palette_len = u32::from_le_bytes(exported[8..12].try_into().unwrap());
+    assert_eq!(palette_len, 3);
+    assert_eq!(&exported[20..24], &[0, 255, 0, 255]);
+
+    let palette_object = r#"{
+        "palette": [[0, 0, 0, 0]],
+        "objects": [{ "id": "cube", "dims": [1, 1, 1], "voxels": [0], "quantize": true }]
+    }"#;
+    let parsed = js_sys::JSON::parse(palette_object).expect("scene JSON");
+    assert!(renderer.upload_scene(parsed).is_err());
+}
+
+/// Uploading a scene that differs only in object ids is skipped, and forcing it or
+/// changing the upload settings is not; changed voxels change the hash, and a binary
+/// upload clears it.
+#[wasm_bindgen_test]
+async fn unchanged_scenes_are_not_uploaded_again() {
+    let mut renderer = create_renderer().await;
+    let scene = r#"{
+        "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+        "objects": [{ "id": "cube", "dims": [1, 1, 1], "voxels": [1] }]
+    }"#;
+    let upload = |renderer: &mut Renderer, scene: &str, force: bool| {
+        let scene = js_sys::JSON::parse(scene).expect("scene JSON");
+        let result = if force {
+            renderer.force_upload_scene(scene)
+        } else {
+            renderer.upload_scene(scene)
+        };
+        result.expect("upload");
+    };
+    let id = |renderer: &Renderer| {
+        let info = js_sys::Reflect::get_u32(&renderer.get_scene_info(), 0).unwrap();
+        js_sys::Reflect::get(&info, &"id".into())
+            .unwrap()
+            .as_string()
+    };
+    assert_eq!(renderer.get_last_uploaded_scene_hash(), 0);
+    upload(&mut renderer, scene, false);
+    let hash = renderer.get_last_uploaded_scene_hash();
+    assert_ne!(hash, 0);
+
+    let renamed = scene.replace("\"cube\"", "\"box\"");
+    upload(&mut renderer, &renamed, false);
+    assert_eq!(
+        id(&renderer).as_deref(),
+        Some("cube"),
+        "the unchanged scene was uploaded"
+    );
+    upload(&mut renderer, &renamed, true);
+    assert_eq!(
+        id(&renderer).as_deref(),
+        Some("box"),
+        "the forced upload was skipped"
+    );
+    assert_eq!(renderer.get_last_uploaded_scene_hash(), hash);
+
+    renderer.set_max_volume_size(1);
+    upload(&mut renderer, scene, false);
+    assert_eq!(
+        id(&renderer).as_deref(),
+        Some("cube"),
+        "the upload under a new volume cap was skipped"
+    );
+    assert_ne!(renderer.get_last_uploaded_scene_hash(), hash);
+    renderer.set_max_volume_size(0);
+    upload(&mut renderer, &renamed, false);
+    renderer.set_trust_inverse_matrices(true);
+    upload(&mut renderer, scene, false);
+    assert_eq!(
+        id(&renderer).as_deref(),
+        Some("cube"),
+        "the upload trusting inverses was skipped"
+    );
+    renderer.set_trust_inverse_matrices(false);
+
+    upload(&mut renderer, &scene.replace("[1] }", "[0] }"), false);
+    assert_ne!(renderer.get_last_uploaded_scene_hash(), hash);
+    renderer.upload_scene_binary(SCENE).expect("upload");
+    assert_eq!(renderer.get_last_uploaded_scene_hash(), 0);
+}
+
+/// Re-uploading the scene after an edit through the renderer replaces the edited
+/// state instead of being skipped.
+#[wasm_bindgen_test]
+async fn edited_scenes_are_uploaded_again() {
+    let mut renderer = create_renderer().await;
+    let scene = r#"{
+        "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+        "objects": [{
+            "id": "cube",
+            "dims": [3, 3, 3],
+            "voxels": [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+                       1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1]
+        }]
+    }"#;
+    let upload = |renderer: &mut Renderer| {
+        let scene = js_sys::JSON::parse(scene).expect("scene JSON");
+        renderer.upload_scene(scene).expect("upload");
+    };
+    upload(&mut renderer);
+    let uploaded = renderer.export_scene();
+
+    JsFuture::from(renderer.erode_object(0))
+        .await
+        .expect("erode");
+    assert_eq!(renderer.get_last_uploaded_scene_hash(), 0);
+    assert_ne!(renderer.export_scene(), uploaded, "erosion changed nothing");
+    upload(&mut renderer);
+    assert_eq!(
+        renderer.export_scene(),
+        uploaded,
+        "the eroded scene was kept"
+    );
+
+    renderer
+        .set_object_pivot(0, &[0.0, 0.0, 0.0])
+        .expect("pivot");
+    assert_eq!(renderer.get_last_uploaded_scene_hash(), 0);
+    assert_ne!(renderer.export_scene(), uploaded, "the pivot did not move");
+    upload(&mut renderer);
+    assert_eq!(
+        renderer.export_scene(),
+        uploaded,
+        "the moved pivot was kept"
+    );
+}
+
+/// Dilating a lone voxel in a 3×3×3 grid fills its 6 face neighbours with its colour,
+/// and eroding that cross leaves nothing, as read back into the exported scene.
+#[wasm_bindgen_test]
+async fn dilation_and_erosion_reach_the_exported_voxels() {
+    let mut renderer = create_renderer().await;
+    let export = |renderer: &mut Renderer, voxels: [u8; 27]| {
+        let json = format!(
+            r#"{{
+                "palette": [[0, 0, 0, 0], [255, 0, 0, 255], [0, 255, 0, 255]],
+                "objects": [{{ "id": "cube", "dims": [3, 3, 3], "voxels": {:?} }}]
+            }}"#,
+            voxels
+        );
+        let scene = js_sys::JSON::parse(&json).expect("scene JSON");
+        renderer.upload_scene(scene).expect("upload");
+        renderer.export_scene()
+    };
+    let mut cross = [0; 27];
+    for i in [4, 10, 12, 13, 14, 16, 22] {
+        cross[i] = 2;
+    }
+    let dilated = export(&mut renderer, cross);
+    let empty = export(&mut renderer, [0; 27]);
+    let mut centre = [0; 27];
+    centre[13] = 2;
+    export(&mut renderer, centre);
+
+    JsFuture::from(renderer.dilate_object(0))
+        .await
+        .expect("dilate");
+    assert_eq!(renderer.export_scene(), dilated, "dilation");
+    JsFuture::from(renderer.erode_object(0))
+        .await
+        .expect("erode");
+    assert_eq!(renderer.export_scene(), empty, "erosion");
+}
+
+/// Offsets past the end of the voxel array, including ones whose end overflows, and
+/// dims whose voxel count overflows fail with the object index instead of panicking.
+#[wasm_bindgen_test]
+async fn voxel_array_offsets_are_checked() {
+    let mut renderer = create_renderer().await;
+    let scene = |dims: &str| {
+        let json = format!(
+            r#"{{
+                "palette": [[0, 0, 0, 0], [255, 0, 0, 255]],
+                "objects": [{{ "id": "cube", "dims": {} }}]
+            }}"#,
+            dims
+        );
+        js_sys::JSON::parse(&json).expect("scene JSON")
+    };
+    let voxels = js_sys::Uint8Array::from([1u8; 8].as_slice());
+    let field = |error: voxellaneous_core::RendererError| {
+        let details = js_sys::Reflect::get(&JsValue::from(error), &"details".into()).unwrap();
+        let get = |key: &str| js_sys::Reflect::get(&details, &key.into()).unwrap();
+        assert_eq!(get("object_id").as_string().as_deref(), Some("0"));
+        get("field").as_string()
+    };
+
+    renderer
+        .upload_scene_with_voxels(scene("[2, 2, 2]"), voxels.clone(), &[0])
+        .expect("upload");
+    for offset in [1, u32::MAX] {
+        let error = renderer
+            .upload_scene_with_voxels(scene("[2, 2, 2]"), voxels.clone(), &[offset])
+            .expect_err("offset past the end");
+        assert_eq!(field(error).as_deref(), Some("offsets"));
+    }
+    let error = renderer
+        .upload_scene_with_voxels(scene("[65536, 65536, 65536]"), voxels, &[0])
+        .expect_err("overflowing dims");
+    assert_eq!(field(error).as_deref(), Some("dims"));
+}
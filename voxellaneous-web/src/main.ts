@@ -71,9 +71,14 @@ function createCanvasAutoresize({ renderer, canvas }: AppData): { autoresizeCanv
   const autoresizeCanvas = () => {
     if (!newCanvasSize) return;
 
-    canvas.width = newCanvasSize.width;
-    canvas.height = newCanvasSize.height;
-    renderer.resize(canvas.width, canvas.height);
+    const [width, height] = renderer.resize_to_display(
+      newCanvasSize.width,
+      newCanvasSize.height,
+      window.devicePixelRatio,
+      undefined,
+    );
+    canvas.width = width;
+    canvas.height = height;
     newCanvasSize = undefined;
   };
 
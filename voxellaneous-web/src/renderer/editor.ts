@@ -10,6 +10,7 @@ type GPUData = {
   driver: string;
   driver_info: string;
   backend: string;
+  linear_z_format: string;
 };
 
 export function initializeRendererTools(pane: Pane, app: AppData, profilerData: ProfilerData): void {
@@ -27,6 +28,10 @@ export function initializeRendererTools(pane: Pane, app: AppData, profilerData:
   settingsFolder.addBinding(app, 'showBboxes', {
     label: 'Show Bounding Boxes',
   });
+  const postSettings = { fxaa: false };
+  settingsFolder
+    .addBinding(postSettings, 'fxaa', { label: 'FXAA' })
+    .on('change', (ev) => app.renderer.set_fxaa_enabled(ev.value));
 
   const lightingFolder = pane.addFolder({ title: 'Lighting' });
   lightingFolder.addBinding(app, 'lightDir', {
@@ -54,6 +59,7 @@ export function initializeRendererTools(pane: Pane, app: AppData, profilerData:
   backendFolder.addBinding(gpuData, 'driver', { label: 'Driver', readonly: true });
   backendFolder.addBinding(gpuData, 'driver_info', { label: 'Driver Info', readonly: true });
   backendFolder.addBinding(gpuData, 'backend', { label: 'Backend', readonly: true });
+  backendFolder.addBinding(gpuData, 'linear_z_format', { label: 'Linear-Z Format', readonly: true });
 
   const performanceFolder = pane.addFolder({ title: 'Performance' });
   performanceFolder.addBinding(profilerData, 'fps', { label: 'FPS', readonly: true, format: (v) => v.toFixed(2) });
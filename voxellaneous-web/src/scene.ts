@@ -5,10 +5,30 @@ export type RGBA = [number, number, number, number];
 
 export interface VoxelObject {
   id: string;
-  model_matrix: mat4;
-  inv_model_matrix: mat4;
+  /** Explicit transform; takes precedence over translation/rotation_quat/scale. */
+  model_matrix?: mat4;
+  /** Computed from the model matrix when omitted. */
+  inv_model_matrix?: mat4;
+  translation?: vec3;
+  /** Rotation quaternion [x, y, z, w]; normalized before use. */
+  rotation_quat?: [number, number, number, number];
+  scale?: vec3;
+  /** Point of the grid in voxel coordinates ([0, dims] per axis) the transform rotates and scales about, as in set_object_pivot. Defaults to the centre, dims / 2. */
+  pivot?: vec3;
   dims: vec3;
-  voxels: Uint8Array;
+  /** Palette index width: 'u8' (the default) or 'u16' for palettes of up to 65536 colors. */
+  format?: 'u8' | 'u16';
+  /** 'palette' (the default), or 'rgba' for 4 bytes of color per voxel, alpha 0 empty. */
+  color_mode?: 'palette' | 'rgba';
+  voxels: Uint8Array | Uint16Array;
+  /** Draw priority; lower values are drawn first. Defaults to 0. */
+  order?: number;
+  /** For 'rgba' objects: store the voxels as up to 255 colors appended to the scene palette. */
+  quantize?: boolean;
+  /** Id of an object whose space (the unit cube of its grid) the transform is relative to. */
+  parent?: string;
+  /** Relative size of a voxel on each axis, for anisotropic grids such as scans. Defaults to [1, 1, 1]. */
+  voxel_size?: vec3;
 }
 
 /** Overall scene definition including a shared 4-color palette and list of voxel objects */